@@ -1,10 +1,15 @@
 use crate::SharedOptions;
 
 use probe_rs::{
-    architecture::arm::ap::AccessPortError, config::TargetSelector, flashing::FileDownloadError,
-    DebugProbeError, Error, Probe, Session,
+    architecture::arm::ap::AccessPortError,
+    config::{BoardDescription, ScanChain, TargetSelector},
+    flashing::FileDownloadError,
+    DebugProbeError, DebugProbeSelector, Error, Probe, Session,
 };
+#[cfg(feature = "ftdi")]
+use probe_rs::{FtdiLayout, FtdiProbe};
 
+use std::borrow::Cow;
 use std::fmt;
 use thiserror::Error;
 
@@ -60,10 +65,18 @@ impl fmt::Display for CliError {
     }
 }
 
-pub(crate) fn open_probe(index: Option<usize>) -> Result<Probe, CliError> {
+pub(crate) fn open_probe(shared_options: &SharedOptions) -> Result<Probe, CliError> {
+    if let Some(ref selector) = shared_options.probe {
+        if let Some(ref layout) = shared_options.ftdi_layout {
+            return open_ftdi_probe(selector, layout);
+        }
+
+        return Ok(Probe::open(selector.clone())?);
+    }
+
     let available_probes = Probe::list_all();
 
-    let device = match index {
+    let device = match shared_options.n {
         Some(index) => available_probes
             .get(index)
             .ok_or(CliError::UnableToOpenProbe(Some("Unable to open the specified probe. Use the 'list' subcommand to see all available probes.")))?,
@@ -85,6 +98,48 @@ pub(crate) fn open_probe(index: Option<usize>) -> Result<Probe, CliError> {
     Ok(probe)
 }
 
+/// Open an FTDI probe using the pin layout with the given name.
+#[cfg(feature = "ftdi")]
+fn open_ftdi_probe(selector: &DebugProbeSelector, layout: &str) -> Result<Probe, CliError> {
+    let layout = FtdiLayout::from_name(layout)
+        .ok_or(CliError::UnableToOpenProbe(Some("Unknown FTDI layout.")))?;
+
+    let probe = FtdiProbe::open_with_layout(selector.clone(), layout)?;
+
+    Ok(Probe::from_specific_probe(probe))
+}
+
+#[cfg(not(feature = "ftdi"))]
+fn open_ftdi_probe(_selector: &DebugProbeSelector, _layout: &str) -> Result<Probe, CliError> {
+    Err(CliError::UnableToOpenProbe(Some(
+        "FTDI support is not enabled, please use the `ftdi` feature.",
+    )))
+}
+
+/// Apply the recording, protocol and JTAG options to the probe, before attaching.
+pub(crate) fn configure_probe(mut probe: Probe, shared_options: &SharedOptions) -> Result<Probe> {
+    if let Some(ref path) = shared_options.record {
+        probe = probe.record(path)?;
+    }
+
+    if let Some(ref protocol) = shared_options.protocol {
+        probe.select_protocol(
+            protocol
+                .parse()
+                .map_err(|_e| CliError::UnableToOpenProbe(Some("Error while parsing protocol")))?,
+        )?;
+    }
+
+    if let Some(target_tap) = shared_options.jtag_tap {
+        probe.set_scan_chain(ScanChain {
+            taps: Cow::Borrowed(&[]),
+            target_tap: Some(target_tap),
+        })?;
+    }
+
+    Ok(probe)
+}
+
 /// Takes a closure that is handed an `DAPLink` instance and then executed.
 /// After the closure is done, the USB device is always closed,
 /// even in an error case inside the closure!
@@ -92,20 +147,24 @@ pub(crate) fn with_device<F>(shared_options: &SharedOptions, f: F) -> Result<()>
 where
     F: FnOnce(Session) -> Result<()>,
 {
-    let mut probe = open_probe(shared_options.n)?;
+    let probe = open_probe(shared_options)?;
+
+    let target_selector = match (&shared_options.board, &shared_options.chip) {
+        (Some(path), chip) => {
+            let mut board = BoardDescription::from_yaml_file(path)?;
+
+            // The chip given on the command line overrides the chip of the board.
+            if let Some(chip) = chip {
+                board.chip = Cow::Owned(chip.clone());
+            }
 
-    let target_selector = match &shared_options.chip {
-        Some(identifier) => identifier.into(),
-        None => TargetSelector::Auto,
+            TargetSelector::Specified(board.target()?)
+        }
+        (None, Some(identifier)) => identifier.into(),
+        (None, None) => TargetSelector::Auto,
     };
 
-    if let Some(ref protocol) = shared_options.protocol {
-        probe.select_protocol(
-            protocol
-                .parse()
-                .map_err(|_e| CliError::UnableToOpenProbe(Some("Error while parsing protocol")))?,
-        )?;
-    }
+    let probe = configure_probe(probe, shared_options)?;
 
     let session = if shared_options.connect_under_reset {
         probe.attach_under_reset(target_selector)?
@@ -3,6 +3,7 @@ use crate::common::CliError;
 use capstone::Capstone;
 use probe_rs::architecture::arm::CortexDump;
 use probe_rs::debug::DebugInfo;
+use probe_rs::semihosting::{SemihostingHost, SemihostingOutcome};
 use probe_rs::{Core, CoreRegisterAddress, MemoryInterface};
 use std::fs::File;
 use std::{io::prelude::*, time::Duration};
@@ -99,6 +100,36 @@ impl DebugCli {
             },
         });
 
+        cli.add_command(Command {
+            name: "run_semihosting",
+            help_text: "Resume the CPU and service semihosting requests",
+
+            function: |cli_data, _args| {
+                cli_data.core.run()?;
+
+                loop {
+                    match cli_data.semihosting.service(&mut cli_data.core)? {
+                        Some(SemihostingOutcome::Continue) => (),
+                        Some(outcome @ SemihostingOutcome::Exit { .. }) => {
+                            println!("Application exited: {:?}", outcome);
+                            break;
+                        }
+                        None => {
+                            if cli_data.core.core_halted()? {
+                                let status = cli_data.core.status()?;
+                                println!("Core halted: {:?}", status);
+                                break;
+                            }
+
+                            std::thread::sleep(Duration::from_millis(10));
+                        }
+                    }
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
         cli.add_command(Command {
             name: "quit",
             help_text: "Exit the program",
@@ -327,6 +358,7 @@ pub struct CliData<'p> {
     pub core: Core<'p>,
     pub debug_info: Option<DebugInfo>,
     pub capstone: Capstone,
+    pub semihosting: SemihostingHost,
 }
 
 pub enum CliState {
@@ -1,4 +1,7 @@
-use crate::{common::open_probe, SharedOptions};
+use crate::{
+    common::{configure_probe, open_probe},
+    SharedOptions,
+};
 
 use probe_rs::{
     architecture::arm::{
@@ -7,15 +10,54 @@ use probe_rs::{
         memory::Component,
         ApInformation, MemoryApInformation,
     },
-    CoreRegister,
+    CoreRegister, Probe,
 };
 
 use anyhow::Result;
 
+/// Print the capabilities of the probe and the target voltage, if the probe reports them.
+pub(crate) fn show_probe_capabilities(probe: &mut Probe) {
+    match probe.capabilities() {
+        Ok(capabilities) => {
+            for line in capabilities.to_string().lines() {
+                println!("\t{}", line);
+            }
+        }
+        Err(e) => log::debug!("Failed to read the probe capabilities: {}", e),
+    }
+
+    match probe.target_voltage() {
+        Ok(Some(voltage)) => println!("\tMeasured target voltage: {:.2} V", voltage),
+        Ok(None) => (),
+        Err(e) => log::debug!("Failed to read the target voltage: {}", e),
+    }
+}
+
 pub(crate) fn show_info_of_device(shared_options: &SharedOptions) -> Result<()> {
-    let mut probe = open_probe(shared_options.n)?;
+    let probe = open_probe(shared_options)?;
+    let mut probe = configure_probe(probe, shared_options)?;
+
+    println!("Probe: {}", probe.get_name());
+    show_probe_capabilities(&mut probe);
+
     probe.attach_to_unspecified()?;
 
+    if let Ok(chain) = probe.scan_chain() {
+        if !chain.is_empty() {
+            println!("\nJTAG scan chain:");
+        }
+
+        for (index, tap) in chain.iter().enumerate() {
+            match tap.idcode {
+                Some(idcode) => println!(
+                    "\tTAP {}: IDCODE = {:#010x}, IR length = {}",
+                    index, idcode, tap.ir_len
+                ),
+                None => println!("\tTAP {}: no IDCODE, IR length = {}", index, tap.ir_len),
+            }
+        }
+    }
+
     /*
         The following code only works with debug port v2,
         which might not necessarily be present.
@@ -2,13 +2,19 @@ mod common;
 mod debugger;
 mod info;
 
-use common::with_device;
+use common::{configure_probe, open_probe, with_device};
 use debugger::CliState;
 
 use probe_rs::{
     debug::DebugInfo,
-    flashing::{download_file, Format},
-    MemoryInterface, Probe, Session,
+    flashing::{
+        download_file_with_options, dump_memory_range, erase_all_with_options,
+        erase_range_with_options, memory_region_range, plan_erase_all, plan_erase_range,
+        DownloadOptions, DumpFormat, DumpOptions, EraseOptions, FlashProgress, Format,
+        ProgressEvent,
+    },
+    semihosting::SemihostingHost,
+    DebugProbeSelector, MemoryInterface, Probe, ProbeServer, Session,
 };
 
 use capstone::{arch::arm::ArchMode, prelude::*, Capstone, Endian};
@@ -17,14 +23,28 @@ use structopt::StructOpt;
 
 use anyhow::{anyhow, Result};
 
+use std::cell::Cell;
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::Instant;
 
 fn parse_hex(src: &str) -> Result<u32, ParseIntError> {
     u32::from_str_radix(src, 16)
 }
 
+fn parse_dump_format(src: &str) -> Result<DumpFormat, String> {
+    match src.to_ascii_lowercase().as_str() {
+        "bin" => Ok(DumpFormat::Bin),
+        "hex" | "ihex" => Ok(DumpFormat::Hex),
+        "elf" => Ok(DumpFormat::Elf),
+        _ => Err(format!(
+            "Unknown format '{}', expected bin, hex or elf",
+            src
+        )),
+    }
+}
+
 #[derive(StructOpt)]
 #[structopt(
     name = "Probe-rs CLI",
@@ -58,6 +78,10 @@ enum CLI {
         #[structopt(long, parse(from_os_str))]
         /// Binary to debug
         exe: Option<PathBuf>,
+
+        #[structopt(long, parse(from_os_str))]
+        /// Directory the target may access using semihosting
+        semihosting_root: Option<PathBuf>,
     },
     /// Dump memory from attached target
     #[structopt(name = "dump")]
@@ -79,6 +103,58 @@ enum CLI {
 
         /// The path to the file to be downloaded to the flash
         path: String,
+
+        /// Only erase and program the sectors whose contents differ from the file
+        #[structopt(long)]
+        incremental: bool,
+    },
+    /// Read back memory of the attached target to a file
+    #[structopt(name = "read-flash")]
+    ReadFlash {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+
+        /// The path of the file to write the memory to
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// The memory region to read, e.g. `flash`, `flash1` or `ram`
+        #[structopt(long, conflicts_with = "address")]
+        region: Option<String>,
+
+        /// The start address of the memory to read (in hexadecimal without 0x prefix)
+        #[structopt(long, parse(try_from_str = parse_hex), requires = "size")]
+        address: Option<u32>,
+
+        /// The amount of memory (in bytes, in hexadecimal without 0x prefix) to read
+        #[structopt(long, parse(try_from_str = parse_hex), requires = "address")]
+        size: Option<u32>,
+
+        /// The format of the file, one of `bin`, `hex` or `elf`. Guessed from the file extension if left out
+        #[structopt(long, parse(try_from_str = parse_dump_format))]
+        format: Option<DumpFormat>,
+    },
+    /// Erase the flash of the attached target, or a part of it
+    #[structopt(name = "erase")]
+    Erase {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+
+        /// The flash region to erase, e.g. `flash` or `flash1`. The whole flash is erased if neither a region nor an address is given
+        #[structopt(long, conflicts_with = "address")]
+        region: Option<String>,
+
+        /// The start address of the flash to erase (in hexadecimal without 0x prefix)
+        #[structopt(long, parse(try_from_str = parse_hex), requires = "size")]
+        address: Option<u32>,
+
+        /// The amount of flash (in bytes, in hexadecimal without 0x prefix) to erase. It is extended to whole sectors
+        #[structopt(long, parse(try_from_str = parse_hex), requires = "address")]
+        size: Option<u32>,
+
+        /// Confirm that the flash the chip boots from may be erased
+        #[structopt(long)]
+        erase_boot_memory: bool,
     },
     #[structopt(name = "trace")]
     Trace {
@@ -89,6 +165,20 @@ enum CLI {
         #[structopt(parse(try_from_str = parse_hex))]
         loc: u32,
     },
+    /// Unlock a chip which is protected against debug access. This usually erases the entire flash
+    #[structopt(name = "unlock")]
+    Unlock {
+        #[structopt(flatten)]
+        shared: SharedOptions,
+    },
+    /// Make the connected debug probes available to other computers
+    #[structopt(name = "serve")]
+    Serve {
+        /// The address to listen on. Use e.g. `0.0.0.0:4269` to make the probes
+        /// available on all network interfaces.
+        #[structopt(long, default_value = "127.0.0.1:4269")]
+        address: String,
+    },
 }
 
 /// Shared options for all commands which use a specific probe
@@ -98,16 +188,37 @@ struct SharedOptions {
     #[structopt(long = "probe-index")]
     n: Option<usize>,
 
+    /// The debug probe to use, in the form `VID:PID:<Serial>` or `remote://host:port/<Serial>`
+    #[structopt(long)]
+    probe: Option<DebugProbeSelector>,
+
+    /// The pin layout of an FTDI probe given with `--probe`, for adapters which can't be detected,
+    /// e.g. `esp-prog` or `tigard`
+    #[structopt(long, requires = "probe")]
+    ftdi_layout: Option<String>,
+
     /// The target to be selected.
     #[structopt(short, long)]
     chip: Option<String>,
 
+    /// A board description, which adds memory like external flash to the chip of the board
+    #[structopt(long, parse(from_os_str))]
+    board: Option<PathBuf>,
+
     /// Protocol to use for target connection
     #[structopt(short, long)]
     protocol: Option<String>,
 
+    /// Index of the TAP in the JTAG scan chain to use, starting with the TAP closest to TDO
+    #[structopt(long)]
+    jtag_tap: Option<usize>,
+
     #[structopt(long)]
     connect_under_reset: bool,
+
+    /// Record all transactions with the target to a file, which can be replayed for testing
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
 }
 
 fn main() -> Result<()> {
@@ -120,10 +231,35 @@ fn main() -> Result<()> {
         CLI::List {} => list_connected_devices(),
         CLI::Info { shared } => crate::info::show_info_of_device(&shared),
         CLI::Reset { shared, assert } => reset_target_of_device(&shared, assert),
-        CLI::Debug { shared, exe } => debug(&shared, exe),
+        CLI::Debug {
+            shared,
+            exe,
+            semihosting_root,
+        } => debug(&shared, exe, semihosting_root),
         CLI::Dump { shared, loc, words } => dump_memory(&shared, loc, words),
-        CLI::Download { shared, path } => download_program_fast(&shared, &path),
+        CLI::Download {
+            shared,
+            path,
+            incremental,
+        } => download_program_fast(&shared, &path, incremental),
+        CLI::ReadFlash {
+            shared,
+            path,
+            region,
+            address,
+            size,
+            format,
+        } => read_flash(&shared, &path, region, address.zip(size), format),
+        CLI::Erase {
+            shared,
+            region,
+            address,
+            size,
+            erase_boot_memory,
+        } => erase(&shared, region, address.zip(size), erase_boot_memory),
         CLI::Trace { shared, loc } => trace_u32_on_target(&shared, loc),
+        CLI::Unlock { shared } => unlock_target(&shared),
+        CLI::Serve { address } => serve(&address),
     }
 }
 
@@ -132,10 +268,14 @@ fn list_connected_devices() -> Result<()> {
 
     if !links.is_empty() {
         println!("The following devices were found:");
-        links
-            .iter()
-            .enumerate()
-            .for_each(|(num, link)| println!("[{}]: {:?}", num, link));
+        for (num, link) in links.iter().enumerate() {
+            println!("[{}]: {:?}", num, link);
+
+            // Probes which are in use can't be opened, but they are still listed.
+            if let Ok(mut probe) = link.open() {
+                crate::info::show_probe_capabilities(&mut probe);
+            }
+        }
     } else {
         println!("No devices were found.");
     }
@@ -143,6 +283,32 @@ fn list_connected_devices() -> Result<()> {
     Ok(())
 }
 
+fn unlock_target(shared_options: &SharedOptions) -> Result<()> {
+    let chip = shared_options
+        .chip
+        .as_ref()
+        .ok_or_else(|| anyhow!("The chip has to be specified to unlock it"))?;
+
+    let probe = open_probe(shared_options)?;
+    let probe = configure_probe(probe, shared_options)?;
+
+    probe.unlock(chip)?;
+
+    println!("The chip was unlocked, it might have to be power cycled before it can be used");
+
+    Ok(())
+}
+
+fn serve(address: &str) -> Result<()> {
+    let server = ProbeServer::bind(address)?;
+
+    println!("Serving the connected probes on {}", server.local_addr()?);
+
+    server.run()?;
+
+    Ok(())
+}
+
 fn dump_memory(shared_options: &SharedOptions, loc: u32, words: u32) -> Result<()> {
     with_device(shared_options, |mut session| {
         let mut data = vec![0_u32; words as usize];
@@ -173,9 +339,149 @@ fn dump_memory(shared_options: &SharedOptions, loc: u32, words: u32) -> Result<(
     })
 }
 
-fn download_program_fast(shared_options: &SharedOptions, path: &str) -> Result<()> {
+fn download_program_fast(
+    shared_options: &SharedOptions,
+    path: &str,
+    incremental: bool,
+) -> Result<()> {
     with_device(shared_options, |mut session| {
-        download_file(&mut session, std::path::Path::new(&path), Format::Elf)?;
+        let skipped = Rc::new(Cell::new(0));
+        let progress = {
+            let skipped = skipped.clone();
+            FlashProgress::new(move |event| {
+                if let ProgressEvent::SectorSkipped { .. } = event {
+                    skipped.set(skipped.get() + 1);
+                }
+            })
+        };
+
+        let options = DownloadOptions {
+            progress: Some(&progress),
+            incremental,
+            ..Default::default()
+        };
+
+        download_file_with_options(
+            &mut session,
+            std::path::Path::new(&path),
+            Format::Elf,
+            options,
+        )?;
+
+        if incremental {
+            println!("Skipped {} unchanged sectors", skipped.get());
+        }
+
+        Ok(())
+    })
+}
+
+fn read_flash(
+    shared_options: &SharedOptions,
+    path: &Path,
+    region: Option<String>,
+    range: Option<(u32, u32)>,
+    format: Option<DumpFormat>,
+) -> Result<()> {
+    let format = format
+        .or_else(|| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(|extension| parse_dump_format(extension).ok())
+        })
+        .unwrap_or(DumpFormat::Bin);
+
+    with_device(shared_options, |mut session| {
+        let range = match range {
+            Some((address, size)) => {
+                address..address.checked_add(size).ok_or_else(|| {
+                    anyhow!("The memory range does not fit into the address space")
+                })?
+            }
+            None => {
+                memory_region_range(session.memory_map(), region.as_deref().unwrap_or("flash"))?
+            }
+        };
+        let size = range.end - range.start;
+
+        let instant = Instant::now();
+
+        dump_memory_range(&mut session, range, path, format, DumpOptions::default())?;
+
+        println!("Read {} bytes in {:?}", size, instant.elapsed());
+
+        Ok(())
+    })
+}
+
+fn erase(
+    shared_options: &SharedOptions,
+    region: Option<String>,
+    range: Option<(u32, u32)>,
+    erase_boot_memory: bool,
+) -> Result<()> {
+    with_device(shared_options, |mut session| {
+        let range = match (region, range) {
+            (_, Some((address, size))) => Some(
+                address..address.checked_add(size).ok_or_else(|| {
+                    anyhow!("The memory range does not fit into the address space")
+                })?,
+            ),
+            (Some(region), None) => Some(memory_region_range(session.memory_map(), &region)?),
+            (None, None) => None,
+        };
+
+        let areas = match &range {
+            Some(range) => plan_erase_range(&session, range.clone())?,
+            None => plan_erase_all(&session)?,
+        };
+
+        for area in &areas {
+            println!(
+                "Erasing {:#010x}..{:#010x} ({} sectors{}{})",
+                area.range.start,
+                area.range.end,
+                area.sectors,
+                if area.chip_erase { ", chip erase" } else { "" },
+                if area.is_boot_memory {
+                    ", boot memory"
+                } else {
+                    ""
+                },
+            );
+
+            if area.is_boot_memory && !erase_boot_memory {
+                return Err(anyhow!(
+                    "The chip boots from {:#010x}..{:#010x}, use --erase-boot-memory to erase it",
+                    area.range.start,
+                    area.range.end
+                ));
+            }
+        }
+
+        let erased = Rc::new(Cell::new(0));
+        let progress = {
+            let erased = erased.clone();
+            FlashProgress::new(move |event| {
+                if let ProgressEvent::SectorErased { .. } = event {
+                    erased.set(erased.get() + 1);
+                }
+            })
+        };
+
+        let options = EraseOptions {
+            progress: Some(&progress),
+            erase_boot_memory,
+        };
+
+        let instant = Instant::now();
+
+        match range {
+            Some(range) => erase_range_with_options(&mut session, range, options)?,
+            None => erase_all_with_options(&mut session, options)?,
+        };
+
+        println!("Erased {} sectors in {:?}", erased.get(), instant.elapsed());
 
         Ok(())
     })
@@ -233,7 +539,11 @@ fn trace_u32_on_target(shared_options: &SharedOptions, loc: u32) -> Result<()> {
     })
 }
 
-fn debug(shared_options: &SharedOptions, exe: Option<PathBuf>) -> Result<()> {
+fn debug(
+    shared_options: &SharedOptions,
+    exe: Option<PathBuf>,
+    semihosting_root: Option<PathBuf>,
+) -> Result<()> {
     let runner = |mut session: Session| {
         let cs = Capstone::new()
             .arm()
@@ -250,10 +560,17 @@ fn debug(shared_options: &SharedOptions, exe: Option<PathBuf>) -> Result<()> {
 
         let core = session.core(0)?;
 
+        let mut semihosting = SemihostingHost::new();
+
+        if let Some(root) = &semihosting_root {
+            semihosting = semihosting.with_root(root);
+        }
+
         let mut cli_data = debugger::CliData {
             core,
             debug_info: di,
             capstone: cs,
+            semihosting,
         };
 
         let mut rl = Editor::<()>::new();
@@ -1,4 +1,5 @@
 use colored::*;
+use std::path::PathBuf;
 use std::sync::Mutex;
 use std::{
     process::{self},
@@ -7,6 +8,7 @@ use std::{
 use structopt::StructOpt;
 
 use probe_rs::{config::TargetSelector, Probe};
+use probe_rs_gdb_server::{GdbServerOptions, SemihostingMode};
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -30,6 +32,30 @@ struct Opt {
         help = "Use this flag to override the default GDB connection string (localhost:1337)."
     )]
     gdb_connection_string: Option<String>,
+    #[structopt(
+        name = "semihosting",
+        long = "semihosting",
+        help = "Use this flag to service semihosting requests of the target. Console output is forwarded to GDB."
+    )]
+    semihosting: bool,
+    #[structopt(
+        name = "semihosting-root",
+        long = "semihosting-root",
+        help = "Allow semihosting file access to files inside this directory. Implies --semihosting."
+    )]
+    semihosting_root: Option<PathBuf>,
+    #[structopt(
+        name = "semihosting-gdb",
+        long = "semihosting-gdb",
+        help = "Use this flag to forward semihosting file and console I/O to GDB, using the File-I/O protocol."
+    )]
+    semihosting_gdb: bool,
+    #[structopt(
+        name = "flash-on-run",
+        long = "flash-on-run",
+        help = "Use this flag to flash the program when it is started with 'run' in extended-remote mode."
+    )]
+    flash_on_run: bool,
 }
 
 fn main() {
@@ -86,6 +112,21 @@ fn main_try() -> Result<(), failure::Error> {
             .reset_and_halt(Duration::from_millis(100))?;
     }
 
+    let semihosting = if opt.semihosting_gdb {
+        SemihostingMode::Gdb
+    } else if opt.semihosting || opt.semihosting_root.is_some() {
+        SemihostingMode::Host {
+            root: opt.semihosting_root,
+        }
+    } else {
+        SemihostingMode::Disabled
+    };
+
+    let options = GdbServerOptions {
+        semihosting,
+        flash_on_run: opt.flash_on_run,
+    };
+
     let gdb_connection_string = opt
         .gdb_connection_string
         .or_else(|| Some("localhost:1337".to_string()));
@@ -94,7 +135,8 @@ fn main_try() -> Result<(), failure::Error> {
         "Firing up GDB stub at {}",
         gdb_connection_string.as_ref().unwrap()
     );
-    if let Err(e) = probe_rs_gdb_server::run(gdb_connection_string, &session) {
+    if let Err(e) = probe_rs_gdb_server::run_with_options(gdb_connection_string, &session, &options)
+    {
         eprintln!("During the execution of GDB an error was encountered:");
         eprintln!("{:?}", e);
     }
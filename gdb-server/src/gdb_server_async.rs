@@ -9,30 +9,57 @@ use futures::channel::mpsc;
 use gdb_protocol::packet::CheckedPacket;
 use probe_rs::Session;
 
+use crate::reader::AckMode;
+use crate::semihosting::SemihostingMode;
+
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 type Sender<T> = mpsc::UnboundedSender<T>;
 type Receiver<T> = mpsc::UnboundedReceiver<T>;
 
 const CONNECTION_STRING: &str = "127.0.0.1:1337";
 
+/// Options which control the behaviour of the GDB stub.
+#[derive(Debug, Clone, Default)]
+pub struct GdbServerOptions {
+    /// How semihosting requests of the target are handled.
+    pub semihosting: SemihostingMode,
+    /// Flash the program before it is started using `run` in extended mode.
+    ///
+    /// The file name of the program is determined by GDB, see `set remote exec-file`.
+    pub flash_on_run: bool,
+}
+
 /// This is the main entrypoint which we will call to start the GDB stub.
 /// This function is blocking. If you would like to use it concurently to other users of the session,
 /// please use a thread.
 pub fn run(connection_string: Option<impl Into<String>>, session: &Mutex<Session>) -> Result<()> {
+    run_with_options(connection_string, session, &GdbServerOptions::default())
+}
+
+/// Start the GDB stub, like [run], using the given options.
+pub fn run_with_options(
+    connection_string: Option<impl Into<String>>,
+    session: &Mutex<Session>,
+    options: &GdbServerOptions,
+) -> Result<()> {
     let connection_string = connection_string
         .map(|cs| cs.into())
         .unwrap_or_else(|| CONNECTION_STRING.to_owned());
     println!("GDB stub listening on {}", connection_string);
-    task::block_on(accept_loop(connection_string, session))
+    task::block_on(accept_loop(connection_string, session, options))
 }
 
 /// This function accepts any incomming connection.
-async fn accept_loop(addr: impl ToSocketAddrs, session: &Mutex<Session>) -> Result<()> {
+async fn accept_loop(
+    addr: impl ToSocketAddrs,
+    session: &Mutex<Session>,
+    options: &GdbServerOptions,
+) -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
 
     let mut incoming = listener.incoming();
     while let Some(stream) = incoming.next().await {
-        if let Err(e) = handle_connection(stream?, session).await {
+        if let Err(e) = handle_connection(stream?, session, options).await {
             eprintln!(
                 "An error with the current connection has been encountered. It has been closed."
             );
@@ -43,7 +70,11 @@ async fn accept_loop(addr: impl ToSocketAddrs, session: &Mutex<Session>) -> Resu
 }
 
 /// Handle a single connection of a client
-async fn handle_connection(stream: TcpStream, session: &Mutex<Session>) -> Result<()> {
+async fn handle_connection(
+    stream: TcpStream,
+    session: &Mutex<Session>,
+    options: &GdbServerOptions,
+) -> Result<()> {
     let (packet_stream_sender, packet_stream_receiver) = mpsc::unbounded();
     let (tbd_sender, tbd_receiver) = mpsc::unbounded();
 
@@ -55,7 +86,7 @@ async fn handle_connection(stream: TcpStream, session: &Mutex<Session>) -> Resul
         packet_stream_receiver,
     ));
 
-    super::worker::worker(tbd_receiver, packet_stream_sender, session).await?;
+    super::worker::worker(tbd_receiver, packet_stream_sender, session, options).await?;
 
     inbound_broker_handle.await?;
 
@@ -72,6 +103,7 @@ async fn inbound_broker_loop(
 
     let mut buffer = vec![];
     let mut tmp_buf = [0; 1024];
+    let mut ack_mode = AckMode::default();
 
     loop {
         let mut packet_stream_2 = packet_stream_2.next().fuse();
@@ -80,7 +112,7 @@ async fn inbound_broker_loop(
         futures::select! {
             packet = packet_stream_2 => {
                 if let Some(packet) = packet {
-                    super::writer::writer(packet, &mut stream, &packet_stream, &mut buffer).await?
+                    super::writer::writer(packet, &mut stream, &packet_stream, &mut buffer, &mut ack_mode).await?
                 }
             },
             n = read => {
@@ -92,7 +124,7 @@ async fn inbound_broker_loop(
                     Ok(n) => {
                         buffer.extend(&tmp_buf[0..n]);
                         log::info!("Current buf {}", String::from_utf8_lossy(&buffer));
-                        super::reader::reader(&mut stream, &packet_stream, &mut buffer).await?
+                        super::reader::reader(&mut stream, &packet_stream, &mut buffer, &mut ack_mode).await?
                     },
                     Err(_e) => {
 
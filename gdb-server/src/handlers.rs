@@ -1,8 +1,26 @@
-use probe_rs::{config::MemoryRegion, Core, CoreStatus, MemoryInterface, Session};
+use crate::semihosting::SemihostingState;
+use crate::stop_reply;
+use probe_rs::{
+    config::MemoryRegion,
+    flashing::{download_file, Format},
+    Core, CoreStatus, HaltReason, MemoryInterface, Session,
+};
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// The maximum size of a packet, which is announced to GDB.
+pub(crate) const MAX_PACKET_SIZE: usize = 0x4000;
+
 pub(crate) fn q_supported() -> Option<String> {
-    Some("PacketSize=2048;swbreak-;hwbreak+;vContSupported+;qXfer:memory-map:read+".into())
+    Some(format!(
+        "PacketSize={:x};QStartNoAckMode+;swbreak+;hwbreak+;vContSupported+;qXfer:memory-map:read+",
+        MAX_PACKET_SIZE
+    ))
+}
+
+pub(crate) fn start_no_ack_mode() -> Option<String> {
+    // Acknowledgements are disabled by the reader once this reply is acknowledged.
+    Some("OK".into())
 }
 
 pub(crate) fn reply_empty() -> Option<String> {
@@ -13,8 +31,8 @@ pub(crate) fn q_attached() -> Option<String> {
     Some("1".into())
 }
 
-pub(crate) fn halt_reason() -> Option<String> {
-    Some("S05".into())
+pub(crate) fn halt_reason(mut core: Core) -> Option<String> {
+    Some(stop_reply::current_stop_reply(&mut core))
 }
 
 pub(crate) fn read_general_registers(mut core: Core) -> Option<String> {
@@ -119,16 +137,13 @@ pub(crate) fn read_register(register: u32, mut core: Core) -> Option<String> {
     Some(register_value)
 }
 
-pub(crate) fn read_memory(address: u32, length: u32, mut core: Core) -> Option<String> {
-    let mut readback_data = vec![0u8; length as usize];
-    match core.read_8(address, &mut readback_data) {
-        Ok(_) => Some(
-            readback_data
-                .iter()
-                .map(|s| format!("{:02x?}", s))
-                .collect::<Vec<String>>()
-                .join(""),
-        ),
+pub(crate) fn read_memory(address: u32, length: u32, mut core: impl ReadMemory) -> Option<String> {
+    // Each byte is sent as two hex digits. GDB accepts replies with less data than requested.
+    let length = (length as usize).min(MAX_PACKET_SIZE / 2);
+
+    let mut readback_data = vec![0u8; length];
+    match read_memory_block(&mut core, address, &mut readback_data) {
+        Ok(_) => Some(hex::encode(readback_data)),
         // We have no clue if this is the right error code since GDB doesn't feel like docs.
         // We just assume Linux ERRNOs and pick a fitting one: https://gist.github.com/greggyNapalm/2413028#file-gistfile1-txt-L138
         // This seems to work in practice and seems to be the way to do stuff around GDB.
@@ -136,6 +151,71 @@ pub(crate) fn read_memory(address: u32, length: u32, mut core: Core) -> Option<S
     }
 }
 
+/// Read memory for the binary `x` packet, the reply is the data prefixed with `b`.
+pub(crate) fn read_memory_binary(address: u32, length: u32, mut core: impl ReadMemory) -> Vec<u8> {
+    // GDB accepts replies with less data than requested.
+    let length = (length as usize).min(MAX_PACKET_SIZE - 1);
+
+    let mut readback_data = vec![0u8; length + 1];
+    readback_data[0] = b'b';
+
+    match read_memory_block(&mut core, address, &mut readback_data[1..]) {
+        Ok(_) => readback_data,
+        Err(_e) => b"E79".to_vec(),
+    }
+}
+
+/// The memory accesses which are used to read memory for GDB.
+pub(crate) trait ReadMemory {
+    fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), probe_rs::Error>;
+    fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), probe_rs::Error>;
+}
+
+impl ReadMemory for Core<'_> {
+    fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), probe_rs::Error> {
+        MemoryInterface::read_8(self, address, data)
+    }
+
+    fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), probe_rs::Error> {
+        MemoryInterface::read_32(self, address, data)
+    }
+}
+
+/// Read `data.len()` bytes of memory, starting at `address`.
+///
+/// The word aligned part is read with a single `read_32` call, which is
+/// a lot faster than reading single bytes with most probes.
+fn read_memory_block(
+    core: &mut impl ReadMemory,
+    address: u32,
+    data: &mut [u8],
+) -> Result<(), probe_rs::Error> {
+    let head_length = (((4 - address % 4) % 4) as usize).min(data.len());
+
+    let (head, rest) = data.split_at_mut(head_length);
+    let word_count = rest.len() / 4;
+    let (words, tail) = rest.split_at_mut(word_count * 4);
+
+    if !head.is_empty() {
+        core.read_8(address, head)?;
+    }
+
+    if word_count > 0 {
+        let mut buffer = vec![0u32; word_count];
+        core.read_32(address + head_length as u32, &mut buffer)?;
+
+        for (bytes, word) in words.chunks_exact_mut(4).zip(buffer) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    if !tail.is_empty() {
+        core.read_8(address + (head_length + words.len()) as u32, tail)?;
+    }
+
+    Ok(())
+}
+
 pub(crate) fn vcont_supported() -> Option<String> {
     // It is important to announce support for both
     // the variants with and without signal support,
@@ -152,9 +232,24 @@ pub(crate) fn host_info() -> Option<String> {
 }
 
 pub(crate) fn run(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
-    core.run().unwrap();
-    *awaits_halt = true;
-    None
+    resume(&mut core, awaits_halt)
+}
+
+/// Resume the core, and wait for it to halt.
+///
+/// The stop reply is sent once the core halts, an error is replied immediately.
+fn resume(core: &mut Core, awaits_halt: &mut bool) -> Option<String> {
+    match core.run() {
+        Ok(()) => {
+            *awaits_halt = true;
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to resume the core: {}", e);
+            *awaits_halt = false;
+            Some("E01".into())
+        }
+    }
 }
 
 pub(crate) fn stop(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
@@ -166,7 +261,7 @@ pub(crate) fn stop(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
 pub(crate) fn step(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
     core.step().unwrap();
     *awaits_halt = false;
-    Some("S05".into())
+    Some(stop_reply::stop_reply(&mut core, HaltReason::Step))
 }
 
 pub(crate) fn insert_hardware_break(address: u32, _kind: u32, mut core: Core) -> Option<String> {
@@ -223,19 +318,163 @@ pub(crate) fn get_memory_map(session: &Session) -> Option<String> {
 pub(crate) fn user_halt(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
     let _ = core.halt(Duration::from_millis(100));
     *awaits_halt = false;
-    Some("T02".into())
+    Some(stop_reply::stop_reply(&mut core, HaltReason::Request))
 }
 
-pub(crate) fn detach(break_due: &mut bool) -> Option<String> {
-    *break_due = true;
+pub(crate) fn detach(
+    mut core: Core,
+    extended_mode: bool,
+    awaits_halt: &mut bool,
+    break_due: &mut bool,
+) -> Option<String> {
+    // The target keeps running after GDB has detached.
+    if let Err(e) = core.run() {
+        log::warn!("Failed to resume the core on detach: {}", e);
+    }
+    *awaits_halt = false;
+
+    // In extended mode, GDB stays connected and can attach again.
+    if !extended_mode {
+        *break_due = true;
+    }
+
     Some("OK".into())
 }
 
-pub(crate) fn reset_halt(mut core: Core) -> Option<String> {
-    let _cpu_info = core.reset_and_halt(Duration::from_millis(400));
+pub(crate) fn enable_extended_mode(extended_mode: &mut bool) -> Option<String> {
+    *extended_mode = true;
     Some("OK".into())
 }
 
+pub(crate) fn attach(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
+    match core.halt(Duration::from_millis(100)) {
+        Ok(_) => {
+            *awaits_halt = false;
+            Some("S05".into())
+        }
+        Err(e) => {
+            log::warn!("Failed to halt the core on attach: {}", e);
+            Some("E01".into())
+        }
+    }
+}
+
+pub(crate) fn restart(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
+    *awaits_halt = false;
+
+    // There is no reply to the `R` packet, unless it failed.
+    reset_and_halt(&mut core).err()
+}
+
+pub(crate) fn arguments(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
+    *awaits_halt = false;
+
+    // The program is restarted, but the arguments are ignored.
+    Some(
+        reset_and_halt(&mut core)
+            .err()
+            .unwrap_or_else(|| "OK".into()),
+    )
+}
+
+/// Reset and halt the core, or return the error reply if this failed.
+fn reset_and_halt(core: &mut Core) -> Result<(), String> {
+    match core.reset_and_halt(Duration::from_millis(400)) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            log::warn!("Failed to reset the core: {}", e);
+            Err("E01".into())
+        }
+    }
+}
+
+pub(crate) fn run_program(
+    session: &mut Session,
+    filename: &[u8],
+    flash: bool,
+    awaits_halt: &mut bool,
+) -> Option<String> {
+    *awaits_halt = false;
+
+    if flash && !filename.is_empty() {
+        let path = PathBuf::from(String::from_utf8_lossy(filename).into_owned());
+
+        log::info!("Flashing {}", path.display());
+
+        if let Err(e) = download_file(session, &path, Format::Elf) {
+            log::warn!("Failed to flash {}: {}", path.display(), e);
+            return Some("E01".into());
+        }
+    }
+
+    match session
+        .core(0)
+        .and_then(|mut core| core.reset_and_halt(Duration::from_millis(400)))
+    {
+        Ok(_) => Some("S05".into()),
+        Err(e) => {
+            log::warn!("Failed to reset the core: {}", e);
+            Some("E01".into())
+        }
+    }
+}
+
+pub(crate) fn kill(
+    mut core: Core,
+    extended_mode: bool,
+    awaits_halt: &mut bool,
+    break_due: &mut bool,
+) -> Option<String> {
+    *awaits_halt = false;
+
+    // Outside of extended mode, GDB closes the connection after a kill.
+    if !extended_mode {
+        *break_due = true;
+    }
+
+    // There is no reply to the `k` packet, unless it failed.
+    reset_and_halt(&mut core).err()
+}
+
+pub(crate) fn v_kill(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
+    *awaits_halt = false;
+    Some(
+        reset_and_halt(&mut core)
+            .err()
+            .unwrap_or_else(|| "OK".into()),
+    )
+}
+
+pub(crate) fn file_io_reply(
+    result: i64,
+    errno: Option<u32>,
+    interrupted: bool,
+    mut core: Core,
+    semihosting: &mut SemihostingState,
+    awaits_halt: &mut bool,
+) -> Option<String> {
+    match semihosting.complete_forwarded(&mut core, result, errno) {
+        Ok(true) => (),
+        Ok(false) => {
+            log::warn!("Received a File-I/O reply, but no request is pending.");
+            return reply_empty();
+        }
+        Err(e) => {
+            log::warn!("Failed to complete semihosting request: {}", e);
+            return Some("E01".into());
+        }
+    }
+
+    if interrupted {
+        // GDB received a Ctrl-C while handling the request, so we
+        // report the core as stopped by SIGINT instead of resuming it.
+        *awaits_halt = false;
+        return Some("T02".into());
+    }
+
+    resume(&mut core, awaits_halt)
+}
+
 fn gdb_sanitize_file(data: &[u8], offset: u32, len: u32) -> Vec<u8> {
     let offset = offset as usize;
     let len = len as usize;
@@ -256,3 +495,86 @@ fn gdb_sanitize_file(data: &[u8], offset: u32, len: u32) -> Vec<u8> {
         trimmed_data
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{read_memory, read_memory_binary, ReadMemory, MAX_PACKET_SIZE};
+    use probe_rs::Error;
+
+    /// Fake memory, where each byte contains the lower 8 bits of its address.
+    ///
+    /// Every access counts as a single probe transaction, except for byte reads,
+    /// which are performed one byte at a time by many probes.
+    #[derive(Default)]
+    struct FakeMemory {
+        transactions: usize,
+    }
+
+    impl ReadMemory for &mut FakeMemory {
+        fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+            for (index, byte) in data.iter_mut().enumerate() {
+                self.transactions += 1;
+                *byte = (address + index as u32) as u8;
+            }
+            Ok(())
+        }
+
+        fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), Error> {
+            assert_eq!(address % 4, 0, "Unaligned 32 bit read");
+            self.transactions += 1;
+            for (index, word) in data.iter_mut().enumerate() {
+                let address = address + index as u32 * 4;
+                *word = u32::from_le_bytes([
+                    address as u8,
+                    (address + 1) as u8,
+                    (address + 2) as u8,
+                    (address + 3) as u8,
+                ]);
+            }
+            Ok(())
+        }
+    }
+
+    fn expected_memory(address: u32, length: u32) -> Vec<u8> {
+        (address..address + length).map(|a| a as u8).collect()
+    }
+
+    #[test]
+    fn read_memory_unaligned() {
+        let mut memory = FakeMemory::default();
+
+        let reply = read_memory(0x2000_0003, 10, &mut memory).unwrap();
+
+        assert_eq!(reply, hex::encode(expected_memory(0x2000_0003, 10)));
+
+        // 1 unaligned byte, 2 words, 1 unaligned byte.
+        assert_eq!(memory.transactions, 3);
+    }
+
+    #[test]
+    fn read_aligned_memory_at_once() {
+        const LENGTH: u32 = 0x2000;
+
+        let mut memory = FakeMemory::default();
+
+        let reply = read_memory_binary(0x2000_0000, LENGTH, &mut memory);
+
+        assert_eq!(reply[0], b'b');
+        assert_eq!(&reply[1..], &expected_memory(0x2000_0000, LENGTH)[..]);
+
+        // The whole aligned block is read in a single transaction,
+        // instead of one transaction per byte.
+        assert_eq!(memory.transactions, 1);
+    }
+
+    #[test]
+    fn read_memory_is_limited_to_packet_size() {
+        let mut memory = FakeMemory::default();
+
+        let reply = read_memory(0x2000_0000, u32::MAX, &mut memory).unwrap();
+        assert_eq!(reply.len(), MAX_PACKET_SIZE);
+
+        let reply = read_memory_binary(0x2000_0000, u32::MAX, &mut memory);
+        assert_eq!(reply.len(), MAX_PACKET_SIZE);
+    }
+}
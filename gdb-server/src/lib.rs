@@ -2,9 +2,13 @@
 
 mod gdb_server_async;
 mod handlers;
+mod monitor;
 mod parser;
 mod reader;
+mod semihosting;
+mod stop_reply;
 mod worker;
 mod writer;
 
-pub use gdb_server_async::run;
+pub use gdb_server_async::{run, run_with_options, GdbServerOptions};
+pub use semihosting::SemihostingMode;
@@ -0,0 +1,94 @@
+//! Minimal decoder for the ITM packet stream received via SWO.
+
+/// Extracts the data written to ITM stimulus port 0 from the raw SWO data.
+///
+/// All other packets are discarded. Incomplete packets at the end of the
+/// data are kept, and decoded once the rest of the packet is received.
+#[derive(Debug, Default)]
+pub(crate) struct ItmDecoder {
+    buffer: Vec<u8>,
+}
+
+impl ItmDecoder {
+    /// Decode `data`, and return the payload of all complete stimulus port 0 packets.
+    pub(crate) fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(data);
+
+        let mut output = Vec::new();
+        let mut position = 0;
+
+        while position < self.buffer.len() {
+            let header = self.buffer[position];
+
+            let packet_length = match header {
+                // Synchronization packets consist of zeros, terminated by 0x80.
+                0x00 | 0x80 => 1,
+                // Source packets, the lower two bits encode the payload size.
+                _ if header & 0b11 != 0 => {
+                    let payload_length = match header & 0b11 {
+                        0b01 => 1,
+                        0b10 => 2,
+                        _ => 4,
+                    };
+
+                    if position + 1 + payload_length > self.buffer.len() {
+                        break;
+                    }
+
+                    // Software source (bit 2 cleared) for stimulus port 0.
+                    if header >> 2 == 0 {
+                        output.extend_from_slice(
+                            &self.buffer[position + 1..position + 1 + payload_length],
+                        );
+                    }
+
+                    1 + payload_length
+                }
+                // Protocol packets without continuation, e.g. overflow.
+                _ if header & 0x80 == 0 => 1,
+                // Protocol packets with continuation, e.g. timestamps.
+                _ => {
+                    match self.buffer[position + 1..]
+                        .iter()
+                        .position(|byte| byte & 0x80 == 0)
+                    {
+                        Some(end) => end + 2,
+                        None => break,
+                    }
+                }
+            };
+
+            position += packet_length;
+        }
+
+        self.buffer.drain(..position);
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ItmDecoder;
+
+    #[test]
+    fn decode_stimulus_port_0() {
+        let mut decoder = ItmDecoder::default();
+
+        // Sync, 1 byte on port 0, 4 bytes on port 1, 2 bytes on port 0
+        let data = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01, b'a', 0x0b, 1, 2, 3, 4, 0x02, b'b', b'c',
+        ];
+
+        assert_eq!(decoder.feed(&data), b"abc");
+    }
+
+    #[test]
+    fn decode_split_packet() {
+        let mut decoder = ItmDecoder::default();
+
+        assert_eq!(decoder.feed(&[0x03, b'a', b'b']), b"");
+        assert_eq!(decoder.feed(&[b'c', b'd', 0xc0, 0x81]), b"abcd");
+        assert_eq!(decoder.feed(&[0x01, 0x01, b'e']), b"e");
+    }
+}
@@ -0,0 +1,451 @@
+//! Handling of `monitor` commands, which GDB sends using `qRcmd` packets.
+//!
+//! The output of a command is sent to GDB as console output,
+//! using `O` packets, before the final reply is sent.
+mod itm;
+mod rtt;
+
+use anyhow::{anyhow, bail, Result};
+use probe_rs::architecture::arm::SwoConfig;
+use probe_rs::flashing::{self, EraseOptions, FlashError};
+use probe_rs::{CoreType, MemoryInterface, Session};
+use std::fmt::Write;
+use std::time::Duration;
+
+use itm::ItmDecoder;
+use rtt::Rtt;
+
+/// Address of the Debug Exception and Monitor Control Register of Cortex-M cores.
+const DEMCR: u32 = 0xE000_EDFC;
+
+/// The vector catch bits for faults in the DEMCR register.
+const DEMCR_VC_HARDERR: u32 = 1 << 10;
+const DEMCR_VC_FAULTS_V7M: u32 = 0b111_1111 << 4;
+const DEMCR_VC_FAULTS_V8M: u32 = 0b1111_1111 << 4;
+
+/// Maximum number of words which can be read by `mdw`, or written by `mww`.
+const MAX_WORD_COUNT: u32 = 1024;
+
+/// Monitor state of a single GDB connection.
+#[derive(Default)]
+pub(crate) struct MonitorState {
+    swo: Option<ItmDecoder>,
+    rtt: Option<Rtt>,
+}
+
+impl MonitorState {
+    /// Read the available SWO data, and return the data written to ITM stimulus port 0.
+    ///
+    /// Returns an empty buffer if SWO has not been started using `monitor swo start`.
+    pub(crate) fn poll_swo(&mut self, session: &mut Session) -> Result<Vec<u8>> {
+        match &mut self.swo {
+            Some(decoder) => {
+                let data = session.read_swo()?;
+                Ok(decoder.feed(&data))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// The result of a monitor command.
+pub(crate) struct MonitorResponse {
+    /// Output which has to be printed on the GDB console.
+    pub(crate) output: String,
+    /// The reply to the `qRcmd` packet.
+    pub(crate) reply: String,
+}
+
+struct MonitorContext<'a> {
+    session: &'a mut Session,
+    state: &'a mut MonitorState,
+    output: String,
+}
+
+struct MonitorCommand {
+    name: &'static str,
+    usage: &'static str,
+    help_text: &'static str,
+
+    function: fn(&mut MonitorContext, args: &[&str]) -> Result<()>,
+}
+
+const COMMANDS: &[MonitorCommand] = &[
+    MonitorCommand {
+        name: "reset",
+        usage: "reset [halt|run]",
+        help_text: "Reset the core, and halt it at the reset vector (default) or let it run",
+        function: |context, args| {
+            let mut core = context.session.core(0)?;
+
+            match args {
+                [] | ["halt"] => {
+                    let cpu_info = core.reset_and_halt(Duration::from_millis(400))?;
+                    writeln!(context.output, "Core halted at {:#010x}", cpu_info.pc)?;
+                }
+                ["run"] => {
+                    core.reset()?;
+                    writeln!(context.output, "Core reset and running")?;
+                }
+                _ => return Err(usage("reset")),
+            }
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "halt",
+        usage: "halt",
+        help_text: "Halt the core",
+        function: |context, _args| {
+            let cpu_info = context.session.core(0)?.halt(Duration::from_millis(100))?;
+            writeln!(context.output, "Core halted at {:#010x}", cpu_info.pc)?;
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "speed",
+        usage: "speed [<khz>]",
+        help_text: "Show or set the communication speed of the probe",
+        function: |context, args| {
+            match args {
+                [] => (),
+                [speed] => {
+                    let speed = speed.parse().map_err(|_| usage("speed"))?;
+                    context.session.set_speed(speed)?;
+                }
+                _ => return Err(usage("speed")),
+            }
+
+            writeln!(
+                context.output,
+                "Probe speed: {} kHz",
+                context.session.speed_khz()
+            )?;
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "mdw",
+        usage: "mdw <address> [<count>]",
+        help_text: "Read 32 bit words from memory",
+        function: |context, args| {
+            let (address, count) = match args {
+                [address] => (parse_number(address)?, 1),
+                [address, count] => (parse_number(address)?, parse_number(count)?),
+                _ => return Err(usage("mdw")),
+            };
+
+            if count > MAX_WORD_COUNT {
+                bail!("At most {} words can be read at once", MAX_WORD_COUNT);
+            }
+
+            let mut data = vec![0u32; count as usize];
+            context.session.core(0)?.read_32(address, &mut data)?;
+
+            for (index, line) in data.chunks(4).enumerate() {
+                write!(context.output, "{:#010x}:", address + index as u32 * 16)?;
+
+                for word in line {
+                    write!(context.output, " {:08x}", word)?;
+                }
+
+                writeln!(context.output)?;
+            }
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "mww",
+        usage: "mww <address> <value> [<count>]",
+        help_text: "Write a 32 bit word to memory, optionally repeated",
+        function: |context, args| {
+            let (address, value, count) = match args {
+                [address, value] => (parse_number(address)?, parse_number(value)?, 1),
+                [address, value, count] => (
+                    parse_number(address)?,
+                    parse_number(value)?,
+                    parse_number(count)?,
+                ),
+                _ => return Err(usage("mww")),
+            };
+
+            if count > MAX_WORD_COUNT {
+                bail!("At most {} words can be written at once", MAX_WORD_COUNT);
+            }
+
+            let data = vec![value; count as usize];
+            context.session.core(0)?.write_32(address, &data)?;
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "erase",
+        usage: "erase [all | <address> <length>] [boot]",
+        help_text: "Erase the whole flash, or the sectors in the given range, and the memory the chip boots from with `boot`",
+        function: |context, args| {
+            let (args, erase_boot_memory) = match args {
+                [args @ .., "boot"] => (args, true),
+                _ => (args, false),
+            };
+            let options = || EraseOptions {
+                erase_boot_memory,
+                ..Default::default()
+            };
+
+            match args {
+                [] | ["all"] => {
+                    flashing::erase_all_with_options(context.session, options())
+                        .map_err(boot_memory_hint)?;
+                    writeln!(context.output, "Flash erased")?;
+                }
+                [address, length] => {
+                    let address = parse_number(address)?;
+                    let length = parse_number(length)?;
+
+                    let areas = flashing::erase_range_with_options(
+                        context.session,
+                        address..address.saturating_add(length),
+                        options(),
+                    )
+                    .map_err(boot_memory_hint)?;
+
+                    for area in areas {
+                        writeln!(
+                            context.output,
+                            "Erased flash from {:#010x} to {:#010x}",
+                            area.range.start, area.range.end
+                        )?;
+                    }
+                }
+                _ => return Err(usage("erase")),
+            }
+
+            // The flash algorithm leaves the core in an undefined state.
+            context
+                .session
+                .core(0)?
+                .reset_and_halt(Duration::from_millis(400))?;
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "swo",
+        usage: "swo start <baud> <tpiu clock hz> | stop",
+        help_text: "Forward ITM stimulus port 0 data received via SWO to GDB",
+        function: |context, args| {
+            match args {
+                ["start", baud, clock] => {
+                    let baud = parse_number(baud)?;
+                    let clock = parse_number(clock)?;
+
+                    context
+                        .session
+                        .setup_swv(&SwoConfig::new(clock).set_baud(baud))?;
+                    context.state.swo = Some(ItmDecoder::default());
+                    writeln!(context.output, "SWO started at {} Bd", baud)?;
+                }
+                ["stop"] => {
+                    context.session.disable_swv()?;
+                    context.state.swo = None;
+                    writeln!(context.output, "SWO stopped")?;
+                }
+                _ => return Err(usage("swo")),
+            }
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "vector_catch",
+        usage: "vector_catch hard|all|none",
+        help_text: "Halt the core when a fault exception occurs",
+        function: |context, args| {
+            let core_type = context
+                .session
+                .list_cores()
+                .first()
+                .map(|(_, core_type)| *core_type);
+
+            let faults = match core_type {
+                Some(CoreType::M0) => DEMCR_VC_HARDERR,
+                Some(CoreType::M3) | Some(CoreType::M4) | Some(CoreType::M7) => DEMCR_VC_FAULTS_V7M,
+                Some(CoreType::M33) => DEMCR_VC_FAULTS_V8M,
+                _ => bail!("Vector catch is only supported on Cortex-M cores"),
+            };
+
+            let catch = match args {
+                ["hard"] => DEMCR_VC_HARDERR,
+                ["all"] => faults,
+                ["none"] => 0,
+                _ => return Err(usage("vector_catch")),
+            };
+
+            let mut core = context.session.core(0)?;
+            let demcr = core.read_word_32(DEMCR)?;
+            core.write_word_32(DEMCR, (demcr & !faults) | catch)?;
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "rtt",
+        usage: "rtt [<channel>]",
+        help_text: "Print the data available in the RTT up channels",
+        function: |context, args| {
+            let channel = match args {
+                [] => None,
+                [channel] => Some(channel.parse::<usize>().map_err(|_| usage("rtt"))?),
+                _ => return Err(usage("rtt")),
+            };
+
+            if context.state.rtt.is_none() {
+                let rtt = Rtt::attach(context.session)?;
+                writeln!(
+                    context.output,
+                    "Found RTT control block at {:#010x}",
+                    rtt.address()
+                )?;
+                context.state.rtt = Some(rtt);
+            }
+
+            let rtt = context.state.rtt.as_mut().unwrap();
+            let mut core = context.session.core(0)?;
+
+            for (number, name, data) in rtt.read_up_channels(&mut core)? {
+                if channel.map(|channel| channel == number).unwrap_or(true) {
+                    writeln!(context.output, "Channel {} ({}):", number, name)?;
+                    context.output.push_str(&String::from_utf8_lossy(&data));
+                    if !data.ends_with(b"\n") {
+                        writeln!(context.output)?;
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    },
+    MonitorCommand {
+        name: "info",
+        usage: "info probe",
+        help_text: "Show information about the probe and target",
+        function: |context, args| {
+            match args {
+                ["probe"] => {
+                    writeln!(context.output, "Probe: {}", context.session.probe_name())?;
+                    writeln!(context.output, "Speed: {} kHz", context.session.speed_khz())?;
+                    writeln!(context.output, "Target: {}", context.session.target().name)?;
+                    writeln!(
+                        context.output,
+                        "Architecture: {:?}",
+                        context.session.architecture()
+                    )?;
+                }
+                _ => return Err(usage("info")),
+            }
+
+            Ok(())
+        },
+    },
+];
+
+/// Execute the monitor command `command`.
+pub(crate) fn monitor(
+    session: &mut Session,
+    state: &mut MonitorState,
+    command: &[u8],
+) -> MonitorResponse {
+    let command = String::from_utf8_lossy(command);
+    let mut parts = command.split_whitespace();
+
+    let mut context = MonitorContext {
+        session,
+        state,
+        output: String::new(),
+    };
+
+    let name = parts.next().unwrap_or("help");
+
+    let result = match COMMANDS.iter().find(|c| c.name == name) {
+        // Special case for inbuilt help
+        None if name == "help" => help(&mut context.output),
+        Some(command) => {
+            let args: Vec<&str> = parts.collect();
+            (command.function)(&mut context, &args)
+        }
+        None => Err(anyhow!(
+            "Unknown monitor command '{}'\nUse 'monitor help' for a list of commands",
+            name
+        )),
+    };
+
+    let mut output = context.output;
+
+    let reply = match result {
+        Ok(()) => "OK".to_string(),
+        Err(e) => {
+            log::debug!("Monitor command '{}' failed: {:?}", command, e);
+            let _ = writeln!(output, "Error: {}", e);
+            "E01".to_string()
+        }
+    };
+
+    MonitorResponse { output, reply }
+}
+
+fn help(output: &mut String) -> Result<()> {
+    writeln!(output, "The following monitor commands are available:")?;
+
+    for command in COMMANDS {
+        writeln!(output, "  {:<36} {}", command.usage, command.help_text)?;
+    }
+
+    Ok(())
+}
+
+fn usage(name: &str) -> anyhow::Error {
+    match COMMANDS.iter().find(|c| c.name == name) {
+        Some(command) => anyhow!("Usage: monitor {}", command.usage),
+        None => anyhow!("Invalid arguments"),
+    }
+}
+
+/// Explain how to erase the memory the chip boots from, if erasing it was refused.
+fn boot_memory_hint(error: FlashError) -> anyhow::Error {
+    match error {
+        FlashError::BootMemoryNotConfirmed { start, end } => anyhow!(
+            "The chip boots from {:#010x}..{:#010x}, add `boot` to erase it",
+            start,
+            end
+        ),
+        error => error.into(),
+    }
+}
+
+/// Parse a number, either in decimal or as hexadecimal with a `0x` prefix.
+fn parse_number(input: &str) -> Result<u32> {
+    let result = match input.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => input.parse(),
+    };
+
+    result.map_err(|_| anyhow!("Invalid number '{}'", input))
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_number;
+
+    #[test]
+    fn parse_decimal_and_hex_numbers() {
+        assert_eq!(parse_number("1024").unwrap(), 1024);
+        assert_eq!(parse_number("0x20000000").unwrap(), 0x2000_0000);
+        assert!(parse_number("0xzz").is_err());
+        assert!(parse_number("").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Reading of the up channels of a SEGGER RTT control block.
+
+use anyhow::{anyhow, bail, Result};
+use probe_rs::config::MemoryRegion;
+use probe_rs::{Core, MemoryInterface, Session};
+
+/// The ID at the start of the RTT control block.
+const RTT_ID: &[u8] = b"SEGGER RTT\0";
+
+/// Size of the ID field of the control block.
+const RTT_ID_SIZE: u32 = 16;
+
+/// Size of a single channel descriptor in the control block.
+const CHANNEL_SIZE: u32 = 24;
+
+/// Upper bound for the number of channels, used to reject invalid control blocks.
+const MAX_CHANNELS: u32 = 32;
+
+/// Upper bound for the size of a channel buffer, used to reject invalid control blocks.
+const MAX_BUFFER_SIZE: u32 = 0x10_0000;
+
+/// Maximum length of a channel name.
+const MAX_NAME_LENGTH: usize = 32;
+
+/// Size of the chunks in which RAM is searched for the control block.
+const SCAN_CHUNK_SIZE: u32 = 1024;
+
+/// An RTT control block in target memory.
+#[derive(Debug)]
+pub(crate) struct Rtt {
+    address: u32,
+    up_channels: u32,
+}
+
+impl Rtt {
+    /// Search the RAM of the target for an RTT control block.
+    pub(crate) fn attach(session: &mut Session) -> Result<Self> {
+        let ranges: Vec<_> = session
+            .memory_map()
+            .iter()
+            .filter_map(|region| match region {
+                MemoryRegion::Ram(ram) => Some(ram.range.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut core = session.core(0)?;
+
+        for range in ranges {
+            if let Some(address) = scan(&mut core, range.start, range.end)? {
+                let mut header = [0u32; 2];
+                core.read_32(address + RTT_ID_SIZE, &mut header)?;
+
+                let up_channels = header[0];
+
+                if up_channels > MAX_CHANNELS || header[1] > MAX_CHANNELS {
+                    bail!(
+                        "Invalid RTT control block at {:#010x}: {} up and {} down channels",
+                        address,
+                        header[0],
+                        header[1]
+                    );
+                }
+
+                return Ok(Rtt {
+                    address,
+                    up_channels,
+                });
+            }
+        }
+
+        Err(anyhow!("No RTT control block found in RAM"))
+    }
+
+    /// The address of the control block.
+    pub(crate) fn address(&self) -> u32 {
+        self.address
+    }
+
+    /// Read the data available in all up channels.
+    ///
+    /// Returns the number, name and data of each channel.
+    pub(crate) fn read_up_channels(
+        &mut self,
+        core: &mut Core,
+    ) -> Result<Vec<(usize, String, Vec<u8>)>> {
+        let mut channels = Vec::new();
+
+        for number in 0..self.up_channels {
+            let descriptor_address = self.address + RTT_ID_SIZE + 8 + number * CHANNEL_SIZE;
+
+            // name, buffer, size, write offset, read offset, flags
+            let mut descriptor = [0u32; 6];
+            core.read_32(descriptor_address, &mut descriptor)?;
+
+            let [name, buffer, size, write, read, _flags] = descriptor;
+
+            // The channel is not configured by the target.
+            if buffer == 0 && size == 0 {
+                continue;
+            }
+
+            if size == 0
+                || size > MAX_BUFFER_SIZE
+                || write >= size
+                || read >= size
+                || buffer.checked_add(size).is_none()
+            {
+                bail!(
+                    "Invalid RTT up channel {}: buffer {:#010x}, size {}, write offset {}, read offset {}",
+                    number,
+                    buffer,
+                    size,
+                    write,
+                    read
+                );
+            }
+
+            let available = if write >= read {
+                write - read
+            } else {
+                size - read + write
+            };
+            let mut data = vec![0u8; available as usize];
+
+            if write >= read {
+                core.read_8(buffer + read, &mut data)?;
+            } else {
+                let (first, second) = data.split_at_mut((size - read) as usize);
+                core.read_8(buffer + read, first)?;
+                core.read_8(buffer, second)?;
+            }
+
+            // Mark the data as read.
+            core.write_word_32(descriptor_address + 16, write)?;
+
+            channels.push((number as usize, read_name(core, name)?, data));
+        }
+
+        Ok(channels)
+    }
+}
+
+/// Search the memory from `start` to `end` for the RTT ID.
+fn scan(core: &mut Core, start: u32, end: u32) -> Result<Option<u32>> {
+    let mut address = start;
+    let mut buffer = vec![0u8; (SCAN_CHUNK_SIZE as usize) + RTT_ID.len()];
+
+    while address < end {
+        // The chunks overlap, so that an ID spanning two chunks is found.
+        let length = (end - address).min(buffer.len() as u32) as usize;
+        core.read_8(address, &mut buffer[..length])?;
+
+        if let Some(offset) = buffer[..length]
+            .windows(RTT_ID.len())
+            .position(|window| window == RTT_ID)
+        {
+            return Ok(Some(address + offset as u32));
+        }
+
+        address += SCAN_CHUNK_SIZE;
+    }
+
+    Ok(None)
+}
+
+fn read_name(core: &mut Core, address: u32) -> Result<String> {
+    if address == 0 {
+        return Ok(String::new());
+    }
+
+    let mut name = [0u8; MAX_NAME_LENGTH];
+    core.read_8(address, &mut name)?;
+
+    let length = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+
+    Ok(String::from_utf8_lossy(&name[..length]).into_owned())
+}
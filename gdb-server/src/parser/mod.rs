@@ -10,9 +10,10 @@ use nom::{
     branch::alt,
     bytes::complete::{tag, take},
     character::complete::char,
-    combinator::value,
+    combinator::{opt, value},
     map, named,
     number::complete::hex_u32,
+    sequence::preceded,
     IResult,
 };
 
@@ -53,8 +54,12 @@ pub enum Packet {
     Debug,
     /// Packet `D`
     Detach,
-    /// Packet `F`
-    FileIO,
+    /// Packet `F`, the reply to a File-I/O request.
+    FileIO {
+        result: i64,
+        errno: Option<u32>,
+        interrupted: bool,
+    },
     /// Packet `g`
     ReadGeneralRegister,
     /// Packet `G`
@@ -74,6 +79,8 @@ pub enum Packet {
     },
     /// Packet 'M'
     WriteMemory,
+    /// Packet `QStartNoAckMode`
+    StartNoAckMode,
     /// Packet 'p'
     ReadRegisterHex(u32),
     /// Packet 'P'
@@ -96,6 +103,11 @@ pub enum Packet {
     ThreadInfo,
     // Packet 'v'
     V(VPacket),
+    // Packet 'x'
+    ReadMemoryBinary {
+        address: u64,
+        length: u32,
+    },
     // Packet 'X'
     WriteMemoryBinary {
         address: u32,
@@ -141,6 +153,12 @@ pub fn parse_packet(input: &[u8]) -> Result<Packet> {
         write_memory_binary,
         ctrl_c_interrupt,
         continue_packet,
+        file_io_reply,
+        kill_request,
+        restart,
+        arguments,
+        read_memory_binary,
+        start_no_ack_mode,
     ))(input);
 
     match parse_result {
@@ -163,6 +181,25 @@ fn detach(input: &[u8]) -> IResult<&[u8], Packet> {
     value(Packet::Detach, char('D'))(input)
 }
 
+fn kill_request(input: &[u8]) -> IResult<&[u8], Packet> {
+    value(Packet::KillRequest, char('k'))(input)
+}
+
+fn restart(input: &[u8]) -> IResult<&[u8], Packet> {
+    // The argument of the packet is ignored by GDB.
+    let (input, _) = char('R')(input)?;
+    let (input, _) = opt(hex_u32)(input)?;
+
+    Ok((input, Packet::Restart))
+}
+
+fn arguments(input: &[u8]) -> IResult<&[u8], Packet> {
+    // The arguments are not used, so the rest of the packet is skipped.
+    let (input, _) = char('A')(input)?;
+
+    Ok((&input[input.len()..], Packet::Arguments))
+}
+
 fn read_register(input: &[u8]) -> IResult<&[u8], Packet> {
     let (input, _) = char('g')(input)?;
 
@@ -202,6 +239,20 @@ fn read_memory(input: &[u8]) -> IResult<&[u8], Packet> {
     Ok((input, Packet::ReadMemory { address, length }))
 }
 
+fn read_memory_binary(input: &[u8]) -> IResult<&[u8], Packet> {
+    let (input, _) = char('x')(input)?;
+
+    let (input, address) = hex_u64(input)?;
+    let (input, _) = char(',')(input)?;
+    let (input, length) = hex_u32(input)?;
+
+    Ok((input, Packet::ReadMemoryBinary { address, length }))
+}
+
+fn start_no_ack_mode(input: &[u8]) -> IResult<&[u8], Packet> {
+    value(Packet::StartNoAckMode, tag("QStartNoAckMode"))(input)
+}
+
 fn breakpoint_type(input: &[u8]) -> IResult<&[u8], BreakpointType> {
     alt((
         value(BreakpointType::Software, char('0')),
@@ -275,6 +326,31 @@ fn write_memory_binary(input: &[u8]) -> IResult<&[u8], Packet> {
     ))
 }
 
+fn file_io_reply(input: &[u8]) -> IResult<&[u8], Packet> {
+    let (input, _) = char('F')(input)?;
+
+    let (input, negative) = opt(char('-'))(input)?;
+    let (input, result) = hex_u64(input)?;
+
+    let result = if negative.is_some() {
+        -(result as i64)
+    } else {
+        result as i64
+    };
+
+    let (input, errno) = opt(preceded(char(','), hex_u32))(input)?;
+    let (input, interrupted) = opt(preceded(char(','), char('C')))(input)?;
+
+    Ok((
+        input,
+        Packet::FileIO {
+            result,
+            errno,
+            interrupted: interrupted.is_some(),
+        },
+    ))
+}
+
 fn ctrl_c_interrupt(input: &[u8]) -> IResult<&[u8], Packet> {
     let (input, _) = tag([0x03])(input)?;
 
@@ -296,6 +372,9 @@ mod test {
             ("c", Packet::Continue),
             ("g", Packet::ReadGeneralRegister),
             ("D", Packet::Detach),
+            ("k", Packet::KillRequest),
+            ("R00", Packet::Restart),
+            ("QStartNoAckMode", Packet::StartNoAckMode),
             ("qSupported", Packet::Query(QueryPacket::Supported(vec![]))),
             ("qHostInfo", Packet::Query(QueryPacket::HostInfo)),
             ("vCont?", Packet::V(VPacket::QueryContSupport)),
@@ -319,6 +398,17 @@ mod test {
         assert_eq!(parse_packet(b"p03").unwrap(), Packet::ReadRegisterHex(3));
     }
 
+    #[test]
+    fn parse_read_memory_binary() {
+        assert_eq!(
+            parse_packet(b"x20000000,400").unwrap(),
+            Packet::ReadMemoryBinary {
+                address: 0x2000_0000,
+                length: 0x400
+            }
+        );
+    }
+
     #[test]
     fn parse_query_attached() {
         assert_eq!(
@@ -411,6 +501,27 @@ mod test {
         );
     }
 
+    #[test]
+    fn parse_file_io_reply() {
+        assert_eq!(
+            parse_packet(b"F-1,9").unwrap(),
+            Packet::FileIO {
+                result: -1,
+                errno: Some(9),
+                interrupted: false,
+            }
+        );
+
+        assert_eq!(
+            parse_packet(b"F20,0,C").unwrap(),
+            Packet::FileIO {
+                result: 0x20,
+                errno: Some(0),
+                interrupted: true,
+            }
+        );
+    }
+
     #[test]
     fn parse_interrupt() {
         assert_eq!(parse_packet(&[0x03]).unwrap(), Packet::Interrupt);
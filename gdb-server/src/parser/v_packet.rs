@@ -1,12 +1,23 @@
-use super::{query::pid, Pid};
+use super::{query::pid, util::hex_bytes, Pid};
 use nom::{
-    branch::alt, bytes::complete::tag, character::complete::char, combinator::value, IResult,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{opt, value},
+    multi::many0,
+    sequence::preceded,
+    IResult,
 };
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum VPacket {
     Attach(Pid),
     Continue(Action),
+    Kill(Pid),
+    Run {
+        filename: Vec<u8>,
+        arguments: Vec<Vec<u8>>,
+    },
     Unknown(Vec<u8>),
     QueryContSupport,
 }
@@ -23,7 +34,7 @@ pub enum Action {
 }
 
 pub fn v_packet(input: &[u8]) -> IResult<&[u8], VPacket> {
-    let parse_result = alt((v_attach, v_cont_support, v_cont))(input);
+    let parse_result = alt((v_attach, v_cont_support, v_cont, v_kill, v_run))(input);
 
     match parse_result {
         Ok((input, packet)) => Ok((input, packet)),
@@ -44,6 +55,30 @@ fn v_attach(input: &[u8]) -> IResult<&[u8], VPacket> {
     Ok((input, VPacket::Attach(pid)))
 }
 
+fn v_kill(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("Kill;")(input)?;
+
+    let (input, pid) = pid(input)?;
+
+    Ok((input, VPacket::Kill(pid)))
+}
+
+fn v_run(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("Run;")(input)?;
+
+    // The filename is empty if GDB should use the default program.
+    let (input, filename) = opt(hex_bytes)(input)?;
+    let (input, arguments) = many0(preceded(char(';'), hex_bytes))(input)?;
+
+    Ok((
+        input,
+        VPacket::Run {
+            filename: filename.unwrap_or_default(),
+            arguments,
+        },
+    ))
+}
+
 fn v_cont_support(input: &[u8]) -> IResult<&[u8], VPacket> {
     let (input, _) = tag("Cont?")(input)?;
 
@@ -77,6 +112,36 @@ mod test {
         assert_eq!(v_packet(b"Attach;7").unwrap(), (EMPTY, VPacket::Attach(7)));
     }
 
+    #[test]
+    fn parse_v_kill() {
+        assert_eq!(v_packet(b"Kill;1").unwrap(), (EMPTY, VPacket::Kill(1)));
+    }
+
+    #[test]
+    fn parse_v_run() {
+        assert_eq!(
+            v_packet(b"Run;").unwrap(),
+            (
+                EMPTY,
+                VPacket::Run {
+                    filename: vec![],
+                    arguments: vec![]
+                }
+            )
+        );
+
+        assert_eq!(
+            v_packet(b"Run;612e656c66;2d76").unwrap(),
+            (
+                EMPTY,
+                VPacket::Run {
+                    filename: b"a.elf".to_vec(),
+                    arguments: vec![b"-v".to_vec()]
+                }
+            )
+        );
+    }
+
     #[test]
     fn parse_v_cont_support() {
         assert_eq!(
@@ -8,10 +8,37 @@ use gdb_protocol::{
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 type Sender<T> = mpsc::UnboundedSender<T>;
 
+/// Tracks whether packets have to be acknowledged, see `QStartNoAckMode`.
+#[derive(Debug, Default)]
+pub struct AckMode {
+    /// Packets are not acknowledged anymore.
+    no_ack: bool,
+    /// GDB requested to stop sending acknowledgements. This takes effect
+    /// once the reply to the request has been acknowledged.
+    no_ack_requested: bool,
+}
+
+impl AckMode {
+    /// Returns `true` if packets have to be acknowledged.
+    pub fn enabled(&self) -> bool {
+        !self.no_ack
+    }
+
+    /// Called once a packet sent to GDB was acknowledged.
+    pub fn packet_acknowledged(&mut self) {
+        if self.no_ack_requested {
+            log::debug!("Disabling packet acknowledgement");
+            self.no_ack = true;
+            self.no_ack_requested = false;
+        }
+    }
+}
+
 pub async fn reader(
     stream: &mut TcpStream,
     packet_stream: &Sender<CheckedPacket>,
     buffer: &mut Vec<u8>,
+    ack_mode: &mut AckMode,
 ) -> Result<()> {
     log::debug!("READ WIN");
     let mut parser = Parser::default();
@@ -36,14 +63,24 @@ pub async fn reader(
             match packet.kind {
                 PacketKind::Packet => match packet.check() {
                     Some(checked) => {
-                        log::debug!("Sending ACK");
-                        stream.write_all(&[b'+']).await?;
+                        if ack_mode.enabled() {
+                            log::debug!("Sending ACK");
+                            stream.write_all(&[b'+']).await?;
+                        }
+
+                        if checked.data == b"QStartNoAckMode" {
+                            ack_mode.no_ack_requested = true;
+                        }
+
                         packet_stream.unbounded_send(checked)?;
                     }
-                    None => {
+                    None if ack_mode.enabled() => {
                         log::debug!("Sending nACK");
                         (&*stream).write_all(&[b'-']).await?;
                     }
+                    None => {
+                        log::warn!("Dropping packet with invalid checksum");
+                    }
                 },
                 // Protocol specifies notifications should not be checked
                 PacketKind::Notification => {
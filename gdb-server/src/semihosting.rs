@@ -0,0 +1,278 @@
+//! Handling of semihosting requests from the target.
+//!
+//! Requests can either be serviced by probe-rs itself, with console output being
+//! sent to GDB using `O` packets, or be forwarded to GDB using the
+//! [File-I/O Remote Protocol Extension].
+//!
+//! [File-I/O Remote Protocol Extension]: https://sourceware.org/gdb/current/onlinedocs/gdb/File_002dI_002fO-Remote-Protocol-Extension.html
+use probe_rs::semihosting::{read_c_string, SemihostingCommand, SemihostingHost};
+use probe_rs::{Core, Error};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Console handles, which are handled locally instead of being forwarded to GDB.
+const CONSOLE_HANDLES: std::ops::RangeInclusive<u32> = 0..=2;
+
+/// File mode used when GDB has to create a file, `0644`.
+const FILE_MODE: u32 = 0o644;
+
+/// Maximum length of a string written by `SYS_WRITE0`.
+const MAX_WRITE0_LENGTH: u32 = 4096;
+
+/// Determines how semihosting requests of the target are handled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SemihostingMode {
+    /// Semihosting requests are not handled, and reported to GDB as a normal breakpoint.
+    Disabled,
+    /// Semihosting requests are serviced by the GDB stub.
+    ///
+    /// Console output is sent to GDB, file access is restricted to the directory `root`.
+    /// If no directory is given, file access is not possible.
+    Host { root: Option<PathBuf> },
+    /// File and console I/O is forwarded to GDB using `F` packets,
+    /// all other requests are serviced by the GDB stub.
+    Gdb,
+}
+
+impl Default for SemihostingMode {
+    fn default() -> Self {
+        SemihostingMode::Disabled
+    }
+}
+
+/// Console output of the semihosting host, which is later sent to GDB.
+#[derive(Clone, Default)]
+struct ConsoleBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for ConsoleBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Semihosting state of a single GDB connection.
+pub(crate) struct SemihostingState {
+    mode: SemihostingMode,
+    host: SemihostingHost,
+    console: ConsoleBuffer,
+    /// A request which was forwarded to GDB, and is waiting for the `F` reply.
+    pending: Option<SemihostingCommand>,
+}
+
+/// The next step after a semihosting request was received.
+pub(crate) enum SemihostingAction {
+    /// The request was serviced, the core was resumed.
+    ///
+    /// The contained console output has to be sent to GDB.
+    Resumed(Vec<u8>),
+    /// The request was forwarded to GDB, the contained `F` packet has to be sent.
+    Forward(String),
+    /// The application exited with the given exit code.
+    Exited(u8),
+}
+
+impl SemihostingState {
+    pub(crate) fn new(mode: SemihostingMode) -> Self {
+        let console = ConsoleBuffer::default();
+
+        let mut host = SemihostingHost::new().with_console(std::io::stdin(), console.clone());
+
+        if let SemihostingMode::Host { root: Some(root) } = &mode {
+            host = host.with_root(root);
+        }
+
+        Self {
+            mode,
+            host,
+            console,
+            pending: None,
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.mode != SemihostingMode::Disabled
+    }
+
+    /// Handle a semihosting request the core is halted at.
+    pub(crate) fn handle_request(
+        &mut self,
+        core: &mut Core,
+        command: SemihostingCommand,
+    ) -> Result<SemihostingAction, Error> {
+        if self.mode == SemihostingMode::Gdb {
+            if let Some(packet) = file_io_request(core, command)? {
+                log::debug!("Forwarding semihosting request {:?} to GDB", command);
+                self.pending = Some(command);
+                return Ok(SemihostingAction::Forward(packet));
+            }
+        }
+
+        match self.host.handle(core, command)? {
+            probe_rs::semihosting::SemihostingOutcome::Continue => {
+                core.run()?;
+
+                let output = std::mem::take(&mut *self.console.0.lock().unwrap());
+
+                Ok(SemihostingAction::Resumed(output))
+            }
+            probe_rs::semihosting::SemihostingOutcome::Exit { code, .. } => {
+                // Abnormal terminations are reported with exit code 1.
+                Ok(SemihostingAction::Exited(code.unwrap_or(1) as u8))
+            }
+        }
+    }
+
+    /// Complete a forwarded request, using the result of an `F` reply packet from GDB.
+    ///
+    /// Returns `false` if there was no request waiting for a reply.
+    pub(crate) fn complete_forwarded(
+        &mut self,
+        core: &mut Core,
+        result: i64,
+        errno: Option<u32>,
+    ) -> Result<bool, Error> {
+        let command = match self.pending.take() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+
+        if let Some(errno) = errno {
+            self.host.set_errno(errno as i32);
+        }
+
+        let result = match command {
+            // SYS_WRITE and SYS_READ report the number of bytes which were *not* transferred.
+            SemihostingCommand::Write { length, .. } if result >= 0 => length as i64 - result,
+            SemihostingCommand::Write { length, .. } => length as i64,
+            SemihostingCommand::Read { length, .. } if result >= 0 => length as i64 - result,
+            SemihostingCommand::WriteC { .. } | SemihostingCommand::Write0 { .. } => 0,
+            // GDB returns the new offset, semihosting expects 0 on success.
+            SemihostingCommand::Seek { .. } if result >= 0 => 0,
+            _ => result,
+        };
+
+        probe_rs::semihosting::complete_request(core, result as u32)?;
+
+        Ok(true)
+    }
+}
+
+/// Translate a semihosting request into a GDB File-I/O request packet.
+///
+/// Returns `None` for requests which have no equivalent in the File-I/O protocol,
+/// or which are handled locally.
+fn file_io_request(core: &mut Core, command: SemihostingCommand) -> Result<Option<String>, Error> {
+    // Paths are passed as `pointer/length`, where the length includes the terminating null byte.
+    let packet = match command {
+        SemihostingCommand::Open {
+            path,
+            path_len,
+            mode,
+        } => {
+            let name = read_c_string(core, path, path_len)?;
+
+            if name == b":tt" {
+                return Ok(None);
+            }
+
+            format!(
+                "Fopen,{:x}/{:x},{:x},{:x}",
+                path,
+                path_len + 1,
+                open_flags(mode),
+                FILE_MODE
+            )
+        }
+        SemihostingCommand::Close { handle } if !CONSOLE_HANDLES.contains(&handle) => {
+            format!("Fclose,{:x}", handle)
+        }
+        SemihostingCommand::WriteC { address } => format!("Fwrite,1,{:x},1", address),
+        SemihostingCommand::Write0 { address } => {
+            let length = read_c_string(core, address, MAX_WRITE0_LENGTH)?.len();
+            format!("Fwrite,1,{:x},{:x}", address, length)
+        }
+        SemihostingCommand::Write {
+            handle,
+            buffer,
+            length,
+        } => format!("Fwrite,{:x},{:x},{:x}", handle, buffer, length),
+        SemihostingCommand::Read {
+            handle,
+            buffer,
+            length,
+        } => format!("Fread,{:x},{:x},{:x}", handle, buffer, length),
+        SemihostingCommand::IsTty { handle } => format!("Fisatty,{:x}", handle),
+        SemihostingCommand::Seek { handle, position } => {
+            format!("Flseek,{:x},{:x},0", handle, position)
+        }
+        SemihostingCommand::Remove { path, path_len } => {
+            format!("Funlink,{:x}/{:x}", path, path_len + 1)
+        }
+        SemihostingCommand::Rename {
+            from,
+            from_len,
+            to,
+            to_len,
+        } => format!(
+            "Frename,{:x}/{:x},{:x}/{:x}",
+            from,
+            from_len + 1,
+            to,
+            to_len + 1
+        ),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(packet))
+}
+
+/// Translate an ISO C `fopen` mode, as used by `SYS_OPEN`, into the
+/// open flags of the GDB File-I/O protocol.
+fn open_flags(mode: u32) -> u32 {
+    const O_RDONLY: u32 = 0x0;
+    const O_WRONLY: u32 = 0x1;
+    const O_RDWR: u32 = 0x2;
+    const O_APPEND: u32 = 0x8;
+    const O_CREAT: u32 = 0x200;
+    const O_TRUNC: u32 = 0x400;
+
+    let update = mode & 2 != 0;
+
+    match mode / 4 {
+        // "r"
+        0 if update => O_RDWR,
+        0 => O_RDONLY,
+        // "w"
+        1 if update => O_RDWR | O_CREAT | O_TRUNC,
+        1 => O_WRONLY | O_CREAT | O_TRUNC,
+        // "a"
+        _ if update => O_RDWR | O_CREAT | O_APPEND,
+        _ => O_WRONLY | O_CREAT | O_APPEND,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::open_flags;
+
+    #[test]
+    fn fopen_mode_to_gdb_flags() {
+        // "r", "rb"
+        assert_eq!(open_flags(0), 0x0);
+        assert_eq!(open_flags(1), 0x0);
+        // "r+"
+        assert_eq!(open_flags(2), 0x2);
+        // "w", "w+b"
+        assert_eq!(open_flags(4), 0x601);
+        assert_eq!(open_flags(7), 0x602);
+        // "a", "a+"
+        assert_eq!(open_flags(8), 0x209);
+        assert_eq!(open_flags(10), 0x20a);
+    }
+}
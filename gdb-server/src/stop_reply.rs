@@ -0,0 +1,276 @@
+//! Creation of the stop replies which tell GDB why the core halted.
+//!
+//! The format of the replies is described in
+//! https://sourceware.org/gdb/current/onlinedocs/gdb/Stop-Reply-Packets.html
+
+use probe_rs::{Architecture, BreakpointCause, Core, CoreStatus, HaltReason, MemoryInterface};
+use std::fmt::Write;
+
+/// Signal numbers as used by GDB, these are not necessarily the same as on the host.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGBUS: u8 = 10;
+const SIGSEGV: u8 = 11;
+
+/// Address of the CPUID register of Cortex-M cores.
+const CPUID: u32 = 0xE000_ED00;
+
+/// Address of the DWT control register, and of the first comparator.
+const DWT_CTRL: u32 = 0xE000_1000;
+const DWT_COMP0: u32 = 0xE000_1020;
+
+/// Offset of the FUNCTION register relative to the COMP register,
+/// and the distance between two comparators.
+const DWT_FUNCTION_OFFSET: u32 = 0x8;
+const DWT_COMPARATOR_STRIDE: u32 = 0x10;
+
+/// Set in the FUNCTION register if the comparator caused the halt.
+const DWT_FUNCTION_MATCHED: u32 = 1 << 24;
+
+/// The xPSR register, the lower bits contain the number of the active exception.
+const XPSR: u16 = 0b1_0000;
+
+/// GDB register numbers of the stack pointer and program counter.
+const ARM_SP: u8 = 13;
+const ARM_PC: u8 = 15;
+const RISCV_SP: u8 = 2;
+const RISCV_PC: u8 = 32;
+
+/// The kind of access which triggered a watchpoint.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub(crate) enum WatchKind {
+    Write,
+    Read,
+    Access,
+}
+
+/// The reason for a stop, as reported to GDB.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub(crate) enum StopReason {
+    SoftwareBreakpoint,
+    HardwareBreakpoint,
+    Watchpoint { kind: WatchKind, address: u32 },
+}
+
+/// A stop reply, which can be formatted as a `T` packet.
+#[derive(Debug, PartialEq)]
+pub(crate) struct StopReply {
+    pub(crate) signal: u8,
+    pub(crate) reason: Option<StopReason>,
+    /// GDB register number and value of the expedited registers.
+    pub(crate) registers: Vec<(u8, u32)>,
+}
+
+impl StopReply {
+    pub(crate) fn format(&self) -> String {
+        let mut reply = format!("T{:02x}", self.signal);
+
+        match self.reason {
+            Some(StopReason::SoftwareBreakpoint) => reply.push_str("swbreak:;"),
+            Some(StopReason::HardwareBreakpoint) => reply.push_str("hwbreak:;"),
+            Some(StopReason::Watchpoint { kind, address }) => {
+                let name = match kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Access => "awatch",
+                };
+                let _ = write!(reply, "{}:{:x};", name, address);
+            }
+            None => (),
+        }
+
+        for (number, value) in &self.registers {
+            // Register values are sent in target byte order.
+            let _ = write!(
+                reply,
+                "{:02x}:{};",
+                number,
+                hex::encode(value.to_le_bytes())
+            );
+        }
+
+        reply
+    }
+}
+
+/// Create the stop reply for a core which halted because of `reason`.
+pub(crate) fn stop_reply(core: &mut Core, reason: HaltReason) -> String {
+    let (signal, reason) = match reason {
+        HaltReason::Breakpoint(BreakpointCause::Software) => {
+            (SIGTRAP, Some(StopReason::SoftwareBreakpoint))
+        }
+        HaltReason::Breakpoint(_) => (SIGTRAP, Some(StopReason::HardwareBreakpoint)),
+        HaltReason::Watchpoint => (SIGTRAP, triggered_watchpoint(core)),
+        HaltReason::Exception => (exception_signal(core), None),
+        HaltReason::Request | HaltReason::External => (SIGINT, None),
+        HaltReason::Step | HaltReason::Unknown => (SIGTRAP, None),
+    };
+
+    StopReply {
+        signal,
+        reason,
+        registers: expedited_registers(core),
+    }
+    .format()
+}
+
+/// Create the stop reply for the current status of the core.
+///
+/// If the core is not halted, a plain SIGTRAP is reported.
+pub(crate) fn current_stop_reply(core: &mut Core) -> String {
+    match core.status() {
+        Ok(CoreStatus::Halted(reason)) => stop_reply(core, reason),
+        Ok(_) => format!("S{:02x}", SIGTRAP),
+        Err(e) => {
+            log::debug!("Unable to read the core status: {}", e);
+            format!("S{:02x}", SIGTRAP)
+        }
+    }
+}
+
+/// Read the program counter and stack pointer, so GDB doesn't have to request them.
+fn expedited_registers(core: &mut Core) -> Vec<(u8, u32)> {
+    let (sp_number, pc_number) = match core.architecture() {
+        Architecture::Arm => (ARM_SP, ARM_PC),
+        Architecture::Riscv => (RISCV_SP, RISCV_PC),
+    };
+
+    let registers = core.registers();
+    let expedited = [
+        (sp_number, registers.stack_pointer()),
+        (pc_number, registers.program_counter()),
+    ];
+
+    let mut values = Vec::new();
+
+    for (number, register) in expedited.iter() {
+        match core.read_core_reg(*register) {
+            Ok(value) => values.push((*number, value)),
+            Err(e) => log::debug!("Unable to read register {}: {}", register.name(), e),
+        }
+    }
+
+    values
+}
+
+/// Map the active exception of a Cortex-M core to a signal.
+fn exception_signal(core: &mut Core) -> u8 {
+    if core.architecture() != Architecture::Arm {
+        return SIGTRAP;
+    }
+
+    let exception = match core.read_core_reg(XPSR) {
+        Ok(xpsr) => xpsr & 0x1ff,
+        Err(e) => {
+            log::debug!("Unable to read xPSR: {}", e);
+            return SIGTRAP;
+        }
+    };
+
+    match exception {
+        // HardFault, MemManage and SecureFault
+        3 | 4 | 7 => SIGSEGV,
+        // BusFault
+        5 => SIGBUS,
+        // UsageFault
+        6 => SIGILL,
+        _ => SIGTRAP,
+    }
+}
+
+/// Find the DWT comparator which caused the halt of a Cortex-M core.
+fn triggered_watchpoint(core: &mut Core) -> Option<StopReason> {
+    if core.architecture() != Architecture::Arm {
+        return None;
+    }
+
+    match read_triggered_watchpoint(core) {
+        Ok(reason) => reason,
+        Err(e) => {
+            log::debug!("Unable to determine the triggered watchpoint: {}", e);
+            None
+        }
+    }
+}
+
+fn read_triggered_watchpoint(core: &mut Core) -> Result<Option<StopReason>, probe_rs::Error> {
+    // ARMv8-M cores use a different encoding of the FUNCTION register.
+    let part_number = (core.read_word_32(CPUID)? >> 4) & 0xfff;
+    let armv8m = (0xd20..=0xd2f).contains(&part_number);
+
+    let comparators = core.read_word_32(DWT_CTRL)? >> 28;
+
+    for index in 0..comparators {
+        let comp_address = DWT_COMP0 + index * DWT_COMPARATOR_STRIDE;
+
+        let function = core.read_word_32(comp_address + DWT_FUNCTION_OFFSET)?;
+
+        if function & DWT_FUNCTION_MATCHED == 0 {
+            continue;
+        }
+
+        let kind = if armv8m {
+            match function & 0xf {
+                0b0100 | 0b1100 => WatchKind::Access,
+                0b0101 | 0b1101 => WatchKind::Write,
+                0b0110 | 0b1110 => WatchKind::Read,
+                _ => continue,
+            }
+        } else {
+            match function & 0xf {
+                0b0101 => WatchKind::Read,
+                0b0110 => WatchKind::Write,
+                0b0111 => WatchKind::Access,
+                _ => continue,
+            }
+        };
+
+        let address = core.read_word_32(comp_address)?;
+
+        return Ok(Some(StopReason::Watchpoint { kind, address }));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StopReason, StopReply, WatchKind};
+
+    #[test]
+    fn format_breakpoint() {
+        let reply = StopReply {
+            signal: 5,
+            reason: Some(StopReason::HardwareBreakpoint),
+            registers: vec![(13, 0x2000_fff0), (15, 0x0800_0124)],
+        };
+
+        assert_eq!(reply.format(), "T05hwbreak:;0d:f0ff0020;0f:24010008;");
+    }
+
+    #[test]
+    fn format_watchpoint() {
+        let reply = StopReply {
+            signal: 5,
+            reason: Some(StopReason::Watchpoint {
+                kind: WatchKind::Read,
+                address: 0x2000_0010,
+            }),
+            registers: vec![],
+        };
+
+        assert_eq!(reply.format(), "T05rwatch:20000010;");
+    }
+
+    #[test]
+    fn format_fault() {
+        let reply = StopReply {
+            signal: 11,
+            reason: None,
+            registers: vec![(2, 0x8000_1000), (32, 0x4200_0000)],
+        };
+
+        assert_eq!(reply.format(), "T0b02:00100080;20:00000042;");
+    }
+}
@@ -4,22 +4,38 @@ use futures::channel::mpsc;
 use futures::future::FutureExt;
 use futures::select;
 use gdb_protocol::packet::{CheckedPacket, Kind as PacketKind};
-use probe_rs::Session;
+use probe_rs::{CoreStatus, Session};
 use std::convert::TryFrom;
 use std::{sync::Mutex, time::Duration};
 
 use crate::parser::parse_packet;
+use crate::semihosting::{SemihostingAction, SemihostingState};
+use crate::GdbServerOptions;
 
 use crate::handlers;
+use crate::monitor::{self, MonitorState};
+use crate::stop_reply;
 
 type ServerResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 type Sender<T> = mpsc::UnboundedSender<T>;
 type Receiver<T> = mpsc::UnboundedReceiver<T>;
 
+/// State of a single GDB connection.
+pub struct ConnectionState {
+    /// Whether GDB has enabled extended mode, using the `!` packet.
+    extended_mode: bool,
+    semihosting: SemihostingState,
+    monitor: MonitorState,
+}
+
+/// Maximum number of bytes of console output sent in a single `O` packet.
+const CONSOLE_OUTPUT_CHUNK_SIZE: usize = 512;
+
 pub async fn worker(
     mut input_stream: Receiver<CheckedPacket>,
     output_stream: Sender<CheckedPacket>,
     session: &Mutex<Session>,
+    options: &GdbServerOptions,
 ) -> ServerResult<()> {
     // When we first attach to the core, GDB expects us to halt the core, so we do this here when a new client connects.
     // If the core is already halted, nothing happens if we issue a halt command again, so we always do this no matter of core state.
@@ -30,20 +46,25 @@ pub async fn worker(
         .halt(Duration::from_millis(100))?;
 
     let mut awaits_halt = false;
+    let mut state = ConnectionState {
+        extended_mode: false,
+        semihosting: SemihostingState::new(options.semihosting.clone()),
+        monitor: MonitorState::default(),
+    };
 
     loop {
         select! {
             potential_packet = input_stream.next().fuse() => {
                 if let Some(packet) = potential_packet {
                     log::warn!("WORKING {}", String::from_utf8_lossy(&packet.data));
-                    if handler(&session, &output_stream, &mut awaits_halt, packet).await? {
+                    if handler(&session, &output_stream, options, &mut awaits_halt, &mut state, packet).await? {
                         break;
                     }
                 } else {
                     break
                 }
             },
-            _ = await_halt(session, &output_stream, &mut awaits_halt).fuse() => {}
+            _ = await_halt(session, &output_stream, &mut awaits_halt, &mut state).fuse() => {}
         }
     }
     Ok(())
@@ -52,7 +73,9 @@ pub async fn worker(
 pub async fn handler(
     session: &Mutex<Session>,
     output_stream: &Sender<CheckedPacket>,
+    options: &GdbServerOptions,
     awaits_halt: &mut bool,
+    state: &mut ConnectionState,
     packet: CheckedPacket,
 ) -> ServerResult<bool> {
     let parsed_packet = parse_packet(&packet.data);
@@ -69,21 +92,15 @@ pub async fn handler(
             log::debug!("Parsed packet: {:?}", parsed_packet);
             let mut session = session.lock().expect("Poisoned Mutex");
             match parsed_packet {
-                HaltReason => handlers::halt_reason(),
+                HaltReason => handlers::halt_reason(session.core(0)?),
                 Continue => handlers::run(session.core(0)?, awaits_halt),
                 V(VPacket::QueryContSupport) => handlers::vcont_supported(),
                 Query(QueryPacket::Supported { .. }) => handlers::q_supported(),
                 Query(QueryPacket::Attached { .. }) => handlers::q_attached(),
                 Query(QueryPacket::Command(cmd)) => {
-                    if cmd == b"reset" {
-                        handlers::reset_halt(session.core(0)?)
-                    } else {
-                        log::debug!("Unknown monitor command: '{:?}'", cmd);
-                        Some(hex::encode(
-                            "Unknown monitor command\nOnly 'reset' is currently supported\n"
-                                .as_bytes(),
-                        ))
-                    }
+                    let response = monitor::monitor(&mut session, &mut state.monitor, &cmd);
+                    send_console_output(output_stream, response.output.as_bytes());
+                    Some(response.reply)
                 }
                 Query(QueryPacket::HostInfo) => handlers::host_info(),
                 ReadGeneralRegister => handlers::read_general_registers(session.core(0)?),
@@ -99,7 +116,44 @@ pub async fn handler(
                         handlers::reply_empty()
                     }
                 }
-                Detach => handlers::detach(&mut break_due),
+                ReadMemoryBinary { address, length } => {
+                    let response = match u32::try_from(address) {
+                        Ok(address) => {
+                            handlers::read_memory_binary(address, length, session.core(0)?)
+                        }
+                        Err(_) => b"E79".to_vec(),
+                    };
+
+                    // The reply contains binary data, so it can't be returned as a string.
+                    output_stream
+                        .unbounded_send(CheckedPacket::from_data(PacketKind::Packet, response))?;
+                    None
+                }
+                StartNoAckMode => handlers::start_no_ack_mode(),
+                Detach => handlers::detach(
+                    session.core(0)?,
+                    state.extended_mode,
+                    awaits_halt,
+                    &mut break_due,
+                ),
+                EnableExtendedMode => handlers::enable_extended_mode(&mut state.extended_mode),
+                V(VPacket::Attach(_)) => handlers::attach(session.core(0)?, awaits_halt),
+                Restart => handlers::restart(session.core(0)?, awaits_halt),
+                // The `A` packet is only used by old versions of GDB.
+                Arguments => handlers::arguments(session.core(0)?, awaits_halt),
+                V(VPacket::Run { filename, .. }) => handlers::run_program(
+                    &mut session,
+                    &filename,
+                    options.flash_on_run,
+                    awaits_halt,
+                ),
+                KillRequest => handlers::kill(
+                    session.core(0)?,
+                    state.extended_mode,
+                    awaits_halt,
+                    &mut break_due,
+                ),
+                V(VPacket::Kill(_)) => handlers::v_kill(session.core(0)?, awaits_halt),
                 V(VPacket::Continue(action)) => match action {
                     Action::Continue => handlers::run(session.core(0)?, awaits_halt),
                     Action::Stop => handlers::stop(session.core(0)?, awaits_halt),
@@ -155,6 +209,18 @@ pub async fn handler(
                     }
                 }
                 Interrupt => handlers::user_halt(session.core(0)?, awaits_halt),
+                FileIO {
+                    result,
+                    errno,
+                    interrupted,
+                } => handlers::file_io_reply(
+                    result,
+                    errno,
+                    interrupted,
+                    session.core(0)?,
+                    &mut state.semihosting,
+                    awaits_halt,
+                ),
                 other => {
                     log::warn!("Unknown command: '{:?}'", other);
 
@@ -193,15 +259,48 @@ pub async fn await_halt(
     session: &Mutex<Session>,
     output_stream: &Sender<CheckedPacket>,
     await_halt: &mut bool,
+    state: &mut ConnectionState,
 ) -> ServerResult<()> {
     task::sleep(Duration::from_millis(10)).await;
     if *await_halt {
         let mut session = session.lock().expect("Poisoned Mutex");
-        if session.core(0)?.core_halted().unwrap() {
-            let response = CheckedPacket::from_data(PacketKind::Packet, b"T05hwbreak:;".to_vec());
 
-            let mut bytes = Vec::new();
-            response.encode(&mut bytes).unwrap();
+        match state.monitor.poll_swo(&mut session) {
+            Ok(data) => send_console_output(output_stream, &data),
+            Err(e) => log::warn!("Failed to read SWO data: {}", e),
+        }
+
+        let mut core = session.core(0)?;
+        if let CoreStatus::Halted(reason) = core.status()? {
+            if state.semihosting.enabled() {
+                if let Some(command) = probe_rs::semihosting::check_semihosting(&mut core)? {
+                    match state.semihosting.handle_request(&mut core, command)? {
+                        SemihostingAction::Resumed(output) => {
+                            send_console_output(output_stream, &output)
+                        }
+                        SemihostingAction::Forward(request) => {
+                            *await_halt = false;
+                            let _ = output_stream.unbounded_send(CheckedPacket::from_data(
+                                PacketKind::Packet,
+                                request.into_bytes(),
+                            ));
+                        }
+                        SemihostingAction::Exited(code) => {
+                            *await_halt = false;
+                            let _ = output_stream.unbounded_send(CheckedPacket::from_data(
+                                PacketKind::Packet,
+                                format!("W{:02x}", code).into_bytes(),
+                            ));
+                        }
+                    }
+
+                    return Ok(());
+                }
+            }
+
+            let reply = stop_reply::stop_reply(&mut core, reason);
+            let response = CheckedPacket::from_data(PacketKind::Packet, reply.into_bytes());
+
             *await_halt = false;
 
             let _ = output_stream.unbounded_send(response);
@@ -210,3 +309,14 @@ pub async fn await_halt(
 
     Ok(())
 }
+
+/// Send output to the GDB console, using hex encoded `O` packets.
+fn send_console_output(output_stream: &Sender<CheckedPacket>, output: &[u8]) {
+    for chunk in output.chunks(CONSOLE_OUTPUT_CHUNK_SIZE) {
+        let packet = format!("O{}", hex::encode(chunk));
+        let _ = output_stream.unbounded_send(CheckedPacket::from_data(
+            PacketKind::Packet,
+            packet.into_bytes(),
+        ));
+    }
+}
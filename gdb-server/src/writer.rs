@@ -2,6 +2,8 @@ use async_std::{io::Write, net::TcpStream, prelude::*};
 use futures::channel::mpsc;
 use gdb_protocol::packet::{CheckedPacket, Kind as PacketKind};
 
+use crate::reader::AckMode;
+
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 type Sender<T> = mpsc::UnboundedSender<T>;
 
@@ -10,6 +12,7 @@ pub async fn writer(
     stream: &mut TcpStream,
     packet_stream: &Sender<CheckedPacket>,
     buffer: &mut Vec<u8>,
+    ack_mode: &mut AckMode,
 ) -> Result<()> {
     let mut tmp_buf = [0; 128];
     log::debug!("WRITE WIN");
@@ -17,6 +20,10 @@ pub async fn writer(
     encode(&packet, stream).await?;
     stream.flush().await?;
 
+    if !ack_mode.enabled() {
+        return Ok(());
+    }
+
     log::debug!("Request ACK for {}", String::from_utf8_lossy(&packet.data));
     'ack: loop {
         log::debug!("Reading");
@@ -48,7 +55,9 @@ pub async fn writer(
         log::debug!("Done checking ACK");
     }
 
-    super::reader::reader(stream, packet_stream, buffer).await
+    ack_mode.packet_acknowledged();
+
+    super::reader::reader(stream, packet_stream, buffer, ack_mode).await
 }
 
 pub async fn encode<W>(packet: &CheckedPacket, w: &mut W) -> Result<()>
@@ -61,7 +70,22 @@ where
     }])
     .await?;
 
-    let mut remaining: &[u8] = &packet.data;
+    let escaped = escape(&packet.data);
+
+    // The checksum is calculated over the escaped data, which is
+    // what is actually sent.
+    let checksum = escaped.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+
+    w.write_all(&escaped).await?;
+    w.write_all(format!("#{:02x}", checksum).as_bytes()).await?;
+    Ok(())
+}
+
+/// Escape all characters in `data` which have a special meaning in the protocol.
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+
+    let mut remaining: &[u8] = data;
     while !remaining.is_empty() {
         let escape1 = memchr::memchr3(b'#', b'$', b'}', remaining);
         let escape2 = memchr::memchr(b'*', remaining);
@@ -71,18 +95,26 @@ where
             escape2.unwrap_or_else(|| remaining.len()),
         );
 
-        w.write_all(&remaining[..escape]).await?;
+        escaped.extend_from_slice(&remaining[..escape]);
         remaining = &remaining[escape..];
 
         if let Some(&b) = remaining.first() {
-            dbg!(b as char);
             // memchr found a character that needs escaping, so let's do that
-            w.write_all(&[b'}', b ^ 0x20]).await?;
+            escaped.extend_from_slice(&[b'}', b ^ 0x20]);
             remaining = &remaining[1..];
         }
     }
 
-    w.write_all(&[b'#']).await?;
-    w.write_all(&packet.checksum).await?;
-    Ok(())
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape;
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(escape(b"OK"), b"OK");
+        assert_eq!(escape(b"a#b$c}d*"), b"a}\x03b}\x04c}]d}\x0a");
+    }
 }
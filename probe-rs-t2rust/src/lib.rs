@@ -40,7 +40,10 @@ pub fn run(input_dir: impl AsRef<Path>, output_file: impl AsRef<Path>) {
         quote::quote! {
             #[allow(unused_imports)]
             use jep106::JEP106Code;
-            use crate::config::{Chip, RawFlashAlgorithm, FlashRegion, MemoryRegion, RamRegion, SectorDescription, FlashProperties};
+            use crate::config::{Chip, RawFlashAlgorithm, FlashRegion, MemoryRegion, RamRegion, SectorDescription, FlashProperties, DebugSequenceDescription, Stm32OptionBytes};
+            // Only used if a chip describes its JTAG scan chain.
+            #[allow(unused_imports)]
+            use crate::config::{ScanChain, ScanChainElement};
 
             use std::borrow::Cow;
         }
@@ -145,12 +148,28 @@ fn extract_algorithms(
                     .as_u64()
                     .map(|v| v as u32),
             );
+
+            // Optional values, which are not part of all target descriptions.
+            let optional_u32 = |key: &str| {
+                quote_option(
+                    algorithm
+                        .get(key)
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as u32),
+                )
+            };
+            let pc_verify = optional_u32("pc_verify");
+            let pc_blank_check = optional_u32("pc_blank_check");
             let data_section_offset = algorithm
                 .get("data_section_offset")
                 .unwrap()
                 .as_u64()
                 .unwrap() as u32;
 
+            let load_address = optional_u32("load_address");
+            let stack_size = optional_u32("stack_size");
+            let data_load_address = optional_u32("data_load_address");
+
             let flash_properties = algorithm.get("flash_properties").unwrap();
 
             let range = flash_properties.get("address_range").unwrap();
@@ -198,7 +217,12 @@ fn extract_algorithms(
                     pc_program_page: #pc_program_page,
                     pc_erase_sector: #pc_erase_sector,
                     pc_erase_all: #pc_erase_all,
+                    pc_verify: #pc_verify,
+                    pc_blank_check: #pc_blank_check,
                     data_section_offset: #data_section_offset,
+                    load_address: #load_address,
+                    stack_size: #stack_size,
+                    data_load_address: #data_load_address,
                     flash_properties: FlashProperties {
                         address_range: #start..#end,
                         page_size: #page_size,
@@ -276,11 +300,18 @@ fn extract_memory_map(chip: &serde_yaml::Value) -> Vec<proc_macro2::TokenStream>
                         let end = range.get("end").unwrap().as_u64().unwrap() as u32;
                         let is_boot_memory =
                             region.get("is_boot_memory").unwrap().as_bool().unwrap();
+                        let algorithm = quote_option(
+                            region
+                                .get("algorithm")
+                                .and_then(|v| v.as_str())
+                                .map(|name| quote::quote! { Cow::Borrowed(#name) }),
+                        );
 
                         quote::quote! {
                             MemoryRegion::Flash(FlashRegion {
                                 range: #start..#end,
                                 is_boot_memory: #is_boot_memory,
+                                algorithm: #algorithm,
                             })
                         }
                     })
@@ -289,6 +320,126 @@ fn extract_memory_map(chip: &serde_yaml::Value) -> Vec<proc_macro2::TokenStream>
         .collect()
 }
 
+/// Extracts the JTAG scan chain token stream from a yaml value.
+fn extract_scan_chain(chip: &serde_yaml::Value) -> proc_macro2::TokenStream {
+    let scan_chain = match chip.get("scan_chain") {
+        Some(scan_chain) => scan_chain,
+        None => return quote_option::<proc_macro2::TokenStream>(None),
+    };
+
+    let taps = scan_chain
+        .get("taps")
+        .unwrap()
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .map(|tap| {
+            let name = quote_option(
+                tap.get("name")
+                    .and_then(|v| v.as_str())
+                    .map(|v| quote::quote! { Cow::Borrowed(#v) }),
+            );
+            let ir_len = quote_option(tap.get("ir_len").and_then(|v| v.as_u64().map(|v| v as u8)));
+            let idcode = quote_option(tap.get("idcode").and_then(|v| v.as_u64().map(|v| v as u32)));
+
+            quote::quote! {
+                ScanChainElement {
+                    name: #name,
+                    ir_len: #ir_len,
+                    idcode: #idcode,
+                }
+            }
+        });
+
+    let target_tap = quote_option(
+        scan_chain
+            .get("target_tap")
+            .and_then(|v| v.as_u64().map(|v| v as usize)),
+    );
+
+    quote::quote! {
+        Some(ScanChain {
+            taps: Cow::Borrowed(&[
+                #(#taps,)*
+            ]),
+            target_tap: #target_tap,
+        })
+    }
+}
+
+/// Extracts the SWD multi-drop TARGETSEL values from a yaml value.
+fn extract_swd_targetsel(chip: &serde_yaml::Value) -> proc_macro2::TokenStream {
+    let targetsel = chip
+        .get("swd_targetsel")
+        .and_then(|v| v.as_sequence())
+        .map(|targetsel| {
+            let values = targetsel
+                .iter()
+                .map(|v| v.as_u64().unwrap() as u32)
+                .collect::<Vec<_>>();
+
+            quote::quote! {
+                Cow::Borrowed(&[#(#values,)*])
+            }
+        });
+
+    quote_option(targetsel)
+}
+
+/// Extracts the debug sequence description token stream from a yaml value.
+fn extract_debug_sequence(chip_family: &serde_yaml::Value) -> proc_macro2::TokenStream {
+    let sequence = match chip_family.get("debug_sequence") {
+        Some(sequence) => sequence,
+        None => return quote_option::<proc_macro2::TokenStream>(None),
+    };
+
+    let address = |value: &serde_yaml::Value, name: &str| {
+        value.get(name).and_then(|v| v.as_u64()).map(|v| v as u32)
+    };
+
+    let description = if sequence.as_str() == Some("Kinetis") {
+        quote::quote! { DebugSequenceDescription::Kinetis }
+    } else if let Some(nrf) = sequence.get("Nrf") {
+        let ctrl_ap = nrf.get("ctrl_ap").unwrap().as_u64().unwrap() as u8;
+        let traceconfig = quote_option(address(nrf, "traceconfig"));
+
+        quote::quote! {
+            DebugSequenceDescription::Nrf {
+                ctrl_ap: #ctrl_ap,
+                traceconfig: #traceconfig,
+            }
+        }
+    } else if let Some(stm32) = sequence.get("Stm32") {
+        let dbgmcu_cr = address(stm32, "dbgmcu_cr").unwrap();
+        let watchdog_freeze = quote_option(address(stm32, "watchdog_freeze"));
+        let option_bytes = quote_option(stm32.get("option_bytes").map(|option_bytes| {
+            if let Some(optcr) = option_bytes.get("Optcr") {
+                let flash_base = address(optcr, "flash_base").unwrap();
+                quote::quote! { Stm32OptionBytes::Optcr { flash_base: #flash_base } }
+            } else if let Some(optr) = option_bytes.get("Optr") {
+                let flash_base = address(optr, "flash_base").unwrap();
+                quote::quote! { Stm32OptionBytes::Optr { flash_base: #flash_base } }
+            } else {
+                panic!("Unknown STM32 option bytes: {:?}", option_bytes);
+            }
+        }));
+
+        quote::quote! {
+            DebugSequenceDescription::Stm32 {
+                dbgmcu_cr: #dbgmcu_cr,
+                watchdog_freeze: #watchdog_freeze,
+                option_bytes: #option_bytes,
+            }
+        }
+    } else {
+        panic!("Unknown debug sequence: {:?}", sequence);
+    };
+
+    quote::quote! {
+        Some(#description)
+    }
+}
+
 /// Extracts a list of algorithm token streams from a yaml value.
 fn extract_variants(chip_family: &serde_yaml::Value) -> Vec<proc_macro2::TokenStream> {
     // Get an iterator over all the algorithms contained in the chip value obtained from the yaml file.
@@ -317,6 +468,10 @@ fn extract_variants(chip_family: &serde_yaml::Value) -> Vec<proc_macro2::TokenSt
                 .as_sequence()
                 .unwrap();
             let flash_algorithm_names = flash_algorithms.iter().map(|a| a.as_str().unwrap());
+
+            let scan_chain = extract_scan_chain(&variant);
+            let swd_targetsel = extract_swd_targetsel(&variant);
+
             quote::quote! {
                 Chip {
                     name: Cow::Borrowed(#name),
@@ -327,6 +482,8 @@ fn extract_variants(chip_family: &serde_yaml::Value) -> Vec<proc_macro2::TokenSt
                     flash_algorithms: Cow::Borrowed(&[
                         #(Cow::Borrowed(#flash_algorithm_names),)*
                     ]),
+                    scan_chain: #scan_chain,
+                    swd_targetsel: #swd_targetsel,
                 }
             }
         })
@@ -357,6 +514,7 @@ fn extract_chip_family(
         .unwrap()
         .to_ascii_lowercase();
     let manufacturer = quote_option(extract_manufacturer(&chip_family));
+    let debug_sequence = extract_debug_sequence(&chip_family);
 
     // Quote the chip.
     let chip_family = quote::quote! {
@@ -370,6 +528,7 @@ fn extract_chip_family(
                 #(#variants,)*
             ]),
             core: Cow::Borrowed(#core),
+            debug_sequence: #debug_sequence,
         }
     };
 
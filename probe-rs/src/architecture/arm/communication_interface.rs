@@ -4,8 +4,8 @@ use super::{
         GenericAP, MemoryAP, BASE, BASE2, CSW, IDR,
     },
     dp::{
-        Abort, Ctrl, DPAccess, DPBankSel, DPRegister, DebugPortError, DebugPortId,
-        DebugPortVersion, Select, DPIDR,
+        multidrop, Abort, Ctrl, DPAccess, DPBankSel, DPRegister, DebugPortError, DebugPortId,
+        DebugPortVersion, DpAddress, Select, DPIDR,
     },
     memory::{adi_v5_memory_interface::ADIMemoryInterface, Component},
     SwoAccess, SwoConfig,
@@ -15,9 +15,11 @@ use crate::{
 };
 use anyhow::anyhow;
 use jep106::JEP106Code;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum DapError {
     #[error("An error occured in the SWD communication between DAPlink and device.")]
     SwdProtocol,
@@ -124,6 +126,14 @@ pub trait DAPAccess: DebugProbe + AsRef<dyn DebugProbe> + AsMut<dyn DebugProbe>
         Ok(())
     }
 
+    /// Send a sequence of `bit_len` bits on SWDIO, or TMS for JTAG, starting with the LSB of `bits`.
+    ///
+    /// This is used to switch the debug ports between SWD, JTAG and the dormant state,
+    /// and to select a debug port using SWD multi-drop.
+    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("SWJ sequences"))
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe>;
 }
 
@@ -138,6 +148,24 @@ pub trait ArmProbeInterface:
 
     fn read_from_rom_table(&mut self) -> Result<Option<ArmChipInfo>, ProbeRsError>;
 
+    /// Select the debug port used for all following accesses.
+    ///
+    /// The state of each debug port, e.g. the available APs, is kept when switching between them.
+    fn select_debug_port(&mut self, dp: DpAddress) -> Result<(), DebugProbeError>;
+
+    /// Read the register at `address` of the AP with the number `port`.
+    ///
+    /// This allows access to vendor specific APs, e.g. to unlock a chip.
+    fn read_raw_ap_register(&mut self, port: u8, address: u8) -> Result<u32, DebugProbeError>;
+
+    /// Write `value` to the register at `address` of the AP with the number `port`.
+    fn write_raw_ap_register(
+        &mut self,
+        port: u8,
+        address: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError>;
+
     fn close(self: Box<Self>) -> Probe;
 }
 
@@ -206,7 +234,13 @@ pub struct MemoryApInformation {
 #[derive(Debug)]
 pub struct ArmCommunicationInterface {
     probe: Box<dyn DAPAccess>,
+    use_overrun_detect: bool,
+    /// The selected debug port, `None` if no debug port has been selected yet.
+    current_dp: Option<DpAddress>,
+    /// State of the selected debug port.
     state: ArmCommunicationInterfaceState,
+    /// State of the debug ports which were used before, but are not selected.
+    dp_states: HashMap<DpAddress, ArmCommunicationInterfaceState>,
 }
 
 impl ArmProbeInterface for ArmCommunicationInterface {
@@ -226,6 +260,23 @@ impl ArmProbeInterface for ArmCommunicationInterface {
         self.state.ap_information.len()
     }
 
+    fn select_debug_port(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        ArmCommunicationInterface::select_debug_port(self, dp)
+    }
+
+    fn read_raw_ap_register(&mut self, port: u8, address: u8) -> Result<u32, DebugProbeError> {
+        ArmCommunicationInterface::read_raw_ap_register(self, port, address)
+    }
+
+    fn write_raw_ap_register(
+        &mut self,
+        port: u8,
+        address: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        ArmCommunicationInterface::write_raw_ap_register(self, port, address, value)
+    }
+
     fn close(self: Box<Self>) -> Probe {
         Probe::from_attached_probe(self.probe.into_probe())
     }
@@ -244,28 +295,78 @@ impl<'a> AsMut<dyn DebugProbe + 'a> for ArmCommunicationInterface {
 }
 
 impl<'interface> ArmCommunicationInterface {
+    /// Create the interface. A debug port has to be selected using
+    /// [ArmCommunicationInterface::select_debug_port] before it is used.
     pub(crate) fn new(
         probe: Box<dyn DAPAccess>,
         use_overrun_detect: bool,
     ) -> Result<Self, DebugProbeError> {
-        let state = ArmCommunicationInterfaceState::new();
+        Ok(Self {
+            probe,
+            use_overrun_detect,
+            current_dp: None,
+            state: ArmCommunicationInterfaceState::new(),
+            dp_states: HashMap::new(),
+        })
+    }
+
+    /// Select the debug port used for all following accesses.
+    ///
+    /// A debug port using SWD multi-drop is selected by writing its TARGETSEL value.
+    /// The first time a debug port is selected, it is powered up and its APs are read.
+    pub fn select_debug_port(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        if self.current_dp == Some(dp) {
+            return Ok(());
+        }
 
-        let mut interface = Self { probe, state };
+        if let DpAddress::Multidrop(targetsel) = dp {
+            self.probe.flush()?;
 
-        interface.enter_debug_mode(use_overrun_detect)?;
+            // The debug ports are dormant, until multi-drop has been used for the first time.
+            if let None | Some(DpAddress::Default) = self.current_dp {
+                multidrop::dormant_to_swd(&mut *self.probe)?;
+            }
+
+            multidrop::select_debug_port(&mut *self.probe, targetsel)?;
+        }
+
+        let (state, initialized) = match self.dp_states.remove(&dp) {
+            Some(state) => (state, true),
+            None => (ArmCommunicationInterfaceState::new(), false),
+        };
+
+        let previous_state = std::mem::replace(&mut self.state, state);
+        if let Some(previous_dp) = self.current_dp.replace(dp) {
+            self.dp_states.insert(previous_dp, previous_state);
+        }
+
+        if !initialized {
+            if let Err(e) = self.initialize_debug_port() {
+                // Retry the initialization the next time this debug port is selected.
+                self.current_dp = None;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Power up the selected debug port, and determine the available APs.
+    fn initialize_debug_port(&mut self) -> Result<(), DebugProbeError> {
+        self.enter_debug_mode(self.use_overrun_detect)?;
 
         /* determine the number and type of available APs */
         log::trace!("Searching valid APs");
 
-        for ap in valid_access_ports(&mut interface) {
-            let ap_state = interface.read_ap_information(ap)?;
+        for ap in valid_access_ports(self) {
+            let ap_state = self.read_ap_information(ap)?;
 
             log::debug!("AP {}: {:?}", ap.port_number(), ap_state);
 
-            interface.state.ap_information.push(ap_state);
+            self.state.ap_information.push(ap_state);
         }
 
-        Ok(interface)
+        Ok(())
     }
 
     pub fn memory_interface(
@@ -510,6 +611,48 @@ impl<'interface> ArmCommunicationInterface {
         Ok(())
     }
 
+    /// Read the register at `address` of the AP with the number `port`.
+    ///
+    /// The upper four bits of the address are used to select the AP bank.
+    pub fn read_raw_ap_register(&mut self, port: u8, address: u8) -> Result<u32, DebugProbeError> {
+        log::debug!("Reading register {:#04x} of AP {}", address, port);
+        self.select_ap_and_ap_bank(port, address >> 4)?;
+
+        let value = self.probe.read_register(
+            PortType::AccessPort(u16::from(self.state.current_apsel)),
+            u16::from(address),
+        )?;
+
+        log::debug!("Read register    {:#04x}, value=0x{:08x}", address, value);
+
+        Ok(value)
+    }
+
+    /// Write `value` to the register at `address` of the AP with the number `port`.
+    ///
+    /// The upper four bits of the address are used to select the AP bank.
+    pub fn write_raw_ap_register(
+        &mut self,
+        port: u8,
+        address: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        log::debug!(
+            "Writing register {:#04x} of AP {}, value=0x{:08x}",
+            address,
+            port,
+            value
+        );
+        self.select_ap_and_ap_bank(port, address >> 4)?;
+
+        self.probe.write_register(
+            PortType::AccessPort(u16::from(self.state.current_apsel)),
+            u16::from(address),
+            value,
+        )?;
+        Ok(())
+    }
+
     /// Determine the type and additional information about a AP
     pub(crate) fn ap_information(&self, access_port: impl AccessPort) -> Option<&ApInformation> {
         self.state
@@ -777,13 +920,10 @@ impl ArmCommunicationInterface {
                 }
             }
         }
-        // log::info!(
-        //     "{}\n{}\n{}\n{}",
-        //     "If you are using a Nordic chip, it might be locked to debug access".yellow(),
-        //     "Run cargo flash with --nrf-recover to unlock".yellow(),
-        //     "WARNING: --nrf-recover will erase the entire code".yellow(),
-        //     "flash and UICR area of the device, in addition to the entire RAM".yellow()
-        // );
+        log::info!(
+            "No ROM table was found. If the chip is locked against debug access, \
+             it can be unlocked using `Probe::unlock`, which usually erases the entire flash."
+        );
 
         Ok(None)
     }
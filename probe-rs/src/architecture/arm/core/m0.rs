@@ -1,4 +1,6 @@
-use super::{reset_catch_clear, reset_catch_set, CortexState, Dfsr, ARM_REGISTER_FILE};
+use super::{
+    identify_breakpoint, reset_catch_clear, reset_catch_set, CortexState, Dfsr, ARM_REGISTER_FILE,
+};
 use crate::core::{
     Architecture, CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress,
     RegisterDescription, RegisterFile, RegisterKind,
@@ -280,7 +282,7 @@ impl<'probe> M0<'probe> {
 
                 let dfsr = Dfsr(memory.read_word_32(Dfsr::ADDRESS)?);
 
-                let reason = dfsr.halt_reason();
+                let reason = identify_breakpoint(&mut memory, dfsr.halt_reason())?;
 
                 CoreStatus::Halted(reason)
             } else {
@@ -493,7 +495,7 @@ impl<'probe> CoreInterface for M0<'probe> {
         if dhcsr.s_halt() {
             let dfsr = Dfsr(self.memory.read_word_32(Dfsr::ADDRESS)?);
 
-            let reason = dfsr.halt_reason();
+            let reason = identify_breakpoint(&mut self.memory, dfsr.halt_reason())?;
 
             // Clear bits from Dfsr register
             self.memory
@@ -16,7 +16,9 @@ use crate::{architecture::arm::core::register, MemoryInterface};
 
 use bitfield::bitfield;
 
-use super::{reset_catch_clear, reset_catch_set, CortexState, Dfsr, ARM_REGISTER_FILE};
+use super::{
+    identify_breakpoint, reset_catch_clear, reset_catch_set, CortexState, Dfsr, ARM_REGISTER_FILE,
+};
 use std::{
     mem::size_of,
     time::{Duration, Instant},
@@ -44,7 +46,7 @@ impl<'probe> M33<'probe> {
 
                 let dfsr = Dfsr(memory.read_word_32(Dfsr::ADDRESS)?);
 
-                let reason = dfsr.halt_reason();
+                let reason = identify_breakpoint(&mut memory, dfsr.halt_reason())?;
 
                 CoreStatus::Halted(reason)
             } else {
@@ -260,7 +262,7 @@ impl<'probe> CoreInterface for M33<'probe> {
         if dhcsr.s_halt() {
             let dfsr = Dfsr(self.memory.read_word_32(Dfsr::ADDRESS)?);
 
-            let reason = dfsr.halt_reason();
+            let reason = identify_breakpoint(&mut self.memory, dfsr.halt_reason())?;
 
             // Clear bits from Dfsr register
             self.memory
@@ -5,7 +5,10 @@ use crate::error::Error;
 use crate::memory::Memory;
 use crate::DebugProbeError;
 
-use super::{register, reset_catch_clear, reset_catch_set, CortexState, Dfsr, ARM_REGISTER_FILE};
+use super::{
+    identify_breakpoint, register, reset_catch_clear, reset_catch_set, CortexState, Dfsr,
+    ARM_REGISTER_FILE,
+};
 use crate::{
     core::{Architecture, CoreStatus, HaltReason},
     MemoryInterface,
@@ -326,7 +329,7 @@ impl<'probe> M4<'probe> {
 
                 let dfsr = Dfsr(memory.read_word_32(Dfsr::ADDRESS)?);
 
-                let reason = dfsr.halt_reason();
+                let reason = identify_breakpoint(&mut memory, dfsr.halt_reason())?;
 
                 CoreStatus::Halted(reason)
             } else {
@@ -394,7 +397,7 @@ impl<'probe> CoreInterface for M4<'probe> {
         if dhcsr.s_halt() {
             let dfsr = Dfsr(self.memory.read_word_32(Dfsr::ADDRESS)?);
 
-            let reason = dfsr.halt_reason();
+            let reason = identify_breakpoint(&mut self.memory, dfsr.halt_reason())?;
 
             // Clear bits from Dfsr register
             self.memory
@@ -1,6 +1,6 @@
 use crate::{
     core::{CoreRegister, CoreRegisterAddress, RegisterDescription, RegisterFile, RegisterKind},
-    CoreStatus, Error, HaltReason, MemoryInterface,
+    BreakpointCause, CoreStatus, Error, HaltReason, Memory, MemoryInterface,
 };
 
 use bitfield::bitfield;
@@ -61,6 +61,38 @@ pub(crate) fn reset_catch_clear(core: &mut impl MemoryInterface) -> Result<(), E
     Ok(())
 }
 
+/// The Thumb encoding of `BKPT 0xAB`, which is used to request a semihosting operation.
+pub(crate) const SEMIHOSTING_BKPT: u16 = 0xBEAB;
+
+/// Determine which kind of breakpoint caused the core to halt.
+///
+/// The DFSR only tells us that a breakpoint was hit, so we look at the
+/// instruction at the current program counter: `BKPT 0xAB` is a semihosting
+/// request, any other `BKPT` instruction is a software breakpoint, and everything
+/// else has to be a breakpoint from the FPB.
+pub(crate) fn identify_breakpoint(
+    memory: &mut Memory,
+    reason: HaltReason,
+) -> Result<HaltReason, Error> {
+    if reason != HaltReason::Breakpoint(BreakpointCause::Unknown) {
+        return Ok(reason);
+    }
+
+    let pc = memory.read_core_reg(register::PC.address)?;
+
+    let mut instruction = [0u8; 2];
+    memory.read_8(pc, &mut instruction)?;
+
+    let cause = match u16::from_le_bytes(instruction) {
+        SEMIHOSTING_BKPT => BreakpointCause::Semihosting,
+        // BKPT #imm8
+        bkpt if bkpt & 0xff00 == 0xbe00 => BreakpointCause::Software,
+        _ => BreakpointCause::Hardware,
+    };
+
+    Ok(HaltReason::Breakpoint(cause))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CortexDump {
     pub regs: [u32; 16],
@@ -256,7 +288,7 @@ impl Dfsr {
             // it could be for multiple reasons.
             HaltReason::Unknown
         } else if self.bkpt() {
-            HaltReason::Breakpoint
+            HaltReason::Breakpoint(BreakpointCause::Unknown)
         } else if self.external() {
             HaltReason::External
         } else if self.dwttrap() {
@@ -0,0 +1,168 @@
+//! Access to the debug port using JTAG (JTAG-DP).
+//!
+//! This is used by probes which only provide raw JTAG scans through [JTAGAccess],
+//! and don't perform the DP and AP accesses themselves.
+//!
+//! See chapter B3, [ARM Debug Interface Architecture Specification].
+//!
+//! [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
+
+use super::{Abort, RdBuff};
+use crate::architecture::arm::{DapError, PortType, Register};
+use crate::probe::JTAGAccess;
+use crate::DebugProbeError;
+
+/// Instructions of the JTAG-DP, the IR is 4 bits long.
+const JTAG_ABORT: u32 = 0x8;
+const JTAG_DPACC: u32 = 0xA;
+const JTAG_APACC: u32 = 0xB;
+
+/// Length of the DPACC, APACC and ABORT scan chains.
+const JTAG_DR_BIT_LENGTH: u32 = 35;
+
+/// Acknowledge values returned in the lowest three bits of a scan.
+///
+/// With JTAG, faults are not signaled by the acknowledge, but
+/// by the sticky error flags in the CTRL/STAT register.
+const JTAG_ACK_OK_FAULT: u8 = 0b010;
+const JTAG_ACK_WAIT: u8 = 0b001;
+
+/// Number of times a transaction is repeated after a WAIT response.
+const MAX_WAIT_RETRIES: usize = 10;
+
+/// Check if `idcode` belongs to a JTAG-DP, i.e. if the designer is ARM.
+pub(crate) fn is_arm_idcode(idcode: u32) -> bool {
+    // JEP106 code of ARM, together with the mandatory bit 0.
+    idcode & 0xfff == 0x477
+}
+
+/// Read a DP or AP register using the JTAG-DP.
+pub(crate) fn read_register<P: JTAGAccess + ?Sized>(
+    probe: &mut P,
+    port: PortType,
+    address: u16,
+) -> Result<u32, DebugProbeError> {
+    let instruction = match port {
+        PortType::DebugPort => JTAG_DPACC,
+        PortType::AccessPort(_) => JTAG_APACC,
+    };
+
+    transfer(probe, instruction, address, None)?;
+
+    // The result of a read is returned by the next transaction. Reading
+    // the RDBUFF register has no side effects, so it is used to fetch it.
+    transfer(probe, JTAG_DPACC, RdBuff::ADDRESS as u16, None)
+}
+
+/// Write a DP or AP register using the JTAG-DP.
+pub(crate) fn write_register<P: JTAGAccess + ?Sized>(
+    probe: &mut P,
+    port: PortType,
+    address: u16,
+    value: u32,
+) -> Result<(), DebugProbeError> {
+    match port {
+        // The ABORT register has its own scan chain.
+        PortType::DebugPort if address == Abort::ADDRESS as u16 => {
+            transfer(probe, JTAG_ABORT, 0, Some(value))?
+        }
+        PortType::DebugPort => transfer(probe, JTAG_DPACC, address, Some(value))?,
+        PortType::AccessPort(_) => transfer(probe, JTAG_APACC, address, Some(value))?,
+    };
+
+    // Wait until the write has been completed.
+    transfer(probe, JTAG_DPACC, RdBuff::ADDRESS as u16, None)?;
+
+    Ok(())
+}
+
+/// Perform a single transaction, and return the data returned by the previous transaction.
+fn transfer<P: JTAGAccess + ?Sized>(
+    probe: &mut P,
+    instruction: u32,
+    address: u16,
+    value: Option<u32>,
+) -> Result<u32, DebugProbeError> {
+    let request = encode_request(address, value);
+
+    for retry in 0..MAX_WAIT_RETRIES {
+        let response = probe.write_register(instruction, &request, JTAG_DR_BIT_LENGTH)?;
+        let (ack, data) = decode_response(&response);
+
+        match ack {
+            JTAG_ACK_OK_FAULT => return Ok(data),
+            JTAG_ACK_WAIT => {
+                // The previous transaction has not completed yet,
+                // so this transaction was ignored and has to be repeated.
+                log::debug!(
+                    "JTAG-DP WAIT, retries remaining {}.",
+                    MAX_WAIT_RETRIES - retry - 1
+                );
+            }
+            other => {
+                log::debug!("Invalid JTAG-DP acknowledge {:#05b}", other);
+                return Err(DapError::NoAcknowledge.into());
+            }
+        }
+    }
+
+    Err(DapError::WaitResponse.into())
+}
+
+/// Encode the 35 bit request: RnW in bit 0, A[3:2] in bits 2:1 and the data in bits 34:3.
+fn encode_request(address: u16, value: Option<u32>) -> [u8; 5] {
+    let read = value.is_none() as u64;
+    let address_bits = ((address >> 2) & 0b11) as u64;
+
+    let request = read | address_bits << 1 | (value.unwrap_or(0) as u64) << 3;
+
+    let mut bytes = [0u8; 5];
+    bytes.copy_from_slice(&request.to_le_bytes()[..5]);
+    bytes
+}
+
+/// Decode the 35 bit response into the acknowledge and the data.
+fn decode_response(response: &[u8]) -> (u8, u32) {
+    let mut bytes = [0u8; 8];
+
+    for (byte, value) in bytes.iter_mut().zip(response) {
+        *byte = *value;
+    }
+
+    let response = u64::from_le_bytes(bytes);
+
+    ((response & 0b111) as u8, (response >> 3) as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_response, encode_request, is_arm_idcode};
+
+    #[test]
+    fn encode_read_request() {
+        // Read of RDBUFF (A[3:2] = 0b11)
+        assert_eq!(encode_request(0xC, None), [0b111, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn encode_write_request() {
+        // Write of SELECT (A[3:2] = 0b10)
+        assert_eq!(
+            encode_request(0x8, Some(0xff00_00f0)),
+            [0x84, 0x07, 0x00, 0xf8, 0x07]
+        );
+    }
+
+    #[test]
+    fn decode_ok_response() {
+        let response = [0x7a, 0x53, 0x3b, 0xdd, 0x05];
+
+        assert_eq!(decode_response(&response), (0b010, 0xbba7_6a6f));
+    }
+
+    #[test]
+    fn arm_idcodes() {
+        assert!(is_arm_idcode(0x4ba0_0477));
+        assert!(!is_arm_idcode(0x1000_563d));
+    }
+}
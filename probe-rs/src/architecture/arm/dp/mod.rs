@@ -1,5 +1,7 @@
 #[macro_use]
 mod register_generation;
+pub(crate) mod jtag;
+pub(crate) mod multidrop;
 
 use super::Register;
 use bitfield::bitfield;
@@ -228,6 +230,53 @@ impl Register for TARGETID {
     const NAME: &'static str = "TARGETID";
 }
 
+bitfield! {
+    /// Selects a debug port when using SWD multi-drop.
+    ///
+    /// This register is written without the target driving the acknowledge,
+    /// see section B4.3.4, [ARM Debug Interface Architecture Specification].
+    ///
+    /// [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
+    #[derive(Clone)]
+    pub struct TARGETSEL(u32);
+    impl Debug;
+    pub u8, tinstance, set_tinstance: 31, 28;
+    pub u16, tpartno, set_tpartno: 27, 12;
+    pub u16, tdesigner, set_tdesigner: 11, 1;
+}
+
+impl From<u32> for TARGETSEL {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<TARGETSEL> for u32 {
+    fn from(raw: TARGETSEL) -> Self {
+        raw.0
+    }
+}
+
+impl DPRegister for TARGETSEL {
+    const DP_BANK: DPBankSel = DPBankSel::DontCare;
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv2;
+}
+
+impl Register for TARGETSEL {
+    const ADDRESS: u8 = 0xc;
+    const NAME: &'static str = "TARGETSEL";
+}
+
+/// The address of a debug port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DpAddress {
+    /// The only debug port connected to the probe, which is used without selecting it.
+    Default,
+    /// A debug port using SWD multi-drop, which is selected by writing
+    /// this value to the [TARGETSEL] register.
+    Multidrop(u32),
+}
+
 #[derive(Debug)]
 pub struct DebugPortId {
     pub revision: u8,
@@ -0,0 +1,93 @@
+//! Selection of a debug port using SWD multi-drop.
+//!
+//! With multi-drop, several debug ports are connected to the same SWD lines, and
+//! none of them responds until it has been selected by writing the [TARGETSEL](super::TARGETSEL) register.
+//!
+//! See section B4.3.4, [ARM Debug Interface Architecture Specification].
+//!
+//! [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
+
+use super::DPIDR;
+use crate::architecture::arm::{DAPAccess, PortType, Register};
+use crate::DebugProbeError;
+
+/// The selection alert sequence, which precedes the activation code when leaving the dormant state.
+const SELECTION_ALERT: u128 = 0x19bc_0ea2_e3dd_afe9_8685_2d95_6209_f392;
+
+/// Activation code to leave the dormant state and select SWD.
+const SWD_ACTIVATION_CODE: u64 = 0x1a;
+
+/// Request of a DP write to TARGETSEL: start, A[3:2] = 0b11, parity, stop and park.
+const TARGETSEL_REQUEST: u64 = 0x99;
+
+/// Wake up all debug ports connected to the probe from the dormant state, and select SWD.
+pub(crate) fn dormant_to_swd<P: DAPAccess + ?Sized>(probe: &mut P) -> Result<(), DebugProbeError> {
+    log::debug!("Waking up debug ports from dormant state");
+
+    // At least 8 cycles with SWDIO high, followed by the selection alert.
+    probe.swj_sequence(8, 0xff)?;
+    probe.swj_sequence(64, SELECTION_ALERT as u64)?;
+    probe.swj_sequence(64, (SELECTION_ALERT >> 64) as u64)?;
+
+    // 4 cycles with SWDIO low, and the activation code.
+    probe.swj_sequence(4, 0)?;
+    probe.swj_sequence(8, SWD_ACTIVATION_CODE)?;
+
+    Ok(())
+}
+
+/// Build the bit sequence writing `targetsel` to the TARGETSEL register.
+///
+/// The target doesn't drive the line during the acknowledge, so
+/// the whole transaction can be sent as a single sequence.
+fn targetsel_sequence(targetsel: u32) -> u64 {
+    let parity = (targetsel.count_ones() & 1) as u64;
+
+    // Request, turnaround, acknowledge and turnaround with SWDIO high, then data and parity.
+    TARGETSEL_REQUEST | 0b1_1111 << 8 | (targetsel as u64) << 13 | parity << 45
+}
+
+/// Select the debug port using the given TARGETSEL value.
+///
+/// All debug ports have to be woken up from the dormant state before.
+pub(crate) fn select_debug_port<P: DAPAccess + ?Sized>(
+    probe: &mut P,
+    targetsel: u32,
+) -> Result<(), DebugProbeError> {
+    log::debug!("Selecting debug port {:#010x}", targetsel);
+
+    // Line reset, followed by some idle cycles.
+    probe.swj_sequence(56, 0x00ff_ffff_ffff_ffff)?;
+    probe.swj_sequence(8, 0)?;
+
+    probe.swj_sequence(46, targetsel_sequence(targetsel))?;
+    probe.swj_sequence(8, 0)?;
+
+    // The selection is completed by reading DPIDR, which also
+    // checks that the selected debug port is present.
+    let dpidr = probe.read_register(PortType::DebugPort, u16::from(DPIDR::ADDRESS))?;
+    log::debug!("Selected debug port has DPIDR {:#010x}", dpidr);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{targetsel_sequence, TARGETSEL_REQUEST};
+    use crate::architecture::arm::{dp::TARGETSEL, Register};
+
+    #[test]
+    fn targetsel_request() {
+        // DP write of address 0xC.
+        assert_eq!(TARGETSEL::ADDRESS, 0xc);
+        assert_eq!(TARGETSEL_REQUEST, 0b1001_1001);
+    }
+
+    #[test]
+    fn rp2040_targetsel() {
+        // Core 0, even parity.
+        assert_eq!(targetsel_sequence(0x0100_2927), 0x0020_0524_ff99);
+        // Core 1, odd parity.
+        assert_eq!(targetsel_sequence(0x1100_2927), 0x2220_0524_ff99);
+    }
+}
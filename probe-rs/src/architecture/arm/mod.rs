@@ -1,9 +1,11 @@
+#[macro_use]
 pub mod ap;
 pub(crate) mod communication_interface;
 pub mod component;
 pub(crate) mod core;
 pub mod dp;
 pub mod memory;
+pub mod sequences;
 pub mod swo;
 
 pub use communication_interface::{
@@ -0,0 +1,141 @@
+//! Debug sequences for NXP Kinetis chips.
+
+use super::{
+    check_idr, read_register, wait_for, write_register, DebugSequence, DebugSequenceError,
+};
+use crate::architecture::arm::{
+    ap::{APRegister, AccessPort},
+    communication_interface::ArmProbeInterface,
+    Register,
+};
+use crate::Error;
+use std::time::Duration;
+
+/// The IDR of the MDM-AP, without the revision.
+const MDM_AP_IDR: u32 = 0x001C_0000;
+
+/// The MDM-AP is always AP 1.
+const MDM_AP_PORT: u8 = 1;
+
+/// Maximum duration of the mass erase.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Maximum time until the flash is ready after a reset.
+const READY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Miscellaneous debug module access port
+//
+// The MDM-AP is used to erase and reset the chip, even if it is secured.
+define_ap!(MdmAP);
+
+define_ap_register!(
+    /// Status of the flash and the security of the chip
+    MdmAP,
+    STATUS,
+    0x000,
+    [
+        (FLASH_MASS_ERASE_ACK: bool),
+        (FLASH_READY: bool),
+        (SYSTEM_SECURITY: bool),
+        (MASS_ERASE_ENABLE: bool),
+    ],
+    value,
+    STATUS {
+        FLASH_MASS_ERASE_ACK: value & (1 << 0) != 0,
+        FLASH_READY: value & (1 << 1) != 0,
+        SYSTEM_SECURITY: value & (1 << 2) != 0,
+        MASS_ERASE_ENABLE: value & (1 << 5) != 0,
+    },
+    (u32::from(value.FLASH_MASS_ERASE_ACK))
+        | (u32::from(value.FLASH_READY) << 1)
+        | (u32::from(value.SYSTEM_SECURITY) << 2)
+        | (u32::from(value.MASS_ERASE_ENABLE) << 5)
+);
+
+define_ap_register!(
+    /// Control of the mass erase and of the system reset
+    MdmAP,
+    CONTROL,
+    0x004,
+    [
+        (FLASH_MASS_ERASE_IN_PROGRESS: bool),
+        (SYSTEM_RESET_REQUEST: bool),
+    ],
+    value,
+    CONTROL {
+        FLASH_MASS_ERASE_IN_PROGRESS: value & (1 << 0) != 0,
+        SYSTEM_RESET_REQUEST: value & (1 << 3) != 0,
+    },
+    (u32::from(value.FLASH_MASS_ERASE_IN_PROGRESS))
+        | (u32::from(value.SYSTEM_RESET_REQUEST) << 3)
+);
+
+/// Unlocks and erases Kinetis chips using the mass erase function of the MDM-AP.
+#[derive(Debug)]
+pub struct Kinetis;
+
+impl Kinetis {
+    fn mdm_ap(&self, interface: &mut dyn ArmProbeInterface) -> Result<MdmAP, Error> {
+        check_idr(interface, "MDM-AP", MDM_AP_PORT, MDM_AP_IDR)?;
+
+        Ok(MdmAP::new(MDM_AP_PORT))
+    }
+}
+
+impl DebugSequence for Kinetis {
+    fn unlock(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        let mdm_ap = self.mdm_ap(interface)?;
+
+        let status: STATUS = read_register(interface, mdm_ap)?;
+        if !status.SYSTEM_SECURITY {
+            log::info!("The chip is not secured, nothing to unlock");
+            return Ok(());
+        }
+
+        self.mass_erase(interface)
+    }
+
+    fn mass_erase(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        let mdm_ap = self.mdm_ap(interface)?;
+
+        // Keep the chip in reset, so that the running firmware can't interfere.
+        write_register(
+            interface,
+            mdm_ap,
+            CONTROL {
+                SYSTEM_RESET_REQUEST: true,
+                ..Default::default()
+            },
+        )?;
+
+        wait_for(READY_TIMEOUT, "flash initialization", || {
+            let status: STATUS = read_register(interface, mdm_ap)?;
+            Ok(status.FLASH_READY)
+        })?;
+
+        let status: STATUS = read_register(interface, mdm_ap)?;
+        if !status.MASS_ERASE_ENABLE {
+            return Err(DebugSequenceError::MassEraseDisabled.into());
+        }
+
+        log::info!("Erasing the flash using the MDM-AP");
+        write_register(
+            interface,
+            mdm_ap,
+            CONTROL {
+                FLASH_MASS_ERASE_IN_PROGRESS: true,
+                SYSTEM_RESET_REQUEST: true,
+            },
+        )?;
+
+        wait_for(ERASE_TIMEOUT, "MDM-AP mass erase", || {
+            let control: CONTROL = read_register(interface, mdm_ap)?;
+            Ok(!control.FLASH_MASS_ERASE_IN_PROGRESS)
+        })?;
+
+        // Release the reset, the chip is unsecured after the next boot.
+        write_register(interface, mdm_ap, CONTROL::default())?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Chip specific debug sequences.
+//!
+//! Some chips need special handling which is not covered by the ARM debug interface,
+//! e.g. to unlock a chip which is protected against debug access, or to enable its
+//! trace pins. This is done using vendor specific access ports or peripherals, and is
+//! implemented by a [DebugSequence].
+//!
+//! The sequence used for a chip is selected by the `debug_sequence` of its
+//! chip family in the target description, see [DebugSequenceDescription].
+
+pub mod kinetis;
+pub mod nrf;
+pub mod stm32;
+
+use super::ap::{APRegister, AccessPort, IDR};
+use super::communication_interface::{ArmProbeInterface, Register};
+use super::core::{reset_catch_clear, reset_catch_set};
+use super::SwoConfig;
+use crate::config::DebugSequenceDescription;
+use crate::{Core, DebugProbeError, Error};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+pub use kinetis::Kinetis;
+pub use nrf::Nrf;
+pub use stm32::Stm32;
+
+#[derive(Debug, Error)]
+pub enum DebugSequenceError {
+    #[error("The {name} was not found at AP {port}")]
+    AccessPortNotFound { name: &'static str, port: u8 },
+    #[error("Timeout while waiting for the {0} to finish")]
+    Timeout(&'static str),
+    #[error("Mass erase is disabled by the security settings of the chip")]
+    MassEraseDisabled,
+    #[error("The chip is permanently locked, and can't be unlocked")]
+    PermanentlyLocked,
+    #[error("The TPIU clock of {0} Hz is not supported by the chip")]
+    UnsupportedTraceClock(u32),
+}
+
+/// Chip specific sequences, which are used in addition to the generic ARM debug functionality.
+///
+/// The [DebugSequence::unlock] and [DebugSequence::mass_erase] sequences only use the ARM
+/// debug interface, and don't require a working connection to the core. This allows them
+/// to be used on chips which are locked against debug access. All other sequences are
+/// used on a halted core, and do nothing by default.
+pub trait DebugSequence: Debug + Send + Sync {
+    /// Remove the protection against debug access, so that the chip can be attached to.
+    ///
+    /// On most chips, this erases the entire flash memory.
+    fn unlock(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        self.mass_erase(interface)
+    }
+
+    /// Erase the entire flash memory of the chip, without using a flash algorithm.
+    fn mass_erase(&self, _interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        Err(DebugProbeError::NotImplemented("mass erase using a debug sequence").into())
+    }
+
+    /// Configure the chip to halt the core at the reset vector on the next reset.
+    fn reset_catch_set(&self, core: &mut Core) -> Result<(), Error> {
+        reset_catch_set(core)
+    }
+
+    /// Undo the configuration of [DebugSequence::reset_catch_set].
+    fn reset_catch_clear(&self, core: &mut Core) -> Result<(), Error> {
+        reset_catch_clear(core)
+    }
+
+    /// Keep the debug connection working while the chip is in a low power mode.
+    fn enable_debug_in_low_power(&self, _core: &mut Core) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Stop the watchdogs of the chip while the core is halted.
+    fn freeze_watchdogs(&self, _core: &mut Core) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Prepare the chip before a flash algorithm is run, e.g. by enabling faster clocks.
+    fn prepare_flashing(&self, _core: &mut Core) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Configure the pins and clocks of the chip which are used to output trace data.
+    fn setup_trace(&self, _core: &mut Core, _config: &SwoConfig) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The debug sequence for chips without special handling.
+#[derive(Debug)]
+pub struct DefaultDebugSequence;
+
+impl DebugSequence for DefaultDebugSequence {}
+
+/// Create the debug sequence from the description in a chip family.
+pub(crate) fn create_debug_sequence(
+    description: Option<DebugSequenceDescription>,
+) -> Arc<dyn DebugSequence> {
+    match description {
+        Some(DebugSequenceDescription::Nrf {
+            ctrl_ap,
+            traceconfig,
+        }) => Arc::new(Nrf::new(ctrl_ap, traceconfig)),
+        Some(DebugSequenceDescription::Stm32 {
+            dbgmcu_cr,
+            watchdog_freeze,
+            option_bytes,
+        }) => Arc::new(Stm32::new(dbgmcu_cr, watchdog_freeze, option_bytes)),
+        Some(DebugSequenceDescription::Kinetis) => Arc::new(Kinetis),
+        None => Arc::new(DefaultDebugSequence),
+    }
+}
+
+/// Read the register `R` of a vendor specific AP.
+fn read_register<AP, R>(
+    interface: &mut dyn ArmProbeInterface,
+    port: AP,
+) -> Result<R, DebugProbeError>
+where
+    AP: AccessPort,
+    R: APRegister<AP>,
+{
+    let value = interface.read_raw_ap_register(port.port_number(), R::ADDRESS)?;
+
+    Ok(R::from(value))
+}
+
+/// Write the register `R` of a vendor specific AP.
+fn write_register<AP, R>(
+    interface: &mut dyn ArmProbeInterface,
+    port: AP,
+    register: R,
+) -> Result<(), DebugProbeError>
+where
+    AP: AccessPort,
+    R: APRegister<AP>,
+{
+    interface.write_raw_ap_register(port.port_number(), R::ADDRESS, register.into())
+}
+
+/// Check that the AP with the number `port` has the given `idr`, ignoring its revision.
+fn check_idr(
+    interface: &mut dyn ArmProbeInterface,
+    name: &'static str,
+    port: u8,
+    idr: u32,
+) -> Result<(), Error> {
+    let value = interface.read_raw_ap_register(port, IDR::ADDRESS)?;
+
+    if value & 0x0FFF_FFFF == idr {
+        Ok(())
+    } else {
+        log::debug!(
+            "AP {} has the IDR {:#010x}, expected {:#010x}",
+            port,
+            value,
+            idr
+        );
+        Err(DebugSequenceError::AccessPortNotFound { name, port }.into())
+    }
+}
+
+/// Poll `done` until it returns true, or `timeout` has elapsed.
+fn wait_for(
+    timeout: Duration,
+    operation: &'static str,
+    mut done: impl FnMut() -> Result<bool, Error>,
+) -> Result<(), Error> {
+    let start = Instant::now();
+
+    while !done()? {
+        if start.elapsed() >= timeout {
+            return Err(DebugSequenceError::Timeout(operation).into());
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create_debug_sequence, wait_for};
+    use crate::config::{DebugSequenceDescription, Stm32OptionBytes};
+    use std::time::Duration;
+
+    #[test]
+    fn sequence_from_description() {
+        let description: DebugSequenceDescription = serde_yaml::from_str(
+            "Stm32:\n  dbgmcu_cr: 3758366724\n  option_bytes:\n    Optcr:\n      flash_base: 1073888256\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            description,
+            DebugSequenceDescription::Stm32 {
+                dbgmcu_cr: 0xE004_2004,
+                watchdog_freeze: None,
+                option_bytes: Some(Stm32OptionBytes::Optcr {
+                    flash_base: 0x4002_3C00
+                }),
+            }
+        );
+
+        let name = |description| format!("{:?}", create_debug_sequence(description));
+
+        assert!(name(Some(description)).starts_with("Stm32"));
+        assert!(name(Some(DebugSequenceDescription::Kinetis)).starts_with("Kinetis"));
+        assert_eq!(name(None), "DefaultDebugSequence");
+    }
+
+    #[test]
+    fn wait_for_times_out() {
+        assert!(wait_for(Duration::from_millis(20), "test", || Ok(false)).is_err());
+        assert!(wait_for(Duration::from_millis(20), "test", || Ok(true)).is_ok());
+    }
+}
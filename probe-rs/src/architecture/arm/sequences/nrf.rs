@@ -0,0 +1,160 @@
+//! Debug sequences for Nordic nRF52 and nRF91 chips.
+
+use super::{
+    check_idr, read_register, wait_for, write_register, DebugSequence, DebugSequenceError,
+};
+use crate::architecture::arm::{
+    ap::{APRegister, AccessPort},
+    communication_interface::ArmProbeInterface,
+    Register, SwoConfig,
+};
+use crate::{Core, Error, MemoryInterface};
+use std::time::Duration;
+
+/// The IDR of the CTRL-AP, without the revision.
+const CTRL_AP_IDR: u32 = 0x0288_0000;
+
+/// Maximum duration of the ERASEALL operation.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Control access port
+//
+// The CTRL-AP is used to erase and reset the chip, even if
+// access to the other APs is blocked by APPROTECT.
+define_ap!(CtrlAP);
+
+define_ap_register!(
+    /// Soft reset triggered through the CTRL-AP
+    CtrlAP,
+    RESET,
+    0x000,
+    [(RESET: bool)],
+    value,
+    RESET {
+        RESET: value & 1 == 1
+    },
+    u32::from(value.RESET)
+);
+
+define_ap_register!(
+    /// Start the erase of all flash, UICR and RAM
+    CtrlAP,
+    ERASEALL,
+    0x004,
+    [(ERASEALL: bool)],
+    value,
+    ERASEALL {
+        ERASEALL: value & 1 == 1
+    },
+    u32::from(value.ERASEALL)
+);
+
+define_ap_register!(
+    /// Status of the ERASEALL operation
+    CtrlAP,
+    ERASEALLSTATUS,
+    0x008,
+    [(ERASEALLSTATUS: bool)],
+    value,
+    ERASEALLSTATUS {
+        ERASEALLSTATUS: value & 1 == 1
+    },
+    u32::from(value.ERASEALLSTATUS)
+);
+
+define_ap_register!(
+    /// Status of the access port protection
+    ///
+    /// `APPROTECTSTATUS` is false if the access port protection is enabled.
+    CtrlAP,
+    APPROTECTSTATUS,
+    0x00C,
+    [(APPROTECTSTATUS: bool)],
+    value,
+    APPROTECTSTATUS {
+        APPROTECTSTATUS: value & 1 == 1
+    },
+    u32::from(value.APPROTECTSTATUS)
+);
+
+/// Unlocks and erases nRF52 and nRF91 chips using the ERASEALL function of the CTRL-AP,
+/// and enables the trace pins using the `TRACECONFIG` register.
+#[derive(Debug)]
+pub struct Nrf {
+    ctrl_ap: CtrlAP,
+    traceconfig: Option<u32>,
+}
+
+impl Nrf {
+    /// Create the sequence for a chip with the CTRL-AP at AP `ctrl_ap`, and the
+    /// `TRACECONFIG` register at the address `traceconfig`, if the chip has one.
+    pub fn new(ctrl_ap: u8, traceconfig: Option<u32>) -> Self {
+        Self {
+            ctrl_ap: CtrlAP::new(ctrl_ap),
+            traceconfig,
+        }
+    }
+
+    fn ctrl_ap(&self, interface: &mut dyn ArmProbeInterface) -> Result<CtrlAP, Error> {
+        check_idr(
+            interface,
+            "CTRL-AP",
+            self.ctrl_ap.port_number(),
+            CTRL_AP_IDR,
+        )?;
+
+        Ok(self.ctrl_ap)
+    }
+}
+
+impl DebugSequence for Nrf {
+    fn unlock(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        let ctrl_ap = self.ctrl_ap(interface)?;
+
+        let status: APPROTECTSTATUS = read_register(interface, ctrl_ap)?;
+        if status.APPROTECTSTATUS {
+            log::info!("The chip is not protected by APPROTECT, nothing to unlock");
+            return Ok(());
+        }
+
+        self.mass_erase(interface)
+    }
+
+    fn mass_erase(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        let ctrl_ap = self.ctrl_ap(interface)?;
+
+        log::info!("Erasing all flash, UICR and RAM using the CTRL-AP");
+        write_register(interface, ctrl_ap, ERASEALL { ERASEALL: true })?;
+
+        wait_for(ERASE_TIMEOUT, "CTRL-AP erase", || {
+            let status: ERASEALLSTATUS = read_register(interface, ctrl_ap)?;
+            Ok(!status.ERASEALLSTATUS)
+        })?;
+
+        // The access port protection is only removed after a reset.
+        write_register(interface, ctrl_ap, RESET { RESET: true })?;
+        write_register(interface, ctrl_ap, RESET { RESET: false })?;
+        write_register(interface, ctrl_ap, ERASEALL { ERASEALL: false })?;
+
+        Ok(())
+    }
+
+    fn setup_trace(&self, core: &mut Core, config: &SwoConfig) -> Result<(), Error> {
+        let traceconfig = match self.traceconfig {
+            Some(traceconfig) => traceconfig,
+            None => return Ok(()),
+        };
+
+        log::debug!("Configuring the trace clock and pins using TRACECONFIG");
+        let mut value: u32 = match config.tpiu_clk() {
+            4_000_000 => 3,
+            8_000_000 => 2,
+            16_000_000 => 1,
+            32_000_000 => 0,
+            tpiu_clk => return Err(DebugSequenceError::UnsupportedTraceClock(tpiu_clk).into()),
+        };
+        value |= 1 << 16; // TRACEMUX: serial
+
+        core.write_word_32(traceconfig, value)
+    }
+}
@@ -0,0 +1,199 @@
+//! Debug sequences for STM32 chips.
+
+use super::{wait_for, DebugSequence, DebugSequenceError};
+use crate::architecture::arm::{
+    ap::MemoryAP, communication_interface::ArmProbeInterface, SwoConfig,
+};
+use crate::config::Stm32OptionBytes;
+use crate::{Core, DebugProbeError, Error, Memory, MemoryInterface};
+use std::time::Duration;
+
+/// Maximum duration of the flash erase caused by the read protection regression.
+const REGRESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Read protection level 0, i.e. the chip is not protected.
+const RDP_LEVEL_0: u32 = 0xAA;
+
+/// Read protection level 2, which can't be removed anymore.
+const RDP_LEVEL_2: u32 = 0xCC;
+
+/// The keys which have to be written to unlock the option byte register.
+const OPTION_KEYS: [u32; 2] = [0x0819_2A3B, 0x4C5D_6E7F];
+
+/// The keys which have to be written to unlock the flash control register.
+const FLASH_KEYS: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+
+/// Busy flag in the flash status register.
+const SR_BSY: u32 = 1 << 16;
+
+/// Debug in sleep, stop and standby mode in `DBGMCU_CR`.
+const DBGMCU_CR_DBG_LOW_POWER: u32 = 0b111;
+
+/// Trace pin enable in `DBGMCU_CR`.
+const DBGMCU_CR_TRACE_IOEN: u32 = 1 << 5;
+
+/// Trace pin mode in `DBGMCU_CR`, the asynchronous mode used for SWO is 0.
+const DBGMCU_CR_TRACE_MODE: u32 = 0b11 << 6;
+
+/// Window watchdog and independent watchdog stop bits in the DBGMCU freeze register.
+const WATCHDOG_STOP: u32 = (1 << 11) | (1 << 12);
+
+/// Configures STM32 chips using the DBGMCU peripheral, and removes their read protection
+/// by changing the read protection level in the option bytes from level 1 back to level 0,
+/// which erases the entire flash.
+///
+/// Removing the read protection is not supported on chips which program the option bytes
+/// using half-word writes, like the STM32F1.
+#[derive(Debug)]
+pub struct Stm32 {
+    dbgmcu_cr: u32,
+    watchdog_freeze: Option<u32>,
+    option_bytes: Option<Stm32OptionBytes>,
+}
+
+impl Stm32 {
+    /// Create the sequence for a chip with the `DBGMCU_CR` register at the address `dbgmcu_cr`.
+    ///
+    /// The watchdogs are only stopped if the address of the freeze register is given, and
+    /// the read protection can only be removed if the layout of the option bytes is given.
+    pub fn new(
+        dbgmcu_cr: u32,
+        watchdog_freeze: Option<u32>,
+        option_bytes: Option<Stm32OptionBytes>,
+    ) -> Self {
+        Self {
+            dbgmcu_cr,
+            watchdog_freeze,
+            option_bytes,
+        }
+    }
+
+    fn unlock_optcr(&self, memory: &mut Memory, flash_base: u32) -> Result<(), Error> {
+        let flash_optkeyr = flash_base + 0x08;
+        let flash_sr = flash_base + 0x0C;
+        let flash_optcr = flash_base + 0x14;
+
+        const OPTCR_OPTLOCK: u32 = 1 << 0;
+        const OPTCR_OPTSTRT: u32 = 1 << 1;
+
+        let optcr = memory.read_word_32(flash_optcr)?;
+        if !check_rdp_level((optcr >> 8) & 0xFF)? {
+            return Ok(());
+        }
+
+        if optcr & OPTCR_OPTLOCK != 0 {
+            for key in &OPTION_KEYS {
+                memory.write_word_32(flash_optkeyr, *key)?;
+            }
+        }
+
+        let optcr = (memory.read_word_32(flash_optcr)? & !(0xFF << 8)) | (RDP_LEVEL_0 << 8);
+        memory.write_word_32(flash_optcr, optcr)?;
+        memory.write_word_32(flash_optcr, optcr | OPTCR_OPTSTRT)?;
+
+        wait_for(REGRESSION_TIMEOUT, "read protection regression", || {
+            Ok(memory.read_word_32(flash_sr)? & SR_BSY == 0)
+        })?;
+
+        memory.write_word_32(flash_optcr, optcr | OPTCR_OPTLOCK)?;
+
+        log::info!("The read protection was removed, the chip has to be power cycled");
+
+        Ok(())
+    }
+
+    fn unlock_optr(&self, memory: &mut Memory, flash_base: u32) -> Result<(), Error> {
+        let flash_keyr = flash_base + 0x08;
+        let flash_optkeyr = flash_base + 0x0C;
+        let flash_sr = flash_base + 0x10;
+        let flash_cr = flash_base + 0x14;
+        let flash_optr = flash_base + 0x20;
+
+        const CR_OPTSTRT: u32 = 1 << 17;
+        const CR_OBL_LAUNCH: u32 = 1 << 27;
+        const CR_OPTLOCK: u32 = 1 << 30;
+        const CR_LOCK: u32 = 1 << 31;
+
+        let optr = memory.read_word_32(flash_optr)?;
+        if !check_rdp_level(optr & 0xFF)? {
+            return Ok(());
+        }
+
+        let cr = memory.read_word_32(flash_cr)?;
+        if cr & CR_LOCK != 0 {
+            for key in &FLASH_KEYS {
+                memory.write_word_32(flash_keyr, *key)?;
+            }
+        }
+        if cr & CR_OPTLOCK != 0 {
+            for key in &OPTION_KEYS {
+                memory.write_word_32(flash_optkeyr, *key)?;
+            }
+        }
+
+        memory.write_word_32(flash_optr, (optr & !0xFF) | RDP_LEVEL_0)?;
+        memory.write_word_32(flash_cr, CR_OPTSTRT)?;
+
+        wait_for(REGRESSION_TIMEOUT, "read protection regression", || {
+            Ok(memory.read_word_32(flash_sr)? & SR_BSY == 0)
+        })?;
+
+        // Loading the option bytes resets the chip, so the write is usually not acknowledged.
+        if let Err(e) = memory.write_word_32(flash_cr, CR_OBL_LAUNCH) {
+            log::debug!("Reloading the option bytes reset the chip: {}", e);
+        }
+
+        log::info!("The read protection was removed");
+
+        Ok(())
+    }
+}
+
+/// Check if the read protection level `rdp` can and has to be changed to level 0.
+fn check_rdp_level(rdp: u32) -> Result<bool, DebugSequenceError> {
+    match rdp {
+        RDP_LEVEL_0 => {
+            log::info!("The chip is not read protected, nothing to unlock");
+            Ok(false)
+        }
+        RDP_LEVEL_2 => Err(DebugSequenceError::PermanentlyLocked),
+        _ => Ok(true),
+    }
+}
+
+impl DebugSequence for Stm32 {
+    fn unlock(&self, interface: &mut dyn ArmProbeInterface) -> Result<(), Error> {
+        let option_bytes = self.option_bytes.ok_or(DebugProbeError::NotImplemented(
+            "removing the read protection of this chip",
+        ))?;
+
+        let mut memory = interface.memory_interface(MemoryAP::new(0))?;
+
+        match option_bytes {
+            Stm32OptionBytes::Optcr { flash_base } => self.unlock_optcr(&mut memory, flash_base),
+            Stm32OptionBytes::Optr { flash_base } => self.unlock_optr(&mut memory, flash_base),
+        }
+    }
+
+    fn enable_debug_in_low_power(&self, core: &mut Core) -> Result<(), Error> {
+        let dbgmcu_cr = core.read_word_32(self.dbgmcu_cr)?;
+        core.write_word_32(self.dbgmcu_cr, dbgmcu_cr | DBGMCU_CR_DBG_LOW_POWER)
+    }
+
+    fn freeze_watchdogs(&self, core: &mut Core) -> Result<(), Error> {
+        if let Some(watchdog_freeze) = self.watchdog_freeze {
+            let freeze = core.read_word_32(watchdog_freeze)?;
+            core.write_word_32(watchdog_freeze, freeze | WATCHDOG_STOP)?;
+        }
+
+        Ok(())
+    }
+
+    fn setup_trace(&self, core: &mut Core, _config: &SwoConfig) -> Result<(), Error> {
+        log::debug!("Enabling the trace pins using DBGMCU_CR");
+        let mut dbgmcu_cr = core.read_word_32(self.dbgmcu_cr)?;
+        dbgmcu_cr |= DBGMCU_CR_TRACE_IOEN;
+        dbgmcu_cr &= !DBGMCU_CR_TRACE_MODE;
+        core.write_word_32(self.dbgmcu_cr, dbgmcu_cr)
+    }
+}
@@ -11,7 +11,7 @@ use communication_interface::{
 };
 
 use crate::core::{CoreInformation, RegisterFile};
-use crate::{CoreRegisterAddress, CoreStatus, Error, HaltReason, MemoryInterface};
+use crate::{BreakpointCause, CoreRegisterAddress, CoreStatus, Error, HaltReason, MemoryInterface};
 use bitfield::bitfield;
 use register::RISCV_REGISTERS;
 use std::time::{Duration, Instant};
@@ -107,6 +107,34 @@ impl<'probe> Riscv32<'probe> {
 
         Ok(())
     }
+
+    /// Check if the `ebreak` instruction at `dpc` is part of the semihosting sequence
+    ///
+    /// ```text
+    /// slli x0, x0, 0x1f
+    /// ebreak
+    /// srai x0, x0, 7
+    /// ```
+    ///
+    /// as defined by the RISC-V semihosting specification.
+    fn is_semihosting_ebreak(&mut self) -> Result<bool, crate::Error> {
+        const SLLI_X0_X0_0X1F: u32 = 0x01f0_1013;
+        const EBREAK: u32 = 0x0010_0073;
+        const SRAI_X0_X0_7: u32 = 0x4070_5013;
+
+        let dpc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
+
+        // The sequence has to be uncompressed and aligned, so that it cannot
+        // span a page boundary.
+        if dpc % 4 != 0 || dpc < 4 {
+            return Ok(false);
+        }
+
+        let mut sequence = [0u32; 3];
+        self.interface.read_32(dpc - 4, &mut sequence)?;
+
+        Ok(sequence == [SLLI_X0_X0_0X1F, EBREAK, SRAI_X0_X0_7])
+    }
 }
 
 impl<'probe> CoreInterface for Riscv32<'probe> {
@@ -513,9 +541,15 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
             let reason = match dcsr.cause() {
                 // An ebreak instruction was hit
-                1 => HaltReason::Breakpoint,
+                1 => {
+                    if self.is_semihosting_ebreak()? {
+                        HaltReason::Breakpoint(BreakpointCause::Semihosting)
+                    } else {
+                        HaltReason::Breakpoint(BreakpointCause::Software)
+                    }
+                }
                 // Trigger module caused halt
-                2 => HaltReason::Breakpoint,
+                2 => HaltReason::Breakpoint(BreakpointCause::Hardware),
                 // Debugger requested a halt
                 3 => HaltReason::Request,
                 // Core halted after single step
@@ -0,0 +1,53 @@
+use super::chip_family::{deserialize, serialize};
+use super::flash_algorithm::RawFlashAlgorithm;
+use super::memory::MemoryRegion;
+use super::registry::{get_target_by_name, read_description, RegistryError};
+use super::target::Target;
+use std::{borrow::Cow, path::Path};
+
+/// A board, which combines a chip with board specific memory,
+/// e.g. external QSPI flash.
+///
+/// This allows a single target description of a chip to be used
+/// with different external flash setups. The board description is
+/// usually read from a YAML file, using [BoardDescription::from_yaml_file],
+/// and the memory regions and flash algorithms in it are added to
+/// the target of the chip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardDescription {
+    /// The name of the board.
+    pub name: Cow<'static, str>,
+    /// The name of the chip on the board.
+    pub chip: Cow<'static, str>,
+    /// The memory regions of the board, which are added to the regions
+    /// of the chip.
+    ///
+    /// Regions of the chip which overlap with a region of the board are
+    /// replaced by the region of the board.
+    #[serde(default)]
+    pub memory_map: Cow<'static, [MemoryRegion]>,
+    /// The flash algorithms used to program the memory of the board.
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize")]
+    #[serde(serialize_with = "serialize")]
+    pub flash_algorithms: Cow<'static, [RawFlashAlgorithm]>,
+}
+
+impl BoardDescription {
+    /// Read a board description in YAML format from a file.
+    ///
+    /// Like in target descriptions, flash algorithms can be given as an ELF file
+    /// using the `elf` key, with a path relative to the board description.
+    pub fn from_yaml_file(path: &Path) -> Result<Self, RegistryError> {
+        Ok(serde_yaml::from_value(read_description(path)?)?)
+    }
+
+    /// Get the target of the chip on the board, including the memory
+    /// regions and flash algorithms of the board.
+    pub fn target(&self) -> Result<Target, RegistryError> {
+        let mut target = get_target_by_name(&self.chip)?;
+        target.apply_board(self);
+
+        Ok(target)
+    }
+}
@@ -1,4 +1,5 @@
 use super::memory::MemoryRegion;
+use super::scan_chain::ScanChain;
 use std::borrow::Cow;
 
 /// A single chip variant.
@@ -24,4 +25,14 @@ pub struct Chip {
     ///
     /// [`ChipFamily::flash_algorithms`]: crate::config::ChipFamily::flash_algorithms
     pub flash_algorithms: Cow<'static, [Cow<'static, str>]>,
+    /// The JTAG scan chain of the chip.
+    ///
+    /// If this is not set, the scan chain is detected automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_chain: Option<ScanChain>,
+    /// The SWD multi-drop `TARGETSEL` values of the debug ports of the chip, one per core.
+    ///
+    /// If this is not set, the chip has a single debug port, which is used without selecting it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swd_targetsel: Option<Cow<'static, [u32]>>,
 }
@@ -1,6 +1,6 @@
 use super::chip::Chip;
+use super::debug_sequence::DebugSequenceDescription;
 use super::flash_algorithm::RawFlashAlgorithm;
-use crate::config::TargetParseError;
 use jep106::JEP106Code;
 use std::borrow::Cow;
 
@@ -27,6 +27,11 @@ pub struct ChipFamily {
     /// The name of the core type.
     /// E.g. `M0` or `M4`.
     pub core: Cow<'static, str>,
+    /// The chip specific debug sequences of the family, e.g. to unlock a chip.
+    ///
+    /// If this is not set, no chip specific sequences are used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_sequence: Option<DebugSequenceDescription>,
 }
 
 pub fn serialize<S>(raw_algorithms: &[RawFlashAlgorithm], serializer: S) -> Result<S::Ok, S::Error>
@@ -72,14 +77,6 @@ where
 }
 
 impl ChipFamily {
-    /// Create a [ChipFamily] from a target
-    /// description in YAML format.
-    pub(crate) fn from_yaml_reader<R: std::io::Read>(
-        definition_reader: R,
-    ) -> Result<Self, TargetParseError> {
-        serde_yaml::from_reader(definition_reader)
-    }
-
     /// Get the different [Chip]s which are part of this
     /// family.
     pub fn variants(&self) -> &[Chip] {
@@ -0,0 +1,48 @@
+/// The chip specific debug sequences of a chip family.
+///
+/// The sequences are implemented in probe-rs, and are configured with the addresses
+/// of the registers they use. This allows using them for new chips by only adding
+/// the description to the target description file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DebugSequenceDescription {
+    /// Nordic chips, which are unlocked using the CTRL-AP.
+    Nrf {
+        /// The number of the CTRL-AP.
+        ctrl_ap: u8,
+        /// The address of the `TRACECONFIG` register, which is used to enable the trace pins.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        traceconfig: Option<u32>,
+    },
+    /// STM32 chips, which are configured using the DBGMCU peripheral.
+    Stm32 {
+        /// The address of the `DBGMCU_CR` register, which is used to keep debugging
+        /// enabled in low power modes, and to enable the trace pins.
+        dbgmcu_cr: u32,
+        /// The address of the DBGMCU register, which stops the window watchdog (bit 11)
+        /// and the independent watchdog (bit 12) while the core is halted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        watchdog_freeze: Option<u32>,
+        /// The flash registers which are used to remove the read protection.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        option_bytes: Option<Stm32OptionBytes>,
+    },
+    /// NXP Kinetis chips, which are unlocked using the MDM-AP.
+    Kinetis,
+}
+
+/// The layout of the flash registers used to program the option bytes of an STM32 chip.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Stm32OptionBytes {
+    /// The read protection level is configured in the `FLASH_OPTCR` register,
+    /// e.g. on STM32F2, STM32F4 and STM32F7 chips.
+    Optcr {
+        /// The base address of the flash registers.
+        flash_base: u32,
+    },
+    /// The read protection level is configured in the `FLASH_OPTR` register,
+    /// and programmed using `FLASH_CR`, e.g. on STM32G0, STM32G4 and STM32L4 chips.
+    Optr {
+        /// The base address of the flash registers.
+        flash_base: u32,
+    },
+}
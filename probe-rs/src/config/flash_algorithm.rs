@@ -1,9 +1,14 @@
 use super::flash_properties::FlashProperties;
-use super::memory::{PageInfo, RamRegion, SectorInfo};
+use super::memory::{MemoryRange, PageInfo, RamRegion, SectorDescription, SectorInfo};
 use crate::architecture::riscv;
 use crate::core::Architecture;
 use crate::flashing::FlashError;
-use std::{borrow::Cow, convert::TryInto};
+use goblin::elf::{section_header::SHT_NOBITS, Elf, SectionHeader};
+use std::{
+    borrow::Cow,
+    convert::{TryFrom, TryInto},
+};
+use thiserror::Error;
 
 /// A flash algorithm, which has been assembled for a specific
 /// chip.
@@ -32,11 +37,17 @@ pub struct FlashAlgorithm {
     pub pc_erase_sector: u32,
     /// Address of the `EraseAll()` entry point. Optional.
     pub pc_erase_all: Option<u32>,
+    /// Address of the `Verify()` entry point. Optional.
+    pub pc_verify: Option<u32>,
+    /// Address of the `BlankCheck()` entry point. Optional.
+    pub pc_blank_check: Option<u32>,
     /// Initial value of the R9 register for calling flash algo entry points, which
     /// determines where the position-independent data resides.
     pub static_base: u32,
     /// Initial value of the stack pointer when calling any flash algo API.
     pub begin_stack: u32,
+    /// Size of the stack, which ends at `begin_stack`.
+    pub stack_size: u32,
     /// Base address of the page buffer. Used if `page_buffers` is not provided.
     pub begin_data: u32,
     /// An optional list of base addresses for page buffers. The buffers must be at
@@ -104,6 +115,27 @@ impl FlashAlgorithm {
     }
 }
 
+/// Error type for all errors which occur when reading a flash algorithm
+/// from an ELF file, using [RawFlashAlgorithm::from_elf].
+#[derive(Debug, Error)]
+pub enum FlashAlgorithmParseError {
+    /// The flash algorithm is not a valid ELF file.
+    #[error("Unable to parse the ELF file of the flash algorithm")]
+    Elf(#[from] goblin::error::Error),
+    /// A required symbol is missing in the flash algorithm.
+    #[error("The flash algorithm is missing the symbol '{0}'")]
+    MissingSymbol(&'static str),
+    /// The `FlashDevice` description of the flash algorithm is invalid.
+    #[error("The flash algorithm contains an invalid FlashDevice description")]
+    InvalidFlashDevice,
+    /// A symbol of the flash algorithm is not placed in its code.
+    #[error("The symbol '{0}' is outside of the code of the flash algorithm")]
+    InvalidSymbol(&'static str),
+    /// A section of the flash algorithm exceeds the 32 bit address space.
+    #[error("The section at {0:#x} exceeds the address space of the flash algorithm")]
+    InvalidSection(u64),
+}
+
 /// The raw flash algorithm is the description of a flash algorithm,
 /// and is usually read from a target description file.
 ///
@@ -132,8 +164,32 @@ pub struct RawFlashAlgorithm {
     pub pc_erase_sector: u32,
     /// Address of the `EraseAll()` entry point. Optional.
     pub pc_erase_all: Option<u32>,
+    /// Address of the `Verify()` entry point. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_verify: Option<u32>,
+    /// Address of the `BlankCheck()` entry point. Optional.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc_blank_check: Option<u32>,
     /// The offset from the start of RAM to the data section.
     pub data_section_offset: u32,
+    /// The address the algorithm is loaded to. Optional.
+    ///
+    /// If this is not set, the algorithm is placed at the start of the
+    /// largest RAM region of the chip it fits into, after its stack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_address: Option<u32>,
+    /// The size of the stack of the algorithm, which is placed below
+    /// the algorithm. Optional.
+    ///
+    /// If this is not set, the largest stack of up to 512 bytes, which
+    /// still leaves room for a page buffer, is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_size: Option<u32>,
+    /// The address of the page buffers. Optional.
+    ///
+    /// If this is not set, the page buffers are placed after the algorithm.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_load_address: Option<u32>,
     /// The properties of the flash on the device.
     pub flash_properties: FlashProperties,
 }
@@ -174,6 +230,7 @@ where
 impl RawFlashAlgorithm {
     const FLASH_ALGO_STACK_SIZE: u32 = 512;
     const FLASH_ALGO_STACK_DECREMENT: u32 = 64;
+    const FLASH_ALGO_MIN_STACK_SIZE: u32 = 64;
 
     // Header for RISCV Flash Algorithms
     const RISCV_FLASH_BLOB_HEADER: [u32; 2] = [riscv::assembly::EBREAK, riscv::assembly::EBREAK];
@@ -219,35 +276,106 @@ impl RawFlashAlgorithm {
             )
             .collect();
 
-        let mut offset = 0;
-        let mut addr_stack = 0;
-        let mut addr_load = 0;
-        let mut addr_data = 0;
-
-        // Try to find a stack size that fits with at least one page of data.
-        for i in 0..Self::FLASH_ALGO_STACK_SIZE / Self::FLASH_ALGO_STACK_DECREMENT {
-            offset = Self::FLASH_ALGO_STACK_SIZE - Self::FLASH_ALGO_STACK_DECREMENT * i;
-            // Stack address
-            addr_stack = ram_region.range.start + offset;
-            // Load address
-            addr_load = addr_stack;
-            offset += (instructions.len() * size_of::<u32>()) as u32;
-
-            // Data buffer 1
-            addr_data = ram_region.range.start + offset;
-            offset += self.flash_properties.page_size;
-
-            if offset <= ram_region.range.end - ram_region.range.start {
-                break;
+        let ram = &ram_region.range;
+        let code_size = (instructions.len() * size_of::<u32>()) as u32;
+        let page_size = self.flash_properties.page_size;
+
+        // The end of a range which exceeds the address space.
+        let does_not_fit = |start| FlashError::FlashAlgorithmDoesNotFit {
+            start,
+            end: u32::MAX,
+        };
+
+        // The stack is placed directly below the algorithm.
+        let (addr_load, stack_size) = match (self.load_address, self.stack_size) {
+            (Some(addr_load), stack_size) => {
+                let stack_size = stack_size.unwrap_or_else(|| {
+                    u32::min(
+                        Self::FLASH_ALGO_STACK_SIZE,
+                        addr_load.saturating_sub(ram.start),
+                    )
+                });
+                (addr_load, stack_size)
+            }
+            (None, Some(stack_size)) => (
+                ram.start
+                    .checked_add(stack_size)
+                    .ok_or_else(|| does_not_fit(ram.start))?,
+                stack_size,
+            ),
+            (None, None) => {
+                let mut stack_size = 0;
+
+                // Try to find a stack size that fits with at least one page of data.
+                for i in 0..Self::FLASH_ALGO_STACK_SIZE / Self::FLASH_ALGO_STACK_DECREMENT {
+                    stack_size = Self::FLASH_ALGO_STACK_SIZE - Self::FLASH_ALGO_STACK_DECREMENT * i;
+
+                    if stack_size
+                        .saturating_add(code_size)
+                        .saturating_add(page_size)
+                        <= ram.end - ram.start
+                    {
+                        break;
+                    }
+                }
+
+                (
+                    ram.start
+                        .checked_add(stack_size)
+                        .ok_or_else(|| does_not_fit(ram.start))?,
+                    stack_size,
+                )
             }
+        };
+        let addr_stack = addr_load;
+
+        if stack_size < Self::FLASH_ALGO_MIN_STACK_SIZE {
+            return Err(FlashError::StackTooSmall {
+                size: stack_size,
+                minimum: Self::FLASH_ALGO_MIN_STACK_SIZE,
+            });
+        }
+
+        let algorithm_end = addr_load
+            .checked_add(code_size)
+            .ok_or_else(|| does_not_fit(addr_load))?;
+        let algorithm_range = addr_stack.saturating_sub(stack_size)..algorithm_end;
+        if !ram.contains_range(&algorithm_range) {
+            return Err(FlashError::FlashAlgorithmDoesNotFit {
+                start: algorithm_range.start,
+                end: algorithm_range.end,
+            });
+        }
+
+        // Data buffer 1
+        let addr_data = self.data_load_address.unwrap_or(algorithm_range.end);
+        let data_end = addr_data
+            .checked_add(page_size)
+            .ok_or_else(|| does_not_fit(addr_data))?;
+        let data_range = addr_data..data_end;
+
+        // An explicitly placed buffer may be located in a different RAM region.
+        if self.data_load_address.is_none() && !ram.contains_range(&data_range) {
+            return Err(FlashError::FlashAlgorithmDoesNotFit {
+                start: algorithm_range.start,
+                end: data_range.end,
+            });
+        }
+        if algorithm_range.intersects_range(&data_range) {
+            return Err(FlashError::FlashAlgorithmDoesNotFit {
+                start: data_range.start,
+                end: data_range.end,
+            });
         }
 
         // Data buffer 2
-        let addr_data2 = ram_region.range.start + offset;
-        offset += self.flash_properties.page_size;
+        let addr_data2 = data_range.end;
+        let double_buffer_range = addr_data..addr_data2.saturating_add(page_size);
 
         // Determine whether we can use double buffering or not by the remaining RAM region size.
-        let page_buffers = if offset <= ram_region.range.end - ram_region.range.start {
+        let page_buffers = if ram.contains_range(&double_buffer_range)
+            && !algorithm_range.intersects_range(&double_buffer_range)
+        {
             vec![addr_data, addr_data2]
         } else {
             vec![addr_data]
@@ -267,18 +395,223 @@ impl RawFlashAlgorithm {
             pc_program_page: code_start + self.pc_program_page,
             pc_erase_sector: code_start + self.pc_erase_sector,
             pc_erase_all: self.pc_erase_all.map(|v| code_start + v),
+            pc_verify: self.pc_verify.map(|v| code_start + v),
+            pc_blank_check: self.pc_blank_check.map(|v| code_start + v),
             static_base: code_start + self.data_section_offset,
             begin_stack: addr_stack,
+            stack_size,
             begin_data: page_buffers[0],
             page_buffers: page_buffers.clone(),
             flash_properties: self.flash_properties.clone(),
         })
     }
+
+    /// Read a flash algorithm from an ELF file, e.g. an FLM file of a CMSIS-Pack.
+    ///
+    /// The algorithm has to be built like a CMSIS flash algorithm: the
+    /// loadable sections, i.e. `PrgCode` and `PrgData` with its RW and ZI
+    /// data, are combined into the instructions of the algorithm, and the
+    /// entry points are read from the `Init`, `UnInit`, `EraseChip`,
+    /// `EraseSector`, `ProgramPage`, `Verify` and `BlankCheck` symbols. Only
+    /// `EraseSector` and `ProgramPage` are required.
+    ///
+    /// The flash properties are read from the `FlashDevice` structure, which
+    /// is placed in the `DevDscr` section and is not loaded into the target.
+    pub fn from_elf(name: &str, data: &[u8]) -> Result<Self, FlashAlgorithmParseError> {
+        let elf = Elf::parse(data)?;
+
+        let mut sections: Vec<&SectionHeader> = elf
+            .section_headers
+            .iter()
+            .filter(|sh| sh.is_alloc() && sh.sh_size > 0)
+            .filter(|sh| section_name(&elf, sh) != Some(DEVICE_DESCRIPTION_SECTION))
+            .collect();
+        sections.sort_by_key(|sh| sh.sh_addr);
+
+        let code_start = sections.first().map(|sh| sh.sh_addr).unwrap_or(0);
+
+        // The offset of the end of a section from the start of the code.
+        let section_end = |sh: &SectionHeader| {
+            sh.sh_addr
+                .checked_sub(code_start)
+                .and_then(|offset| offset.checked_add(sh.sh_size))
+                .and_then(|end| u32::try_from(end).ok())
+                .ok_or(FlashAlgorithmParseError::InvalidSection(sh.sh_addr))
+        };
+
+        let mut instructions = Vec::new();
+        for sh in &sections {
+            let end = section_end(sh)? as usize;
+            let offset = end - sh.sh_size as usize;
+
+            if instructions.len() < end {
+                instructions.resize(end, 0);
+            }
+
+            // ZI data is not contained in the file, and is left zeroed.
+            if sh.sh_type != SHT_NOBITS {
+                let contents = data.get(sh.file_range()).ok_or_else(|| {
+                    goblin::error::Error::Malformed(format!(
+                        "Section at {:#x} exceeds the file",
+                        sh.sh_addr
+                    ))
+                })?;
+                instructions[offset..end].copy_from_slice(contents);
+            }
+        }
+
+        // The algorithm is loaded in 32 bit words.
+        while instructions.len() % 4 != 0 {
+            instructions.push(0);
+        }
+
+        let data_section_offset = sections
+            .iter()
+            .find(|sh| sh.is_writable())
+            // The offsets of all sections were checked above.
+            .map(|sh| (sh.sh_addr - code_start) as u32)
+            .unwrap_or(instructions.len() as u32);
+
+        let entry_point = |name| {
+            find_symbol(&elf, name)
+                .map(|address| {
+                    address
+                        .checked_sub(code_start)
+                        .and_then(|offset| u32::try_from(offset).ok())
+                        .ok_or(FlashAlgorithmParseError::InvalidSymbol(name))
+                })
+                .transpose()
+        };
+
+        let pc_program_page = entry_point("ProgramPage")?
+            .ok_or(FlashAlgorithmParseError::MissingSymbol("ProgramPage"))?;
+        let pc_erase_sector = entry_point("EraseSector")?
+            .ok_or(FlashAlgorithmParseError::MissingSymbol("EraseSector"))?;
+
+        let (description, flash_properties) = read_flash_device(&elf, data)?;
+
+        Ok(RawFlashAlgorithm {
+            name: Cow::Owned(name.to_owned()),
+            description: Cow::Owned(description),
+            default: false,
+            instructions: Cow::Owned(instructions),
+            pc_init: entry_point("Init")?,
+            pc_uninit: entry_point("UnInit")?,
+            pc_program_page,
+            pc_erase_sector,
+            pc_erase_all: entry_point("EraseChip")?,
+            pc_verify: entry_point("Verify")?,
+            pc_blank_check: entry_point("BlankCheck")?,
+            data_section_offset,
+            load_address: None,
+            stack_size: None,
+            data_load_address: None,
+            flash_properties,
+        })
+    }
+}
+
+/// Name of the section containing the `FlashDevice` structure of an ELF flash algorithm.
+const DEVICE_DESCRIPTION_SECTION: &str = "DevDscr";
+
+/// Marks the end of the sector list in the `FlashDevice` structure.
+const SECTOR_END: u32 = 0xFFFF_FFFF;
+
+/// Offsets of the fields of the `FlashDevice` structure.
+mod flash_device {
+    pub const DEV_NAME: usize = 2;
+    pub const DEV_NAME_LEN: usize = 128;
+    pub const DEV_ADR: usize = 132;
+    pub const SZ_DEV: usize = 136;
+    pub const SZ_PAGE: usize = 140;
+    pub const VAL_EMPTY: usize = 148;
+    pub const TO_PROG: usize = 152;
+    pub const TO_ERASE: usize = 156;
+    pub const SECTORS: usize = 160;
+}
+
+/// Read the `FlashDevice` structure of an ELF flash algorithm, and return the
+/// name of the device together with the properties of the flash.
+fn read_flash_device(
+    elf: &Elf,
+    data: &[u8],
+) -> Result<(String, FlashProperties), FlashAlgorithmParseError> {
+    let address = find_symbol(elf, "FlashDevice")
+        .ok_or(FlashAlgorithmParseError::MissingSymbol("FlashDevice"))?;
+
+    let section = elf
+        .section_headers
+        .iter()
+        .find(|sh| sh.sh_type != SHT_NOBITS && sh.vm_range().contains(&(address as usize)))
+        .ok_or(FlashAlgorithmParseError::InvalidFlashDevice)?;
+
+    let start = (section.sh_offset + address - section.sh_addr) as usize;
+    let end = section.file_range().end;
+    let device = data
+        .get(start..end)
+        .ok_or(FlashAlgorithmParseError::InvalidFlashDevice)?;
+
+    let read_u32 = |offset: usize| {
+        device
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or(FlashAlgorithmParseError::InvalidFlashDevice)
+    };
+
+    let name = device
+        .get(flash_device::DEV_NAME..flash_device::DEV_NAME + flash_device::DEV_NAME_LEN)
+        .ok_or(FlashAlgorithmParseError::InvalidFlashDevice)?;
+    let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    let name = String::from_utf8_lossy(&name[..name_len]).into_owned();
+
+    let flash_start = read_u32(flash_device::DEV_ADR)?;
+    let flash_size = read_u32(flash_device::SZ_DEV)?;
+
+    let mut sectors = Vec::new();
+    let mut offset = flash_device::SECTORS;
+    loop {
+        let size = read_u32(offset)?;
+        let address = read_u32(offset + 4)?;
+
+        if size == SECTOR_END && address == SECTOR_END {
+            break;
+        }
+
+        sectors.push(SectorDescription { size, address });
+        offset += 8;
+    }
+
+    let flash_end = flash_start
+        .checked_add(flash_size)
+        .ok_or(FlashAlgorithmParseError::InvalidFlashDevice)?;
+
+    let flash_properties = FlashProperties {
+        address_range: flash_start..flash_end,
+        page_size: read_u32(flash_device::SZ_PAGE)?,
+        erased_byte_value: *device
+            .get(flash_device::VAL_EMPTY)
+            .ok_or(FlashAlgorithmParseError::InvalidFlashDevice)?,
+        program_page_timeout: read_u32(flash_device::TO_PROG)?,
+        erase_sector_timeout: read_u32(flash_device::TO_ERASE)?,
+        sectors: Cow::Owned(sectors),
+    };
+
+    Ok((name, flash_properties))
+}
+
+fn section_name<'a>(elf: &'a Elf, sh: &SectionHeader) -> Option<&'a str> {
+    elf.shdr_strtab.get(sh.sh_name).and_then(Result::ok)
+}
+
+fn find_symbol(elf: &Elf, name: &str) -> Option<u64> {
+    elf.syms
+        .iter()
+        .find(|sym| elf.strtab.get(sym.st_name).and_then(Result::ok) == Some(name))
+        .map(|sym| sym.st_value)
 }
 
 #[test]
 fn flash_sector_single_size() {
-    use crate::config::SectorDescription;
     let config = FlashAlgorithm {
         flash_properties: FlashProperties {
             sectors: Cow::Borrowed(&[SectorDescription {
@@ -308,7 +641,6 @@ fn flash_sector_single_size() {
 
 #[test]
 fn flash_sector_single_size_weird_sector_size() {
-    use crate::config::SectorDescription;
     let config = FlashAlgorithm {
         flash_properties: FlashProperties {
             sectors: Cow::Borrowed(&[SectorDescription {
@@ -338,7 +670,6 @@ fn flash_sector_single_size_weird_sector_size() {
 
 #[test]
 fn flash_sector_multiple_sizes() {
-    use crate::config::SectorDescription;
     let config = FlashAlgorithm {
         flash_properties: FlashProperties {
             sectors: Cow::Borrowed(&[
@@ -381,3 +712,180 @@ fn flash_sector_multiple_sizes() {
     assert_eq!(Some(expected_b), config.sector_info(0x801_0000));
     assert_eq!(Some(expected_c), config.sector_info(0x80A_0000));
 }
+
+#[test]
+fn flash_algorithm_from_elf() {
+    let algorithm = RawFlashAlgorithm::from_elf(
+        "qspi",
+        include_bytes!("../../tests/qspi_flash_algorithm.elf"),
+    )
+    .unwrap();
+
+    assert_eq!(algorithm.name, "qspi");
+    assert_eq!(algorithm.description, "QSPI 8MB Flash");
+
+    // Code, RW and ZI data, without the FlashDevice structure.
+    assert_eq!(algorithm.instructions.len(), 0x24);
+    assert_eq!(algorithm.data_section_offset, 0x1C);
+
+    assert_eq!(algorithm.pc_init, Some(0x1));
+    assert_eq!(algorithm.pc_uninit, Some(0x5));
+    assert_eq!(algorithm.pc_erase_all, Some(0x9));
+    assert_eq!(algorithm.pc_erase_sector, 0xD);
+    assert_eq!(algorithm.pc_program_page, 0x11);
+    assert_eq!(algorithm.pc_verify, Some(0x15));
+    assert_eq!(algorithm.pc_blank_check, Some(0x19));
+
+    let properties = &algorithm.flash_properties;
+    assert_eq!(properties.address_range, 0x9000_0000..0x9080_0000);
+    assert_eq!(properties.page_size, 0x100);
+    assert_eq!(properties.erased_byte_value, 0xFF);
+    assert_eq!(
+        properties.sectors.as_ref(),
+        [SectorDescription {
+            size: 0x1000,
+            address: 0x0,
+        }]
+    );
+}
+
+#[test]
+fn flash_algorithm_from_elf_with_symbol_before_code() {
+    let mut data = include_bytes!("../../tests/qspi_flash_algorithm.elf").to_vec();
+
+    // Move the code behind the entry points, by changing the address of the sections.
+    let elf = Elf::parse(&data).unwrap();
+    let section_addresses: Vec<usize> = elf
+        .section_headers
+        .iter()
+        .enumerate()
+        .filter(|(_, sh)| sh.is_alloc())
+        .map(|(i, _)| elf.header.e_shoff as usize + i * elf.header.e_shentsize as usize + 12)
+        .collect();
+
+    for offset in section_addresses {
+        let address = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        data[offset..offset + 4].copy_from_slice(&(address + 0x100).to_le_bytes());
+    }
+
+    assert!(matches!(
+        RawFlashAlgorithm::from_elf("qspi", &data),
+        Err(FlashAlgorithmParseError::InvalidSymbol(_))
+    ));
+}
+
+#[test]
+fn flash_algorithm_from_elf_beyond_address_space() {
+    let mut data = include_bytes!("../../tests/qspi_flash_algorithm.elf").to_vec();
+
+    // Place the flash at the end of the address space, so that it overflows.
+    let elf = Elf::parse(&data).unwrap();
+    let address = find_symbol(&elf, "FlashDevice").unwrap();
+    let section = elf
+        .section_headers
+        .iter()
+        .find(|sh| sh.sh_type != SHT_NOBITS && sh.vm_range().contains(&(address as usize)))
+        .unwrap();
+    let device = (section.sh_offset + address - section.sh_addr) as usize;
+
+    data[device + flash_device::DEV_ADR..][..4].copy_from_slice(&0xFFFF_0000u32.to_le_bytes());
+    data[device + flash_device::SZ_DEV..][..4].copy_from_slice(&0x2_0000u32.to_le_bytes());
+
+    assert!(matches!(
+        RawFlashAlgorithm::from_elf("qspi", &data),
+        Err(FlashAlgorithmParseError::InvalidFlashDevice)
+    ));
+}
+
+#[test]
+fn assemble_with_explicit_placement() {
+    let mut algorithm = RawFlashAlgorithm::from_elf(
+        "qspi",
+        include_bytes!("../../tests/qspi_flash_algorithm.elf"),
+    )
+    .unwrap();
+    algorithm.load_address = Some(0x2000_1000);
+    algorithm.stack_size = Some(0x800);
+    algorithm.data_load_address = Some(0x2000_8000);
+
+    let ram = RamRegion {
+        range: 0x2000_0000..0x2001_0000,
+        is_boot_memory: false,
+    };
+    let assembled = algorithm.assemble(&ram, Architecture::Arm).unwrap();
+
+    assert_eq!(assembled.load_address, 0x2000_1000);
+    assert_eq!(assembled.begin_stack, 0x2000_1000);
+    assert_eq!(assembled.stack_size, 0x800);
+    assert_eq!(assembled.page_buffers, [0x2000_8000, 0x2000_8100]);
+}
+
+#[test]
+fn assemble_without_room_for_stack() {
+    let mut algorithm = RawFlashAlgorithm::from_elf(
+        "qspi",
+        include_bytes!("../../tests/qspi_flash_algorithm.elf"),
+    )
+    .unwrap();
+    algorithm.load_address = Some(0x2000_0000);
+
+    let ram = RamRegion {
+        range: 0x2000_0000..0x2001_0000,
+        is_boot_memory: false,
+    };
+    let result = algorithm.assemble(&ram, Architecture::Arm);
+
+    assert!(matches!(
+        result,
+        Err(FlashError::StackTooSmall { size: 0, .. })
+    ));
+}
+
+#[test]
+fn assemble_beyond_address_space() {
+    let mut algorithm = RawFlashAlgorithm::from_elf(
+        "qspi",
+        include_bytes!("../../tests/qspi_flash_algorithm.elf"),
+    )
+    .unwrap();
+    algorithm.load_address = Some(0xFFFF_FFF0);
+
+    let ram = RamRegion {
+        range: 0xFFFF_0000..0xFFFF_FFFF,
+        is_boot_memory: false,
+    };
+    let result = algorithm.assemble(&ram, Architecture::Arm);
+
+    assert!(matches!(
+        result,
+        Err(FlashError::FlashAlgorithmDoesNotFit {
+            start: 0xFFFF_FFF0,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn assemble_stack_does_not_fit() {
+    let mut algorithm = RawFlashAlgorithm::from_elf(
+        "qspi",
+        include_bytes!("../../tests/qspi_flash_algorithm.elf"),
+    )
+    .unwrap();
+    algorithm.load_address = Some(0x2000_1000);
+    algorithm.stack_size = Some(0x2000);
+
+    let ram = RamRegion {
+        range: 0x2000_0000..0x2001_0000,
+        is_boot_memory: false,
+    };
+    let result = algorithm.assemble(&ram, Architecture::Arm);
+
+    assert!(matches!(
+        result,
+        Err(FlashError::FlashAlgorithmDoesNotFit {
+            start: 0x1FFF_F000,
+            ..
+        })
+    ));
+}
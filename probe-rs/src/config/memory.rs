@@ -1,4 +1,5 @@
 use core::ops::Range;
+use std::borrow::Cow;
 
 /// Represents a region in flash.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -7,6 +8,12 @@ pub struct FlashRegion {
     pub range: Range<u32>,
     /// True if the chip boots from this memory
     pub is_boot_memory: bool,
+    /// The name of the flash algorithm used to program this region, e.g.
+    /// for external flash.
+    ///
+    /// If this is not set, the algorithm is selected by its address range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<Cow<'static, str>>,
 }
 
 impl FlashRegion {
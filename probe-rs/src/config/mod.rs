@@ -21,22 +21,43 @@
 //! To add a target at runtime, the [add_target_from_yaml] file can
 //! be used to read targets from a YAML file.
 //!
+//! Targets can also be read directly from a CMSIS-Pack, using the
+//! [add_target_from_pack] function. The devices described in the pack
+//! are added together with the flash algorithms contained in the pack.
+//! A [ChipFamily] which was created in code is added using [add_target_family].
+//!
+//! ## Boards
+//!
+//! Memory which is not part of the chip, e.g. external flash, can be
+//! described using a [BoardDescription]. It adds the memory regions
+//! and flash algorithms of the board to the target of the chip.
+//!
 
+mod board;
 mod chip;
 mod chip_family;
 mod chip_info;
+mod debug_sequence;
 mod flash_algorithm;
 mod flash_properties;
 mod memory;
+mod pack;
 mod registry;
+mod scan_chain;
 mod target;
 
+pub use board::BoardDescription;
 pub use chip::Chip;
 pub use chip_family::ChipFamily;
-pub use flash_algorithm::{FlashAlgorithm, RawFlashAlgorithm};
+pub use debug_sequence::{DebugSequenceDescription, Stm32OptionBytes};
+pub use flash_algorithm::{FlashAlgorithm, FlashAlgorithmParseError, RawFlashAlgorithm};
 pub use flash_properties::FlashProperties;
 pub use memory::{FlashRegion, MemoryRegion, PageInfo, RamRegion, SectorDescription, SectorInfo};
-pub use registry::{add_target_from_yaml, families, RegistryError};
+pub use pack::PackError;
+pub use registry::{
+    add_target_family, add_target_from_pack, add_target_from_yaml, families, RegistryError,
+};
+pub use scan_chain::{ScanChain, ScanChainElement};
 pub use target::{Target, TargetParseError, TargetSelector};
 
 // Crate-internal API
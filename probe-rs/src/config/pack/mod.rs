@@ -0,0 +1,107 @@
+//! Import of targets from CMSIS-Packs.
+//!
+//! A CMSIS-Pack is a zip archive, which contains a package description (PDSC)
+//! file describing the devices in the pack, and the flash algorithms (FLM files)
+//! of the devices. The flash algorithms are ELF files, which are read using
+//! [RawFlashAlgorithm::from_elf].
+
+mod pdsc;
+
+use super::{ChipFamily, FlashAlgorithmParseError, RawFlashAlgorithm};
+use std::io::{Read, Seek};
+use thiserror::Error;
+use zip::ZipArchive;
+
+/// Error type for all errors which occur when reading a CMSIS-Pack.
+#[derive(Debug, Error)]
+pub enum PackError {
+    /// An IO error which occured when reading the pack.
+    #[error("An IO error was encountered")]
+    Io(#[from] std::io::Error),
+    /// The pack is not a valid zip archive.
+    #[error("Unable to read the pack archive")]
+    Zip(#[from] zip::result::ZipError),
+    /// The package description could not be parsed.
+    #[error("Unable to parse the package description")]
+    Xml(#[from] roxmltree::Error),
+    /// A flash algorithm of the pack could not be read.
+    #[error("Unable to read the flash algorithm")]
+    Algorithm(#[from] FlashAlgorithmParseError),
+    /// The pack does not contain a package description file.
+    #[error("The pack does not contain a package description (.pdsc) file")]
+    MissingDescription,
+    /// A required attribute is missing in the package description.
+    #[error("The element '{element}' is missing the attribute '{attribute}'")]
+    MissingAttribute {
+        /// The element with the missing attribute.
+        element: String,
+        /// The name of the missing attribute.
+        attribute: &'static str,
+    },
+    /// A number in the package description could not be parsed.
+    #[error("Invalid number '{0}' in the package description")]
+    InvalidNumber(String),
+    /// A flash algorithm referenced by a device is not contained in the pack.
+    #[error("The flash algorithm '{0}' is not contained in the pack")]
+    AlgorithmNotFound(String),
+    /// A memory of a device ends beyond the 32-bit address space.
+    #[error("The memory '{memory}' of the device '{device}' exceeds the address space")]
+    InvalidMemory {
+        /// The name of the device.
+        device: String,
+        /// The `id` or `name` of the memory.
+        memory: String,
+    },
+}
+
+/// Read all chip families contained in a CMSIS-Pack.
+pub(crate) fn read_pack<R: Read + Seek>(reader: R) -> Result<Vec<ChipFamily>, PackError> {
+    let mut archive = ZipArchive::new(reader)?;
+
+    let description_name = archive
+        .file_names()
+        .find(|name| !name.contains('/') && name.to_ascii_lowercase().ends_with(".pdsc"))
+        .map(str::to_owned)
+        .ok_or(PackError::MissingDescription)?;
+
+    let mut description = String::new();
+    archive
+        .by_name(&description_name)?
+        .read_to_string(&mut description)?;
+
+    let mut families = Vec::new();
+
+    for family in pdsc::parse_families(&description)? {
+        let family = family.into_chip_family(|name, path| {
+            let data = read_file(&mut archive, path)?;
+            Ok(RawFlashAlgorithm::from_elf(name, &data)?)
+        })?;
+
+        families.extend(family);
+    }
+
+    Ok(families)
+}
+
+/// Read a file from the pack.
+///
+/// Paths in the package description are relative to the pack root and may use
+/// backslashes or a different case than the archive.
+fn read_file<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+) -> Result<Vec<u8>, PackError> {
+    let path = path.replace('\\', "/");
+    let path = path.trim_start_matches("./");
+
+    let name = archive
+        .file_names()
+        .find(|name| name.eq_ignore_ascii_case(path))
+        .map(str::to_owned)
+        .ok_or_else(|| PackError::AlgorithmNotFound(path.to_owned()))?;
+
+    let mut data = Vec::new();
+    archive.by_name(&name)?.read_to_end(&mut data)?;
+
+    Ok(data)
+}
@@ -0,0 +1,434 @@
+//! Parser for the package description (PDSC) of CMSIS-Packs.
+
+use super::PackError;
+use crate::config::memory::GenericRegion;
+use crate::config::{Chip, ChipFamily, FlashRegion, MemoryRegion, RamRegion, RawFlashAlgorithm};
+use roxmltree::{Document, Node};
+use std::{borrow::Cow, collections::HashMap, path::Path};
+
+/// A device family, as described by a `family` element.
+#[derive(Debug)]
+pub(super) struct Family {
+    name: String,
+    devices: Vec<Device>,
+}
+
+/// A single device, i.e. a `device` or `variant` element.
+///
+/// The elements describing a device are inherited from the enclosing
+/// `family`, `subFamily` and `device` elements.
+#[derive(Debug)]
+struct Device {
+    name: String,
+    core: Option<String>,
+    memories: Vec<Memory>,
+    algorithms: Vec<Algorithm>,
+}
+
+#[derive(Debug)]
+struct Memory {
+    /// The `id` or `name` of the memory, which is used to override
+    /// memories of the enclosing elements.
+    key: String,
+    kind: MemoryKind,
+    start: u32,
+    size: u32,
+    startup: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MemoryKind {
+    Flash,
+    Ram,
+    Generic,
+}
+
+#[derive(Debug)]
+struct Algorithm {
+    path: String,
+    default: bool,
+}
+
+/// Parse all device families of a package description.
+pub(super) fn parse_families(description: &str) -> Result<Vec<Family>, PackError> {
+    let document = Document::parse(description)?;
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("family"))
+        .map(parse_family)
+        .collect()
+}
+
+fn parse_family(family: Node) -> Result<Family, PackError> {
+    let name = attribute(family, "Dfamily")?.to_owned();
+
+    let devices = family
+        .descendants()
+        .filter(|node| match node.tag_name().name() {
+            "variant" => true,
+            // A device with variants is only described by its variants.
+            "device" => !node.children().any(|child| child.has_tag_name("variant")),
+            _ => false,
+        })
+        .map(parse_device)
+        .collect::<Result<_, _>>()?;
+
+    Ok(Family { name, devices })
+}
+
+fn parse_device(node: Node) -> Result<Device, PackError> {
+    let name = if node.has_tag_name("variant") {
+        attribute(node, "Dvariant")?
+    } else {
+        attribute(node, "Dname")?
+    };
+
+    let mut device = Device {
+        name: name.to_owned(),
+        core: None,
+        memories: Vec::new(),
+        algorithms: Vec::new(),
+    };
+
+    // Walk from the family down to the device, so that more specific
+    // elements override the inherited ones.
+    let mut levels: Vec<Node> = node
+        .ancestors()
+        .take_while(|ancestor| !ancestor.has_tag_name("devices"))
+        .collect();
+    levels.reverse();
+
+    for level in levels {
+        for element in level.children().filter(Node::is_element) {
+            match element.tag_name().name() {
+                "processor" => {
+                    if let Some(core) = element.attribute("Dcore") {
+                        device.core = Some(core.to_owned());
+                    }
+                }
+                "memory" => {
+                    let memory = parse_memory(element)?;
+                    match device.memories.iter_mut().find(|m| m.key == memory.key) {
+                        Some(inherited) => *inherited = memory,
+                        None => device.memories.push(memory),
+                    }
+                }
+                "algorithm" => {
+                    let algorithm = Algorithm {
+                        path: attribute(element, "name")?.replace('\\', "/"),
+                        default: element.attribute("default").map_or(false, parse_bool),
+                    };
+                    match device
+                        .algorithms
+                        .iter_mut()
+                        .find(|a| a.path == algorithm.path)
+                    {
+                        Some(inherited) => *inherited = algorithm,
+                        None => device.algorithms.push(algorithm),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    Ok(device)
+}
+
+fn parse_memory(element: Node) -> Result<Memory, PackError> {
+    let (key, kind) = if let Some(id) = element.attribute("id") {
+        let kind = if id.starts_with("IROM") {
+            MemoryKind::Flash
+        } else if id.starts_with("IRAM") {
+            MemoryKind::Ram
+        } else {
+            MemoryKind::Generic
+        };
+        (id, kind)
+    } else {
+        let name = attribute(element, "name")?;
+        let access = element.attribute("access").unwrap_or("");
+        let kind = if access.contains('p') {
+            MemoryKind::Generic
+        } else if access.contains('w') {
+            MemoryKind::Ram
+        } else if access.contains('r') || access.contains('x') {
+            MemoryKind::Flash
+        } else {
+            MemoryKind::Generic
+        };
+        (name, kind)
+    };
+
+    Ok(Memory {
+        key: key.to_owned(),
+        kind,
+        start: parse_number(attribute(element, "start")?)?,
+        size: parse_number(attribute(element, "size")?)?,
+        startup: element.attribute("startup").map_or(false, parse_bool),
+    })
+}
+
+impl Family {
+    /// Convert the family into a [ChipFamily], using `load_algorithm` to read the
+    /// flash algorithms referenced by the devices, given their name and path.
+    ///
+    /// Devices with a core which is not supported by probe-rs are skipped. If no
+    /// device of the family is supported, `None` is returned.
+    pub(super) fn into_chip_family(
+        self,
+        mut load_algorithm: impl FnMut(&str, &str) -> Result<RawFlashAlgorithm, PackError>,
+    ) -> Result<Option<ChipFamily>, PackError> {
+        let mut core: Option<&'static str> = None;
+        let mut variants = Vec::new();
+        let mut flash_algorithms: Vec<RawFlashAlgorithm> = Vec::new();
+        let mut algorithm_names: HashMap<String, String> = HashMap::new();
+        let family_name = self.name;
+
+        for device in self.devices {
+            let device_core = match device.core.as_deref().and_then(core_name) {
+                Some(device_core) => device_core,
+                None => {
+                    log::warn!(
+                        "Skipping device {}, the core {:?} is not supported",
+                        device.name,
+                        device.core
+                    );
+                    continue;
+                }
+            };
+
+            match core {
+                None => core = Some(device_core),
+                Some(core) if core != device_core => {
+                    log::warn!(
+                        "Skipping device {}, its core {} differs from the core {} of the family {}",
+                        device.name,
+                        device_core,
+                        core,
+                        family_name
+                    );
+                    continue;
+                }
+                Some(_) => (),
+            }
+
+            let mut chip_algorithms = Vec::new();
+
+            for algorithm in &device.algorithms {
+                let name = match algorithm_names.get(&algorithm.path) {
+                    Some(name) => name.clone(),
+                    None => {
+                        let name = algorithm_name(&algorithm.path);
+
+                        let mut raw = load_algorithm(&name, &algorithm.path)?;
+                        raw.default = algorithm.default;
+                        flash_algorithms.push(raw);
+
+                        algorithm_names.insert(algorithm.path.clone(), name.clone());
+                        name
+                    }
+                };
+
+                chip_algorithms.push(Cow::Owned(name));
+            }
+
+            let memory_map = device
+                .memories
+                .iter()
+                .map(|memory| {
+                    let end = memory.start.checked_add(memory.size).ok_or_else(|| {
+                        PackError::InvalidMemory {
+                            device: device.name.clone(),
+                            memory: memory.key.clone(),
+                        }
+                    })?;
+                    let range = memory.start..end;
+                    Ok(match memory.kind {
+                        MemoryKind::Flash => MemoryRegion::Flash(FlashRegion {
+                            range,
+                            is_boot_memory: memory.startup,
+                            algorithm: None,
+                        }),
+                        MemoryKind::Ram => MemoryRegion::Ram(RamRegion {
+                            range,
+                            is_boot_memory: memory.startup,
+                        }),
+                        MemoryKind::Generic => MemoryRegion::Generic(GenericRegion { range }),
+                    })
+                })
+                .collect::<Result<Vec<_>, PackError>>()?;
+
+            variants.push(Chip {
+                name: Cow::Owned(device.name),
+                part: None,
+                memory_map: Cow::Owned(memory_map),
+                flash_algorithms: Cow::Owned(chip_algorithms),
+                scan_chain: None,
+                swd_targetsel: None,
+            });
+        }
+
+        Ok(core.map(|core| ChipFamily {
+            name: Cow::Owned(family_name),
+            manufacturer: None,
+            variants: Cow::Owned(variants),
+            flash_algorithms: Cow::Owned(flash_algorithms),
+            core: Cow::Borrowed(core),
+            debug_sequence: None,
+        }))
+    }
+}
+
+/// Map the `Dcore` of a processor to the core names used in target descriptions.
+fn core_name(dcore: &str) -> Option<&'static str> {
+    match dcore {
+        "Cortex-M0" | "Cortex-M0+" => Some("M0"),
+        "Cortex-M3" => Some("M3"),
+        "Cortex-M4" => Some("M4"),
+        "Cortex-M7" => Some("M7"),
+        "Cortex-M33" => Some("M33"),
+        _ => None,
+    }
+}
+
+/// The name of an algorithm is the lowercase name of its file, without the extension.
+fn algorithm_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| path.to_lowercase())
+}
+
+fn attribute<'a>(element: Node<'a, '_>, name: &'static str) -> Result<&'a str, PackError> {
+    element
+        .attribute(name)
+        .ok_or_else(|| PackError::MissingAttribute {
+            element: element.tag_name().name().to_owned(),
+            attribute: name,
+        })
+}
+
+fn parse_bool(value: &str) -> bool {
+    value == "1" || value.eq_ignore_ascii_case("true")
+}
+
+fn parse_number(value: &str) -> Result<u32, PackError> {
+    let value = value.trim();
+
+    let result = if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        u32::from_str_radix(hex, 16)
+    } else {
+        value.parse()
+    };
+
+    result.map_err(|_| PackError::InvalidNumber(value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.4">
+  <devices>
+    <family Dfamily="Test Series" Dvendor="Test:0">
+      <processor Dcore="Cortex-M0+"/>
+      <algorithm name="Flash\TEST.FLM" start="0x0" size="0x8000" default="1"/>
+      <memory id="IRAM1" start="0x20000000" size="0x1000"/>
+      <device Dname="TEST1">
+        <memory id="IROM1" start="0x0" size="0x8000" startup="1"/>
+        <variant Dvariant="TEST1A"/>
+        <variant Dvariant="TEST1B">
+          <memory id="IRAM1" start="0x20000000" size="8192"/>
+        </variant>
+      </device>
+      <device Dname="TEST2">
+        <processor Dcore="Cortex-M23"/>
+      </device>
+    </family>
+  </devices>
+</package>"#;
+
+    #[test]
+    fn parse_inherited_elements() {
+        let families = parse_families(DESCRIPTION).unwrap();
+        assert_eq!(families.len(), 1);
+
+        let family = families
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_chip_family(|name, path| {
+                assert_eq!(path, "Flash/TEST.FLM");
+                Ok(RawFlashAlgorithm {
+                    name: Cow::Owned(name.to_owned()),
+                    ..Default::default()
+                })
+            })
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(family.name, "Test Series");
+        assert_eq!(family.core, "M0");
+
+        // TEST2 is skipped, because its core is not supported.
+        let names: Vec<_> = family.variants.iter().map(|v| v.name.as_ref()).collect();
+        assert_eq!(names, ["TEST1A", "TEST1B"]);
+
+        assert_eq!(family.flash_algorithms.len(), 1);
+        assert_eq!(family.flash_algorithms[0].name, "test");
+        assert!(family.flash_algorithms[0].default);
+
+        let variant = &family.variants[1];
+        assert_eq!(variant.flash_algorithms.as_ref(), ["test"]);
+        assert_eq!(
+            variant.memory_map.as_ref(),
+            [
+                MemoryRegion::Ram(RamRegion {
+                    range: 0x2000_0000..0x2000_2000,
+                    is_boot_memory: false,
+                }),
+                MemoryRegion::Flash(FlashRegion {
+                    range: 0x0..0x8000,
+                    is_boot_memory: true,
+                    algorithm: None,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_memory_beyond_address_space() {
+        let description = DESCRIPTION.replace(
+            r#"<memory id="IROM1" start="0x0" size="0x8000" startup="1"/>"#,
+            r#"<memory id="IROM1" start="0xFFFF0000" size="0x20000" startup="1"/>"#,
+        );
+        let families = parse_families(&description).unwrap();
+
+        let result = families
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_chip_family(|name, _| {
+                Ok(RawFlashAlgorithm {
+                    name: Cow::Owned(name.to_owned()),
+                    ..Default::default()
+                })
+            });
+
+        assert!(matches!(result, Err(PackError::InvalidMemory { .. })));
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(parse_number("0x08000000").unwrap(), 0x0800_0000);
+        assert_eq!(parse_number("0X10").unwrap(), 0x10);
+        assert_eq!(parse_number("4096").unwrap(), 4096);
+        assert!(parse_number("0xZZ").is_err());
+    }
+}
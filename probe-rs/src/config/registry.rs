@@ -1,7 +1,8 @@
 //! Internal target registry
 
+use super::pack::{self, PackError};
 use super::target::Target;
-use crate::config::{Chip, ChipFamily, ChipInfo};
+use crate::config::{Chip, ChipFamily, ChipInfo, FlashAlgorithmParseError, RawFlashAlgorithm};
 use crate::core::CoreType;
 use lazy_static::lazy_static;
 use std::fs::File;
@@ -38,6 +39,12 @@ pub enum RegistryError {
     /// An error occured while deserializing a YAML target description file.
     #[error("Deserializing the yaml encountered an error")]
     Yaml(#[from] serde_yaml::Error),
+    /// An error occured while reading a flash algorithm from an ELF file.
+    #[error("Reading the flash algorithm encountered an error")]
+    FlashAlgorithm(#[from] FlashAlgorithmParseError),
+    /// An error occured while reading a CMSIS-Pack.
+    #[error("Reading the CMSIS-Pack encountered an error")]
+    Pack(#[from] PackError),
     /// Unable to lock the registry.
     #[error("Unable to lock registry")]
     LockUnavailable,
@@ -58,9 +65,12 @@ const GENERIC_TARGETS: [ChipFamily; 6] = [
             part: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
+            scan_chain: None,
+            swd_targetsel: None,
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M0"),
+        debug_sequence: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M4"),
@@ -70,9 +80,12 @@ const GENERIC_TARGETS: [ChipFamily; 6] = [
             part: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
+            scan_chain: None,
+            swd_targetsel: None,
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M4"),
+        debug_sequence: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M3"),
@@ -82,9 +95,12 @@ const GENERIC_TARGETS: [ChipFamily; 6] = [
             part: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
+            scan_chain: None,
+            swd_targetsel: None,
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M3"),
+        debug_sequence: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M33"),
@@ -94,9 +110,12 @@ const GENERIC_TARGETS: [ChipFamily; 6] = [
             part: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
+            scan_chain: None,
+            swd_targetsel: None,
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M33"),
+        debug_sequence: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Cortex-M7"),
@@ -106,9 +125,12 @@ const GENERIC_TARGETS: [ChipFamily; 6] = [
             part: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
+            scan_chain: None,
+            swd_targetsel: None,
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("M7"),
+        debug_sequence: None,
     },
     ChipFamily {
         name: Cow::Borrowed("Generic Riscv"),
@@ -118,9 +140,12 @@ const GENERIC_TARGETS: [ChipFamily; 6] = [
             part: None,
             memory_map: Cow::Borrowed(&[]),
             flash_algorithms: Cow::Borrowed(&[]),
+            scan_chain: None,
+            swd_targetsel: None,
         }]),
         flash_algorithms: Cow::Borrowed(&[]),
         core: Cow::Borrowed("riscv"),
+        debug_sequence: None,
     },
 ];
 
@@ -251,24 +276,98 @@ impl Registry {
             .cloned()
             .collect();
 
-        Ok(Target::new(chip, chip_algorithms, core))
+        Ok(Target::new(family, chip, chip_algorithms, core))
     }
 
     fn add_target_from_yaml(&mut self, path_to_yaml: &Path) -> Result<(), RegistryError> {
-        let file = File::open(path_to_yaml)?;
-        let chip = ChipFamily::from_yaml_reader(file)?;
+        let chip: ChipFamily = serde_yaml::from_value(read_description(path_to_yaml)?)?;
 
+        self.add_family(chip);
+
+        Ok(())
+    }
+
+    fn add_target_from_pack(&mut self, path_to_pack: &Path) -> Result<(), RegistryError> {
+        let file = File::open(path_to_pack)?;
+        let families = pack::read_pack(file)?;
+
+        for family in families {
+            self.add_family(family);
+        }
+
+        Ok(())
+    }
+
+    /// Add a family to the registry, replacing a family with the same name.
+    fn add_family(&mut self, family: ChipFamily) {
         let index = self
             .families
             .iter()
-            .position(|old_chip| old_chip.name == chip.name);
+            .position(|old_family| old_family.name == family.name);
         if let Some(index) = index {
             self.families.remove(index);
         }
-        self.families.push(chip);
+        self.families.push(family);
+    }
+}
 
-        Ok(())
+/// Read a YAML description, e.g. a target or board description, and load
+/// the flash algorithms which are given as an ELF file.
+pub(super) fn read_description(path: &Path) -> Result<serde_yaml::Value, RegistryError> {
+    let file = File::open(path)?;
+    let mut description: serde_yaml::Value = serde_yaml::from_reader(file)?;
+
+    // Flash algorithms given as ELF files are relative to the description.
+    let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+    load_elf_algorithms(&mut description, base_path)?;
+
+    Ok(description)
+}
+
+/// Replace the flash algorithms of a target description, which are given as an
+/// ELF file using the `elf` key, with the algorithm read from the file.
+///
+/// The `name`, `description`, `default` and `flash_properties` of the entry
+/// override the values read from the file.
+fn load_elf_algorithms(
+    description: &mut serde_yaml::Value,
+    base_path: &Path,
+) -> Result<(), RegistryError> {
+    let algorithms = match description
+        .get_mut("flash_algorithms")
+        .and_then(|algorithms| algorithms.as_mapping_mut())
+    {
+        Some(algorithms) => algorithms,
+        None => return Ok(()),
+    };
+
+    for (key, entry) in algorithms.iter_mut() {
+        let path = match entry.get("elf").and_then(|elf| elf.as_str()) {
+            Some(path) => base_path.join(path),
+            None => continue,
+        };
+
+        let name = entry
+            .get("name")
+            .unwrap_or(key)
+            .as_str()
+            .unwrap_or_default();
+        let mut algorithm = RawFlashAlgorithm::from_elf(name, &std::fs::read(&path)?)?;
+
+        if let Some(description) = entry.get("description").and_then(|d| d.as_str()) {
+            algorithm.description = Cow::Owned(description.to_owned());
+        }
+        if let Some(default) = entry.get("default").and_then(|d| d.as_bool()) {
+            algorithm.default = default;
+        }
+        if let Some(flash_properties) = entry.get("flash_properties") {
+            algorithm.flash_properties = serde_yaml::from_value(flash_properties.clone())?;
+        }
+
+        *entry = serde_yaml::to_value(&algorithm)?;
     }
+
+    Ok(())
 }
 
 /// Get a target from the internal registry based on its name.
@@ -283,10 +382,30 @@ pub(crate) fn get_target_by_chip_info(chip_info: ChipInfo) -> Result<Target, Reg
 
 /// Parse a target description file and add the contained targets
 /// to the internal target registry.
+///
+/// Flash algorithms can also be given as an ELF file, e.g. a CMSIS flash
+/// algorithm, using the `elf` key with a path relative to the target
+/// description. They are read using [RawFlashAlgorithm::from_elf].
 pub fn add_target_from_yaml(path_to_yaml: &Path) -> Result<(), RegistryError> {
     REGISTRY.try_lock()?.add_target_from_yaml(path_to_yaml)
 }
 
+/// Read the devices described in a CMSIS-Pack and add them, together
+/// with their flash algorithms, to the internal target registry.
+///
+/// Each device family of the pack is added as a [ChipFamily], replacing
+/// families with the same name.
+pub fn add_target_from_pack(path_to_pack: &Path) -> Result<(), RegistryError> {
+    REGISTRY.try_lock()?.add_target_from_pack(path_to_pack)
+}
+
+/// Add a chip family to the internal target registry, replacing
+/// a family with the same name.
+pub fn add_target_family(family: ChipFamily) -> Result<(), RegistryError> {
+    REGISTRY.try_lock()?.add_family(family);
+    Ok(())
+}
+
 /// Get a list of all families which are contained in the internal
 /// registry.
 pub fn families() -> Result<Vec<ChipFamily>, RegistryError> {
@@ -0,0 +1,36 @@
+use std::borrow::Cow;
+
+/// The JTAG scan chain of a target.
+///
+/// This only has to be specified if the chain can't be detected automatically,
+/// e.g. if it contains multiple TAPs and the TAP used for debugging can't be
+/// determined from the IDCODEs, or if a TAP captures an unusual IR value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanChain {
+    /// The TAPs in the scan chain, starting with the TAP closest to TDO.
+    pub taps: Cow<'static, [ScanChainElement]>,
+    /// The index of the TAP used for debugging.
+    ///
+    /// If this is not set, the first TAP of a supported debug module is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_tap: Option<usize>,
+}
+
+/// A single TAP in a JTAG scan chain.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ScanChainElement {
+    /// The name of the TAP, only used for diagnostics.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<Cow<'static, str>>,
+    /// The length of the instruction register.
+    ///
+    /// If this is not set, it is determined from the value captured
+    /// in the instruction register when scanning the chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ir_len: Option<u8>,
+    /// The expected IDCODE of the TAP.
+    ///
+    /// If this is set, attaching fails if the TAP reports a different IDCODE.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idcode: Option<u32>,
+}
@@ -1,7 +1,13 @@
+use super::board::BoardDescription;
 use super::chip::Chip;
+use super::chip_family::ChipFamily;
 use super::flash_algorithm::RawFlashAlgorithm;
-use super::memory::MemoryRegion;
+use super::memory::{FlashRegion, MemoryRange, MemoryRegion};
+use super::scan_chain::ScanChain;
+use crate::architecture::arm::dp::DpAddress;
+use crate::architecture::arm::sequences::{create_debug_sequence, DebugSequence};
 use crate::core::{Architecture, CoreType};
+use std::{ops::Range, sync::Arc};
 
 /// This describes a complete target with a fixed chip model and variant.
 #[derive(Clone)]
@@ -14,6 +20,12 @@ pub struct Target {
     pub core_type: CoreType,
     /// The memory map of the target.
     pub memory_map: Vec<MemoryRegion>,
+    /// The JTAG scan chain of the target, if it can't be detected automatically.
+    pub scan_chain: Option<ScanChain>,
+    /// The debug ports of the target, one for each core.
+    pub debug_ports: Vec<DpAddress>,
+    /// The chip specific sequences, e.g. to unlock the chip.
+    pub debug_sequence: Arc<dyn DebugSequence>,
 }
 
 impl std::fmt::Debug for Target {
@@ -36,6 +48,7 @@ pub type TargetParseError = serde_yaml::Error;
 impl Target {
     /// Create a new target
     pub fn new(
+        family: &ChipFamily,
         chip: &Chip,
         flash_algorithms: Vec<RawFlashAlgorithm>,
         core_type: CoreType,
@@ -45,6 +58,59 @@ impl Target {
             flash_algorithms,
             core_type,
             memory_map: chip.memory_map.clone().into_owned(),
+            scan_chain: chip.scan_chain.clone(),
+            debug_ports: match &chip.swd_targetsel {
+                Some(targetsel) if !targetsel.is_empty() => targetsel
+                    .iter()
+                    .map(|&targetsel| DpAddress::Multidrop(targetsel))
+                    .collect(),
+                _ => vec![DpAddress::Default],
+            },
+            debug_sequence: create_debug_sequence(family.debug_sequence),
+        }
+    }
+
+    /// Add a flash algorithm to the target, e.g. one read using [RawFlashAlgorithm::from_elf].
+    ///
+    /// An algorithm with the same name is replaced. If the flash of the algorithm is not
+    /// part of the memory map yet, a flash region covering it is added, so that it can be
+    /// programmed, e.g. using [download_file](crate::flashing::download_file).
+    pub fn add_flash_algorithm(&mut self, algorithm: RawFlashAlgorithm) {
+        let range = algorithm.flash_properties.address_range.clone();
+
+        let is_mapped = self.memory_map.iter().any(|region| match region {
+            MemoryRegion::Flash(flash) => flash.range.intersects_range(&range),
+            _ => false,
+        });
+        if !is_mapped {
+            self.memory_map.push(MemoryRegion::Flash(FlashRegion {
+                range,
+                is_boot_memory: false,
+                algorithm: Some(algorithm.name.clone()),
+            }));
+        }
+
+        self.flash_algorithms
+            .retain(|old| old.name != algorithm.name);
+        self.flash_algorithms.push(algorithm);
+    }
+
+    /// Add the memory regions and flash algorithms of a board to the target,
+    /// e.g. external flash.
+    ///
+    /// Memory regions of the target which overlap with a region of the board
+    /// are replaced by the region of the board.
+    pub fn apply_board(&mut self, board: &BoardDescription) {
+        for region in board.memory_map.iter() {
+            let range = region_range(region);
+
+            self.memory_map
+                .retain(|old| !region_range(old).intersects_range(range));
+            self.memory_map.push(region.clone());
+        }
+
+        for algorithm in board.flash_algorithms.iter() {
+            self.add_flash_algorithm(algorithm.clone());
         }
     }
 
@@ -61,6 +127,14 @@ impl Target {
     }
 }
 
+fn region_range(region: &MemoryRegion) -> &Range<u32> {
+    match region {
+        MemoryRegion::Ram(r) => &r.range,
+        MemoryRegion::Flash(r) => &r.range,
+        MemoryRegion::Generic(r) => &r.range,
+    }
+}
+
 /// Selector for the debug target.
 #[derive(Debug, Clone)]
 pub enum TargetSelector {
@@ -592,7 +592,7 @@ impl CoreStatus {
 pub enum HaltReason {
     /// Core halted due to a breakpoint, either
     /// a *soft* or a *hard* breakpoint.
-    Breakpoint,
+    Breakpoint(BreakpointCause),
     /// Core halted due to an exception, e.g. an
     /// an interrupt.
     Exception,
@@ -608,3 +608,17 @@ pub enum HaltReason {
     /// example when the core is already halted when we connect.
     Unknown,
 }
+
+/// The kind of breakpoint which caused a core to halt.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BreakpointCause {
+    /// A breakpoint set in one of the hardware breakpoint units.
+    Hardware,
+    /// A breakpoint instruction placed in the program, e.g. `BKPT` or `ebreak`.
+    Software,
+    /// A breakpoint instruction used to request a semihosting operation
+    /// from the debugger, see [crate::semihosting].
+    Semihosting,
+    /// The exact kind of the breakpoint could not be determined.
+    Unknown,
+}
@@ -1,5 +1,6 @@
+use crate::architecture::arm::{ap::AccessPortError, sequences::DebugSequenceError};
+use crate::config::RegistryError;
 use crate::DebugProbeError;
-use crate::{architecture::arm::ap::AccessPortError, config::RegistryError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -31,3 +32,9 @@ impl From<AccessPortError> for Error {
         Error::architecture_specific(err)
     }
 }
+
+impl From<DebugSequenceError> for Error {
+    fn from(err: DebugSequenceError) -> Self {
+        Error::architecture_specific(err)
+    }
+}
@@ -146,6 +146,38 @@ impl FlashLayout {
         &self.data_blocks
     }
 
+    /// Remove the given sectors from the layout, together with the pages and fills in them.
+    pub(super) fn skip_sectors(&mut self, skipped: &[FlashSector]) {
+        let is_skipped = |address: u32| {
+            skipped
+                .iter()
+                .any(|sector| (sector.address..sector.address + sector.size).contains(&address))
+        };
+
+        self.sectors.retain(|sector| !skipped.contains(sector));
+
+        // The new index of every page, to update the page index of the fills.
+        let mut page_indices = Vec::with_capacity(self.pages.len());
+        let mut pages = Vec::with_capacity(self.pages.len());
+        for page in self.pages.drain(..) {
+            if is_skipped(page.address) {
+                page_indices.push(None);
+            } else {
+                page_indices.push(Some(pages.len()));
+                pages.push(page);
+            }
+        }
+        self.pages = pages;
+
+        self.fills = self
+            .fills
+            .drain(..)
+            .filter_map(|fill| {
+                page_indices[fill.page_index].map(|page_index| FlashFill { page_index, ..fill })
+            })
+            .collect();
+    }
+
     pub fn visualize(&self) -> FlashVisualizer {
         FlashVisualizer::new(&self)
     }
@@ -1262,4 +1294,47 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn skip_sector_with_pages_and_fills() {
+        let flash_algorithm = assemble_demo_flash1();
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0x0000, &[42]).unwrap();
+        flash_builder.add_data(0x1000, &[43]).unwrap();
+        let mut flash_layout = flash_builder
+            .build_sectors_and_pages(&flash_algorithm, false)
+            .unwrap();
+
+        let first_sector = flash_layout.sectors()[0].clone();
+        flash_layout.skip_sectors(&[first_sector]);
+
+        let erased_byte_value = flash_algorithm.flash_properties.erased_byte_value;
+
+        assert_eq!(
+            flash_layout.sectors(),
+            [FlashSector {
+                address: 0x1000,
+                size: 0x1000,
+            }]
+        );
+        assert_eq!(
+            flash_layout.pages(),
+            [FlashPage {
+                address: 0x1000,
+                data: {
+                    let mut data = vec![erased_byte_value; 1024];
+                    data[0] = 43;
+                    data
+                },
+            }]
+        );
+        assert_eq!(
+            flash_layout.fills(),
+            [FlashFill {
+                address: 0x1001,
+                size: 0x03FF,
+                page_index: 0,
+            }]
+        );
+    }
 }
@@ -0,0 +1,77 @@
+//! CRC32 checksums of the flash contents, which are used to skip unchanged sectors.
+//!
+//! The checksum is the common CRC-32 (IEEE 802.3), which is calculated on the
+//! target using [CRC32_ROUTINE], or on the host using [crc32].
+
+/// A Thumb routine calculating the CRC32 of a memory range, which runs on all Cortex-M cores.
+///
+/// The routine is called with the start address in `r0` and the length in bytes
+/// in `r1`, and returns the checksum in `r0`. It uses `r0` to `r5`, but no stack.
+///
+/// ```text
+///     movs r2, #0
+///     mvns r2, r2
+///     ldr  r3, poly
+/// loop_byte:
+///     cmp  r1, #0
+///     beq  done
+///     ldrb r4, [r0]
+///     eors r2, r4
+///     movs r5, #8
+/// loop_bit:
+///     lsrs r2, r2, #1
+///     bcc  no_xor
+///     eors r2, r3
+/// no_xor:
+///     subs r5, #1
+///     bne  loop_bit
+///     adds r0, #1
+///     subs r1, #1
+///     b    loop_byte
+/// done:
+///     mvns r0, r2
+///     bx   lr
+/// poly:
+///     .word 0xEDB88320
+/// ```
+pub(crate) const CRC32_ROUTINE: [u32; 10] = [
+    0x43D2_2200,
+    0x2900_4B07,
+    0x7804_D00A,
+    0x2508_4062,
+    0xD300_0852,
+    0x3D01_405A,
+    0x3001_D1FA,
+    0xE7F2_3901,
+    0x4770_43D0,
+    0xEDB8_8320,
+];
+
+/// Calculate the CRC32 of `data`, like [CRC32_ROUTINE] does on the target.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc32;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+}
@@ -75,6 +75,12 @@ pub struct DownloadOptions<'progress> {
     /// instead of the full sector, the excessively erased bytes wont match the contents before the erase which might not be intuitive
     /// to the user or even worse, result in unexpected behavior if those contents contain important data.
     pub keep_unwritten_bytes: bool,
+    /// If `incremental` is `true`, only the sectors whose contents differ from the file are
+    /// erased and programmed. The contents are compared using a CRC32 checksum, which is
+    /// calculated on the target if possible.
+    ///
+    /// Skipped sectors are reported as [ProgressEvent::SectorSkipped].
+    pub incremental: bool,
 }
 
 /// Downloads a file of given `format` at `path` to the flash of the target given in `session`.
@@ -123,6 +129,7 @@ pub fn download_file_with_options(
             session,
             options.progress.unwrap_or(&FlashProgress::new(|_| {})),
             false,
+            options.incremental,
         )
         .map_err(FileDownloadError::Flash)
 }
@@ -0,0 +1,358 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::Path,
+    time::Instant,
+};
+
+use super::FlashProgress;
+use crate::{config::MemoryRegion, core::Architecture, error, session::Session, MemoryInterface};
+
+use thiserror::Error;
+
+/// The size of the blocks in which memory is read, and progress is reported.
+const READ_BLOCK_SIZE: u32 = 0x4000;
+
+/// The maximum number of bytes in a single data record of an Intel HEX file.
+const IHEX_RECORD_SIZE: usize = 16;
+
+/// A finite list of the file formats memory can be dumped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// The raw contents of the memory.
+    Bin,
+    /// An [Intel HEX](https://en.wikipedia.org/wiki/Intel_HEX) file.
+    Hex,
+    /// An [ELF](https://en.wikipedia.org/wiki/Executable_and_Linkable_Format) file,
+    /// with a single `PT_LOAD` segment containing the memory.
+    Elf,
+}
+
+/// A finite list of all the errors that can occur when dumping memory to a file.
+#[derive(Debug, Error)]
+pub enum DumpError {
+    /// Reading the memory of the target has failed.
+    #[error("Error while reading the memory")]
+    Memory(#[source] error::Error),
+    /// An IO error has occured while writing the file.
+    #[error("I/O error")]
+    IO(#[from] std::io::Error),
+    /// Writing the Intel HEX records has failed.
+    #[error("Could not write ihex format")]
+    IhexWrite(#[from] ihex::WriterError),
+    /// The memory map of the target does not contain a region with the given name.
+    #[error("The memory map does not contain the region '{0}'")]
+    RegionNotFound(String),
+    /// The range to read is empty, or does not fit into the address space.
+    #[error("Invalid memory range {start:#010x}..{end:#010x}")]
+    InvalidRange {
+        /// The start address of the range.
+        start: u32,
+        /// The end address of the range.
+        end: u32,
+    },
+}
+
+/// Options for dumping memory of a target.
+#[derive(Default)]
+pub struct DumpOptions<'progress> {
+    /// An optional progress reporter which is used if this argument is set to `Some(...)`.
+    pub progress: Option<&'progress FlashProgress>,
+}
+
+/// Find the address range of a memory region by its name.
+///
+/// Regions are named by their kind, i.e. `flash`, `ram` or `generic`,
+/// followed by their index among the regions of that kind in the memory map.
+/// The index can be left out for the first region, so `flash` and `flash0`
+/// both name the first flash region.
+pub fn memory_region_range(
+    memory_map: &[MemoryRegion],
+    name: &str,
+) -> Result<Range<u32>, DumpError> {
+    let not_found = || DumpError::RegionNotFound(name.to_owned());
+
+    let lowercase = name.to_ascii_lowercase();
+    let kind = lowercase.trim_end_matches(|c: char| c.is_ascii_digit());
+    let index = match &lowercase[kind.len()..] {
+        "" => 0,
+        index => index.parse::<usize>().map_err(|_| not_found())?,
+    };
+
+    memory_map
+        .iter()
+        .filter_map(|region| match (kind, region) {
+            ("flash", MemoryRegion::Flash(region)) => Some(region.range.clone()),
+            ("ram", MemoryRegion::Ram(region)) => Some(region.range.clone()),
+            ("generic", MemoryRegion::Generic(region)) => Some(region.range.clone()),
+            _ => None,
+        })
+        .nth(index)
+        .ok_or_else(not_found)
+}
+
+/// Read the memory in `range` from the target given in `session`.
+///
+/// The memory is read in large blocks, and the progress is reported
+/// as a [ProgressEvent::BlockRead](super::ProgressEvent::BlockRead) for every block.
+pub fn read_memory_range(
+    session: &mut Session,
+    range: Range<u32>,
+    options: &DumpOptions<'_>,
+) -> Result<Vec<u8>, DumpError> {
+    if range.start >= range.end {
+        return Err(DumpError::InvalidRange {
+            start: range.start,
+            end: range.end,
+        });
+    }
+
+    let no_progress = FlashProgress::new(|_| {});
+    let progress = options.progress.unwrap_or(&no_progress);
+
+    progress.started_reading(range.end - range.start);
+
+    let result = read_blocks(session, range, progress);
+
+    match result {
+        Ok(_) => progress.finished_reading(),
+        Err(_) => progress.failed_reading(),
+    }
+
+    result
+}
+
+fn read_blocks(
+    session: &mut Session,
+    range: Range<u32>,
+    progress: &FlashProgress,
+) -> Result<Vec<u8>, DumpError> {
+    let mut core = session.core(0).map_err(DumpError::Memory)?;
+    let mut data = vec![0; (range.end - range.start) as usize];
+
+    for (i, block) in data.chunks_mut(READ_BLOCK_SIZE as usize).enumerate() {
+        let t = Instant::now();
+
+        core.read_8(range.start + i as u32 * READ_BLOCK_SIZE, block)
+            .map_err(DumpError::Memory)?;
+
+        progress.block_read(block.len() as u32, t.elapsed());
+    }
+
+    Ok(data)
+}
+
+/// Read the memory in `range` from the target given in `session`, and write it
+/// to the file at `path` in the given `format`.
+pub fn dump_memory_range(
+    session: &mut Session,
+    range: Range<u32>,
+    path: &Path,
+    format: DumpFormat,
+    options: DumpOptions<'_>,
+) -> Result<(), DumpError> {
+    let data = read_memory_range(session, range.clone(), &options)?;
+
+    let mut file = BufWriter::new(File::create(path)?);
+
+    match format {
+        DumpFormat::Bin => file.write_all(&data)?,
+        DumpFormat::Hex => write_hex(&mut file, range.start, &data)?,
+        DumpFormat::Elf => write_elf(&mut file, session.architecture(), range.start, &data)?,
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Write `data`, located at `address`, as Intel HEX records.
+fn write_hex(writer: &mut impl Write, address: u32, data: &[u8]) -> Result<(), DumpError> {
+    use ihex::Record;
+
+    let mut records = Vec::new();
+    let mut upper_address = None;
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let current = address + offset as u32;
+
+        // A record must not cross a 64 KiB boundary.
+        let segment_end = ((current as usize) | 0xFFFF) + 1;
+        let size = IHEX_RECORD_SIZE
+            .min(data.len() - offset)
+            .min(segment_end - current as usize);
+
+        if upper_address != Some(current >> 16) {
+            upper_address = Some(current >> 16);
+            records.push(Record::ExtendedLinearAddress((current >> 16) as u16));
+        }
+
+        records.push(Record::Data {
+            offset: current as u16,
+            value: data[offset..offset + size].to_vec(),
+        });
+
+        offset += size;
+    }
+
+    records.push(Record::EndOfFile);
+
+    let hex = ihex::create_object_file_representation(&records)?;
+    writer.write_all(hex.as_bytes())?;
+
+    Ok(())
+}
+
+/// Write `data`, located at `address`, as a 32 bit ELF file with a single `PT_LOAD` segment.
+fn write_elf(
+    writer: &mut impl Write,
+    architecture: Architecture,
+    address: u32,
+    data: &[u8],
+) -> Result<(), DumpError> {
+    const ELF_HEADER_SIZE: u16 = 52;
+    const PROGRAM_HEADER_SIZE: u16 = 32;
+
+    const ET_EXEC: u16 = 2;
+    const EM_ARM: u16 = 40;
+    const EM_RISCV: u16 = 243;
+    const PT_LOAD: u32 = 1;
+    const PF_R: u32 = 4;
+
+    let machine = match architecture {
+        Architecture::Arm => EM_ARM,
+        Architecture::Riscv => EM_RISCV,
+    };
+
+    let data_offset = u32::from(ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE);
+
+    let mut header = Vec::with_capacity(data_offset as usize);
+
+    // Identification: 32 bit, little endian, version 1.
+    header.extend_from_slice(&[0x7F, b'E', b'L', b'F', 1, 1, 1]);
+    header.resize(16, 0);
+
+    header.extend_from_slice(&ET_EXEC.to_le_bytes());
+    header.extend_from_slice(&machine.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    header.extend_from_slice(&0u32.to_le_bytes()); // e_entry
+    header.extend_from_slice(&u32::from(ELF_HEADER_SIZE).to_le_bytes()); // e_phoff
+    header.extend_from_slice(&0u32.to_le_bytes()); // e_shoff
+    header.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    header.extend_from_slice(&ELF_HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&PROGRAM_HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+    header.extend_from_slice(&0u16.to_le_bytes()); // e_shentsize
+    header.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+    header.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+
+    // The program header of the segment.
+    header.extend_from_slice(&PT_LOAD.to_le_bytes());
+    header.extend_from_slice(&data_offset.to_le_bytes()); // p_offset
+    header.extend_from_slice(&address.to_le_bytes()); // p_vaddr
+    header.extend_from_slice(&address.to_le_bytes()); // p_paddr
+    header.extend_from_slice(&(data.len() as u32).to_le_bytes()); // p_filesz
+    header.extend_from_slice(&(data.len() as u32).to_le_bytes()); // p_memsz
+    header.extend_from_slice(&PF_R.to_le_bytes()); // p_flags
+    header.extend_from_slice(&1u32.to_le_bytes()); // p_align
+
+    writer.write_all(&header)?;
+    writer.write_all(data)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FlashRegion, RamRegion};
+
+    #[test]
+    fn find_region_by_name() {
+        let memory_map = [
+            MemoryRegion::Ram(RamRegion {
+                range: 0x2000_0000..0x2000_4000,
+                is_boot_memory: false,
+            }),
+            MemoryRegion::Flash(FlashRegion {
+                range: 0x0..0x4_0000,
+                is_boot_memory: true,
+                algorithm: None,
+            }),
+            MemoryRegion::Flash(FlashRegion {
+                range: 0x9000_0000..0x9080_0000,
+                is_boot_memory: false,
+                algorithm: None,
+            }),
+        ];
+
+        assert_eq!(
+            memory_region_range(&memory_map, "flash").unwrap(),
+            0x0..0x4_0000
+        );
+        assert_eq!(
+            memory_region_range(&memory_map, "FLASH1").unwrap(),
+            0x9000_0000..0x9080_0000
+        );
+        assert_eq!(
+            memory_region_range(&memory_map, "ram0").unwrap(),
+            0x2000_0000..0x2000_4000
+        );
+        assert!(memory_region_range(&memory_map, "flash2").is_err());
+        assert!(memory_region_range(&memory_map, "generic").is_err());
+    }
+
+    #[test]
+    fn hex_records_split_at_64k_boundary() {
+        let data: Vec<u8> = (0..0x20u8).collect();
+
+        let mut hex = Vec::new();
+        write_hex(&mut hex, 0x0800_FFF8, &data).unwrap();
+        let hex = String::from_utf8(hex).unwrap();
+
+        let records = ihex::Reader::new(&hex)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            records,
+            [
+                ihex::Record::ExtendedLinearAddress(0x0800),
+                ihex::Record::Data {
+                    offset: 0xFFF8,
+                    value: data[..0x8].to_vec(),
+                },
+                ihex::Record::ExtendedLinearAddress(0x0801),
+                ihex::Record::Data {
+                    offset: 0x0000,
+                    value: data[0x8..0x18].to_vec(),
+                },
+                ihex::Record::Data {
+                    offset: 0x0010,
+                    value: data[0x18..].to_vec(),
+                },
+                ihex::Record::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn elf_with_load_segment() {
+        let data: Vec<u8> = (0..0x100u32).map(|i| i as u8).collect();
+
+        let mut elf = Vec::new();
+        write_elf(&mut elf, Architecture::Arm, 0x0800_0000, &data).unwrap();
+
+        let binary = goblin::elf::Elf::parse(&elf).unwrap();
+        assert_eq!(binary.header.e_machine, goblin::elf::header::EM_ARM);
+        assert_eq!(binary.program_headers.len(), 1);
+
+        let segment = &binary.program_headers[0];
+        assert_eq!(segment.p_type, goblin::elf::program_header::PT_LOAD);
+        assert_eq!(segment.p_paddr, 0x0800_0000);
+        assert_eq!(segment.p_filesz, data.len() as u64);
+        assert_eq!(&elf[segment.file_range()], data.as_slice());
+    }
+}
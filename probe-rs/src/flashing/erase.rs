@@ -0,0 +1,278 @@
+use super::{flash_algorithm_for_region, memory_region_range, FlashError, FlashProgress, Flasher};
+use crate::config::{FlashAlgorithm, FlashRegion, MemoryRange, MemoryRegion};
+use crate::session::Session;
+use std::{ops::Range, time::Instant};
+
+/// Options for erasing the flash of a target.
+#[derive(Default)]
+pub struct EraseOptions<'progress> {
+    /// An optional progress reporter which is used if this argument is set to `Some(...)`.
+    pub progress: Option<&'progress FlashProgress>,
+    /// If this flag is set to true, regions the chip boots from are erased as well.
+    /// Otherwise, erasing them fails with [FlashError::BootMemoryNotConfirmed].
+    pub erase_boot_memory: bool,
+}
+
+/// An area of a flash region which is erased.
+///
+/// The area always consists of whole sectors, so it can be larger than the
+/// range which was requested to be erased.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EraseArea {
+    /// The address range which is erased.
+    pub range: Range<u32>,
+    /// The number of sectors in the area.
+    pub sectors: usize,
+    /// True if the area covers the whole flash of the flash algorithm,
+    /// and is erased using its chip erase routine.
+    pub chip_erase: bool,
+    /// True if the chip boots from the flash region of the area.
+    pub is_boot_memory: bool,
+    region: FlashRegion,
+}
+
+/// Erase the complete flash of the target, including the memory the chip boots from.
+///
+/// If the flash algorithm of a region supports erasing the whole chip, this is used,
+/// otherwise all sectors of the region are erased one by one.
+pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
+    erase_all_with_options(
+        session,
+        EraseOptions {
+            erase_boot_memory: true,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Erase the complete flash of the target with the given options.
+///
+/// The erased areas are returned.
+pub fn erase_all_with_options(
+    session: &mut Session,
+    options: EraseOptions<'_>,
+) -> Result<Vec<EraseArea>, FlashError> {
+    let areas = plan_erase_all(session)?;
+
+    erase_areas(session, areas, options)
+}
+
+/// Erase the flash region with the given name, e.g. `flash` or `flash1`.
+///
+/// See [memory_region_range] for how regions are named. The erased areas are returned.
+pub fn erase_region(
+    session: &mut Session,
+    name: &str,
+    options: EraseOptions<'_>,
+) -> Result<Vec<EraseArea>, FlashError> {
+    let range = memory_region_range(session.memory_map(), name)
+        .ok()
+        .filter(|range| {
+            flash_regions(session)
+                .iter()
+                .any(|region| region.range == *range)
+        })
+        .ok_or_else(|| FlashError::RegionNotFound(name.to_owned()))?;
+
+    erase_range_with_options(session, range, options)
+}
+
+/// Erase all flash sectors which overlap with `range`, including the memory the chip boots from.
+///
+/// Because flash can only be erased in whole sectors, the erased range can be larger than `range`.
+/// The range which was actually erased is returned.
+pub fn erase_range(session: &mut Session, range: Range<u32>) -> Result<Range<u32>, FlashError> {
+    let areas = erase_range_with_options(
+        session,
+        range,
+        EraseOptions {
+            erase_boot_memory: true,
+            ..Default::default()
+        },
+    )?;
+
+    // `plan_erase_range` fails if no area is erased.
+    let start = areas.iter().map(|area| area.range.start).min().unwrap();
+    let end = areas.iter().map(|area| area.range.end).max().unwrap();
+
+    Ok(start..end)
+}
+
+/// Erase all flash sectors which overlap with `range` with the given options.
+///
+/// The erased areas, as reported by [plan_erase_range], are returned.
+pub fn erase_range_with_options(
+    session: &mut Session,
+    range: Range<u32>,
+    options: EraseOptions<'_>,
+) -> Result<Vec<EraseArea>, FlashError> {
+    let areas = plan_erase_range(session, range)?;
+
+    erase_areas(session, areas, options)
+}
+
+/// Determine which areas of the flash are erased when erasing the complete flash,
+/// without erasing anything.
+pub fn plan_erase_all(session: &Session) -> Result<Vec<EraseArea>, FlashError> {
+    flash_regions(session)
+        .iter()
+        .map(|region| plan_region(session, region, region.range.clone()))
+        .collect()
+}
+
+/// Determine which areas of the flash are erased when erasing `range`, without erasing anything.
+///
+/// The range is extended to whole sectors, and split at the boundaries of the flash regions.
+pub fn plan_erase_range(
+    session: &Session,
+    range: Range<u32>,
+) -> Result<Vec<EraseArea>, FlashError> {
+    let areas = flash_regions(session)
+        .iter()
+        .filter(|region| region.range.intersects_range(&range))
+        .map(|region| {
+            let start = range.start.max(region.range.start);
+            let end = range.end.min(region.range.end);
+
+            plan_region(session, region, start..end)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if areas.is_empty() {
+        return Err(FlashError::NoSuitableFlash {
+            start: range.start,
+            end: range.end,
+        });
+    }
+
+    Ok(areas)
+}
+
+fn flash_regions(session: &Session) -> Vec<FlashRegion> {
+    session
+        .memory_map()
+        .iter()
+        .filter_map(|region| match region {
+            MemoryRegion::Flash(region) => Some(region.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Determine the sectors of `region` which overlap with `range`.
+fn plan_region(
+    session: &Session,
+    region: &FlashRegion,
+    range: Range<u32>,
+) -> Result<EraseArea, FlashError> {
+    let flash_algorithm = flash_algorithm_for_region(session, region)?;
+
+    let sectors = sector_addresses(&flash_algorithm, range)?;
+
+    let erased = match (sectors.first(), sectors.last()) {
+        (Some(first), Some(last)) => first.0..last.0 + last.1,
+        _ => {
+            return Err(FlashError::NoSuitableFlash {
+                start: region.range.start,
+                end: region.range.end,
+            })
+        }
+    };
+
+    let chip_erase = flash_algorithm.pc_erase_all.is_some()
+        && erased.contains_range(&flash_algorithm.flash_properties.address_range);
+
+    Ok(EraseArea {
+        range: erased,
+        sectors: sectors.len(),
+        chip_erase,
+        is_boot_memory: region.is_boot_memory,
+        region: region.clone(),
+    })
+}
+
+/// Return the address and size of all sectors which overlap with `range`.
+fn sector_addresses(
+    flash_algorithm: &FlashAlgorithm,
+    range: Range<u32>,
+) -> Result<Vec<(u32, u32)>, FlashError> {
+    let mut sectors = Vec::new();
+    let mut address = range.start;
+
+    while address < range.end {
+        let sector = flash_algorithm
+            .sector_info(address)
+            .ok_or(FlashError::InvalidFlashAddress(address))?;
+
+        sectors.push((sector.base_address, sector.size));
+        address = sector.base_address + sector.size;
+    }
+
+    Ok(sectors)
+}
+
+/// Erase the given areas, after checking that boot memory may be erased.
+fn erase_areas(
+    session: &mut Session,
+    areas: Vec<EraseArea>,
+    options: EraseOptions<'_>,
+) -> Result<Vec<EraseArea>, FlashError> {
+    if !options.erase_boot_memory {
+        if let Some(area) = areas.iter().find(|area| area.is_boot_memory) {
+            return Err(FlashError::BootMemoryNotConfirmed {
+                start: area.range.start,
+                end: area.range.end,
+            });
+        }
+    }
+
+    let no_progress = FlashProgress::new(|_| {});
+    let progress = options.progress.unwrap_or(&no_progress);
+
+    progress.started_erasing();
+
+    let result = areas
+        .iter()
+        .try_for_each(|area| erase_area(session, area, progress));
+
+    match result {
+        Ok(()) => progress.finished_erasing(),
+        Err(_) => progress.failed_erasing(),
+    }
+
+    result.map(|_| areas)
+}
+
+fn erase_area(
+    session: &mut Session,
+    area: &EraseArea,
+    progress: &FlashProgress,
+) -> Result<(), FlashError> {
+    let flash_algorithm = flash_algorithm_for_region(session, &area.region)?;
+    let sectors = sector_addresses(&flash_algorithm, area.range.clone())?;
+
+    let mut flasher = Flasher::new(session, flash_algorithm, area.region.clone());
+
+    if area.chip_erase {
+        let mut t = Instant::now();
+        flasher.run_erase(|active| active.erase_all().map_err(FlashError::from))?;
+
+        for (_, size) in &sectors {
+            progress.sector_erased(*size, t.elapsed());
+            t = Instant::now();
+        }
+    } else {
+        flasher.run_erase(|active| {
+            for (address, size) in &sectors {
+                let t = Instant::now();
+                active.erase_sector(*address).map_err(FlashError::from)?;
+                progress.sector_erased(*size, t.elapsed());
+            }
+            Ok::<(), FlashError>(())
+        })?;
+    }
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
 #![allow(missing_docs)]
 
+use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
 
 use crate::config::FlashRegion;
@@ -8,10 +10,29 @@ use crate::error;
 /// Describes any error that happened during the or in preparation for the flashing procedure.
 #[derive(Error, Debug)]
 pub enum FlashError {
-    #[error("The execution of '{name}' failed with code {errorcode}. Perhaps your chip has write protected sectors that need to be cleared? Perhaps you need the --nmagic linker arg https://github.com/rust-embedded/cortex-m-quickstart/pull/95")]
-    EraseFailed { name: &'static str, errorcode: u32 },
-    #[error("The execution of '{name}' failed with code {errorcode}")]
-    RoutineCallFailed { name: &'static str, errorcode: u32 },
+    #[error("The execution of '{name}' failed with code {errorcode}{}. Perhaps your chip has write protected sectors that need to be cleared? Perhaps you need the --nmagic linker arg https://github.com/rust-embedded/cortex-m-quickstart/pull/95", state_suffix(.state))]
+    EraseFailed {
+        name: &'static str,
+        errorcode: u32,
+        state: Option<CoreSnapshot>,
+    },
+    #[error("The execution of '{name}' failed with code {errorcode}{}", state_suffix(.state))]
+    RoutineCallFailed {
+        name: &'static str,
+        errorcode: u32,
+        state: Option<CoreSnapshot>,
+    },
+    #[error("The execution of '{name}' did not finish within {timeout:?}, the flash algorithm seems to hang{}", state_suffix(.state))]
+    RoutineTimeout {
+        name: &'static str,
+        timeout: Duration,
+        state: Option<CoreSnapshot>,
+    },
+    #[error("The execution of '{name}' caused a fault in the flash algorithm ({state})")]
+    RoutineFault {
+        name: &'static str,
+        state: CoreSnapshot,
+    },
     #[error("The '{0}' routine is not supported with the given flash algorithm.")]
     RoutineNotSupported(&'static str),
     #[error("Buffer {n}/{max} does not exist")]
@@ -24,14 +45,25 @@ pub enum FlashError {
     AddressNotInRegion { address: u32, region: FlashRegion },
     #[error("Flash algorithm length is not 32 bit aligned.")]
     InvalidFlashAlgorithmLength,
+    #[error("The flash algorithm does not fit into RAM at {start:#010x}..{end:#010x}.")]
+    FlashAlgorithmDoesNotFit { start: u32, end: u32 },
+    #[error("The flash algorithm overflowed its stack of {size} bytes at {bottom:#010x}. Try to increase the stack size of the algorithm.")]
+    StackOverflow { bottom: u32, size: u32 },
+    #[error("The stack of the flash algorithm has {size} bytes, but at least {minimum} bytes are required.")]
+    StackTooSmall { size: u32, minimum: u32 },
     #[error(
         "The RAM contents did not match the expected contents after loading the flash algorithm."
     )]
     FlashAlgorithmNotLoaded,
     #[error(
-        "The page write of the page at address {page_address:#08X} failed with error code {error_code}."
+        "The page write of the page at address {page_address:#08X} failed with error code {error_code}{}.",
+        state_suffix(.state)
     )]
-    PageWrite { page_address: u32, error_code: u32 },
+    PageWrite {
+        page_address: u32,
+        error_code: u32,
+        state: Option<CoreSnapshot>,
+    },
     #[error("Overlap in data, address {0:#010x} was already written earlier.")]
     DataOverlap(u32),
     #[error("Address {0:#010x} is not a valid address in the flash area.")]
@@ -40,8 +72,84 @@ pub enum FlashError {
         "No flash memory contains the entire requested memory range {start:#08X}..{end:#08X}."
     )]
     NoSuitableFlash { start: u32, end: u32 },
+    #[error("The memory map does not contain a flash region named '{0}'.")]
+    RegionNotFound(String),
+    #[error("The flash at {start:#010x}..{end:#010x} contains the memory the chip boots from, and erasing it has to be confirmed explicitly.")]
+    BootMemoryNotConfirmed { start: u32, end: u32 },
     #[error("Trying to write flash, but no suitable flash loader algorithm is linked to the given target information.")]
     NoFlashLoaderAlgorithmAttached,
+    #[error("The flash algorithm '{name}' of the flash region {start:#08X}..{end:#08X} does not exist, or does not cover the region.")]
+    FlashAlgorithmNotFound { name: String, start: u32, end: u32 },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+/// The state of the core, captured when a routine of the flash algorithm failed.
+#[derive(Debug, Clone)]
+pub struct CoreSnapshot {
+    /// The program counter.
+    pub pc: u32,
+    /// The return address, i.e. the link register on ARM cores.
+    pub lr: u32,
+    /// The stack pointer.
+    pub sp: u32,
+    /// The Configurable Fault Status Register, on Cortex-M cores with one.
+    pub cfsr: Option<u32>,
+    /// The HardFault Status Register, on Cortex-M cores with one.
+    pub hfsr: Option<u32>,
+    /// The number of the active exception, on ARM cores.
+    pub exception: Option<u32>,
+    /// Whether the core is in lockup, on ARM cores.
+    pub lockup: bool,
+    /// The top of the stack of the algorithm, starting at the stack pointer.
+    pub stack: Vec<u32>,
+}
+
+/// Format the state of the core for an error message, if it could be captured.
+fn state_suffix(state: &Option<CoreSnapshot>) -> String {
+    match state {
+        Some(state) => format!(" ({})", state),
+        None => String::new(),
+    }
+}
+
+impl CoreSnapshot {
+    /// Returns `true` if the core was handling a fault exception, or is in lockup
+    /// after a fault it couldn't handle.
+    pub fn is_fault(&self) -> bool {
+        // HardFault, MemManage, BusFault, UsageFault and SecureFault.
+        self.lockup || matches!(self.exception, Some(3..=7))
+    }
+}
+
+impl fmt::Display for CoreSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PC: {:#010x}, LR: {:#010x}, SP: {:#010x}",
+            self.pc, self.lr, self.sp
+        )?;
+
+        if let Some(exception) = self.exception {
+            write!(f, ", exception: {}", exception)?;
+        }
+        if let Some(cfsr) = self.cfsr {
+            write!(f, ", CFSR: {:#010x}", cfsr)?;
+        }
+        if let Some(hfsr) = self.hfsr {
+            write!(f, ", HFSR: {:#010x}", hfsr)?;
+        }
+        if self.lockup {
+            write!(f, ", in lockup")?;
+        }
+
+        write!(f, ", stack: [")?;
+        for (i, word) in self.stack.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:#010x}", word)?;
+        }
+        write!(f, "]")
+    }
+}
@@ -1,15 +1,47 @@
 use super::FlashProgress;
-use super::{FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage};
-use crate::config::{FlashAlgorithm, FlashRegion, MemoryRange};
+use super::{crc32, CRC32_ROUTINE};
+use super::{
+    CoreSnapshot, FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage, FlashSector,
+};
+use crate::architecture::arm::core::m4::Dhcsr;
+use crate::config::{FlashAlgorithm, FlashProperties, FlashRegion, MemoryRange};
 use crate::memory::MemoryInterface;
 use crate::{
-    core::{Architecture, RegisterFile},
+    core::{Architecture, CoreRegister, RegisterFile},
+    error,
     session::Session,
-    Core, CoreRegisterAddress,
+    Core, CoreRegisterAddress, CoreType,
 };
 use anyhow::{anyhow, Result};
 use std::{fmt::Debug, time::Duration};
 
+/// The value the stack of the flash algorithm is painted with, to detect a stack overflow.
+const STACK_CANARY: u32 = 0xA5A5_A5A5;
+
+/// The number of words at the bottom of the stack, which are checked for a stack overflow.
+const STACK_CANARY_WORDS: usize = 4;
+
+/// The number of words on the top of the stack, which are captured when a routine fails.
+const SNAPSHOT_STACK_WORDS: u32 = 8;
+
+/// The timeout for routines of the flash algorithm, which have no timing in the target description.
+const DEFAULT_ROUTINE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The upper bound of the timeout for erasing the entire chip.
+const MAX_ERASE_ALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The additional time the CRC32 routine may take for every KiB of flash.
+const CRC32_TIMEOUT_PER_KIB: Duration = Duration::from_millis(20);
+
+/// Address of the Configurable Fault Status Register of Cortex-M cores.
+const CFSR: u32 = 0xE000_ED28;
+
+/// Address of the HardFault Status Register of Cortex-M cores.
+const HFSR: u32 = 0xE000_ED2C;
+
+/// The xPSR register of Cortex-M cores, which contains the number of the active exception.
+const XPSR: CoreRegisterAddress = CoreRegisterAddress(0b1_0000);
+
 pub(super) trait Operation {
     fn operation() -> u32;
     fn operation_name(&self) -> &str {
@@ -95,6 +127,14 @@ impl<'session> Flasher<'session> {
             address = Some(self.region.flash_info().rom_start);
         }
 
+        let sequence = self.session.target().debug_sequence.clone();
+        let core_type = self
+            .session
+            .list_cores()
+            .first()
+            .map(|(_, core_type)| *core_type)
+            .ok_or(FlashError::Core(error::Error::CoreNotFound(0)))?;
+
         // Attach to memory and core.
         let mut core = self.session.core(0).map_err(FlashError::Memory)?;
 
@@ -108,7 +148,10 @@ impl<'session> Flasher<'session> {
         core.reset_and_halt(Duration::from_millis(500))
             .map_err(FlashError::Core)?;
 
-        // TODO: Possible special preparation of the target such as enabling faster clocks for the flash e.g.
+        // Special preparation of the target, such as enabling faster clocks for the flash.
+        sequence
+            .prepare_flashing(&mut core)
+            .map_err(FlashError::Core)?;
 
         // Load flash algorithm code into target RAM.
         log::debug!(
@@ -142,6 +185,16 @@ impl<'session> Flasher<'session> {
 
         log::debug!("RAM contents match flashing algo blob.");
 
+        // Paint the stack, to detect if the algorithm overflows it.
+        let stack_words = (algo.stack_size / 4) as usize;
+        if stack_words >= STACK_CANARY_WORDS {
+            core.write_32(
+                algo.begin_stack - algo.stack_size,
+                &vec![STACK_CANARY; stack_words],
+            )
+            .map_err(FlashError::Memory)?;
+        }
+
         log::debug!("Preparing Flasher for region:");
         log::debug!("{:#?}", &self.region);
         log::debug!(
@@ -150,6 +203,7 @@ impl<'session> Flasher<'session> {
         );
         let mut flasher = ActiveFlasher::<O> {
             core,
+            core_type,
             flash_algorithm: self.flash_algorithm.clone(),
             _double_buffering_supported: self.double_buffering_supported,
             _operation: core::marker::PhantomData,
@@ -222,7 +276,7 @@ impl<'session> Flasher<'session> {
 
         let mut fb = FlashBuilder::new();
         fb.add_data(address, data)?;
-        self.program(&fb, do_chip_erase, true, false, progress)?;
+        self.program(&fb, do_chip_erase, true, false, false, progress)?;
 
         Ok(())
     }
@@ -232,12 +286,16 @@ impl<'session> Flasher<'session> {
     /// If `restore_unwritten_bytes` is `true`, all bytes of a sector,
     /// that are not to be written during flashing will be read from the flash first
     /// and written again once the sector is erased.
+    ///
+    /// If `incremental` is `true`, sectors which already contain the data
+    /// to be written are skipped.
     pub(super) fn program(
         &mut self,
         flash_builder: &FlashBuilder,
         mut do_chip_erase: bool,
         restore_unwritten_bytes: bool,
         enable_double_buffering: bool,
+        incremental: bool,
         progress: &FlashProgress,
     ) -> Result<()> {
         // Convert the list of flash operations into flash sectors and pages.
@@ -277,6 +335,17 @@ impl<'session> Flasher<'session> {
         // We successfully finished filling.
         progress.finished_filling();
 
+        // Skip the sectors which are already up to date.
+        if incremental {
+            if do_chip_erase {
+                log::warn!(
+                    "Incremental flashing is not possible with a chip erase, flashing all sectors."
+                );
+            } else {
+                self.skip_unchanged_sectors(&mut flash_layout, progress)?;
+            }
+        }
+
         // Erase all necessary sectors.
         if do_chip_erase {
             self.chip_erase(&flash_layout, progress)?;
@@ -306,6 +375,66 @@ impl<'session> Flasher<'session> {
         self.run_verify(|active| active.read_block8(fill.address(), page_slice))
     }
 
+    /// Remove all sectors from `flash_layout`, whose contents on the target already
+    /// match the layout, by comparing their checksums.
+    fn skip_unchanged_sectors(
+        &mut self,
+        flash_layout: &mut FlashLayout,
+        progress: &FlashProgress,
+    ) -> Result<()> {
+        let properties = &self.flash_algorithm.flash_properties;
+
+        // A page spanning multiple sectors can only be programmed if all of them are erased.
+        if flash_layout
+            .sectors()
+            .iter()
+            .any(|sector| sector.size() < properties.page_size)
+        {
+            log::warn!("Sectors are smaller than pages, flashing all sectors.");
+            return Ok(());
+        }
+
+        let sectors = flash_layout.sectors().to_vec();
+        let expected: Vec<u32> = sectors
+            .iter()
+            .map(|sector| {
+                crc32(&sector_contents(
+                    flash_layout,
+                    sector,
+                    properties.erased_byte_value,
+                ))
+            })
+            .collect();
+
+        let actual = self.run_verify(|active| {
+            let routine = active.load_crc32_routine()?;
+            sectors
+                .iter()
+                .map(|sector| active.checksum(routine, sector.address(), sector.size()))
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let skipped: Vec<FlashSector> = sectors
+            .into_iter()
+            .zip(expected.iter().zip(actual.iter()))
+            .filter(|(_, (expected, actual))| expected == actual)
+            .map(|(sector, _)| sector)
+            .collect();
+
+        log::info!(
+            "Skipping {} of {} sectors, which are already up to date.",
+            skipped.len(),
+            expected.len()
+        );
+
+        for sector in &skipped {
+            progress.sector_skipped(sector.address(), sector.size());
+        }
+        flash_layout.skip_sectors(&skipped);
+
+        Ok(())
+    }
+
     /// Erase the entire flash of the chip.
     ///
     /// This takes the list of available sectors only for progress reporting reasons.
@@ -398,13 +527,15 @@ impl<'session> Flasher<'session> {
 
                 // Then wait for the active RAM -> Flash copy process to finish.
                 // Also check if it finished properly. If it didn't, return an error.
-                let result = active.wait_for_completion(Duration::from_secs(2))?;
+                let timeout = active.program_page_timeout();
+                let result = active.wait_for_completion("program_page", timeout)?;
                 progress.page_programmed(page.size(), t.elapsed());
                 t = std::time::Instant::now();
                 if result != 0 {
                     return Err(FlashError::PageWrite {
                         page_address: page.address(),
                         error_code: result,
+                        state: active.failure_state(),
                     });
                 }
 
@@ -452,6 +583,7 @@ impl Debug for Registers {
 
 pub(super) struct ActiveFlasher<'probe, O: Operation> {
     core: Core<'probe>,
+    core_type: CoreType,
     flash_algorithm: FlashAlgorithm,
     _double_buffering_supported: bool,
     _operation: core::marker::PhantomData<O>,
@@ -465,6 +597,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         // Execute init routine if one is present.
         if let Some(pc_init) = algo.pc_init {
             let result = self.call_function_and_wait(
+                "init",
                 &Registers {
                     pc: pc_init,
                     r0: address,
@@ -473,13 +606,14 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                     r3: None,
                 },
                 true,
-                Duration::from_secs(2),
+                DEFAULT_ROUTINE_TIMEOUT,
             )?;
 
             if result != 0 {
                 return Err(anyhow!(FlashError::RoutineCallFailed {
                     name: "init",
                     errorcode: result,
+                    state: self.failure_state(),
                 }));
             }
         }
@@ -497,6 +631,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
 
         if let Some(pc_uninit) = algo.pc_uninit {
             let result = self.call_function_and_wait(
+                "uninit",
                 &Registers {
                     pc: pc_uninit,
                     r0: Some(O::operation()),
@@ -505,13 +640,14 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                     r3: None,
                 },
                 false,
-                Duration::from_secs(2),
+                DEFAULT_ROUTINE_TIMEOUT,
             )?;
 
             if result != 0 {
                 return Err(anyhow!(FlashError::RoutineCallFailed {
                     name: "uninit",
                     errorcode: result,
+                    state: self.failure_state(),
                 }));
             }
         }
@@ -520,12 +656,13 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
 
     fn call_function_and_wait(
         &mut self,
+        name: &'static str,
         registers: &Registers,
         init: bool,
         duration: Duration,
     ) -> Result<u32> {
         self.call_function(registers, init)?;
-        self.wait_for_completion(duration)
+        self.wait_for_completion(name, duration)
     }
 
     fn call_function(&mut self, registers: &Registers, init: bool) -> Result<()> {
@@ -598,13 +735,48 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         Ok(())
     }
 
-    pub(super) fn wait_for_completion(&mut self, timeout: Duration) -> Result<u32> {
+    /// Wait until the routine `name` returns, and return its result.
+    ///
+    /// If the routine does not return within `timeout`, or causes a fault,
+    /// the state of the core is captured in the returned error.
+    pub(super) fn wait_for_completion(
+        &mut self,
+        name: &'static str,
+        timeout: Duration,
+    ) -> Result<u32> {
         log::debug!("Waiting for routine call completion.");
         let regs = self.core.registers();
 
-        self.core
-            .wait_for_core_halted(timeout)
-            .map_err(FlashError::Core)?;
+        if let Err(e) = self.core.wait_for_core_halted(timeout) {
+            // Only a core which is still running has timed out, everything else is
+            // an error in the communication with the core.
+            if self.core.core_halted().map_err(FlashError::Core)? {
+                return Err(anyhow!(FlashError::Core(e)));
+            }
+
+            return Err(anyhow!(match self.failure_state() {
+                Some(state) if state.is_fault() => FlashError::RoutineFault { name, state },
+                state => FlashError::RoutineTimeout {
+                    name,
+                    timeout,
+                    state,
+                },
+            }));
+        }
+
+        // The core only halts in an exception handler, if the handler contains a breakpoint.
+        if self.core.architecture() == Architecture::Arm {
+            let xpsr = self.core.read_core_reg(XPSR).map_err(FlashError::Core)?;
+            if xpsr & 0x1FF != 0 {
+                if let Some(state) = self.failure_state() {
+                    if state.is_fault() {
+                        return Err(anyhow!(FlashError::RoutineFault { name, state }));
+                    }
+                }
+            }
+        }
+
+        self.check_stack_canary()?;
 
         let r = self
             .core
@@ -613,6 +785,117 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         Ok(r)
     }
 
+    /// Capture the state of the core for the error of a failed routine.
+    ///
+    /// If the state can't be captured, the failure is logged and `None` is returned,
+    /// so that the error of the routine is still reported.
+    fn failure_state(&mut self) -> Option<CoreSnapshot> {
+        match self.snapshot() {
+            Ok(state) => Some(state),
+            Err(error) => {
+                log::warn!("Failed to capture the state of the core: {:?}", error);
+                None
+            }
+        }
+    }
+
+    /// Capture the state of the core, to diagnose a failed routine.
+    ///
+    /// The core is halted, if it is still running.
+    pub(super) fn snapshot(&mut self) -> Result<CoreSnapshot> {
+        if !self.core.core_halted().map_err(FlashError::Core)? {
+            self.core
+                .halt(Duration::from_millis(100))
+                .map_err(FlashError::Core)?;
+        }
+
+        let regs = self.core.registers();
+        let pc = self
+            .core
+            .read_core_reg(regs.program_counter().address)
+            .map_err(FlashError::Core)?;
+        let lr = self
+            .core
+            .read_core_reg(regs.return_address().address)
+            .map_err(FlashError::Core)?;
+        let sp = self
+            .core
+            .read_core_reg(regs.stack_pointer().address)
+            .map_err(FlashError::Core)?;
+
+        let mut snapshot = CoreSnapshot {
+            pc,
+            lr,
+            sp,
+            cfsr: None,
+            hfsr: None,
+            exception: None,
+            lockup: false,
+            stack: Vec::new(),
+        };
+
+        if self.core.architecture() == Architecture::Arm {
+            let xpsr = self.core.read_core_reg(XPSR).map_err(FlashError::Core)?;
+            snapshot.exception = Some(xpsr & 0x1FF);
+
+            let dhcsr = Dhcsr(
+                self.core
+                    .read_word_32(Dhcsr::ADDRESS)
+                    .map_err(FlashError::Memory)?,
+            );
+            snapshot.lockup = dhcsr.s_lockup();
+
+            // The fault status registers are not implemented on ARMv6-M.
+            if !matches!(self.core_type, CoreType::M0) {
+                snapshot.cfsr = Some(self.core.read_word_32(CFSR).map_err(FlashError::Memory)?);
+                snapshot.hfsr = Some(self.core.read_word_32(HFSR).map_err(FlashError::Memory)?);
+            }
+        }
+
+        // Only read the stack, if the stack pointer still points into the stack of the algorithm.
+        let algo = &self.flash_algorithm;
+        let stack_range = algo.begin_stack - algo.stack_size..algo.begin_stack;
+        if stack_range.contains(&sp) && sp % 4 == 0 {
+            let words = u32::min(SNAPSHOT_STACK_WORDS, (stack_range.end - sp) / 4);
+            snapshot.stack = vec![0; words as usize];
+            self.core
+                .read_32(sp, &mut snapshot.stack)
+                .map_err(FlashError::Memory)?;
+        }
+
+        Ok(snapshot)
+    }
+
+    /// The timeout for programming a single page.
+    pub(super) fn program_page_timeout(&self) -> Duration {
+        routine_timeout(self.flash_algorithm.flash_properties.program_page_timeout)
+    }
+
+    /// Check that the canary at the bottom of the stack is intact, i.e. that
+    /// the algorithm did not overflow its stack.
+    fn check_stack_canary(&mut self) -> Result<()> {
+        let algo = &self.flash_algorithm;
+
+        if ((algo.stack_size / 4) as usize) < STACK_CANARY_WORDS {
+            return Ok(());
+        }
+
+        let bottom = algo.begin_stack - algo.stack_size;
+        let mut canary = [0; STACK_CANARY_WORDS];
+        self.core
+            .read_32(bottom, &mut canary)
+            .map_err(FlashError::Memory)?;
+
+        if canary.iter().any(|&word| word != STACK_CANARY) {
+            return Err(anyhow!(FlashError::StackOverflow {
+                bottom,
+                size: algo.stack_size,
+            }));
+        }
+
+        Ok(())
+    }
+
     pub(super) fn read_block8(&mut self, address: u32, data: &mut [u8]) -> Result<()> {
         self.core
             .read_8(address, data)
@@ -621,6 +904,64 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
     }
 }
 
+impl<'probe> ActiveFlasher<'probe, Verify> {
+    /// Load [CRC32_ROUTINE] into the first page buffer, and return its address.
+    ///
+    /// Returns `None` if the routine can't run on the core, or doesn't fit
+    /// into the page buffer.
+    pub(super) fn load_crc32_routine(&mut self) -> Result<Option<u32>> {
+        let address = self.flash_algorithm.begin_data;
+        let size = (CRC32_ROUTINE.len() * 4) as u32;
+
+        if self.core.architecture() != Architecture::Arm
+            || address % 4 != 0
+            || self.flash_algorithm.flash_properties.page_size < size
+        {
+            return Ok(None);
+        }
+
+        self.core
+            .write_32(address, &CRC32_ROUTINE)
+            .map_err(FlashError::Memory)?;
+
+        Ok(Some(address))
+    }
+
+    /// Calculate the CRC32 of the flash contents in `address..address + size`.
+    ///
+    /// The checksum is calculated on the target using the CRC32 routine at `routine`,
+    /// or by reading back the contents if the routine is not available.
+    pub(super) fn checksum(
+        &mut self,
+        routine: Option<u32>,
+        address: u32,
+        size: u32,
+    ) -> Result<u32> {
+        let routine = match routine {
+            Some(routine) => routine,
+            None => {
+                let mut data = vec![0; size as usize];
+                self.read_block8(address, &mut data)?;
+                return Ok(crc32(&data));
+            }
+        };
+
+        self.call_function_and_wait(
+            "crc32",
+            &Registers {
+                // Stay in Thumb mode.
+                pc: routine + 1,
+                r0: Some(address),
+                r1: Some(size),
+                r2: None,
+                r3: None,
+            },
+            false,
+            DEFAULT_ROUTINE_TIMEOUT + CRC32_TIMEOUT_PER_KIB * (size / 1024),
+        )
+    }
+}
+
 impl<'probe> ActiveFlasher<'probe, Erase> {
     pub(super) fn erase_all(&mut self) -> Result<()> {
         log::debug!("Erasing entire chip.");
@@ -629,6 +970,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
 
         if let Some(pc_erase_all) = algo.pc_erase_all {
             let result = flasher.call_function_and_wait(
+                "erase_all",
                 &Registers {
                     pc: pc_erase_all,
                     r0: None,
@@ -637,13 +979,14 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
                     r3: None,
                 },
                 false,
-                Duration::from_secs(5),
+                erase_all_timeout(&algo.flash_properties),
             )?;
 
             if result != 0 {
                 Err(anyhow!(FlashError::EraseFailed {
                     name: "erase_all",
                     errorcode: result,
+                    state: flasher.failure_state(),
                 }))
             } else {
                 Ok(())
@@ -658,6 +1001,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
         let t1 = std::time::Instant::now();
 
         let result = self.call_function_and_wait(
+            "erase_sector",
             &Registers {
                 pc: self.flash_algorithm.pc_erase_sector,
                 r0: Some(address),
@@ -666,7 +1010,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
                 r3: None,
             },
             false,
-            Duration::from_secs(5),
+            routine_timeout(self.flash_algorithm.flash_properties.erase_sector_timeout),
         )?;
         log::info!(
             "Done erasing sector. Result is {}. This took {:?}",
@@ -678,6 +1022,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
             Err(anyhow!(FlashError::EraseFailed {
                 name: "erase_sector",
                 errorcode: result,
+                state: self.failure_state(),
             }))
         } else {
             Ok(())
@@ -701,6 +1046,7 @@ impl<'p> ActiveFlasher<'p, Program> {
             .map_err(FlashError::Memory)?;
 
         let result = self.call_function_and_wait(
+            "program_page",
             &Registers {
                 pc: self.flash_algorithm.pc_program_page,
                 r0: Some(address),
@@ -709,7 +1055,7 @@ impl<'p> ActiveFlasher<'p, Program> {
                 r3: None,
             },
             false,
-            Duration::from_secs(2),
+            self.program_page_timeout(),
         )?;
         log::info!("Flashing took: {:?}", t1.elapsed());
 
@@ -717,6 +1063,7 @@ impl<'p> ActiveFlasher<'p, Program> {
             Err(anyhow!(FlashError::RoutineCallFailed {
                 name: "program_page",
                 errorcode: result,
+                state: self.failure_state(),
             }))
         } else {
             Ok(())
@@ -778,3 +1125,116 @@ impl<'p> ActiveFlasher<'p, Program> {
         Ok(())
     }
 }
+
+/// The contents of `sector` after programming `flash_layout`.
+fn sector_contents(
+    flash_layout: &FlashLayout,
+    sector: &FlashSector,
+    erased_byte_value: u8,
+) -> Vec<u8> {
+    let mut contents = vec![erased_byte_value; sector.size() as usize];
+    let sector_range = sector.address()..sector.address() + sector.size();
+
+    for page in flash_layout.pages() {
+        if sector_range.contains(&page.address()) {
+            let offset = (page.address() - sector.address()) as usize;
+            let size = page.data().len().min(contents.len() - offset);
+            contents[offset..offset + size].copy_from_slice(&page.data()[..size]);
+        }
+    }
+
+    contents
+}
+
+/// Convert a timeout of the target description in milliseconds into a [Duration].
+fn routine_timeout(millis: u32) -> Duration {
+    if millis == 0 {
+        DEFAULT_ROUTINE_TIMEOUT
+    } else {
+        Duration::from_millis(u64::from(millis))
+    }
+}
+
+/// The timeout for erasing the entire chip.
+///
+/// Erasing the whole chip takes about as long as erasing all sectors, but
+/// the timeout is limited to [MAX_ERASE_ALL_TIMEOUT].
+fn erase_all_timeout(properties: &FlashProperties) -> Duration {
+    routine_timeout(properties.erase_sector_timeout)
+        .checked_mul(sector_count(properties))
+        .map_or(MAX_ERASE_ALL_TIMEOUT, |timeout| {
+            timeout.min(MAX_ERASE_ALL_TIMEOUT)
+        })
+}
+
+/// The number of sectors of the flash described by `properties`.
+fn sector_count(properties: &FlashProperties) -> u32 {
+    let flash_size = properties.address_range.end - properties.address_range.start;
+
+    properties
+        .sectors
+        .iter()
+        .enumerate()
+        .map(|(i, sector)| {
+            let end = properties
+                .sectors
+                .get(i + 1)
+                .map_or(flash_size, |next| next.address);
+            end.saturating_sub(sector.address) / sector.size.max(1)
+        })
+        .sum::<u32>()
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SectorDescription;
+    use std::borrow::Cow;
+
+    #[test]
+    fn sector_count_with_multiple_sizes() {
+        let properties = FlashProperties {
+            address_range: 0x800_0000..0x810_0000,
+            sectors: Cow::Borrowed(&[
+                SectorDescription {
+                    size: 0x4000,
+                    address: 0x0,
+                },
+                SectorDescription {
+                    size: 0x1_0000,
+                    address: 0x1_0000,
+                },
+                SectorDescription {
+                    size: 0x2_0000,
+                    address: 0x2_0000,
+                },
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(sector_count(&properties), 4 + 1 + 7);
+        assert_eq!(erase_all_timeout(&properties), DEFAULT_ROUTINE_TIMEOUT * 12);
+    }
+
+    #[test]
+    fn erase_all_timeout_is_limited() {
+        let properties = FlashProperties {
+            address_range: 0x0..0x10_0000,
+            erase_sector_timeout: 3000,
+            sectors: Cow::Borrowed(&[SectorDescription {
+                size: 0x400,
+                address: 0x0,
+            }]),
+            ..Default::default()
+        };
+
+        assert_eq!(erase_all_timeout(&properties), MAX_ERASE_ALL_TIMEOUT);
+    }
+
+    #[test]
+    fn routine_timeout_from_target_description() {
+        assert_eq!(routine_timeout(300), Duration::from_millis(300));
+        assert_eq!(routine_timeout(0), DEFAULT_ROUTINE_TIMEOUT);
+    }
+}
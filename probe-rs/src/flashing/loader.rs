@@ -1,7 +1,9 @@
 use super::{FlashBuilder, FlashError, FlashProgress, Flasher};
-use crate::config::{FlashRegion, MemoryRange, MemoryRegion};
+use crate::config::{FlashAlgorithm, FlashRegion, MemoryRange, MemoryRegion, RawFlashAlgorithm};
+use crate::core::Architecture;
 use crate::session::Session;
 use anyhow::anyhow;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 
 /// `FlashLoader` is a struct which manages the flashing of any chunks of data onto any sections of flash.
@@ -87,11 +89,15 @@ impl<'mmap, 'data> FlashLoader<'mmap, 'data> {
     /// Requires a session with an attached target that has a known flash algorithm.
     ///
     /// If `do_chip_erase` is `true` the entire flash will be erased.
+    ///
+    /// If `incremental` is `true`, the checksums of the sectors on the target are compared
+    /// with the data first, and only the sectors which differ are erased and programmed.
     pub(super) fn commit(
         &mut self,
         session: &mut Session,
         progress: &FlashProgress,
         do_chip_erase: bool,
+        incremental: bool,
     ) -> Result<(), FlashError> {
         // Iterate over builders we've created and program the data.
         for (region, builder) in &self.builders {
@@ -101,56 +107,156 @@ impl<'mmap, 'data> FlashLoader<'mmap, 'data> {
                 region.range.end
             );
 
-            // Try to find a flash algorithm for the range of the current builder
-            for algorithm in session.flash_algorithms() {
-                log::debug!(
-                    "Algorithm {} - start: {:#08x} - size: {:#08x}",
-                    algorithm.name,
-                    algorithm.flash_properties.address_range.start,
-                    algorithm.flash_properties.address_range.end
-                        - algorithm.flash_properties.address_range.start
-                );
+            let flash_algorithm = flash_algorithm_for_region(session, region)?;
+
+            // Program the data.
+            let mut flasher = Flasher::new(session, flash_algorithm, region.clone());
+            flasher.program(
+                builder,
+                do_chip_erase,
+                self.keep_unwritten,
+                false,
+                incremental,
+                progress,
+            )?
+        }
+
+        Ok(())
+    }
+}
+
+/// Select and assemble the flash algorithm which is used to program `region`.
+///
+/// If the region names its flash algorithm, that algorithm is used. Otherwise,
+/// the algorithm is selected by its address range, and if multiple algorithms
+/// cover the region, the default algorithm is used.
+pub(super) fn flash_algorithm_for_region(
+    session: &Session,
+    region: &FlashRegion,
+) -> Result<FlashAlgorithm, FlashError> {
+    // Try to find a flash algorithm for the range of the current builder
+    for algorithm in session.flash_algorithms() {
+        log::debug!(
+            "Algorithm {} - start: {:#08x} - size: {:#08x}",
+            algorithm.name,
+            algorithm.flash_properties.address_range.start,
+            algorithm.flash_properties.address_range.end
+                - algorithm.flash_properties.address_range.start
+        );
+    }
+
+    let algorithms = session.flash_algorithms();
+    let algorithms = algorithms
+        .iter()
+        .filter(|fa| {
+            fa.flash_properties
+                .address_range
+                .contains_range(&region.range)
+        })
+        .filter(|fa| match &region.algorithm {
+            Some(name) => fa.name == *name,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+
+    log::debug!("Algorithms: {:?}", &algorithms);
+
+    let raw_flash_algorithm = match algorithms.len() {
+        0 => {
+            if let Some(name) = &region.algorithm {
+                return Err(FlashError::FlashAlgorithmNotFound {
+                    name: name.to_string(),
+                    start: region.range.start,
+                    end: region.range.end,
+                });
             }
+            return Err(FlashError::NoFlashLoaderAlgorithmAttached);
+        }
+        1 => &algorithms[0],
+        _ => algorithms
+            .iter()
+            .find(|a| a.default)
+            .ok_or(FlashError::NoFlashLoaderAlgorithmAttached)?,
+    };
 
-            let algorithms = session.flash_algorithms();
-            let algorithms = algorithms
-                .iter()
-                .filter(|fa| {
-                    fa.flash_properties
-                        .address_range
-                        .contains_range(&region.range)
-                })
-                .collect::<Vec<_>>();
-
-            log::debug!("Algorithms: {:?}", &algorithms);
-
-            let raw_flash_algorithm = match algorithms.len() {
-                0 => {
-                    return Err(FlashError::NoFlashLoaderAlgorithmAttached);
-                }
-                1 => &algorithms[0],
-                _ => algorithms
-                    .iter()
-                    .find(|a| a.default)
-                    .ok_or(FlashError::NoFlashLoaderAlgorithmAttached)?,
-            };
+    assemble_in_ram(
+        raw_flash_algorithm,
+        session.memory_map(),
+        session.architecture(),
+    )
+}
 
-            let mm = session.memory_map();
-            let ram = mm
-                .iter()
-                .find_map(|mm| match mm {
-                    MemoryRegion::Ram(ram) => Some(ram),
-                    _ => None,
-                })
-                .ok_or_else(|| anyhow!("No RAM defined for chip."))?;
+/// Assemble `raw_flash_algorithm` for the RAM of the chip described by `memory_map`.
+///
+/// The algorithm is placed in the RAM region containing its load address, or in the
+/// largest RAM region it fits into, if it has no load address.
+fn assemble_in_ram(
+    raw_flash_algorithm: &RawFlashAlgorithm,
+    memory_map: &[MemoryRegion],
+    architecture: Architecture,
+) -> Result<FlashAlgorithm, FlashError> {
+    let mut ram_regions = memory_map.iter().filter_map(|mm| match mm {
+        MemoryRegion::Ram(ram) => Some(ram),
+        _ => None,
+    });
 
-            let flash_algorithm = raw_flash_algorithm.assemble(ram, session.architecture())?;
+    if let Some(load_address) = raw_flash_algorithm.load_address {
+        let ram = ram_regions
+            .find(|ram| ram.range.contains(&load_address))
+            .ok_or_else(|| {
+                anyhow!(
+                    "No RAM region contains the load address {:#010x} of the flash algorithm.",
+                    load_address
+                )
+            })?;
 
-            // Program the data.
-            let mut flasher = Flasher::new(session, flash_algorithm, region.clone());
-            flasher.program(builder, do_chip_erase, self.keep_unwritten, false, progress)?
+        return raw_flash_algorithm.assemble(ram, architecture);
+    }
+
+    let mut ram_regions: Vec<_> = ram_regions.collect();
+    ram_regions.sort_by_key(|ram| Reverse(ram.range.end - ram.range.start));
+
+    let mut result = Err(FlashError::Other(anyhow!("No RAM defined for chip.")));
+    for ram in ram_regions {
+        result = raw_flash_algorithm.assemble(ram, architecture);
+        if result.is_ok() {
+            break;
         }
+    }
 
-        Ok(())
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::assemble_in_ram;
+    use crate::config::{FlashProperties, MemoryRegion, RamRegion, RawFlashAlgorithm};
+    use crate::core::Architecture;
+    use std::borrow::Cow;
+
+    #[test]
+    fn algorithm_is_placed_in_largest_ram() {
+        let algorithm = RawFlashAlgorithm {
+            instructions: Cow::Owned(vec![0; 0x400]),
+            flash_properties: FlashProperties {
+                page_size: 0x100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let memory_map = [
+            MemoryRegion::Ram(RamRegion {
+                range: 0x1000_0000..0x1000_0800,
+                is_boot_memory: false,
+            }),
+            MemoryRegion::Ram(RamRegion {
+                range: 0x2000_0000..0x2000_8000,
+                is_boot_memory: false,
+            }),
+        ];
+
+        let assembled = assemble_in_ram(&algorithm, &memory_map, Architecture::Arm).unwrap();
+        assert_eq!(assembled.load_address, 0x2000_0000 + 512);
     }
 }
@@ -2,13 +2,17 @@
 
 //! Flash programming operations.
 //!
-//! This modules provides a means to do flash unlocking, erasing and programming.
+//! This modules provides a means to do flash unlocking, erasing and programming,
+//! and to read back the contents of the flash or other memory to a file.
 //!
 //! It provides a convenient highlevel interface that can flash an ELF, IHEX or BIN file
 //! as well as a lower level block based interface.
 
 mod builder;
+mod crc;
 mod download;
+mod dump;
+mod erase;
 mod error;
 mod flasher;
 mod loader;
@@ -16,7 +20,10 @@ mod progress;
 mod visualizer;
 
 use builder::*;
+pub(crate) use crc::*;
 pub use download::*;
+pub use dump::*;
+pub use erase::*;
 pub use error::*;
 pub use flasher::*;
 use loader::*;
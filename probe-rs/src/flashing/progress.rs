@@ -61,6 +61,11 @@ impl FlashProgress {
         self.emit(ProgressEvent::SectorErased { size, time });
     }
 
+    /// Signalize that a sector is skipped, because its contents are already up to date.
+    pub(super) fn sector_skipped(&self, address: u32, size: u32) {
+        self.emit(ProgressEvent::SectorSkipped { address, size });
+    }
+
     /// Signalize that the page filling procedure has made progress.
     pub(super) fn page_filled(&self, size: u32, time: Duration) {
         self.emit(ProgressEvent::PageFilled { size, time });
@@ -95,6 +100,26 @@ impl FlashProgress {
     pub(super) fn finished_filling(&self) {
         self.emit(ProgressEvent::FinishedFilling);
     }
+
+    /// Signalize that reading back `size` bytes of memory started.
+    pub(super) fn started_reading(&self, size: u32) {
+        self.emit(ProgressEvent::StartedReading { size });
+    }
+
+    /// Signalize that the reading procedure has made progress.
+    pub(super) fn block_read(&self, size: u32, time: Duration) {
+        self.emit(ProgressEvent::BlockRead { size, time });
+    }
+
+    /// Signalize that the reading procedure failed.
+    pub(super) fn failed_reading(&self) {
+        self.emit(ProgressEvent::FailedReading);
+    }
+
+    /// Signalize that the reading procedure completed successfully.
+    pub(super) fn finished_reading(&self) {
+        self.emit(ProgressEvent::FinishedReading);
+    }
 }
 
 /// Possible events during the flashing process.
@@ -106,6 +131,7 @@ impl FlashProgress {
 /// * `StartedFilling`
 /// * `PageFilled` for every page
 /// * `FinishedFilling`
+/// * `SectorSkipped` for every unchanged sector, when flashing incrementally
 /// * `StartedErasing`
 /// * `SectorErased` for every sector
 /// * `FinishedErasing`
@@ -115,6 +141,15 @@ impl FlashProgress {
 ///
 /// If an erorr occurs in any stage, one of the `Failed*` event will be returned,
 /// and no further events will be returned.
+///
+/// Skipped sectors, and their pages, are neither erased nor programmed.
+///
+/// When memory is read back, the events are `StartedReading`, `BlockRead`
+/// for every block and `FinishedReading`, or `FailedReading` on an error.
+///
+/// When flash is only erased, e.g. using [erase_range_with_options](super::erase_range_with_options),
+/// the events are `StartedErasing`, `SectorErased` for every sector and
+/// `FinishedErasing`, or `FailedErasing` on an error.
 #[derive(Debug)]
 pub enum ProgressEvent {
     /// The flash layout has been built and the flashing procedure was initialized.
@@ -138,6 +173,13 @@ pub enum ProgressEvent {
     FailedFilling,
     /// Filling of the pages has finished successfully.
     FinishedFilling,
+    /// A sector is skipped, because it already contains the data to be flashed.
+    SectorSkipped {
+        /// The start address of the sector.
+        address: u32,
+        /// The size of the sector in bytes.
+        size: u32,
+    },
     /// Erasing of flash has started.
     StartedErasing,
     /// A sector has been erased successfully.
@@ -164,4 +206,20 @@ pub enum ProgressEvent {
     FailedProgramming,
     /// Programming of the flash has finished successfully.
     FinishedProgramming,
+    /// Reading back memory has started.
+    StartedReading {
+        /// The total number of bytes to read.
+        size: u32,
+    },
+    /// A block of memory has been read successfully.
+    BlockRead {
+        /// The size of the block in bytes.
+        size: u32,
+        /// The time it took to read this block.
+        time: Duration,
+    },
+    /// Reading back memory failed.
+    FailedReading,
+    /// Reading back memory has finished successfully.
+    FinishedReading,
 }
@@ -78,18 +78,24 @@ mod error;
 pub mod flashing;
 mod memory;
 mod probe;
+pub mod semihosting;
 mod session;
 
 pub use crate::config::Target;
 pub use crate::core::CoreType;
 pub use crate::core::{
-    Architecture, Breakpoint, BreakpointId, CommunicationInterface, Core, CoreInformation,
-    CoreInterface, CoreList, CoreRegister, CoreRegisterAddress, CoreStatus, HaltReason,
+    Architecture, Breakpoint, BreakpointCause, BreakpointId, CommunicationInterface, Core,
+    CoreInformation, CoreInterface, CoreList, CoreRegister, CoreRegisterAddress, CoreStatus,
+    HaltReason, RegisterFile,
 };
 pub use crate::error::Error;
 pub use crate::memory::{Memory, MemoryInterface, MemoryList};
+#[cfg(feature = "ftdi")]
+pub use crate::probe::ftdi::{FtdiLayout, FtdiProbe};
 pub use crate::probe::{
     AttachMethod, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
-    Probe, WireProtocol,
+    JtagChainItem, Probe, ProbeCapabilities, ProbeServer, RecordedError, Recording, RecordingError,
+    RecordingProbe, RemoteError, RemoteProbe, ReplayError, ReplayProbe, Request, Response,
+    ScanChainError, SimulatedProbe, SimulatedTarget, Transaction, WireProtocol,
 };
 pub use crate::session::Session;
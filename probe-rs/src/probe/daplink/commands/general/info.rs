@@ -186,7 +186,7 @@ fn string_from_bytes<R, F: Fn(String) -> R>(
     offset: usize,
     constructor: &F,
 ) -> Result<R> {
-    let string_len = buffer[dbg!(offset)] as usize; // including the zero terminator
+    let string_len = buffer[offset] as usize; // including the zero terminator
 
     let string_start = offset + 1;
     let string_end = string_start + string_len;
@@ -0,0 +1,44 @@
+/// Implementation of the DAP_JTAG_Configure command
+///
+use super::super::{Category, CmsisDapError, Request, Response, Result, Status};
+use anyhow::anyhow;
+
+/// Configures the IR length of each device in the JTAG chain.
+///
+/// The devices are listed in the order of the scan chain, starting with the device closest to TDO.
+#[derive(Clone, Debug)]
+pub struct ConfigureRequest {
+    ir_lengths: Vec<u8>,
+}
+
+impl ConfigureRequest {
+    pub(crate) fn new(ir_lengths: &[u8]) -> Result<ConfigureRequest> {
+        if ir_lengths.len() > 255 {
+            return Err(anyhow!(CmsisDapError::TooMuchData));
+        }
+
+        Ok(ConfigureRequest {
+            ir_lengths: ir_lengths.to_vec(),
+        })
+    }
+}
+
+impl Request for ConfigureRequest {
+    const CATEGORY: Category = Category(0x15);
+
+    fn to_bytes(&self, buffer: &mut [u8], offset: usize) -> Result<usize> {
+        buffer[offset] = self.ir_lengths.len() as u8;
+        buffer[offset + 1..offset + 1 + self.ir_lengths.len()].copy_from_slice(&self.ir_lengths);
+
+        Ok(1 + self.ir_lengths.len())
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigureResponse(pub(crate) Status);
+
+impl Response for ConfigureResponse {
+    fn from_bytes(buffer: &[u8], offset: usize) -> Result<Self> {
+        Ok(ConfigureResponse(Status::from_byte(buffer[offset])?))
+    }
+}
@@ -0,0 +1,34 @@
+/// Implementation of the DAP_JTAG_IDCODE command
+///
+use super::super::{Category, Request, Response, Result, Status};
+use scroll::{Pread, LE};
+
+/// Reads the IDCODE of the device with the given index in the JTAG chain.
+#[derive(Clone, Copy, Debug)]
+pub struct IdCodeRequest {
+    pub(crate) index: u8,
+}
+
+impl Request for IdCodeRequest {
+    const CATEGORY: Category = Category(0x16);
+
+    fn to_bytes(&self, buffer: &mut [u8], offset: usize) -> Result<usize> {
+        buffer[offset] = self.index;
+        Ok(1)
+    }
+}
+
+#[derive(Debug)]
+pub struct IdCodeResponse {
+    pub(crate) status: Status,
+    pub(crate) idcode: u32,
+}
+
+impl Response for IdCodeResponse {
+    fn from_bytes(buffer: &[u8], offset: usize) -> Result<Self> {
+        Ok(IdCodeResponse {
+            status: Status::from_byte(buffer[offset])?,
+            idcode: buffer.pread_with(offset + 1, LE)?,
+        })
+    }
+}
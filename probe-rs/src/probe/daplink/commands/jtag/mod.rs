@@ -0,0 +1,3 @@
+pub mod configure;
+pub mod idcode;
+pub mod sequence;
@@ -0,0 +1,131 @@
+/// Implementation of the DAP_JTAG_Sequence command
+///
+use super::super::{Category, CmsisDapError, Request, Response, Result, Status};
+use anyhow::anyhow;
+
+/// The maximum number of TCK cycles in a single sequence.
+pub(crate) const MAX_SEQUENCE_CYCLES: usize = 64;
+
+/// A single JTAG sequence, which generates up to 64 TCK cycles with a constant TMS value.
+#[derive(Clone, Copy, Debug)]
+pub struct Sequence {
+    tck_cycles: u8,
+    tdo_capture: bool,
+    tms: bool,
+    /// TDI data, LSB first.
+    data: [u8; 8],
+}
+
+impl Sequence {
+    pub(crate) fn new(tck_cycles: u8, tdo_capture: bool, tms: bool, data: [u8; 8]) -> Result<Self> {
+        if tck_cycles == 0 || tck_cycles as usize > MAX_SEQUENCE_CYCLES {
+            return Err(anyhow!(
+                "A JTAG sequence must contain between 1 and {} TCK cycles, not {}",
+                MAX_SEQUENCE_CYCLES,
+                tck_cycles
+            ));
+        }
+
+        Ok(Sequence {
+            tck_cycles,
+            tdo_capture,
+            tms,
+            data,
+        })
+    }
+
+    pub(crate) fn tck_cycles(&self) -> u8 {
+        self.tck_cycles
+    }
+
+    pub(crate) fn tdo_capture(&self) -> bool {
+        self.tdo_capture
+    }
+
+    /// Number of bytes used for the TDI data in the request, and for the TDO data in the response.
+    pub(crate) fn byte_count(&self) -> usize {
+        (self.tck_cycles as usize + 7) / 8
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SequenceRequest {
+    sequences: Vec<Sequence>,
+}
+
+impl SequenceRequest {
+    pub(crate) fn new(sequences: &[Sequence]) -> Result<SequenceRequest> {
+        if sequences.len() > 255 {
+            return Err(anyhow!(CmsisDapError::TooMuchData));
+        }
+
+        Ok(SequenceRequest {
+            sequences: sequences.to_vec(),
+        })
+    }
+}
+
+impl Request for SequenceRequest {
+    const CATEGORY: Category = Category(0x14);
+
+    fn to_bytes(&self, buffer: &mut [u8], offset: usize) -> Result<usize> {
+        buffer[offset] = self.sequences.len() as u8;
+        let mut size = 1;
+
+        for sequence in &self.sequences {
+            // A value of 0 encodes 64 TCK cycles.
+            buffer[offset + size] = (sequence.tck_cycles % 64)
+                | (sequence.tms as u8) << 6
+                | (sequence.tdo_capture as u8) << 7;
+            size += 1;
+
+            let byte_count = sequence.byte_count();
+            buffer[offset + size..offset + size + byte_count]
+                .copy_from_slice(&sequence.data[..byte_count]);
+            size += byte_count;
+        }
+
+        Ok(size)
+    }
+}
+
+/// The response contains the captured TDO data of all sequences with `tdo_capture` set.
+#[derive(Debug)]
+pub struct SequenceResponse(pub(crate) Status, pub(crate) Vec<u8>);
+
+impl Response for SequenceResponse {
+    fn from_bytes(buffer: &[u8], offset: usize) -> Result<Self> {
+        Ok(SequenceResponse(
+            Status::from_byte(buffer[offset])?,
+            buffer[offset + 1..].to_vec(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Request, Sequence, SequenceRequest};
+
+    #[test]
+    fn encode_sequences() {
+        let sequences = [
+            Sequence::new(5, false, true, [0; 8]).unwrap(),
+            Sequence::new(64, true, false, [0xaa; 8]).unwrap(),
+            Sequence::new(9, true, true, [0x34, 0x01, 0, 0, 0, 0, 0, 0]).unwrap(),
+        ];
+
+        let mut buffer = [0u8; 32];
+        let size = SequenceRequest::new(&sequences)
+            .unwrap()
+            .to_bytes(&mut buffer, 0)
+            .unwrap();
+
+        assert_eq!(
+            &buffer[..size],
+            &[
+                3, 0x45, 0x00, 0x80, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xc9, 0x34,
+                0x01
+            ]
+        );
+    }
+}
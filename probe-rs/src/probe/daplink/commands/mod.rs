@@ -1,4 +1,5 @@
 pub mod general;
+pub mod jtag;
 pub mod swd;
 pub mod swj;
 pub mod swo;
@@ -7,6 +8,7 @@ pub mod transfer;
 use crate::architecture::arm::DapError;
 use crate::DebugProbeError;
 use core::ops::Deref;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
@@ -19,7 +21,7 @@ pub enum CmsisDapError {
     UnexpectedAnswer,
     #[error("CMSIS-DAP responded with an error")]
     ErrorResponse,
-    #[error("Too much data provided for SWJ Sequence command")]
+    #[error("Too much data provided for SWJ or JTAG command")]
     TooMuchData,
     #[error("Not enough data in response from probe")]
     NotEnoughData,
@@ -50,8 +52,10 @@ pub enum DAPLinkDevice {
     V1(hidapi::HidDevice),
 
     /// CMSIS-DAP v2 over WinUSB/Bulk. Stores an rusb device handle and out/in EP addresses.
+    ///
+    /// The handle is shared with the thread reading the SWO streaming endpoint.
     V2 {
-        handle: rusb::DeviceHandle<rusb::Context>,
+        handle: Arc<rusb::DeviceHandle<rusb::Context>>,
         out_ep: u8,
         in_ep: u8,
         swo_ep: Option<u8>,
@@ -100,22 +104,20 @@ impl DAPLinkDevice {
         }
     }
 
-    /// Read into `buf` from the SWO streaming endpoint.
+    /// Get the device handle and address of the SWO streaming endpoint, which can
+    /// be read from another thread.
     ///
     /// Returns SWOModeNotAvailable if this device does not support SWO streaming.
-    ///
-    /// On timeout, returns Ok(0).
-    pub(super) fn read_swo_stream(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+    pub(super) fn swo_stream_endpoint(
+        &self,
+    ) -> Result<(Arc<rusb::DeviceHandle<rusb::Context>>, u8), CmsisDapError> {
         match self {
-            DAPLinkDevice::V1(_) => Err(CmsisDapError::SWOModeNotAvailable.into()),
-            DAPLinkDevice::V2 { handle, swo_ep, .. } => match swo_ep {
-                Some(ep) => match handle.read_bulk(*ep, buf, timeout) {
-                    Ok(n) => Ok(n),
-                    Err(rusb::Error::Timeout) => Ok(0),
-                    Err(e) => Err(e.into()),
-                },
-                None => Err(CmsisDapError::SWOModeNotAvailable.into()),
-            },
+            DAPLinkDevice::V2 {
+                handle,
+                swo_ep: Some(ep),
+                ..
+            } => Ok((handle.clone(), *ep)),
+            _ => Err(CmsisDapError::SWOModeNotAvailable),
         }
     }
 }
@@ -55,6 +55,18 @@ impl SequenceRequest {
             data: owned_data,
         })
     }
+
+    /// Create a request for a sequence of `bit_count` bits, starting with the LSB of `bits`.
+    pub(crate) fn from_bits(bit_count: u8, bits: u64) -> Result<SequenceRequest> {
+        if bit_count == 0 || bit_count > 64 {
+            return Err(anyhow!(CmsisDapError::TooMuchData));
+        }
+
+        let mut data = [0u8; 32];
+        data[..8].copy_from_slice(&bits.to_le_bytes());
+
+        Ok(SequenceRequest { bit_count, data })
+    }
 }
 
 #[derive(Debug)]
@@ -1,4 +1,5 @@
 pub mod configure;
+pub mod write_abort;
 
 use super::{Category, Request, Response, Result};
 use crate::architecture::arm::PortType as ArmPortType;
@@ -198,7 +199,7 @@ impl Response for TransferResponse {
 pub(crate) struct TransferBlockRequest {
     /// Zero-based device index of the selected JTAG device. For SWD mode the
     /// value is ignored.
-    dap_index: u8,
+    pub(crate) dap_index: u8,
     /// Number of transfers
     transfer_count: u16,
 
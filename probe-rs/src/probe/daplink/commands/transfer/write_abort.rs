@@ -0,0 +1,35 @@
+use super::super::{Category, Request, Response, Result, Status};
+use anyhow::anyhow;
+use scroll::{Pwrite, LE};
+
+/// Writes the ABORT register of the debug port.
+///
+/// With JTAG, the ABORT register is accessed using a separate instruction,
+/// and can't be written using a normal DP register write.
+#[derive(Clone, Copy, Debug)]
+pub struct WriteAbortRequest {
+    /// Index of the device in the JTAG chain, ignored for SWD.
+    pub(crate) dap_index: u8,
+    pub(crate) abort: u32,
+}
+
+impl Request for WriteAbortRequest {
+    const CATEGORY: Category = Category(0x08);
+
+    fn to_bytes(&self, buffer: &mut [u8], offset: usize) -> Result<usize> {
+        buffer[offset] = self.dap_index;
+        buffer
+            .pwrite_with(self.abort, offset + 1, LE)
+            .map_err(|_| anyhow!("This is a bug. Please report it."))?;
+        Ok(5)
+    }
+}
+
+#[derive(Debug)]
+pub struct WriteAbortResponse(pub(crate) Status);
+
+impl Response for WriteAbortResponse {
+    fn from_bytes(buffer: &[u8], offset: usize) -> Result<Self> {
+        Ok(WriteAbortResponse(Status::from_byte(buffer[offset])?))
+    }
+}
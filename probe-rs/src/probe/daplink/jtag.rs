@@ -0,0 +1,317 @@
+//! JTAG support for CMSIS-DAP probes.
+//!
+//! Raw JTAG scans, as used for RISC-V targets, are implemented using the
+//! DAP_JTAG_Sequence command. ARM targets are accessed using the normal
+//! DAP_Transfer commands, the probe takes care of the DPACC and APACC scans.
+
+use super::{commands, CmsisDapError, DAPLink};
+use crate::probe::{
+    scan_chain::{self, bits_to_vec, bytes_to_bits, ChainParams, RawJtagIo},
+    DebugProbe, DebugProbeError, JTAGAccess,
+};
+
+use commands::{
+    jtag::{
+        configure::{ConfigureRequest, ConfigureResponse},
+        idcode::{IdCodeRequest, IdCodeResponse},
+        sequence::{Sequence, SequenceRequest, SequenceResponse, MAX_SEQUENCE_CYCLES},
+    },
+    swj::sequence::SequenceRequest as SwjSequenceRequest,
+    Status,
+};
+
+impl DAPLink {
+    /// Switch the SWJ-DP of the target from SWD to JTAG.
+    ///
+    /// Afterwards, the TAP controller is in the Test-Logic-Reset state.
+    pub(super) fn switch_to_jtag(&mut self) -> Result<(), DebugProbeError> {
+        // ~50 SWCLKTCK
+        self.send_swj_sequences(SwjSequenceRequest::new(&[
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ])?)?;
+
+        // 16-bit SWD-to-JTAG select sequence
+        self.send_swj_sequences(SwjSequenceRequest::new(&[0x3c, 0xe7])?)?;
+
+        // At least 5 TCK cycles with TMS high, to enter Test-Logic-Reset
+        self.send_swj_sequences(SwjSequenceRequest::new(&[0xff])?)?;
+
+        log::debug!("Successfully changed to JTAG.");
+
+        Ok(())
+    }
+
+    /// Scan the JTAG chain, and configure the probe for it.
+    pub(super) fn configure_jtag_chain(&mut self) -> Result<(), DebugProbeError> {
+        let config = self.scan_chain_config.clone();
+
+        let chain = scan_chain::scan(self, config.as_ref())?;
+        let index = scan_chain::select_target_tap(&chain, config.as_ref())?;
+
+        let ir_lengths: Vec<u8> = chain.iter().map(|tap| tap.ir_len as u8).collect();
+
+        let response: ConfigureResponse =
+            commands::send_command(&mut self.device, ConfigureRequest::new(&ir_lengths)?)?;
+
+        if let ConfigureResponse(Status::DAPError) = response {
+            return Err(CmsisDapError::ErrorResponse.into());
+        }
+
+        self.jtag_chain_params = Some(ChainParams::new(&chain, index));
+        self.jtag_tap_index = index as u8;
+        self.scan_chain = chain;
+
+        let response: IdCodeResponse = commands::send_command(
+            &mut self.device,
+            IdCodeRequest {
+                index: self.jtag_tap_index,
+            },
+        )?;
+
+        match response.status {
+            Status::DAPOk => {
+                log::debug!("JTAG IDCODE: {:#010x}", response.idcode);
+                self.jtag_idcode = Some(response.idcode);
+            }
+            Status::DAPError => log::debug!("Unable to read the JTAG IDCODE"),
+        }
+
+        Ok(())
+    }
+
+    /// Move the TAP controller to Test-Logic-Reset, and then to Run-Test/Idle.
+    fn jtag_reset(&mut self) -> Result<(), DebugProbeError> {
+        let mut sequences = Vec::new();
+        push_tms(&mut sequences, true, 5)?;
+        push_tms(&mut sequences, false, 1)?;
+
+        self.send_jtag_sequences(&sequences)?;
+        self.current_ir_reg = None;
+
+        Ok(())
+    }
+
+    /// Shift `tdi` into the IR, starting and ending in Run-Test/Idle.
+    ///
+    /// Returns the bits shifted out of the IR.
+    fn shift_ir(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        let mut sequences = Vec::new();
+
+        // Select-DR-Scan, Select-IR-Scan
+        push_tms(&mut sequences, true, 2)?;
+        // Capture-IR, Shift-IR
+        push_tms(&mut sequences, false, 2)?;
+
+        push_shift(&mut sequences, tdi)?;
+
+        // Update-IR, Run-Test/Idle
+        push_tms(&mut sequences, true, 1)?;
+        push_tms(&mut sequences, false, 1)?;
+
+        self.send_jtag_sequences(&sequences)
+    }
+
+    /// Shift `tdi` into the DR, starting and ending in Run-Test/Idle.
+    ///
+    /// Returns the bits shifted out of the DR.
+    fn shift_dr(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        let mut sequences = Vec::new();
+
+        // Select-DR-Scan
+        push_tms(&mut sequences, true, 1)?;
+        // Capture-DR, Shift-DR
+        push_tms(&mut sequences, false, 2)?;
+
+        push_shift(&mut sequences, tdi)?;
+
+        // Update-DR, Run-Test/Idle, and the additional idle cycles
+        push_tms(&mut sequences, true, 1)?;
+        push_tms(&mut sequences, false, 1 + self.jtag_idle_cycles as usize)?;
+
+        self.send_jtag_sequences(&sequences)
+    }
+
+    /// Send the JTAG sequences, using as many commands as necessary.
+    ///
+    /// Returns the captured TDO bits.
+    fn send_jtag_sequences(
+        &mut self,
+        sequences: &[Sequence],
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        // Each sequence needs at most 9 bytes in the request,
+        // in addition to the command and sequence count bytes.
+        let max_sequences = ((self.packet_size.unwrap_or(64) as usize - 2) / 9).min(255);
+
+        let mut tdo = Vec::new();
+
+        for chunk in sequences.chunks(max_sequences) {
+            let response: SequenceResponse =
+                commands::send_command(&mut self.device, SequenceRequest::new(chunk)?)?;
+
+            if let SequenceResponse(Status::DAPError, _) = response {
+                return Err(CmsisDapError::ErrorResponse.into());
+            }
+
+            let data = response.1;
+            let mut offset = 0;
+
+            for sequence in chunk.iter().filter(|sequence| sequence.tdo_capture()) {
+                let bytes = data
+                    .get(offset..offset + sequence.byte_count())
+                    .ok_or(CmsisDapError::NotEnoughData)?;
+
+                tdo.extend(
+                    (0..sequence.tck_cycles() as usize)
+                        .map(|bit| bytes[bit / 8] & (1 << (bit % 8)) != 0),
+                );
+
+                offset += sequence.byte_count();
+            }
+        }
+
+        Ok(tdo)
+    }
+
+    /// Select the instruction register `address`, if it isn't already selected.
+    ///
+    /// All other TAPs in the chain are put in BYPASS.
+    fn select_ir(&mut self, address: u32) -> Result<(), DebugProbeError> {
+        let params = self.jtag_chain_params.ok_or(DebugProbeError::NotAttached)?;
+
+        if !params.is_valid_instruction(address) {
+            return Err(DebugProbeError::NotImplemented(
+                "JTAG register address does not fit into the instruction register",
+            ));
+        }
+
+        if self.current_ir_reg != Some(address) {
+            self.shift_ir(&params.ir_bits(address))?;
+            self.current_ir_reg = Some(address);
+        }
+
+        Ok(())
+    }
+
+    /// Shift `tdi` into the data register `address` of the target TAP.
+    ///
+    /// Returns the previous content of the data register.
+    fn transfer_dr(&mut self, address: u32, tdi: &[bool]) -> Result<Vec<u8>, DebugProbeError> {
+        self.select_ir(address)?;
+
+        let params = self.jtag_chain_params.ok_or(DebugProbeError::NotAttached)?;
+
+        let tdo = self.shift_dr(&params.dr_bits(tdi))?;
+
+        Ok(bits_to_vec(params.dr_result(&tdo, tdi.len())))
+    }
+}
+
+impl RawJtagIo for DAPLink {
+    fn reset_chain(&mut self) -> Result<(), DebugProbeError> {
+        self.jtag_reset()
+    }
+
+    fn shift_ir_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        self.current_ir_reg = None;
+        self.shift_ir(tdi)
+    }
+
+    fn shift_dr_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        self.shift_dr(tdi)
+    }
+}
+
+/// Add sequences which clock `cycles` TCK cycles with a constant TMS value.
+fn push_tms(
+    sequences: &mut Vec<Sequence>,
+    tms: bool,
+    cycles: usize,
+) -> Result<(), DebugProbeError> {
+    let mut remaining = cycles;
+
+    while remaining > 0 {
+        let chunk = remaining.min(MAX_SEQUENCE_CYCLES);
+        sequences.push(Sequence::new(chunk as u8, false, tms, [0; 8])?);
+        remaining -= chunk;
+    }
+
+    Ok(())
+}
+
+/// Add sequences which shift `tdi` into the currently selected register, and capture TDO.
+///
+/// The last bit is shifted with TMS high, which leaves the Shift-IR or Shift-DR state.
+fn push_shift(sequences: &mut Vec<Sequence>, tdi: &[bool]) -> Result<(), DebugProbeError> {
+    let (last, bits) = match tdi.split_last() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    for chunk in bits.chunks(MAX_SEQUENCE_CYCLES) {
+        sequences.push(Sequence::new(
+            chunk.len() as u8,
+            true,
+            false,
+            sequence_data(chunk),
+        )?);
+    }
+
+    sequences.push(Sequence::new(1, true, true, sequence_data(&[*last]))?);
+
+    Ok(())
+}
+
+/// Convert up to 64 bits into the TDI data of a sequence.
+fn sequence_data(bits: &[bool]) -> [u8; 8] {
+    let mut data = [0u8; 8];
+    let bytes = bits_to_vec(bits);
+    data[..bytes.len()].copy_from_slice(&bytes);
+    data
+}
+
+impl JTAGAccess for DAPLink {
+    /// Read the data register
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        self.transfer_dr(address, &vec![false; len as usize])
+    }
+
+    /// Write the data register
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        self.transfer_dr(address, &bytes_to_bits(data, len as usize))
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        self.jtag_idle_cycles = idle_cycles;
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{push_shift, push_tms};
+
+    #[test]
+    fn long_shifts_are_split() {
+        let mut sequences = Vec::new();
+
+        push_tms(&mut sequences, false, 70).unwrap();
+        assert_eq!(sequences.len(), 2);
+
+        sequences.clear();
+
+        // 64 bits in the first sequence, 36 in the second, and the last bit with TMS high.
+        push_shift(&mut sequences, &[true; 101]).unwrap();
+
+        let cycles: Vec<u8> = sequences.iter().map(|s| s.tck_cycles()).collect();
+        assert_eq!(cycles, [64, 36, 1]);
+        assert!(sequences.iter().all(|s| s.tdo_capture()));
+    }
+}
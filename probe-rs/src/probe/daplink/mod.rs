@@ -1,16 +1,25 @@
 pub mod commands;
+mod jtag;
+mod swo_stream;
 pub mod tools;
 
 use crate::{
     architecture::arm::{
         communication_interface::ArmProbeInterface,
-        dp::{Abort, Ctrl, DPAccess, DPRegister, DebugPortError},
+        dp::{jtag::is_arm_idcode, Abort, Ctrl, DPAccess, DPRegister, DebugPortError},
         swo::poll_interval_from_buf_size,
         ArmCommunicationInterface, DAPAccess, DapError, PortType, Register, SwoAccess, SwoConfig,
         SwoMode,
     },
-    probe::{daplink::commands::CmsisDapError, BatchCommand},
-    DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, WireProtocol,
+    architecture::riscv::communication_interface::RiscvCommunicationInterface,
+    config::ScanChain,
+    probe::{
+        daplink::commands::CmsisDapError,
+        scan_chain::{ChainParams, JtagChainItem},
+        BatchCommand, JTAGAccess,
+    },
+    DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, ProbeCapabilities,
+    WireProtocol,
 };
 
 use commands::{
@@ -18,7 +27,9 @@ use commands::{
         connect::{ConnectRequest, ConnectResponse},
         disconnect::{DisconnectRequest, DisconnectResponse},
         host_status::{HostStatusRequest, HostStatusResponse},
-        info::{Capabilities, Command, PacketCount, PacketSize, SWOTraceBufferSize},
+        info::{
+            Capabilities, Command, FirmwareVersion, PacketCount, PacketSize, SWOTraceBufferSize,
+        },
         reset::{ResetRequest, ResetResponse},
     },
     swd,
@@ -30,11 +41,13 @@ use commands::{
     swo,
     transfer::{
         configure::{ConfigureRequest, ConfigureResponse},
+        write_abort::{WriteAbortRequest, WriteAbortResponse},
         Ack, InnerTransferRequest, TransferBlockRequest, TransferBlockResponse, TransferRequest,
         TransferResponse, RW,
     },
     DAPLinkDevice, Status,
 };
+use swo_stream::SwoStream;
 
 use log::debug;
 
@@ -54,12 +67,34 @@ pub struct DAPLink {
     capabilities: Option<Capabilities>,
     swo_buffer_size: Option<usize>,
     swo_active: bool,
-    swo_streaming: bool,
+    /// Reads the SWO data if it is streamed using the dedicated endpoint,
+    /// otherwise it is polled using DAP_SWO_Data commands.
+    swo_stream: Option<SwoStream>,
 
     /// Speed in kHz
     speed_khz: u32,
 
     batch: Vec<BatchCommand>,
+
+    /// Position of the target TAP in the JTAG scan chain, determined when attaching using JTAG.
+    jtag_chain_params: Option<ChainParams>,
+
+    /// Index of the target TAP in the JTAG scan chain, used for DAP transfers.
+    jtag_tap_index: u8,
+
+    /// Idle cycles necessary between consecutive
+    /// accesses to the DMI register
+    jtag_idle_cycles: u8,
+
+    /// The currently selected JTAG instruction, if known.
+    current_ir_reg: Option<u32>,
+    /// IDCODE of the JTAG target, read when attaching using JTAG.
+    jtag_idcode: Option<u32>,
+
+    /// The configured JTAG scan chain, if it isn't detected automatically.
+    scan_chain_config: Option<ScanChain>,
+    /// The TAPs found when attaching using JTAG.
+    scan_chain: Vec<JtagChainItem>,
 }
 
 impl std::fmt::Debug for DAPLink {
@@ -71,7 +106,7 @@ impl std::fmt::Debug for DAPLink {
             .field("capabilities", &self.capabilities)
             .field("swo_buffer_size", &self.swo_buffer_size)
             .field("swo_active", &self.swo_active)
-            .field("swo_streaming", &self.swo_streaming)
+            .field("swo_streaming", &self.swo_stream.is_some())
             .field("speed_khz", &self.speed_khz)
             .finish()
     }
@@ -101,9 +136,16 @@ impl DAPLink {
             capabilities: None,
             swo_buffer_size: None,
             swo_active: false,
-            swo_streaming: false,
+            swo_stream: None,
             speed_khz: 1_000,
             batch: Vec::new(),
+            jtag_chain_params: None,
+            jtag_tap_index: 0,
+            jtag_idle_cycles: 0,
+            current_ir_reg: None,
+            jtag_idcode: None,
+            scan_chain_config: None,
+            scan_chain: Vec::new(),
         }
     }
 
@@ -160,6 +202,57 @@ impl DAPLink {
         Ok(())
     }
 
+    /// Switch the SWJ-DP of the target from JTAG to SWD.
+    fn switch_to_swd(&mut self) -> Result<(), DebugProbeError> {
+        self.configure_swd(swd::configure::ConfigureRequest {})?;
+
+        // SWJ-DP defaults to JTAG operation on powerup reset
+        // Switching from JTAG to SWD operation
+
+        // ~50 SWCLKTCK
+        self.send_swj_sequences(SequenceRequest::new(&[
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ])?)?;
+
+        // 16-bit JTAG-to-SWD select sequence
+        self.send_swj_sequences(SequenceRequest::new(&[0x9e, 0xe7])?)?;
+
+        // ~50 SWCLKTCK
+        self.send_swj_sequences(SequenceRequest::new(&[
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ])?)?;
+
+        // returning to low state? 2 idle cycles?
+        self.send_swj_sequences(SequenceRequest::new(&[0x00])?)?;
+
+        // On selecting SWD operation, the SWD interface returns to its reset state.
+
+        debug!("Successfully changed to SWD.");
+
+        Ok(())
+    }
+
+    /// Write the ABORT register of the debug port.
+    ///
+    /// This uses a dedicated command, because the ABORT register
+    /// can't be written with a normal transfer when using JTAG.
+    fn write_abort(&mut self, value: u32) -> Result<(), DebugProbeError> {
+        self.process_batch()?;
+
+        let response = commands::send_command(
+            &mut self.device,
+            WriteAbortRequest {
+                dap_index: self.jtag_tap_index,
+                abort: value,
+            },
+        )?;
+
+        match response {
+            WriteAbortResponse(Status::DAPOk) => Ok(()),
+            WriteAbortResponse(Status::DAPError) => Err(CmsisDapError::ErrorResponse.into()),
+        }
+    }
+
     /// Immediately send whatever is in our batch if it is not empty.
     ///
     /// This will ensure any pending writes are processed and errors from them
@@ -188,9 +281,12 @@ impl DAPLink {
                 })
                 .collect();
 
+            let mut request = TransferRequest::new(&transfers);
+            request.dap_index = self.jtag_tap_index;
+
             let response = commands::send_command::<TransferRequest, TransferResponse>(
                 &mut self.device,
-                TransferRequest::new(&transfers),
+                request,
             )?;
 
             let count = response.transfer_count as usize;
@@ -334,7 +430,6 @@ impl DAPLink {
     }
 
     /// Fetch current SWO trace status.
-    #[allow(dead_code)]
     fn get_swo_status(&mut self) -> Result<swo::StatusResponse, DebugProbeError> {
         Ok(commands::send_command(
             &mut self.device,
@@ -355,6 +450,19 @@ impl DAPLink {
         Ok(commands::send_command(&mut self.device, request)?)
     }
 
+    /// Report an overrun of the SWO trace buffer of the probe, and fail on trace errors.
+    fn check_swo_status(status: swo::TraceStatus) -> Result<(), DebugProbeError> {
+        if status.overrun {
+            log::warn!("The SWO trace buffer of the probe overflowed, some data was lost");
+        }
+
+        if status.error {
+            Err(CmsisDapError::SWOTraceStreamError.into())
+        } else {
+            Ok(())
+        }
+    }
+
     /// Fetch latest SWO trace data by sending a DAP_SWO_Data request.
     fn get_swo_data(&mut self) -> Result<Vec<u8>, DebugProbeError> {
         match self.swo_buffer_size {
@@ -370,11 +478,9 @@ impl DAPLink {
 
                 let response: swo::DataResponse =
                     commands::send_command(&mut self.device, swo::DataRequest { max_count: n })?;
-                if response.status.error {
-                    Err(CmsisDapError::SWOTraceStreamError.into())
-                } else {
-                    Ok(response.data)
-                }
+                Self::check_swo_status(response.status)?;
+
+                Ok(response.data)
             }
             None => Ok(Vec::new()),
         }
@@ -384,7 +490,7 @@ impl DAPLink {
 impl DPAccess for DAPLink {
     fn read_dp_register<R: DPRegister>(&mut self) -> Result<R, DebugPortError> {
         debug!("Reading DP register {}", R::NAME);
-        let result = self.read_register(PortType::DebugPort, u16::from(R::ADDRESS))?;
+        let result = DAPAccess::read_register(self, PortType::DebugPort, u16::from(R::ADDRESS))?;
 
         debug!("Read    DP register {}, value=0x{:08x}", R::NAME, result);
 
@@ -395,7 +501,7 @@ impl DPAccess for DAPLink {
         let value = register.into();
 
         debug!("Writing DP register {}, value=0x{:08x}", R::NAME, value);
-        self.write_register(PortType::DebugPort, u16::from(R::ADDRESS), value)?;
+        DAPAccess::write_register(self, PortType::DebugPort, u16::from(R::ADDRESS), value)?;
 
         Ok(())
     }
@@ -467,11 +573,14 @@ impl DebugProbe for DAPLink {
             ConnectRequest::UseDefaultPort
         };
 
-        let _result = commands::send_command(&mut self.device, protocol).and_then(|v| match v {
-            ConnectResponse::SuccessfulInitForSWD => Ok(WireProtocol::Swd),
-            ConnectResponse::SuccessfulInitForJTAG => Ok(WireProtocol::Jtag),
-            ConnectResponse::InitFailed => Err(anyhow!(CmsisDapError::ErrorResponse)),
-        })?;
+        let protocol =
+            commands::send_command(&mut self.device, protocol).and_then(|v| match v {
+                ConnectResponse::SuccessfulInitForSWD => Ok(WireProtocol::Swd),
+                ConnectResponse::SuccessfulInitForJTAG => Ok(WireProtocol::Jtag),
+                ConnectResponse::InitFailed => Err(anyhow!(CmsisDapError::ErrorResponse)),
+            })?;
+
+        self.protocol = Some(protocol);
 
         // Set speed after connecting as it can be reset during protocol selection
         self.set_speed(self.speed_khz)?;
@@ -482,30 +591,13 @@ impl DebugProbe for DAPLink {
             match_retry: 0,
         })?;
 
-        self.configure_swd(swd::configure::ConfigureRequest {})?;
-
-        // SWJ-DP defaults to JTAG operation on powerup reset
-        // Switching from JTAG to SWD operation
-
-        // ~50 SWCLKTCK
-        self.send_swj_sequences(SequenceRequest::new(&[
-            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
-        ])?)?;
-
-        // 16-bit JTAG-to-SWD select sequence
-        self.send_swj_sequences(SequenceRequest::new(&[0x9e, 0xe7])?)?;
-
-        // ~50 SWCLKTCK
-        self.send_swj_sequences(SequenceRequest::new(&[
-            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
-        ])?)?;
-
-        // returning to low state? 2 idle cycles?
-        self.send_swj_sequences(SequenceRequest::new(&[0x00])?)?;
-
-        // On selecting SWD operation, the SWD interface returns to its reset state.
-
-        debug!("Successfully changed to SWD.");
+        match protocol {
+            WireProtocol::Swd => self.switch_to_swd()?,
+            WireProtocol::Jtag => {
+                self.switch_to_jtag()?;
+                self.configure_jtag_chain()?;
+            }
+        }
 
         // Tell the probe we are connected so it can turn on an LED.
         let _: Result<HostStatusResponse, _> =
@@ -538,10 +630,8 @@ impl DebugProbe for DAPLink {
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
         match protocol {
             WireProtocol::Jtag => {
-                log::warn!(
-                    "Support for JTAG protocol is not yet implemented for CMSIS-DAP based probes."
-                );
-                Err(DebugProbeError::UnsupportedProtocol(WireProtocol::Jtag))
+                self.protocol = Some(WireProtocol::Jtag);
+                Ok(())
             }
             WireProtocol::Swd => {
                 self.protocol = Some(WireProtocol::Swd);
@@ -550,6 +640,33 @@ impl DebugProbe for DAPLink {
         }
     }
 
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        let caps: Capabilities = commands::send_command(&mut self.device, Command::Capabilities)?;
+        let FirmwareVersion(version) =
+            commands::send_command(&mut self.device, Command::FirmwareVersion)?;
+
+        let mut protocols = Vec::new();
+        if caps.swd_implemented {
+            protocols.push(WireProtocol::Swd);
+        }
+        if caps.jtag_implemented {
+            protocols.push(WireProtocol::Jtag);
+        }
+
+        // The maximum clock and the target voltage are not reported using CMSIS-DAP,
+        // but the nRESET pin can always be driven using DAP_SWJ_Pins.
+        Ok(ProbeCapabilities {
+            firmware_version: Some(version.trim_end_matches('\0').to_owned())
+                .filter(|version| !version.is_empty()),
+            protocols,
+            max_speed_khz: None,
+            swo_uart: caps.swo_uart_implemented,
+            swo_manchester: caps.swo_manchester_implemented,
+            reset_control: true,
+            target_voltage: false,
+        })
+    }
+
     /// Asserts the nRESET pin.
     fn target_reset(&mut self) -> Result<(), DebugProbeError> {
         commands::send_command(&mut self.device, ResetRequest).map(|v: ResetResponse| {
@@ -593,7 +710,44 @@ impl DebugProbe for DAPLink {
     }
 
     fn has_arm_interface(&self) -> bool {
-        true
+        match self.protocol {
+            // Only ARM targets have a JTAG-DP, other targets can't be accessed this way.
+            Some(WireProtocol::Jtag) => self.jtag_idcode.map(is_arm_idcode).unwrap_or(true),
+            _ => true,
+        }
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        self.scan_chain_config = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        Ok(&self.scan_chain)
+    }
+
+    fn get_riscv_interface(
+        mut self: Box<Self>,
+    ) -> Result<Option<RiscvCommunicationInterface>, DebugProbeError> {
+        if self.protocol == Some(WireProtocol::Jtag) {
+            // The instruction register was possibly changed by DAP transfers.
+            self.current_ir_reg = None;
+            Ok(Some(RiscvCommunicationInterface::new(self)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.protocol == Some(WireProtocol::Jtag)
+    }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        Some(self as _)
     }
 }
 
@@ -622,6 +776,13 @@ impl DAPAccess for DAPLink {
         addr: u16,
         value: u32,
     ) -> Result<(), DebugProbeError> {
+        if self.protocol == Some(WireProtocol::Jtag)
+            && port == PortType::DebugPort
+            && addr == Abort::ADDRESS as u16
+        {
+            return self.write_abort(value);
+        }
+
         self.batch_add(BatchCommand::Write(port, addr, value))
             .map(|_| ())
     }
@@ -649,11 +810,12 @@ impl DAPAccess for DAPLink {
         let data_chunk_len = max_packet_size_words as usize;
 
         for (i, chunk) in values.chunks(data_chunk_len).enumerate() {
-            let request = TransferBlockRequest::write_request(
+            let mut request = TransferBlockRequest::write_request(
                 register_address as u8,
                 port.into(),
                 Vec::from(chunk),
             );
+            request.dap_index = self.jtag_tap_index;
 
             debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
 
@@ -691,11 +853,12 @@ impl DAPAccess for DAPLink {
         let data_chunk_len = max_packet_size_words as usize;
 
         for (i, chunk) in values.chunks_mut(data_chunk_len).enumerate() {
-            let request = TransferBlockRequest::read_request(
+            let mut request = TransferBlockRequest::read_request(
                 register_address as u8,
                 port.into(),
                 chunk.len() as u16,
             );
+            request.dap_index = self.jtag_tap_index;
 
             debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
 
@@ -717,6 +880,12 @@ impl DAPAccess for DAPLink {
         Ok(())
     }
 
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.process_batch()?;
+        self.send_swj_sequences(SequenceRequest::from_bits(bit_len, bits)?)?;
+        Ok(())
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
@@ -746,20 +915,20 @@ impl SwoAccess for DAPLink {
 
         // Stop any ongoing trace
         self.stop_swo_capture()?;
+        self.swo_stream = None;
 
         // Set transport. If the dedicated endpoint is available and we have opened
         // the probe in V2 mode and it has an SWO endpoint, request that, otherwise
         // request the DAP_SWO_Data polling mode.
-        if caps.swo_streaming_trace_implemented
-            && self.device.get_mut().unwrap().swo_streaming_supported()
-        {
+        let streaming = caps.swo_streaming_trace_implemented
+            && self.device.get_mut().unwrap().swo_streaming_supported();
+
+        if streaming {
             debug!("Starting SWO capture with WinUSB transport");
             self.set_swo_transport(swo::TransportRequest::WinUsbEndpoint)?;
-            self.swo_streaming = true;
         } else {
             debug!("Starting SWO capture with polled transport");
             self.set_swo_transport(swo::TransportRequest::DataCommand)?;
-            self.swo_streaming = false;
         }
 
         // Set mode. We've already checked that the requested mode is listed as supported.
@@ -780,6 +949,21 @@ impl SwoAccess for DAPLink {
 
         self.start_swo_capture()?;
 
+        // Read the streaming endpoint continuously, so that the trace data
+        // doesn't have to be fetched in between other commands.
+        if streaming {
+            let (handle, endpoint) = self
+                .device
+                .get_mut()
+                .unwrap()
+                .swo_stream_endpoint()
+                .map_err(DebugProbeError::from)?;
+
+            self.swo_stream = Some(SwoStream::start(move |buffer| {
+                handle.read_bulk(endpoint, buffer, swo_stream::READ_TIMEOUT)
+            }));
+        }
+
         self.swo_active = true;
         Ok(())
     }
@@ -787,22 +971,24 @@ impl SwoAccess for DAPLink {
     fn disable_swo(&mut self) -> Result<(), ProbeRsError> {
         debug!("Stopping SWO capture");
         self.stop_swo_capture()?;
+        self.swo_stream = None;
         self.swo_active = false;
         Ok(())
     }
 
     fn read_swo_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, ProbeRsError> {
         if self.swo_active {
-            if self.swo_streaming {
-                let device = self
-                    .device
-                    .get_mut()
-                    .expect("This is a bug. Please report it.");
-                let mut buffer = vec![0u8; 1024];
-                let n = device.read_swo_stream(&mut buffer, timeout)?;
-                buffer.truncate(n);
-                log::trace!("SWO streaming buffer: {:?}", buffer);
-                Ok(buffer)
+            if let Some(stream) = &mut self.swo_stream {
+                let data = stream.read(timeout).map_err(DebugProbeError::from)?;
+                log::trace!("SWO streaming buffer: {:?}", data);
+
+                // The stream doesn't contain the trace status, so it is fetched separately.
+                if stream.status_due() {
+                    let status = self.get_swo_status()?;
+                    Self::check_swo_status(status.status)?;
+                }
+
+                Ok(data)
             } else {
                 let data = self.get_swo_data()?;
                 log::trace!("SWO polled data: {:?}", data);
@@ -0,0 +1,200 @@
+//! Continuous reading of SWO data from the streaming endpoint of CMSIS-DAP v2 probes.
+
+use super::commands::CmsisDapError;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Maximum amount of SWO data which is buffered until it is read.
+const RING_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Size of a single read from the streaming endpoint.
+const READ_SIZE: usize = 1024;
+
+/// Timeout of a single read from the streaming endpoint.
+///
+/// This determines how long it takes to stop the background thread.
+pub(super) const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Interval in which the trace status of the probe is checked.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// SWO data received from the probe, which wasn't read yet.
+#[derive(Debug, Default)]
+struct RingBuffer {
+    data: VecDeque<u8>,
+    /// Number of bytes dropped, because the buffer was full.
+    dropped: usize,
+    /// Error which stopped the background thread.
+    error: Option<rusb::Error>,
+}
+
+impl RingBuffer {
+    /// Append `data`, dropping the oldest data if the buffer is full.
+    fn push(&mut self, data: &[u8]) {
+        let data = &data[data.len().saturating_sub(RING_BUFFER_SIZE)..];
+        let excess = (self.data.len() + data.len()).saturating_sub(RING_BUFFER_SIZE);
+
+        self.data.drain(..excess.min(self.data.len()));
+        self.data.extend(data);
+        self.dropped += excess;
+    }
+}
+
+/// State shared between the [SwoStream] and its background thread.
+#[derive(Debug, Default)]
+struct Shared {
+    buffer: Mutex<RingBuffer>,
+    available: Condvar,
+    stop: AtomicBool,
+}
+
+/// Reads SWO data from the streaming endpoint in a background thread,
+/// so that no trace data is lost while the probe is used for debugging.
+#[derive(Debug)]
+pub(super) struct SwoStream {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    last_status: Instant,
+}
+
+impl SwoStream {
+    /// Start reading SWO data using `read`, which reads from the streaming endpoint
+    /// into a buffer and returns `rusb::Error::Timeout` if no data was received.
+    pub(super) fn start(
+        mut read: impl FnMut(&mut [u8]) -> Result<usize, rusb::Error> + Send + 'static,
+    ) -> Self {
+        let shared = Arc::new(Shared::default());
+
+        let thread = {
+            let shared = shared.clone();
+
+            std::thread::spawn(move || {
+                let mut data = [0u8; READ_SIZE];
+
+                while !shared.stop.load(Ordering::Relaxed) {
+                    match read(&mut data) {
+                        Ok(n) => shared.buffer.lock().unwrap().push(&data[..n]),
+                        Err(rusb::Error::Timeout) => continue,
+                        Err(e) => {
+                            log::warn!("Failed to read from the SWO streaming endpoint: {}", e);
+                            shared.buffer.lock().unwrap().error = Some(e);
+                            shared.available.notify_all();
+                            break;
+                        }
+                    }
+
+                    shared.available.notify_all();
+                }
+            })
+        };
+
+        Self {
+            shared,
+            thread: Some(thread),
+            last_status: Instant::now(),
+        }
+    }
+
+    /// Read the received SWO data, waiting up to `timeout` if no data is available.
+    pub(super) fn read(&mut self, timeout: Duration) -> Result<Vec<u8>, CmsisDapError> {
+        let buffer = self.shared.buffer.lock().unwrap();
+
+        let (mut buffer, _) = self
+            .shared
+            .available
+            .wait_timeout_while(buffer, timeout, |buffer| {
+                buffer.data.is_empty() && buffer.error.is_none()
+            })
+            .unwrap();
+
+        if buffer.dropped > 0 {
+            log::warn!(
+                "{} bytes of SWO data were dropped, because they were not read in time",
+                buffer.dropped
+            );
+            buffer.dropped = 0;
+        }
+
+        if buffer.data.is_empty() {
+            if let Some(error) = buffer.error.take() {
+                return Err(error.into());
+            }
+        }
+
+        Ok(buffer.data.drain(..).collect())
+    }
+
+    /// Returns true if the trace status of the probe should be checked again.
+    pub(super) fn status_due(&mut self) -> bool {
+        if self.last_status.elapsed() >= STATUS_INTERVAL {
+            self.last_status = Instant::now();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Drop for SwoStream {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RingBuffer, SwoStream, RING_BUFFER_SIZE};
+    use std::time::Duration;
+
+    #[test]
+    fn ring_buffer_drops_oldest_data() {
+        let mut buffer = RingBuffer::default();
+
+        buffer.push(&vec![1; RING_BUFFER_SIZE - 2]);
+        buffer.push(&[2, 3, 4, 5]);
+
+        assert_eq!(buffer.data.len(), RING_BUFFER_SIZE);
+        assert_eq!(buffer.dropped, 2);
+        assert_eq!(
+            buffer.data.iter().rev().take(4).collect::<Vec<_>>(),
+            [&5, &4, &3, &2]
+        );
+    }
+
+    #[test]
+    fn stream_reads_in_background() {
+        let mut chunks = vec![vec![1, 2], vec![3]].into_iter();
+
+        let mut stream = SwoStream::start(move |buffer| match chunks.next() {
+            Some(chunk) => {
+                buffer[..chunk.len()].copy_from_slice(&chunk);
+                Ok(chunk.len())
+            }
+            None => {
+                std::thread::sleep(Duration::from_millis(10));
+                Err(rusb::Error::Timeout)
+            }
+        });
+
+        let mut data = Vec::new();
+        while data.len() < 3 {
+            data.extend(stream.read(Duration::from_millis(100)).unwrap());
+        }
+
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn stream_reports_errors() {
+        let mut stream = SwoStream::start(|_| Err(rusb::Error::NoDevice));
+
+        assert!(stream.read(Duration::from_secs(1)).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     DebugProbeSelector,
 };
 use rusb::{Device, DeviceDescriptor, UsbContext};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Finds all CMSIS-DAP devices, either v1 (HID) or v2 (WinUSB Bulk).
@@ -135,7 +136,7 @@ pub fn open_v2_device(device: Device<rusb::Context>) -> Option<DAPLinkDevice> {
                 Ok(()) => {
                     log::debug!("Opening {:04x}:{:04x} in CMSIS-DAPv2 mode", vid, pid);
                     return Some(DAPLinkDevice::V2 {
-                        handle,
+                        handle: Arc::new(handle),
                         out_ep,
                         in_ep,
                         swo_ep,
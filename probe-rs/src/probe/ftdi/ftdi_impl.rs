@@ -6,11 +6,12 @@ use libftdi1_sys as ffi;
 use std::convert::TryInto;
 use std::io::{self, ErrorKind, Read, Write};
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::{mem, ptr};
 use thiserror::Error;
 
 /// The target interface
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Interface {
     A,
     B,
@@ -83,8 +84,25 @@ impl Builder {
         }
     }
 
-    pub fn usb_open(mut self, vendor: u16, product: u16) -> Result<Device> {
-        let result = unsafe { ffi::ftdi_usb_open(self.context, vendor as i32, product as i32) };
+    /// Open the device with the given IDs, and the given serial number if it is set.
+    pub fn usb_open(mut self, vendor: u16, product: u16, serial: Option<&str>) -> Result<Device> {
+        let serial = serial
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| Error::InvalidInput("serial number contains a null byte"))?;
+        let serial_ptr = serial
+            .as_ref()
+            .map_or(ptr::null(), |serial| serial.as_ptr());
+
+        let result = unsafe {
+            ffi::ftdi_usb_open_desc(
+                self.context,
+                vendor as i32,
+                product as i32,
+                ptr::null(),
+                serial_ptr,
+            )
+        };
         match result {
             0 => Ok(Device {
                 context: mem::replace(&mut self.context, ptr::null_mut()),
@@ -114,12 +132,60 @@ impl Drop for Builder {
     }
 }
 
+/// The type of the FTDI chip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChipType {
+    Am,
+    Bm,
+    FT2232C,
+    R,
+    FT2232H,
+    FT4232H,
+    FT232H,
+    FT230X,
+    Unknown,
+}
+
+impl ChipType {
+    /// The chip supports the 60 MHz clock of the high speed MPSSE.
+    pub fn is_high_speed(self) -> bool {
+        match self {
+            ChipType::FT2232H | ChipType::FT4232H | ChipType::FT232H => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Device {
     context: *mut ffi::ftdi_context,
 }
 
 impl Device {
+    pub fn chip_type(&self) -> ChipType {
+        let chip_type = unsafe { (*self.context).type_ };
+
+        if chip_type == ffi::ftdi_chip_type::TYPE_AM {
+            ChipType::Am
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_BM {
+            ChipType::Bm
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_2232C {
+            ChipType::FT2232C
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_R {
+            ChipType::R
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_2232H {
+            ChipType::FT2232H
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_4232H {
+            ChipType::FT4232H
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_232H {
+            ChipType::FT232H
+        } else if chip_type == ffi::ftdi_chip_type::TYPE_230X {
+            ChipType::FT230X
+        } else {
+            ChipType::Unknown
+        }
+    }
+
     pub fn usb_reset(&mut self) -> Result<()> {
         let result = unsafe { ffi::ftdi_usb_reset(self.context) };
         match result {
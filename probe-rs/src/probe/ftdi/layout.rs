@@ -0,0 +1,203 @@
+//! Pin layouts of FTDI based adapters.
+//!
+//! The MPSSE always uses ADBUS0..3 for TCK, TDI, TDO and TMS. How the remaining
+//! GPIO pins are used differs between adapters, so the layout describes their
+//! initial state, and which pins are used for the reset signals and the LED.
+
+use super::ftdi::Interface;
+
+/// A GPIO pin of the adapter, which is used for an additional signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Signal {
+    /// The mask of the pin. ADBUS0..7 are bits 0..7, ACBUS0..7 are bits 8..15.
+    pub(crate) mask: u16,
+    /// The signal is asserted by driving the pin low.
+    pub(crate) active_low: bool,
+    /// The pin is only driven while the signal is asserted, and released otherwise.
+    pub(crate) open_drain: bool,
+}
+
+impl Signal {
+    const fn active_low(mask: u16) -> Self {
+        Self {
+            mask,
+            active_low: true,
+            open_drain: false,
+        }
+    }
+
+    const fn active_high(mask: u16) -> Self {
+        Self {
+            mask,
+            active_low: false,
+            open_drain: false,
+        }
+    }
+
+    const fn open_drain(mask: u16) -> Self {
+        Self {
+            mask,
+            active_low: true,
+            open_drain: true,
+        }
+    }
+
+    /// Update the output and direction of the GPIO pins to assert or deassert the signal.
+    pub(crate) fn apply(&self, output: &mut u16, direction: &mut u16, asserted: bool) {
+        let high = asserted != self.active_low;
+
+        if high {
+            *output |= self.mask;
+        } else {
+            *output &= !self.mask;
+        }
+
+        if self.open_drain && !asserted {
+            *direction &= !self.mask;
+        } else {
+            *direction |= self.mask;
+        }
+    }
+}
+
+/// The pin layout of an FTDI based adapter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FtdiLayout {
+    /// The name used to select the layout.
+    pub(crate) name: &'static str,
+    /// USB vendor and product IDs of the adapter.
+    ///
+    /// Adapters using the default IDs of the FTDI chips can't be identified by these.
+    pub(crate) usb_ids: &'static [(u16, u16)],
+    /// The interface of the FTDI chip the MPSSE is connected to.
+    pub(crate) interface: Interface,
+    /// Initial output values of the GPIO pins.
+    pub(crate) output: u16,
+    /// Initial direction of the GPIO pins, pins with a set bit are outputs.
+    pub(crate) direction: u16,
+    /// The JTAG TAP reset.
+    pub(crate) ntrst: Option<Signal>,
+    /// The system reset of the target.
+    pub(crate) nsrst: Option<Signal>,
+    /// The LED, which is turned on while attached.
+    pub(crate) led: Option<Signal>,
+    /// SWD is supported, using a resistor between TDI and TDO, with TDO connected to SWDIO.
+    pub(crate) swd: bool,
+}
+
+/// All known adapter layouts.
+const FTDI_LAYOUTS: &[FtdiLayout] = &[
+    FtdiLayout {
+        name: "olimex-arm-usb-ocd",
+        usb_ids: &[(0x15ba, 0x0003), (0x15ba, 0x002b)],
+        interface: Interface::A,
+        output: 0x0908,
+        direction: 0x0b1b,
+        ntrst: Some(Signal::active_low(0x0100)),
+        nsrst: Some(Signal::open_drain(0x0200)),
+        led: Some(Signal::active_high(0x0800)),
+        swd: true,
+    },
+    FtdiLayout {
+        name: "tigard",
+        usb_ids: &[],
+        interface: Interface::B,
+        output: 0x0038,
+        direction: 0x003b,
+        ntrst: Some(Signal::active_low(0x0010)),
+        nsrst: Some(Signal::active_low(0x0020)),
+        led: None,
+        swd: true,
+    },
+    FtdiLayout {
+        name: "esp-prog",
+        usb_ids: &[],
+        interface: Interface::A,
+        output: 0x0008,
+        direction: 0x000b,
+        ntrst: None,
+        nsrst: None,
+        led: None,
+        swd: false,
+    },
+    FtdiLayout {
+        name: "ft2232h-breakout",
+        usb_ids: &[(0x0403, 0x6010), (0x0403, 0x6014)],
+        interface: Interface::A,
+        output: 0x0038,
+        direction: 0x003b,
+        ntrst: Some(Signal::active_low(0x0010)),
+        nsrst: Some(Signal::active_low(0x0020)),
+        led: None,
+        swd: true,
+    },
+];
+
+impl FtdiLayout {
+    /// All known adapter layouts.
+    pub fn all() -> &'static [FtdiLayout] {
+        FTDI_LAYOUTS
+    }
+
+    /// The name used to select the layout.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Find a layout by its name.
+    pub fn from_name(name: &str) -> Option<&'static FtdiLayout> {
+        FTDI_LAYOUTS
+            .iter()
+            .find(|layout| layout.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Determine the layout of an adapter from its USB IDs and product string.
+    pub(crate) fn detect(
+        vendor_id: u16,
+        product_id: u16,
+        product: Option<&str>,
+    ) -> Option<&'static FtdiLayout> {
+        // The Tigard uses the default IDs of the FT2232H, but has its own product string.
+        if let Some(product) = product {
+            if product.starts_with("Tigard") {
+                return FtdiLayout::from_name("tigard");
+            }
+        }
+
+        FTDI_LAYOUTS
+            .iter()
+            .find(|layout| layout.usb_ids.contains(&(vendor_id, product_id)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FtdiLayout, Signal};
+
+    #[test]
+    fn detect_layout() {
+        let layout = FtdiLayout::detect(0x15ba, 0x002b, Some("Olimex OpenOCD JTAG ARM-USB-OCD-H"));
+        assert_eq!(layout.map(|l| l.name), Some("olimex-arm-usb-ocd"));
+
+        let layout = FtdiLayout::detect(0x0403, 0x6010, Some("Tigard V1.1"));
+        assert_eq!(layout.map(|l| l.name), Some("tigard"));
+
+        let layout = FtdiLayout::detect(0x0403, 0x6010, Some("Dual RS232-HS"));
+        assert_eq!(layout.map(|l| l.name), Some("ft2232h-breakout"));
+
+        assert!(FtdiLayout::detect(0x1234, 0x5678, None).is_none());
+    }
+
+    #[test]
+    fn open_drain_signal() {
+        let signal = Signal::open_drain(0x0200);
+        let mut output = 0x0908;
+        let mut direction = 0x0b1b;
+
+        signal.apply(&mut output, &mut direction, false);
+        assert_eq!((output, direction), (0x0908, 0x091b));
+
+        signal.apply(&mut output, &mut direction, true);
+        assert_eq!((output, direction), (0x0908, 0x0b1b));
+    }
+}
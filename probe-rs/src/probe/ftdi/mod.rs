@@ -1,52 +1,106 @@
+use crate::architecture::arm::{
+    communication_interface::ArmProbeInterface, dp::jtag, ArmCommunicationInterface, DAPAccess,
+    PortType,
+};
 use crate::architecture::riscv::communication_interface::RiscvCommunicationInterface;
-use crate::probe::{JTAGAccess, ProbeCreationError};
+use crate::config::ScanChain;
+use crate::probe::{
+    scan_chain::{self, bits_to_vec, bytes_to_bits, ChainParams, JtagChainItem, RawJtagIo},
+    JTAGAccess, ProbeCreationError,
+};
 use crate::{
-    DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType, WireProtocol,
+    DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
+    ProbeCapabilities, WireProtocol,
 };
-use bitvec::{order::Lsb0, slice::BitSlice, vec::BitVec};
 use rusb::UsbContext;
-use std::convert::TryInto;
+use std::convert::TryFrom;
 use std::io::{self, Read, Write};
 use std::sync::Mutex;
+use std::thread;
 use std::time::Duration;
 
 mod ftdi_impl;
+mod layout;
+mod swd;
+
 use ftdi_impl as ftdi;
+use layout::Signal;
 
-#[derive(Debug)]
-struct JtagChainItem {
-    idcode: u32,
-    irlen: usize,
-}
+pub use layout::FtdiLayout;
+
+/// Speed used if no speed is set before attaching.
+const DEFAULT_SPEED_KHZ: u32 = 1000;
+
+/// How long the target is held in reset by `target_reset`.
+const RESET_DURATION: Duration = Duration::from_millis(20);
+
+/// How long nTRST is asserted to reset the TAPs when attaching.
+const TAP_RESET_DURATION: Duration = Duration::from_millis(1);
+
+/// Calculate the MPSSE clock divisor for the requested speed.
+///
+/// The clock is `base_khz / (divisor + 1)`, so the divisor is rounded up to
+/// never exceed the requested speed. Returns the divisor and the actual speed,
+/// or `None` if the speed is slower than the slowest possible clock.
+fn clock_divisor(base_khz: u32, speed_khz: u32) -> Option<(u16, u32)> {
+    if speed_khz == 0 {
+        return None;
+    }
+
+    let divisor = (base_khz / speed_khz + u32::from(base_khz % speed_khz != 0)).saturating_sub(1);
+    let divisor = u16::try_from(divisor).ok()?;
 
-#[derive(Clone, Debug)]
-struct ChainParams {
-    irpre: usize,
-    irpost: usize,
-    drpre: usize,
-    drpost: usize,
-    irlen: usize,
+    Some((divisor, base_khz / (u32::from(divisor) + 1)))
 }
 
 #[derive(Debug)]
 pub struct JtagAdapter {
     device: ftdi::Device,
+    layout: &'static FtdiLayout,
+    chip_type: ftdi::ChipType,
+    /// Current output values of the GPIO pins.
+    output: u16,
+    /// Current direction of the GPIO pins.
+    direction: u16,
+    initialized: bool,
     chain_params: Option<ChainParams>,
 }
 
 impl JtagAdapter {
-    pub fn open(vid: u16, pid: u16) -> Result<Self, ftdi::Error> {
+    pub fn open(
+        selector: &DebugProbeSelector,
+        layout: &'static FtdiLayout,
+    ) -> Result<Self, ftdi::Error> {
         let mut builder = ftdi::Builder::new();
-        builder.set_interface(ftdi::Interface::A)?;
-        let device = builder.usb_open(vid, pid)?;
+        builder.set_interface(layout.interface)?;
+        let device = builder.usb_open(
+            selector.vendor_id,
+            selector.product_id,
+            selector.serial_number.as_deref(),
+        )?;
+        let chip_type = device.chip_type();
+
+        log::debug!("FTDI chip type {:?}, layout {}", chip_type, layout.name);
 
         Ok(Self {
             device,
+            layout,
+            chip_type,
+            output: layout.output,
+            direction: layout.direction,
+            initialized: false,
             chain_params: None,
         })
     }
 
-    pub fn attach(&mut self) -> Result<(), ftdi::Error> {
+    /// Put the MPSSE into a known state and set up the GPIO pins of the layout.
+    ///
+    /// This only has an effect the first time it is called.
+    pub fn init(&mut self) -> Result<(), ftdi::Error> {
+        if self.initialized {
+            return Ok(());
+        }
+
         self.device.usb_reset()?;
         self.device.set_latency_timer(1)?;
         self.device.set_bitmode(0x0b, ftdi::BitMode::Mpsse)?;
@@ -55,20 +109,79 @@ impl JtagAdapter {
         let mut junk = vec![];
         let _ = self.device.read_to_end(&mut junk);
 
-        // Minimal values, may not work with all probes
-        let output: u16 = 0x0008;
-        let direction: u16 = 0x000b;
-        self.device
-            .write_all(&[0x80, output as u8, direction as u8])?;
-        self.device
-            .write_all(&[0x82, (output >> 8) as u8, (direction >> 8) as u8])?;
+        if self.chip_type.is_high_speed() {
+            // Use the 60 MHz clock without the divide by 5, and disable
+            // adaptive and three phase clocking.
+            self.device.write_all(&[0x8a, 0x97, 0x8d])?;
+        }
 
         // Disable loopback
         self.device.write_all(&[0x85])?;
 
+        self.output = self.layout.output;
+        self.direction = self.layout.direction;
+
+        for signal in self.layout.ntrst.iter().chain(&self.layout.nsrst) {
+            signal.apply(&mut self.output, &mut self.direction, false);
+        }
+        if let Some(led) = &self.layout.led {
+            led.apply(&mut self.output, &mut self.direction, true);
+        }
+        self.write_gpio()?;
+
+        self.initialized = true;
+
         Ok(())
     }
 
+    /// The fastest clock the MPSSE can generate, in kHz.
+    fn max_speed_khz(&self) -> u32 {
+        if self.chip_type.is_high_speed() {
+            30_000
+        } else {
+            6_000
+        }
+    }
+
+    /// Set the clock speed, and return the actual speed.
+    pub fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let (divisor, actual_khz) = clock_divisor(self.max_speed_khz(), speed_khz)
+            .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+        let [low, high] = divisor.to_le_bytes();
+        self.device
+            .write_all(&[0x86, low, high])
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
+
+        log::debug!(
+            "FTDI clock divisor {}, {} kHz requested, {} kHz set",
+            divisor,
+            speed_khz,
+            actual_khz
+        );
+
+        Ok(actual_khz)
+    }
+
+    fn write_gpio(&mut self) -> io::Result<()> {
+        let [output_low, output_high] = self.output.to_le_bytes();
+        let [direction_low, direction_high] = self.direction.to_le_bytes();
+
+        self.device.write_all(&[
+            0x80,
+            output_low,
+            direction_low,
+            0x82,
+            output_high,
+            direction_high,
+        ])
+    }
+
+    /// Assert or deassert one of the additional signals of the layout.
+    fn set_signal(&mut self, signal: Signal, asserted: bool) -> io::Result<()> {
+        signal.apply(&mut self.output, &mut self.direction, asserted);
+        self.write_gpio()
+    }
+
     fn read_response(&mut self, size: usize) -> io::Result<Vec<u8>> {
         let timeout = Duration::from_millis(10);
         let mut result = Vec::new();
@@ -235,93 +348,16 @@ impl JtagAdapter {
         Ok(r)
     }
 
-    fn scan(&mut self) -> io::Result<Vec<JtagChainItem>> {
-        let max_device_count = 8;
-
-        self.reset()?;
-
-        let cmd = vec![0xff; max_device_count * 4];
-        let r = self.transfer_dr(&cmd, cmd.len() * 8)?;
-        let mut targets = vec![];
-        for i in 0..max_device_count {
-            let idcode = u32::from_le_bytes(r[i * 4..(i + 1) * 4].try_into().unwrap());
-            if idcode != 0xffffffff {
-                log::debug!("tap found: {:08x}", idcode);
-                let target = JtagChainItem { idcode, irlen: 0 };
-                targets.push(target);
-            } else {
-                break;
-            }
-        }
-
-        self.reset()?;
-        let cmd = vec![0xff; max_device_count];
-        let mut r = self.transfer_ir(&cmd, cmd.len() * 8)?;
-
-        let mut ir = 0;
-        let mut irbits = 0;
-        for (i, target) in targets.iter_mut().enumerate() {
-            if (!r.is_empty()) && irbits < 8 {
-                let byte = r[0];
-                r.remove(0);
-                ir |= (byte as u32) << irbits;
-                irbits += 8;
-            }
-            if ir & 0b11 == 0b01 {
-                ir &= !1;
-                let irlen = ir.trailing_zeros();
-                ir >>= irlen;
-                irbits -= irlen;
-                log::debug!("tap {} irlen: {}", i, irlen);
-                target.irlen = irlen as usize;
-            } else {
-                log::debug!("invalid irlen for tap {}", i);
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid IR sequence during the chain scan",
-                ));
-            }
-        }
-
-        Ok(targets)
-    }
-
-    pub fn select_target(&mut self, idcode: u32) -> io::Result<()> {
-        let taps = self.scan()?;
-
-        let mut found = false;
-        let mut params = ChainParams {
-            irpre: 0,
-            irpost: 0,
-            drpre: 0,
-            drpost: 0,
-            irlen: 0,
-        };
-        for tap in taps {
-            if tap.idcode == idcode {
-                params.irlen = tap.irlen;
-                found = true;
-            } else if found {
-                params.irpost += tap.irlen;
-                params.drpost += 1;
-            } else {
-                params.irpre += tap.irlen;
-                params.drpre += 1;
-            }
-        }
-
-        if found {
-            log::debug!("Target chain params: {:?}", params);
-            self.chain_params = Some(params);
-            Ok(())
-        } else {
-            Err(io::Error::new(io::ErrorKind::NotFound, "target not found"))
-        }
+    /// Use the TAP at `index` of `chain` for all following transfers.
+    pub fn select_target(&mut self, chain: &[JtagChainItem], index: usize) {
+        let params = ChainParams::new(chain, index);
+        log::debug!("Target chain params: {:?}", params);
+        self.chain_params = Some(params);
     }
 
     fn get_chain_params(&self) -> io::Result<ChainParams> {
         match &self.chain_params {
-            Some(params) => Ok(params.clone()),
+            Some(params) => Ok(*params),
             None => Err(io::Error::new(
                 io::ErrorKind::Other,
                 "target is not selected",
@@ -336,8 +372,7 @@ impl JtagAdapter {
         len_bits: usize,
     ) -> io::Result<Vec<u8>> {
         let params = self.get_chain_params()?;
-        let max_address = (1 << params.irlen) - 1;
-        if address > max_address {
+        if !params.is_valid_instruction(address) {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "invalid register address",
@@ -345,92 +380,163 @@ impl JtagAdapter {
         }
 
         // Write IR register
-        let irbits = params.irpre + params.irlen + params.irpost;
-        assert!(irbits <= 32);
-        let mut ir: u32 = (1 << params.irpre) - 1;
-        ir |= address << params.irpre;
-        ir |= ((1 << params.irpost) - 1) << (params.irpre + params.irlen);
-        self.shift_ir(&ir.to_le_bytes(), irbits)?;
-
-        let drbits = params.drpre + len_bits + params.drpost;
-        let request = if let Some(data_slice) = data {
-            let data = BitSlice::<Lsb0, u8>::from_slice(data_slice).ok_or(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "could not create bitslice",
-            ))?;
-            let mut data = BitVec::<Lsb0, u8>::from_bitslice(&data);
-            data.truncate(len_bits);
-
-            let mut buf = BitVec::<Lsb0, u8>::new();
-            buf.resize(params.drpre, false);
-            buf.append(&mut data);
-            buf.resize(buf.len() + params.drpost, false);
+        let ir = params.ir_bits(address);
+        self.shift_ir(&bits_to_vec(&ir), ir.len())?;
 
-            buf.into_vec()
-        } else {
-            vec![0; (drbits + 7) / 8]
+        let request = match data {
+            Some(data) => bytes_to_bits(data, len_bits),
+            None => vec![false; len_bits],
         };
-        let reply = self.transfer_dr(&request, drbits)?;
+        let request = params.dr_bits(&request);
+        let reply = self.transfer_dr(&bits_to_vec(&request), request.len())?;
 
         // Process the reply
-        let mut reply = BitVec::<Lsb0, u8>::from_vec(reply);
-        if params.drpre > 0 {
-            reply = reply.split_off(params.drpre);
-        }
-        reply.truncate(len_bits);
-        let reply = reply.into_vec();
+        let reply = bytes_to_bits(&reply, request.len());
+        let reply = bits_to_vec(params.dr_result(&reply, len_bits));
 
         Ok(reply)
     }
 }
 
+impl RawJtagIo for JtagAdapter {
+    fn reset_chain(&mut self) -> Result<(), DebugProbeError> {
+        self.reset()
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))
+    }
+
+    fn shift_ir_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        let tdo = self
+            .transfer_ir(&bits_to_vec(tdi), tdi.len())
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
+        Ok(bytes_to_bits(&tdo, tdi.len()))
+    }
+
+    fn shift_dr_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        let tdo = self
+            .transfer_dr(&bits_to_vec(tdi), tdi.len())
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
+        Ok(bytes_to_bits(&tdo, tdi.len()))
+    }
+}
+
 #[derive(Debug)]
 pub struct FtdiProbe {
     adapter: Mutex<JtagAdapter>,
+    protocol: WireProtocol,
     speed_khz: u32,
     idle_cycles: u8,
+    /// IDCODE of the selected TAP.
+    idcode: Option<u32>,
+    /// The configured scan chain, if it isn't detected automatically.
+    scan_chain_config: Option<ScanChain>,
+    /// The TAPs found when attaching.
+    scan_chain: Vec<JtagChainItem>,
 }
 
-impl DebugProbe for FtdiProbe {
-    fn new_from_selector(
+impl FtdiProbe {
+    /// Open an FTDI based probe using the given pin layout.
+    ///
+    /// This is needed for adapters which can't be identified by their USB IDs.
+    pub fn open_with_layout(
         selector: impl Into<DebugProbeSelector>,
-    ) -> Result<Box<Self>, DebugProbeError>
-    where
-        Self: Sized,
-    {
+        layout: &'static FtdiLayout,
+    ) -> Result<Box<Self>, DebugProbeError> {
         let selector = selector.into();
 
-        // Only open FTDI probes
-        if selector.vendor_id != 0x0403 {
-            return Err(DebugProbeError::ProbeCouldNotBeCreated(
-                ProbeCreationError::NotFound,
-            ));
-        }
-
-        let adapter = JtagAdapter::open(selector.vendor_id, selector.product_id)
+        let adapter = JtagAdapter::open(&selector, layout)
             .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
 
         let probe = FtdiProbe {
             adapter: Mutex::new(adapter),
-            speed_khz: 0,
+            protocol: WireProtocol::Jtag,
+            speed_khz: DEFAULT_SPEED_KHZ,
             idle_cycles: 0,
+            idcode: None,
+            scan_chain_config: None,
+            scan_chain: Vec::new(),
         };
         log::debug!("opened probe: {:?}", probe);
         Ok(Box::new(probe))
     }
 
+    /// Drive the nSRST pin of the layout.
+    fn set_reset(&mut self, asserted: bool) -> Result<(), DebugProbeError> {
+        let adapter = self.adapter.get_mut().unwrap();
+
+        adapter
+            .init()
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
+
+        let nsrst = adapter
+            .layout
+            .nsrst
+            .ok_or(DebugProbeError::InterfaceNotAvailable("nSRST"))?;
+
+        adapter
+            .set_signal(nsrst, asserted)
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))
+    }
+}
+
+impl DebugProbe for FtdiProbe {
+    fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        let selector = selector.into();
+
+        // Only open probes with a known layout
+        let product = read_product_string(&selector);
+        let layout =
+            FtdiLayout::detect(selector.vendor_id, selector.product_id, product.as_deref()).ok_or(
+                DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound),
+            )?;
+
+        Self::open_with_layout(selector, layout)
+    }
+
     fn get_name(&self) -> &str {
         "FTDI"
     }
 
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        let adapter = self.adapter.get_mut().unwrap();
+
+        let mut protocols = vec![WireProtocol::Jtag];
+        if adapter.layout.swd {
+            protocols.push(WireProtocol::Swd);
+        }
+
+        Ok(ProbeCapabilities {
+            firmware_version: None,
+            protocols,
+            max_speed_khz: Some(adapter.max_speed_khz()),
+            swo_uart: false,
+            swo_manchester: false,
+            reset_control: adapter.layout.nsrst.is_some(),
+            target_voltage: false,
+        })
+    }
+
     fn speed(&self) -> u32 {
         self.speed_khz
     }
 
     fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
-        self.speed_khz = speed_khz;
-        // TODO
-        Ok(speed_khz)
+        let adapter = self.adapter.get_mut().unwrap();
+
+        // The speed is applied when attaching, unless the adapter is already set up.
+        self.speed_khz = if adapter.initialized {
+            adapter.set_speed(speed_khz)?
+        } else {
+            clock_divisor(adapter.max_speed_khz(), speed_khz)
+                .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+            speed_khz
+        };
+
+        Ok(self.speed_khz)
     }
 
     fn attach(&mut self) -> Result<(), DebugProbeError> {
@@ -438,74 +544,117 @@ impl DebugProbe for FtdiProbe {
         let adapter = self.adapter.get_mut().unwrap();
 
         adapter
-            .attach()
+            .init()
             .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
 
-        let taps = adapter
-            .scan()
-            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
-        if taps.is_empty() {
-            log::warn!("no JTAG taps detected");
-            return Err(DebugProbeError::TargetNotFound);
+        self.speed_khz = adapter.set_speed(self.speed_khz)?;
+
+        if self.protocol == WireProtocol::Swd {
+            return adapter.swd_init();
         }
-        if taps.len() == 1 {
+
+        if let Some(ntrst) = adapter.layout.ntrst {
             adapter
-                .select_target(taps[0].idcode)
+                .set_signal(ntrst, true)
+                .and_then(|_| {
+                    thread::sleep(TAP_RESET_DURATION);
+                    adapter.set_signal(ntrst, false)
+                })
                 .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
-        } else {
-            let known_idcodes = [
-                0x1000563d, // GD32VF103
-            ];
-            let idcode = taps
-                .iter()
-                .map(|tap| tap.idcode)
-                .find(|idcode| known_idcodes.iter().any(|v| v == idcode));
-            if let Some(idcode) = idcode {
-                adapter
-                    .select_target(idcode)
-                    .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
-            } else {
-                return Err(DebugProbeError::TargetNotFound);
-            }
         }
+
+        let chain = scan_chain::scan(adapter, self.scan_chain_config.as_ref())?;
+        let index = scan_chain::select_target_tap(&chain, self.scan_chain_config.as_ref())?;
+
+        adapter.select_target(&chain, index);
+        self.idcode = chain[index].idcode;
+        self.scan_chain = chain;
         Ok(())
     }
 
     fn detach(&mut self) -> Result<(), DebugProbeError> {
+        let adapter = self.adapter.get_mut().unwrap();
+
+        if let (true, Some(led)) = (adapter.initialized, adapter.layout.led) {
+            adapter
+                .set_signal(led, false)
+                .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
+        }
+
         Ok(())
     }
 
     fn target_reset(&mut self) -> Result<(), DebugProbeError> {
-        log::error!("FTDI target_reset");
-        unimplemented!()
+        self.target_reset_assert()?;
+        thread::sleep(RESET_DURATION);
+        self.target_reset_deassert()
     }
 
     fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
-        log::error!("FTDI target_reset_assert");
-        unimplemented!()
+        self.set_reset(true)
     }
 
     fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
-        log::error!("FTDI target_reset_deassert");
-        unimplemented!()
+        self.set_reset(false)
     }
 
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
-        if protocol != WireProtocol::Jtag {
-            Err(DebugProbeError::UnsupportedProtocol(protocol))
-        } else {
-            Ok(())
+        let adapter = self.adapter.get_mut().unwrap();
+
+        match protocol {
+            WireProtocol::Jtag => {}
+            WireProtocol::Swd if adapter.layout.swd => {}
+            _ => return Err(DebugProbeError::UnsupportedProtocol(protocol)),
         }
+
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        self.scan_chain_config = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        Ok(&self.scan_chain)
     }
 
     fn get_riscv_interface(
         self: Box<Self>,
     ) -> Result<Option<RiscvCommunicationInterface>, DebugProbeError> {
+        if self.protocol != WireProtocol::Jtag {
+            return Ok(None);
+        }
+
         Ok(Some(RiscvCommunicationInterface::new(self)?))
     }
 
+    fn get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
+        let interface = ArmCommunicationInterface::new(self, false)?;
+
+        Ok(Some(Box::new(interface)))
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        match self.protocol {
+            WireProtocol::Swd => true,
+            WireProtocol::Jtag => self.idcode.map(jtag::is_arm_idcode).unwrap_or(false),
+        }
+    }
+
     fn has_riscv_interface(&self) -> bool {
-        true
+        self.protocol == WireProtocol::Jtag
+    }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        Some(self as _)
     }
 }
 
@@ -557,6 +706,42 @@ impl JTAGAccess for FtdiProbe {
     }
 }
 
+impl DAPAccess for FtdiProbe {
+    fn read_register(&mut self, port: PortType, addr: u16) -> Result<u32, DebugProbeError> {
+        match self.protocol {
+            WireProtocol::Swd => self.adapter.get_mut().unwrap().swd_read(port, addr),
+            WireProtocol::Jtag => jtag::read_register(self, port, addr),
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        match self.protocol {
+            WireProtocol::Swd => self.adapter.get_mut().unwrap().swd_write(port, addr, value),
+            WireProtocol::Jtag => jtag::write_register(self, port, addr, value),
+        }
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        if self.protocol != WireProtocol::Swd {
+            return Err(DebugProbeError::NotImplemented("SWJ sequences using JTAG"));
+        }
+
+        self.adapter
+            .get_mut()
+            .unwrap()
+            .swd_sequence(bit_len as usize, bits)
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
 impl AsRef<dyn DebugProbe> for FtdiProbe {
     fn as_ref(&self) -> &(dyn DebugProbe + 'static) {
         self
@@ -569,9 +754,36 @@ impl AsMut<dyn DebugProbe> for FtdiProbe {
     }
 }
 
+/// Read the product string of the first device matching the selector.
+fn read_product_string(selector: &DebugProbeSelector) -> Option<String> {
+    let context = rusb::Context::new().ok()?;
+
+    context.devices().ok()?.iter().find_map(|device| {
+        let d_desc = device.device_descriptor().ok()?;
+        if d_desc.vendor_id() != selector.vendor_id || d_desc.product_id() != selector.product_id {
+            return None;
+        }
+
+        let handle = device.open().ok()?;
+
+        if let Some(serial_number) = &selector.serial_number {
+            let sn_str = handle.read_serial_number_string_ascii(&d_desc).ok()?;
+            if sn_str != *serial_number {
+                return None;
+            }
+        }
+
+        handle.read_product_string_ascii(&d_desc).ok()
+    })
+}
+
 fn get_device_info(device: &rusb::Device<rusb::Context>) -> Option<DebugProbeInfo> {
     let d_desc = device.device_descriptor().ok()?;
-    if d_desc.vendor_id() != 0x0403 || d_desc.product_id() != 0x6010 {
+    let usb_ids = (d_desc.vendor_id(), d_desc.product_id());
+    if !FtdiLayout::all()
+        .iter()
+        .any(|layout| layout.usb_ids.contains(&usb_ids))
+    {
         return None;
     }
 
@@ -598,3 +810,30 @@ pub(crate) fn list_ftdi_devices() -> Vec<DebugProbeInfo> {
         Err(_) => vec![],
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::clock_divisor;
+
+    #[test]
+    fn clock_divisor_high_speed() {
+        assert_eq!(clock_divisor(30_000, 30_000), Some((0, 30_000)));
+        assert_eq!(clock_divisor(30_000, 1_000), Some((29, 1_000)));
+        assert_eq!(clock_divisor(30_000, 4_000), Some((7, 3_750)));
+        assert_eq!(clock_divisor(30_000, 100_000), Some((0, 30_000)));
+        assert_eq!(clock_divisor(30_000, u32::MAX), Some((0, 30_000)));
+    }
+
+    #[test]
+    fn clock_divisor_slowest() {
+        assert_eq!(clock_divisor(6_000, 1), Some((5_999, 1)));
+        assert_eq!(clock_divisor(6_000, 1_000), Some((5, 1_000)));
+        assert_eq!(clock_divisor(0x1_0000, 1), Some((0xffff, 1)));
+    }
+
+    #[test]
+    fn clock_divisor_unsupported() {
+        assert_eq!(clock_divisor(6_000, 0), None);
+        assert_eq!(clock_divisor(0x1_0001, 1), None);
+    }
+}
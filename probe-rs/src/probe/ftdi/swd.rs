@@ -0,0 +1,266 @@
+//! SWD using the MPSSE.
+//!
+//! SWDIO is connected directly to TDO, and to TDI through a resistor of about 470 Ω.
+//! TDI drives SWDIO while the probe is transmitting, and is overridden by the target
+//! while it is transmitting. TCK is used as SWCLK.
+
+use super::JtagAdapter;
+use crate::architecture::arm::{
+    dp::{Abort, RdBuff},
+    DapError, PortType, Register,
+};
+use crate::DebugProbeError;
+use std::io::{self, Write};
+
+/// Number of times a transaction is repeated after a WAIT response.
+const MAX_WAIT_RETRIES: usize = 10;
+
+/// Acknowledge values, transmitted LSB first.
+const SWD_ACK_OK: u8 = 0b001;
+const SWD_ACK_WAIT: u8 = 0b010;
+const SWD_ACK_FAULT: u8 = 0b100;
+
+/// Number of idle cycles after each transaction, with SWDIO low.
+const IDLE_BITS: usize = 8;
+
+/// MPSSE commands using the SWD clocking: LSB first, data written and read on the falling edge.
+const MPSSE_SWD_WRITE_BYTES: u8 = 0x19;
+const MPSSE_SWD_WRITE_BITS: u8 = 0x1b;
+const MPSSE_SWD_TRANSFER_BYTES: u8 = 0x3d;
+const MPSSE_SWD_TRANSFER_BITS: u8 = 0x3f;
+
+/// Build the request byte of a transaction.
+fn encode_request(port: PortType, address: u16, read: bool) -> u8 {
+    let ap = match port {
+        PortType::DebugPort => 0,
+        PortType::AccessPort(_) => 1,
+    };
+    let a2 = ((address >> 2) & 1) as u8;
+    let a3 = ((address >> 3) & 1) as u8;
+    let parity = (ap + read as u8 + a2 + a3) & 1;
+
+    // Start, APnDP, RnW, A[2:3], parity, stop and park.
+    1 | ap << 1 | (read as u8) << 2 | a2 << 3 | a3 << 4 | parity << 5 | 1 << 7
+}
+
+fn parity(value: u32) -> u64 {
+    (value.count_ones() & 1) as u64
+}
+
+fn swd_error(error: io::Error) -> DebugProbeError {
+    DebugProbeError::ProbeSpecific(Box::new(error))
+}
+
+impl JtagAdapter {
+    /// Clock out the lowest `bits` bits of `data`, LSB first.
+    fn clock_out(&mut self, data: u64, bits: usize) -> io::Result<()> {
+        assert!(bits > 0 && bits <= 64);
+
+        let bytes = data.to_le_bytes();
+        let full_bytes = bits / 8;
+        let remaining_bits = bits % 8;
+
+        let mut command = vec![];
+
+        if full_bytes > 0 {
+            let n = (full_bytes - 1) as u16;
+            command.push(MPSSE_SWD_WRITE_BYTES);
+            command.extend_from_slice(&n.to_le_bytes());
+            command.extend_from_slice(&bytes[..full_bytes]);
+        }
+
+        if remaining_bits > 0 {
+            command.extend_from_slice(&[
+                MPSSE_SWD_WRITE_BITS,
+                (remaining_bits - 1) as u8,
+                bytes[full_bytes],
+            ]);
+        }
+
+        self.device.write_all(&command)
+    }
+
+    /// Clock out the lowest `bits` bits of `data`, and return the bits read at the same time.
+    fn clock_in(&mut self, data: u64, bits: usize) -> io::Result<u64> {
+        assert!(bits > 0 && bits <= 64);
+
+        let bytes = data.to_le_bytes();
+        let full_bytes = bits / 8;
+        let remaining_bits = bits % 8;
+
+        let mut command = vec![];
+
+        if full_bytes > 0 {
+            let n = (full_bytes - 1) as u16;
+            command.push(MPSSE_SWD_TRANSFER_BYTES);
+            command.extend_from_slice(&n.to_le_bytes());
+            command.extend_from_slice(&bytes[..full_bytes]);
+        }
+
+        if remaining_bits > 0 {
+            command.extend_from_slice(&[
+                MPSSE_SWD_TRANSFER_BITS,
+                (remaining_bits - 1) as u8,
+                bytes[full_bytes],
+            ]);
+        }
+
+        self.device.write_all(&command)?;
+
+        let expected = full_bytes + (remaining_bits > 0) as usize;
+        let mut reply = self.read_response(expected)?;
+
+        // Bits are shifted in from the MSB, so a partial byte has to be realigned.
+        if remaining_bits > 0 {
+            reply[full_bytes] >>= 8 - remaining_bits;
+        }
+
+        let mut result = [0u8; 8];
+        result[..reply.len()].copy_from_slice(&reply);
+
+        Ok(u64::from_le_bytes(result))
+    }
+
+    /// Switch the target from JTAG to SWD, and perform a line reset.
+    pub(super) fn swd_init(&mut self) -> Result<(), DebugProbeError> {
+        // At least 50 cycles with SWDIO high, the JTAG-to-SWD sequence, another
+        // line reset, and some idle cycles.
+        self.clock_out(u64::max_value(), 56).map_err(swd_error)?;
+        self.clock_out(0xe79e, 16).map_err(swd_error)?;
+        self.clock_out(u64::max_value(), 56).map_err(swd_error)?;
+        self.clock_out(0, IDLE_BITS).map_err(swd_error)?;
+
+        Ok(())
+    }
+
+    /// Clock out a sequence of bits, e.g. to switch the debug ports to SWD.
+    pub(super) fn swd_sequence(&mut self, bits: usize, data: u64) -> Result<(), DebugProbeError> {
+        self.clock_out(data, bits).map_err(swd_error)
+    }
+
+    /// Send the request, and return the acknowledge of the target.
+    fn swd_request(&mut self, port: PortType, address: u16, read: bool) -> io::Result<u8> {
+        let request = encode_request(port, address, read) as u64;
+
+        // SWDIO is kept high during the turnaround and the acknowledge.
+        let response = self.clock_in(request | 0xf00, 12)?;
+
+        Ok(((response >> 9) & 0b111) as u8)
+    }
+
+    /// Finish a transaction which was not acknowledged with OK.
+    fn swd_abort_transfer(&mut self, ack: u8) -> Result<(), DebugProbeError> {
+        self.clock_out(0, 1 + IDLE_BITS).map_err(swd_error)?;
+
+        match ack {
+            SWD_ACK_WAIT => Ok(()),
+            SWD_ACK_FAULT => Err(DapError::FaultResponse.into()),
+            other => {
+                log::debug!("Invalid SWD acknowledge {:#05b}", other);
+                Err(DapError::NoAcknowledge.into())
+            }
+        }
+    }
+
+    fn swd_transfer(
+        &mut self,
+        port: PortType,
+        address: u16,
+        value: Option<u32>,
+    ) -> Result<u32, DebugProbeError> {
+        for retry in 0..MAX_WAIT_RETRIES {
+            let ack = self
+                .swd_request(port, address, value.is_none())
+                .map_err(swd_error)?;
+
+            if ack != SWD_ACK_OK {
+                self.swd_abort_transfer(ack)?;
+
+                log::debug!(
+                    "SWD WAIT, retries remaining {}.",
+                    MAX_WAIT_RETRIES - retry - 1
+                );
+                continue;
+            }
+
+            return match value {
+                Some(value) => {
+                    // Turnaround, data, parity and idle cycles.
+                    let data = (value as u64) << 1 | parity(value) << 33;
+                    self.clock_out(data, 1 + 33 + IDLE_BITS)
+                        .map_err(swd_error)?;
+
+                    Ok(0)
+                }
+                None => {
+                    // Data, parity and turnaround.
+                    let response = self.clock_in(u64::max_value(), 34).map_err(swd_error)?;
+                    self.clock_out(0, IDLE_BITS).map_err(swd_error)?;
+
+                    let data = response as u32;
+                    if (response >> 32) & 1 != parity(data) {
+                        return Err(DapError::IncorrectParity.into());
+                    }
+
+                    Ok(data)
+                }
+            };
+        }
+
+        Err(DapError::WaitResponse.into())
+    }
+
+    /// Read a DP or AP register.
+    pub(super) fn swd_read(
+        &mut self,
+        port: PortType,
+        address: u16,
+    ) -> Result<u32, DebugProbeError> {
+        match port {
+            PortType::DebugPort => self.swd_transfer(port, address, None),
+            PortType::AccessPort(_) => {
+                // AP reads are posted, the result is returned by the next read.
+                self.swd_transfer(port, address, None)?;
+                self.swd_transfer(PortType::DebugPort, RdBuff::ADDRESS as u16, None)
+            }
+        }
+    }
+
+    /// Write a DP or AP register.
+    pub(super) fn swd_write(
+        &mut self,
+        port: PortType,
+        address: u16,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        self.swd_transfer(port, address, Some(value))?;
+
+        // The ABORT register is written without waiting for pending transactions.
+        if port == PortType::DebugPort && address == Abort::ADDRESS as u16 {
+            return Ok(());
+        }
+
+        // Wait until the write has been completed.
+        self.swd_transfer(PortType::DebugPort, RdBuff::ADDRESS as u16, None)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::encode_request;
+    use crate::architecture::arm::PortType;
+
+    #[test]
+    fn request_byte() {
+        // Read DPIDR
+        assert_eq!(encode_request(PortType::DebugPort, 0x0, true), 0xa5);
+        // Write SELECT
+        assert_eq!(encode_request(PortType::DebugPort, 0x8, false), 0xb1);
+        // Read AP register 0xC
+        assert_eq!(encode_request(PortType::AccessPort(0), 0xc, true), 0x9f);
+        // Write AP register 0x4
+        assert_eq!(encode_request(PortType::AccessPort(0), 0x4, false), 0x8b);
+    }
+}
@@ -3,7 +3,7 @@
 use jaylink::{CommunicationSpeed, Interface, JayLink};
 use thiserror::Error;
 
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::iter;
 use std::sync::Mutex;
 
@@ -13,15 +13,17 @@ use crate::{
         arm::{
             communication_interface::ArmProbeInterface,
             dp::Abort,
-            dp::{Ctrl, RdBuff},
+            dp::{jtag, Ctrl, RdBuff},
             swo::SwoConfig,
             ArmCommunicationInterface, SwoAccess,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
+    config::ScanChain,
     probe::{
+        scan_chain::{self, bits_to_vec, bytes_to_bits, ChainParams, JtagChainItem, RawJtagIo},
         DAPAccess, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, JTAGAccess,
-        WireProtocol,
+        ProbeCapabilities, WireProtocol,
     },
     DebugProbeSelector, Error as ProbeRsError,
 };
@@ -43,7 +45,20 @@ pub(crate) struct JLink {
     /// Protocols supported by the connected J-Link probe.
     supported_protocols: Vec<WireProtocol>,
 
-    current_ir_reg: u32,
+    /// The currently selected JTAG instruction, if known.
+    current_ir_reg: Option<u32>,
+
+    /// Position of the target TAP in the JTAG scan chain, determined when attaching using JTAG.
+    chain_params: Option<ChainParams>,
+
+    /// IDCODE of the JTAG target, read when attaching using JTAG.
+    jtag_idcode: Option<u32>,
+
+    /// The configured JTAG scan chain, if it isn't detected automatically.
+    scan_chain_config: Option<ScanChain>,
+
+    /// The TAPs found when attaching using JTAG.
+    scan_chain: Vec<JtagChainItem>,
 
     speed_khz: u32,
 }
@@ -98,223 +113,93 @@ impl JLink {
         }
     }
 
-    fn read_dr(&mut self, register_bits: usize) -> Result<Vec<u8>, DebugProbeError> {
-        log::debug!("Read {} bits from DR", register_bits);
-
-        let tms_enter_shift = [true, false, false];
-
-        // Last bit of data is shifted out when we exi the SHIFT-DR State
-        let tms_shift_out_value = iter::repeat(false).take(register_bits - 1);
-
-        let tms_enter_idle = [true, true, false];
-
-        let mut tms = Vec::with_capacity(register_bits + 7);
-
-        tms.extend_from_slice(&tms_enter_shift);
-        tms.extend(tms_shift_out_value);
-        tms.extend_from_slice(&tms_enter_idle);
-
-        let tdi = iter::repeat(false).take(tms.len() + self.idle_cycles() as usize);
-
-        // We have to stay in the idle cycle a bit
-        tms.extend(iter::repeat(false).take(self.idle_cycles() as usize));
+    /// Select the instruction register `address`, if it isn't already selected.
+    fn select_ir(&mut self, address: u32) -> Result<(), DebugProbeError> {
+        let params = self.chain_params.ok_or(DebugProbeError::NotAttached)?;
 
-        let jlink = self.handle.get_mut().unwrap();
-        let mut response = jlink.jtag_io(tms, tdi)?;
+        if !params.is_valid_instruction(address) {
+            return Err(DebugProbeError::NotImplemented(
+                "JTAG register address does not fit into the instruction register",
+            ));
+        }
 
-        log::trace!("Response: {:?}", response);
+        if self.current_ir_reg != Some(address) {
+            // Write IR register, the other TAPs in the chain are put in BYPASS.
+            self.shift_chain(true, &params.ir_bits(address), 0)?;
+            self.current_ir_reg = Some(address);
+        }
 
-        let _remainder = response.split_off(tms_enter_shift.len());
+        Ok(())
+    }
 
-        let mut remaining_bits = register_bits;
+    /// Shift `data` into the data register `address` of the target TAP.
+    ///
+    /// Returns the previous content of the data register.
+    fn transfer_dr(&mut self, address: u32, data: &[bool]) -> Result<Vec<u8>, DebugProbeError> {
+        self.select_ir(address)?;
 
-        let mut result = Vec::new();
+        let params = self.chain_params.ok_or(DebugProbeError::NotAttached)?;
 
-        while remaining_bits >= 8 {
-            let byte = bits_to_byte(response.split_off(8)) as u8;
-            result.push(byte);
-            remaining_bits -= 8;
-        }
+        // We have to stay in the idle state a bit
+        let idle_cycles = self.idle_cycles() as usize;
+        let response = self.shift_chain(false, &params.dr_bits(data), idle_cycles)?;
 
-        // Handle leftover bytes
-        if remaining_bits > 0 {
-            result.push(bits_to_byte(response.split_off(remaining_bits)) as u8);
-        }
+        let result = bits_to_vec(params.dr_result(&response, data.len()));
 
-        log::debug!("Read from DR: {:?}", result);
+        log::trace!("DR {:#x}: {:?}", address, result);
 
         Ok(result)
     }
 
-    /// Write IR register with the specified data. The
-    /// IR register might have an odd length, so the dta
-    /// will be truncated to `len` bits. If data has less
-    /// than `len` bits, an error will be returned.
-    fn write_ir(&mut self, data: &[u8], len: usize) -> Result<(), DebugProbeError> {
-        log::debug!("Write IR: {:?}, len={}", data, len);
-
-        // Check the bit length, enough data has to be
-        // available
-        if data.len() * 8 < len {
-            todo!("Proper error for incorrect length");
-        }
-
-        // At least one bit has to be sent
-        if len < 1 {
-            todo!("Proper error for incorrect length");
+    /// Shift `tdi` through the IR or DR chain, and stay in the Run-Test/Idle
+    /// state for `idle_cycles` cycles afterwards.
+    ///
+    /// Returns the bits shifted out of the chain.
+    fn shift_chain(
+        &mut self,
+        ir: bool,
+        tdi: &[bool],
+        idle_cycles: usize,
+    ) -> Result<Vec<bool>, DebugProbeError> {
+        if tdi.is_empty() {
+            return Ok(Vec::new());
         }
 
-        let tms_enter_ir_shift = [true, true, false, false];
+        let tms_enter_shift: &[bool] = if ir {
+            &[true, true, false, false]
+        } else {
+            &[true, false, false]
+        };
 
-        // The last bit will be transmitted when exiting the shift state,
-        // so we need to stay in the shift stay for one period less than
+        // The last bit is transmitted when exiting the shift state,
+        // so we need to stay in the shift state for one period less than
         // we have bits to transmit
-        let tms_data = iter::repeat(false).take(len - 1);
+        let tms_data = iter::repeat(false).take(tdi.len() - 1);
 
         let tms_enter_idle = [true, true, false];
 
-        let mut tms = Vec::with_capacity(tms_enter_ir_shift.len() + len + tms_enter_ir_shift.len());
+        let mut tms = Vec::with_capacity(tms_enter_shift.len() + tdi.len() + 2 + idle_cycles);
 
-        tms.extend_from_slice(&tms_enter_ir_shift);
+        tms.extend_from_slice(tms_enter_shift);
         tms.extend(tms_data);
         tms.extend_from_slice(&tms_enter_idle);
+        tms.extend(iter::repeat(false).take(idle_cycles));
 
-        let tdi_enter_ir_shift = [false, false, false, false];
-
-        // This is one less than the enter idle for tms, because
-        // the last bit is transmitted when exiting the IR shift state
-        let tdi_enter_idle = [false, false];
-
-        let mut tdi = Vec::with_capacity(tdi_enter_ir_shift.len() + tdi_enter_idle.len() + len);
-
-        tdi.extend_from_slice(&tdi_enter_ir_shift);
-
-        let num_bytes = len / 8;
-
-        let num_bits = len - (num_bytes * 8);
-
-        for bytes in &data[..num_bytes] {
-            let mut byte = *bytes;
-
-            for _ in 0..8 {
-                tdi.push(byte & 1 == 1);
-
-                byte >>= 1;
-            }
-        }
-
-        if num_bits > 0 {
-            let mut remaining_byte = data[num_bytes];
-
-            for _ in 0..num_bits {
-                tdi.push(remaining_byte & 1 == 1);
-                remaining_byte >>= 1;
-            }
-        }
-
-        tdi.extend_from_slice(&tdi_enter_idle);
+        let mut tdi_bits = vec![false; tms_enter_shift.len()];
+        tdi_bits.extend_from_slice(tdi);
+        tdi_bits.resize(tms.len(), false);
 
         log::trace!("tms: {:?}", tms);
-        log::trace!("tdi: {:?}", tdi);
+        log::trace!("tdi: {:?}", tdi_bits);
 
         let jlink = self.handle.get_mut().unwrap();
-        let response = jlink.jtag_io(tms, tdi)?;
+        let response: Vec<bool> = jlink.jtag_io(tms, tdi_bits)?.collect();
 
         log::trace!("Response: {:?}", response);
 
-        if len >= 8 {
-            return Err(DebugProbeError::NotImplemented(
-                "Not yet implemented for IR registers larger than 8 bit",
-            ));
-        }
-
-        self.current_ir_reg = data[0] as u32;
+        let start = tms_enter_shift.len();
 
-        // Maybe we could return the previous state of the IR register here...
-
-        Ok(())
-    }
-
-    fn write_dr(&mut self, data: &[u8], register_bits: usize) -> Result<Vec<u8>, DebugProbeError> {
-        log::debug!("Write DR: {:?}, len={}", data, register_bits);
-
-        let tms_enter_shift = [true, false, false];
-
-        // Last bit of data is shifted out when we exi the SHIFT-DR State
-        let tms_shift_out_value = iter::repeat(false).take(register_bits - 1);
-
-        let tms_enter_idle = [true, true, false];
-
-        let mut tms = Vec::with_capacity(register_bits + 7);
-
-        tms.extend_from_slice(&tms_enter_shift);
-        tms.extend(tms_shift_out_value);
-        tms.extend_from_slice(&tms_enter_idle);
-
-        let tdi_enter_shift = [false, false, false];
-
-        let tdi_enter_idle = [false, false];
-
-        // TODO: TDI data
-        let mut tdi =
-            Vec::with_capacity(tdi_enter_shift.len() + tdi_enter_idle.len() + register_bits);
-
-        tdi.extend_from_slice(&tdi_enter_shift);
-
-        let num_bytes = register_bits / 8;
-
-        let num_bits = register_bits - (num_bytes * 8);
-
-        for bytes in &data[..num_bytes] {
-            let mut byte = *bytes;
-
-            for _ in 0..8 {
-                tdi.push(byte & 1 == 1);
-
-                byte >>= 1;
-            }
-        }
-
-        if num_bits > 0 {
-            let mut remaining_byte = data[num_bytes];
-
-            for _ in 0..num_bits {
-                tdi.push(remaining_byte & 1 == 1);
-                remaining_byte >>= 1;
-            }
-        }
-
-        tdi.extend_from_slice(&tdi_enter_idle);
-
-        // We need to stay in the idle cycle a bit
-        tms.extend(iter::repeat(false).take(self.idle_cycles() as usize));
-        tdi.extend(iter::repeat(false).take(self.idle_cycles() as usize));
-
-        let jlink = self.handle.get_mut().unwrap();
-        let mut response = jlink.jtag_io(tms, tdi)?;
-
-        log::trace!("Response: {:?}", response);
-
-        let _remainder = response.split_off(tms_enter_shift.len());
-
-        let mut remaining_bits = register_bits;
-
-        let mut result = Vec::new();
-
-        while remaining_bits >= 8 {
-            let byte = bits_to_byte(response.split_off(8)) as u8;
-            result.push(byte);
-            remaining_bits -= 8;
-        }
-
-        // Handle leftover bytes
-        if remaining_bits > 0 {
-            result.push(bits_to_byte(response.split_off(remaining_bits)) as u8);
-        }
-
-        log::trace!("result: {:?}", result);
-
-        Ok(result)
+        Ok(response[start..start + tdi.len()].to_vec())
     }
 
     /// Try to perform a SWD line reset, followed by a read of the DPIDR register.
@@ -457,7 +342,11 @@ impl DebugProbe for JLink {
             supported_protocols,
             jtag_idle_cycles: 0,
             protocol: None,
-            current_ir_reg: 1,
+            current_ir_reg: None,
+            chain_params: None,
+            jtag_idcode: None,
+            scan_chain_config: None,
+            scan_chain: Vec::new(),
             speed_khz: 0,
         }))
     }
@@ -480,6 +369,37 @@ impl DebugProbe for JLink {
         "J-Link"
     }
 
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        let jlink = self.handle.get_mut().unwrap();
+        let capabilities = jlink.read_capabilities()?;
+
+        let max_speed_khz = if capabilities.contains(jaylink::Capabilities::SPEED_INFO) {
+            let speeds = jlink.read_speeds()?;
+            Some(speeds.base_freq() / u32::from(speeds.min_div()) / 1000)
+        } else {
+            None
+        };
+
+        Ok(ProbeCapabilities {
+            firmware_version: Some(jlink.read_firmware_version()?),
+            protocols: self.supported_protocols.clone(),
+            max_speed_khz,
+            swo_uart: capabilities.contains(jaylink::Capabilities::SWO),
+            swo_manchester: false,
+            reset_control: true,
+            target_voltage: true,
+        })
+    }
+
+    fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        let jlink = self.handle.get_mut().unwrap();
+
+        // The voltage is reported in mV.
+        let voltage = jlink.read_target_voltage()?;
+
+        Ok(Some(f32::from(voltage) / 1000.0))
+    }
+
     fn speed(&self) -> u32 {
         self.speed_khz
     }
@@ -537,6 +457,8 @@ impl DebugProbe for JLink {
 
         log::debug!("Attaching with protocol '{}'", actual_protocol);
 
+        self.protocol = Some(actual_protocol);
+
         // Get reference to JayLink instance
         let jlink: &mut JayLink = self.handle.get_mut().unwrap();
         let capabilities = jlink.read_capabilities()?;
@@ -570,21 +492,16 @@ impl DebugProbe for JLink {
                 log::debug!("Resetting JTAG chain using trst");
                 jlink.reset_trst()?;
 
-                log::debug!("Resetting JTAG chain by setting tms high for 32 bits");
-
-                // Reset JTAG chain (5 times TMS high), and enter idle state afterwards
-                let tms = vec![true, true, true, true, true, false];
-                let tdi = iter::repeat(false).take(6);
+                let config = self.scan_chain_config.clone();
 
-                let response: Vec<_> = jlink.jtag_io(tms, tdi)?.collect();
+                let chain = scan_chain::scan(self, config.as_ref())?;
+                let index = scan_chain::select_target_tap(&chain, config.as_ref())?;
 
-                log::debug!("Response to reset: {:?}", response);
+                log::debug!("Using JTAG TAP {}", index);
 
-                // try to read the idcode
-                let idcode_bytes = self.read_dr(32)?;
-                let idcode = u32::from_le_bytes((&idcode_bytes[..]).try_into().unwrap());
-
-                log::debug!("IDCODE: {:#010x}", idcode);
+                self.chain_params = Some(ChainParams::new(&chain, index));
+                self.jtag_idcode = chain[index].idcode;
+                self.scan_chain = chain;
             }
             WireProtocol::Swd => {
                 // Construct the JTAG to SWD sequence.
@@ -645,6 +562,15 @@ impl DebugProbe for JLink {
         Ok(())
     }
 
+    fn set_scan_chain(&mut self, scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        self.scan_chain_config = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        Ok(&self.scan_chain)
+    }
+
     fn get_riscv_interface(
         self: Box<Self>,
     ) -> Result<Option<RiscvCommunicationInterface>, DebugProbeError> {
@@ -666,43 +592,73 @@ impl DebugProbe for JLink {
     fn get_arm_interface<'probe>(
         self: Box<Self>,
     ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
-        if self.supported_protocols.contains(&WireProtocol::Swd) {
-            let interface = ArmCommunicationInterface::new(self, true)?;
+        match self.protocol {
+            Some(WireProtocol::Jtag) => {
+                let interface = ArmCommunicationInterface::new(self, false)?;
 
-            Ok(Some(Box::new(interface)))
-        } else {
-            Ok(None)
+                Ok(Some(Box::new(interface)))
+            }
+            _ if self.supported_protocols.contains(&WireProtocol::Swd) => {
+                let interface = ArmCommunicationInterface::new(self, true)?;
+
+                Ok(Some(Box::new(interface)))
+            }
+            _ => Ok(None),
         }
     }
 
     fn has_arm_interface(&self) -> bool {
-        self.supported_protocols.contains(&WireProtocol::Swd)
+        match self.protocol {
+            // Only ARM targets have a JTAG-DP, other targets can't be accessed this way.
+            Some(WireProtocol::Jtag) => self.jtag_idcode.map(jtag::is_arm_idcode).unwrap_or(true),
+            _ => self.supported_protocols.contains(&WireProtocol::Swd),
+        }
     }
 
     fn has_riscv_interface(&self) -> bool {
         self.supported_protocols.contains(&WireProtocol::Jtag)
     }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        Some(self as _)
+    }
 }
 
-impl JTAGAccess for JLink {
-    /// Read the data register
-    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
-        let address_bits = address.to_le_bytes();
+impl RawJtagIo for JLink {
+    fn reset_chain(&mut self) -> Result<(), DebugProbeError> {
+        // Reset JTAG chain (5 times TMS high), and enter idle state afterwards
+        let tms = vec![true, true, true, true, true, false];
+        let tdi = iter::repeat(false).take(6);
 
-        // TODO: This is limited to 5 bit addresses for now
-        if address > 0x1f {
-            return Err(DebugProbeError::NotImplemented(
-                "JTAG Register addresses are fixed to 5 bits",
-            ));
-        }
+        let jlink = self.handle.get_mut().unwrap();
+        let response: Vec<_> = jlink.jtag_io(tms, tdi)?.collect();
 
-        if self.current_ir_reg != address {
-            // Write IR register
-            self.write_ir(&address_bits[..1], 5)?;
-        }
+        log::debug!("Response to reset: {:?}", response);
+
+        self.current_ir_reg = None;
+
+        Ok(())
+    }
 
+    fn shift_ir_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        self.current_ir_reg = None;
+        self.shift_chain(true, tdi, 0)
+    }
+
+    fn shift_dr_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        self.shift_chain(false, tdi, 0)
+    }
+}
+
+impl JTAGAccess for JLink {
+    /// Read the data register
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
         // read DR register
-        self.read_dr(len as usize)
+        self.transfer_dr(address, &vec![false; len as usize])
     }
 
     /// Write the data register
@@ -712,22 +668,8 @@ impl JTAGAccess for JLink {
         data: &[u8],
         len: u32,
     ) -> Result<Vec<u8>, DebugProbeError> {
-        let address_bits = address.to_le_bytes();
-
-        // TODO: This is limited to 5 bit addresses for now
-        if address > 0x1f {
-            return Err(DebugProbeError::NotImplemented(
-                "JTAG Register addresses are fixed to 5 bits",
-            ));
-        }
-
-        if self.current_ir_reg != address {
-            // Write IR register
-            self.write_ir(&address_bits[..1], 5)?;
-        }
-
         // write DR register
-        self.write_dr(data, len as usize)
+        self.transfer_dr(address, &bytes_to_bits(data, len as usize))
     }
 
     fn set_idle_cycles(&mut self, idle_cycles: u8) {
@@ -866,6 +808,10 @@ fn build_swd_transfer(
 
 impl DAPAccess for JLink {
     fn read_register(&mut self, port: PortType, address: u16) -> Result<u32, DebugProbeError> {
+        if self.protocol == Some(WireProtocol::Jtag) {
+            return jtag::read_register(self, port, address);
+        }
+
         // JLink operates on raw SWD bit sequences.
         // So we need to manually assemble the read and write bitsequences.
         // The following code with the comments hopefully explains well enough how it works.
@@ -1004,6 +950,10 @@ impl DAPAccess for JLink {
         address: u16,
         value: u32,
     ) -> Result<(), DebugProbeError> {
+        if self.protocol == Some(WireProtocol::Jtag) {
+            return jtag::write_register(self, port, address, value);
+        }
+
         // JLink operates on raw SWD bit sequences.
         // So we need to manually assemble the read and write bitsequences.
         // The following code with the comments hopefully explains well enough how it works.
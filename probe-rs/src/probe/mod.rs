@@ -2,16 +2,31 @@ pub(crate) mod daplink;
 #[cfg(feature = "ftdi")]
 pub(crate) mod ftdi;
 pub(crate) mod jlink;
+pub(crate) mod recording;
+pub(crate) mod remote;
+pub(crate) mod scan_chain;
+pub(crate) mod simulator;
 pub(crate) mod stlink;
 
 use crate::architecture::{
-    arm::{communication_interface::ArmProbeInterface, DAPAccess, PortType, SwoAccess},
+    arm::{
+        communication_interface::ArmProbeInterface, dp::DpAddress, sequences::DebugSequence,
+        DAPAccess, PortType, SwoAccess,
+    },
     riscv::communication_interface::RiscvCommunicationInterface,
 };
-use crate::config::{RegistryError, TargetSelector};
+use crate::config::{RegistryError, ScanChain, Target, TargetSelector};
+use crate::core::Architecture;
 use crate::error::Error;
 use crate::Session;
 use jlink::list_jlink_devices;
+pub use recording::{
+    RecordedError, Recording, RecordingError, RecordingProbe, ReplayError, ReplayProbe, Request,
+    Response, Transaction,
+};
+pub use remote::{ProbeServer, RemoteError, RemoteProbe};
+pub use scan_chain::{JtagChainItem, ScanChainError};
+pub use simulator::{SimulatedProbe, SimulatedTarget};
 use std::{convert::TryFrom, fmt};
 use thiserror::Error;
 
@@ -107,6 +122,8 @@ pub enum DebugProbeError {
     CommandNotSupportedByProbe,
     #[error("Unable to set hardware breakpoint, all available breakpoint units are in use.")]
     BreakpointUnitsExceeded,
+    #[error("Error in the JTAG scan chain")]
+    ScanChain(#[from] ScanChainError),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -147,6 +164,9 @@ pub enum ProbeCreationError {
 pub struct Probe {
     inner: Box<dyn DebugProbe>,
     attached: bool,
+    /// Set if the scan chain was configured using [Probe::set_scan_chain],
+    /// which takes precedence over the scan chain of the target.
+    scan_chain_configured: bool,
 }
 
 impl Probe {
@@ -154,6 +174,7 @@ impl Probe {
         Self {
             inner: Box::new(probe),
             attached: false,
+            scan_chain_configured: false,
         }
     }
 
@@ -161,6 +182,7 @@ impl Probe {
         Self {
             inner: probe,
             attached: true,
+            scan_chain_configured: false,
         }
     }
 
@@ -168,7 +190,27 @@ impl Probe {
         Probe {
             inner: probe,
             attached: false,
+            scan_chain_configured: false,
+        }
+    }
+
+    /// Record all transactions with the target, and save them to `path` when the session ends.
+    ///
+    /// The recording can be replayed using a [ReplayProbe]. This has to be called before attaching.
+    pub fn record(self, path: impl Into<std::path::PathBuf>) -> Result<Self, DebugProbeError> {
+        if self.attached {
+            return Err(DebugProbeError::Attached);
         }
+
+        Ok(Self {
+            inner: Box::new(RecordingProbe::new(self.inner, path)),
+            ..self
+        })
+    }
+
+    /// Get the probe wrapped by this `Probe`.
+    pub(crate) fn into_inner(self) -> Box<dyn DebugProbe> {
+        self.inner
     }
 
     /// Get a list of all debug probes found.
@@ -191,6 +233,13 @@ impl Probe {
     /// `Probe::list_all()` function to get the information
     /// about all probes available.
     pub fn open(selector: impl Into<DebugProbeSelector> + Clone) -> Result<Self, DebugProbeError> {
+        let selector = selector.into();
+
+        if selector.remote_address.is_some() {
+            let probe = remote::RemoteProbe::new_from_selector(selector)?;
+            return Ok(Probe::from_specific_probe(probe));
+        }
+
         match daplink::DAPLink::new_from_selector(selector.clone()) {
             Ok(link) => return Ok(Probe::from_specific_probe(link)),
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
@@ -218,59 +267,6 @@ impl Probe {
         ))
     }
 
-    // /// Tries to mass erase a locked nRF52 chip, this process may timeout, if it does, the chip
-    // /// might be unlocked or not, it is advised to try again if flashing fails
-    // pub fn nrf_recover(&mut self) -> Result<(), DebugProbeError> {
-    //     let ctrl_port = match get_ap_by_idr(self, |idr| idr == CTRL_AP_IDR) {
-    //         Some(port) => CtrlAP::from(port),
-    //         None => {
-    //             return Err(DebugProbeError::AccessPortError(
-    //                 AccessPortError::CtrlAPNotFound,
-    //             ));
-    //         }
-    //     };
-    //     log::info!("Starting mass erase...");
-    //     let mut erase_reg = ERASEALL::from(1);
-    //     let status_reg = ERASEALLSTATUS::from(0);
-    //     let mut reset_reg = RESET::from(1);
-
-    //     // Reset first
-    //     self.write_ap_register(ctrl_port, reset_reg)?;
-    //     reset_reg.RESET = false;
-    //     self.write_ap_register(ctrl_port, reset_reg)?;
-
-    //     self.write_ap_register(ctrl_port, erase_reg)?;
-
-    //     // Prepare timeout
-    //     let now = Instant::now();
-    //     let status = self.read_ap_register(ctrl_port, status_reg)?;
-    //     log::info!("Erase status: {:?}", status.ERASEALLSTATUS);
-    //     let timeout = loop {
-    //         let status = self.read_ap_register(ctrl_port, status_reg)?;
-    //         if !status.ERASEALLSTATUS {
-    //             break false;
-    //         }
-    //         if now.elapsed().as_secs() >= UNLOCK_TIMEOUT {
-    //             break true;
-    //         }
-    //     };
-    //     reset_reg.RESET = true;
-    //     self.write_ap_register(ctrl_port, reset_reg)?;
-    //     reset_reg.RESET = false;
-    //     self.write_ap_register(ctrl_port, reset_reg)?;
-    //     erase_reg.ERASEALL = false;
-    //     self.write_ap_register(ctrl_port, erase_reg)?;
-    //     if timeout {
-    //         log::error!(
-    //             "    {} Mass erase process timeout, the chip might still be locked.",
-    //             "Error".red().bold()
-    //         );
-    //     } else {
-    //         log::info!("Mass erase completed, chip unlocked");
-    //     }
-    //     Ok(())
-    // }
-
     /// Get human readable name for the probe
     pub fn get_name(&self) -> String {
         self.inner.get_name().to_string()
@@ -282,6 +278,8 @@ impl Probe {
     ///
     /// If this doesn't work, you might want to try `attach_under_reset`
     pub fn attach(mut self, target: impl Into<TargetSelector>) -> Result<Session, Error> {
+        let target = self.configure_scan_chain(target.into())?;
+
         self.inner.attach()?;
         self.attached = true;
 
@@ -303,6 +301,8 @@ impl Probe {
         mut self,
         target: impl Into<TargetSelector>,
     ) -> Result<Session, Error> {
+        let target = self.configure_scan_chain(target.into())?;
+
         log::debug!("Asserting reset");
         self.inner.target_reset_assert()?;
 
@@ -314,6 +314,122 @@ impl Probe {
         Session::new(self, target, AttachMethod::UnderReset)
     }
 
+    /// Unlock a chip which is protected against debug access, using the [DebugSequence] of `target`.
+    ///
+    /// This works on chips which can't be attached to, and usually erases the entire flash.
+    /// Depending on the chip, it has to be reset or power cycled afterwards before
+    /// [Probe::attach] succeeds.
+    pub fn unlock(self, target: impl Into<TargetSelector>) -> Result<Probe, Error> {
+        self.run_debug_sequence(target.into(), |sequence, interface| {
+            sequence.unlock(interface)
+        })
+    }
+
+    /// Erase the entire flash of the chip using the [DebugSequence] of `target`,
+    /// without attaching to a core or using a flash algorithm.
+    pub fn mass_erase(self, target: impl Into<TargetSelector>) -> Result<Probe, Error> {
+        self.run_debug_sequence(target.into(), |sequence, interface| {
+            sequence.mass_erase(interface)
+        })
+    }
+
+    /// Run a sequence of the [DebugSequence] of `target`, using only the ARM debug interface.
+    ///
+    /// The probe is detached afterwards, so that it can be used to attach to the chip.
+    fn run_debug_sequence(
+        mut self,
+        target: TargetSelector,
+        sequence: impl FnOnce(&dyn DebugSequence, &mut dyn ArmProbeInterface) -> Result<(), Error>,
+    ) -> Result<Probe, Error> {
+        let target = match self.configure_scan_chain(target)? {
+            TargetSelector::Specified(target) => target,
+            TargetSelector::Unspecified(name) => crate::config::get_target_by_name(name)?,
+            TargetSelector::Auto => {
+                return Err(Error::Other(anyhow::anyhow!(
+                    "The target has to be specified, locked chips can't be detected automatically"
+                )))
+            }
+        };
+
+        if target.architecture() != Architecture::Arm {
+            return Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"]));
+        }
+
+        self.inner.attach()?;
+        self.attached = true;
+
+        let debug_port = target
+            .debug_ports
+            .first()
+            .copied()
+            .unwrap_or(DpAddress::Default);
+        let mut interface = self
+            .into_arm_interface_with_dp(debug_port)?
+            .ok_or(DebugProbeError::InterfaceNotAvailable("ARM"))?;
+
+        let result = sequence(&*target.debug_sequence, &mut *interface);
+
+        let mut probe = interface.close();
+        probe.detach()?;
+
+        result.map(|()| probe)
+    }
+
+    /// Configure the JTAG scan chain from the description of `target`,
+    /// unless it was already configured using [Probe::set_scan_chain].
+    ///
+    /// Targets specified by name are resolved, so that they are only looked up once.
+    fn configure_scan_chain(&mut self, target: TargetSelector) -> Result<TargetSelector, Error> {
+        let target = match target {
+            TargetSelector::Unspecified(name) => {
+                TargetSelector::Specified(crate::config::get_target_by_name(name)?)
+            }
+            target => target,
+        };
+
+        if let TargetSelector::Specified(Target {
+            scan_chain: Some(scan_chain),
+            ..
+        }) = &target
+        {
+            if !self.scan_chain_configured {
+                match self.inner.set_scan_chain(scan_chain.clone()) {
+                    Ok(()) => (),
+                    Err(DebugProbeError::NotImplemented(_)) => {
+                        log::debug!("The probe does not support JTAG, ignoring the scan chain of the target.")
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        Ok(target)
+    }
+
+    /// Configure the JTAG scan chain.
+    ///
+    /// This takes precedence over the scan chain in the target description,
+    /// and has to be done before attaching.
+    pub fn set_scan_chain(&mut self, scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        if self.attached {
+            return Err(DebugProbeError::Attached);
+        }
+
+        self.inner.set_scan_chain(scan_chain)?;
+        self.scan_chain_configured = true;
+
+        Ok(())
+    }
+
+    /// Get the TAPs found in the JTAG scan chain when attaching.
+    pub fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        if self.attached {
+            self.inner.scan_chain()
+        } else {
+            Err(DebugProbeError::NotAttached)
+        }
+    }
+
     /// Selects the transport protocol to be used by the debug probe.
     pub fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
         if !self.attached {
@@ -354,6 +470,16 @@ impl Probe {
         self.inner.speed()
     }
 
+    /// Query the features supported by the probe.
+    pub fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        self.inner.capabilities()
+    }
+
+    /// Measure the target voltage in Volt, if the probe supports it.
+    pub fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        self.inner.target_voltage()
+    }
+
     /// Check if the probe has an interface to
     /// debug ARM chips.
     pub fn has_arm_interface(&self) -> bool {
@@ -362,12 +488,29 @@ impl Probe {
 
     pub fn into_arm_interface<'probe>(
         self,
+    ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
+        self.into_arm_interface_with_dp(DpAddress::Default)
+    }
+
+    /// Get the interface to debug ARM chips, using the given debug port.
+    ///
+    /// This is required for targets using SWD multi-drop, where
+    /// no debug port responds until it has been selected.
+    pub fn into_arm_interface_with_dp<'probe>(
+        self,
+        dp: DpAddress,
     ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
         if !self.attached {
             // TODO: Return self here
-            Err(DebugProbeError::NotAttached)
-        } else {
-            self.inner.get_arm_interface()
+            return Err(DebugProbeError::NotAttached);
+        }
+
+        match self.inner.get_arm_interface()? {
+            Some(mut interface) => {
+                interface.select_debug_port(dp)?;
+                Ok(Some(interface))
+            }
+            None => Ok(None),
         }
     }
 
@@ -448,6 +591,33 @@ pub trait DebugProbe: Send + Sync + fmt::Debug {
     /// Selects the transport protocol to be used by the debug probe.
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError>;
 
+    /// Configure the JTAG scan chain, which is used instead of the
+    /// automatically detected scan chain when attaching.
+    fn set_scan_chain(&mut self, _scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented(
+            "JTAG scan chain configuration",
+        ))
+    }
+
+    /// Get the TAPs found in the JTAG scan chain when attaching.
+    fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("JTAG scan chain"))
+    }
+
+    /// Query the features supported by the probe.
+    ///
+    /// This can be called before attaching.
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("probe capabilities"))
+    }
+
+    /// Measure the target voltage in Volt.
+    ///
+    /// Returns `None` if the probe can't measure the target voltage.
+    fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        Ok(None)
+    }
+
     /// Check if the proble offers an interface to debug ARM chips.
     fn has_arm_interface(&self) -> bool {
         false
@@ -481,6 +651,22 @@ pub trait DebugProbe: Send + Sync + fmt::Debug {
     fn get_swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
         None
     }
+
+    /// Get direct access to the DP and AP registers, if the probe provides it.
+    ///
+    /// This bypasses [DebugProbe::get_arm_interface], and is used
+    /// by probes wrapping another probe, like the [RecordingProbe].
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        None
+    }
+
+    /// Get direct access to the JTAG registers, if the probe provides it.
+    ///
+    /// This bypasses [DebugProbe::get_riscv_interface], and is used
+    /// by probes wrapping another probe, like the [RecordingProbe].
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -489,6 +675,63 @@ pub enum DebugProbeType {
     FTDI,
     STLink,
     JLink,
+    Remote,
+}
+
+/// The features supported by a probe, see [DebugProbe::capabilities].
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ProbeCapabilities {
+    /// The version of the probe firmware, if it is known.
+    pub firmware_version: Option<String>,
+    /// The wire protocols supported by the probe.
+    pub protocols: Vec<WireProtocol>,
+    /// The maximum speed of the debug protocol in kHz, if it is known.
+    pub max_speed_khz: Option<u32>,
+    /// SWO using UART (NRZ) encoding is supported.
+    pub swo_uart: bool,
+    /// SWO using Manchester encoding is supported.
+    pub swo_manchester: bool,
+    /// The probe can drive the reset pin of the target.
+    pub reset_control: bool,
+    /// The probe can measure the target voltage.
+    pub target_voltage: bool,
+}
+
+impl fmt::Display for ProbeCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let protocols: Vec<_> = self.protocols.iter().map(|p| p.to_string()).collect();
+
+        writeln!(
+            f,
+            "Firmware version: {}",
+            self.firmware_version.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(f, "Protocols: {}", protocols.join(", "))?;
+        match self.max_speed_khz {
+            Some(speed_khz) => writeln!(f, "Maximum speed: {} kHz", speed_khz)?,
+            None => writeln!(f, "Maximum speed: unknown")?,
+        }
+        writeln!(
+            f,
+            "SWO: {}",
+            match (self.swo_uart, self.swo_manchester) {
+                (true, true) => "UART, Manchester",
+                (true, false) => "UART",
+                (false, true) => "Manchester",
+                (false, false) => "not supported",
+            }
+        )?;
+        writeln!(f, "Reset control: {}", yes_no(self.reset_control))?;
+        write!(f, "Target voltage: {}", yes_no(self.target_voltage))
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
 }
 
 #[derive(Clone)]
@@ -546,6 +789,10 @@ pub enum DebugProbeSelectorParseError {
     ParseInt(#[from] std::num::ParseIntError),
     #[error("Please use a string in the form `VID:PID:<Serial>` where Serial is optional.")]
     Format,
+    #[error(
+        "Please use a string in the form `remote://host:port/<Serial>` where Serial is optional."
+    )]
+    RemoteFormat,
 }
 
 /// A struct to describe the way a probe should be selected.
@@ -557,22 +804,47 @@ pub enum DebugProbeSelectorParseError {
 /// use std::convert::TryInto;
 /// let selector: probe_rs::DebugProbeSelector = "1337:1337:SERIAL".try_into().unwrap();
 /// ```
+///
+/// A probe connected to a [ProbeServer] on another computer is selected using
+/// `remote://host:port/SERIAL`, where the serial number is optional.
 #[derive(Debug, Clone)]
 pub struct DebugProbeSelector {
     pub vendor_id: u16,
     pub product_id: u16,
     pub serial_number: Option<String>,
+    /// The address of the [ProbeServer] the probe is connected to.
+    pub remote_address: Option<String>,
 }
 
 impl TryFrom<&str> for DebugProbeSelector {
     type Error = DebugProbeSelectorParseError;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some(remote) = value.strip_prefix("remote://") {
+            let mut split = remote.splitn(2, '/');
+
+            let address = match split.next() {
+                Some(address) if !address.is_empty() => address,
+                _ => return Err(DebugProbeSelectorParseError::RemoteFormat),
+            };
+
+            return Ok(DebugProbeSelector {
+                vendor_id: 0,
+                product_id: 0,
+                serial_number: split
+                    .next()
+                    .filter(|serial| !serial.is_empty())
+                    .map(str::to_owned),
+                remote_address: Some(address.to_owned()),
+            });
+        }
+
         let split = value.split(':').collect::<Vec<_>>();
         let mut selector = if split.len() > 1 {
             DebugProbeSelector {
                 vendor_id: u16::from_str_radix(split[0], 16)?,
                 product_id: u16::from_str_radix(split[1], 16)?,
                 serial_number: None,
+                remote_address: None,
             }
         } else {
             return Err(DebugProbeSelectorParseError::Format);
@@ -606,6 +878,7 @@ impl From<DebugProbeInfo> for DebugProbeSelector {
             vendor_id: selector.vendor_id,
             product_id: selector.product_id,
             serial_number: selector.serial_number,
+            remote_address: None,
         }
     }
 }
@@ -616,6 +889,7 @@ impl From<&DebugProbeInfo> for DebugProbeSelector {
             vendor_id: selector.vendor_id,
             product_id: selector.product_id,
             serial_number: selector.serial_number.clone(),
+            remote_address: None,
         }
     }
 }
@@ -641,19 +915,19 @@ impl DebugProbe for FakeProbe {
     }
 
     fn speed(&self) -> u32 {
-        unimplemented!()
+        1000
     }
 
-    fn set_speed(&mut self, _speed_khz: u32) -> Result<u32, DebugProbeError> {
-        unimplemented!()
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        Ok(speed_khz)
     }
 
     fn attach(&mut self) -> Result<(), DebugProbeError> {
-        unimplemented!()
+        Ok(())
     }
 
     fn select_protocol(&mut self, _protocol: WireProtocol) -> Result<(), DebugProbeError> {
-        unimplemented!()
+        Ok(())
     }
 
     /// Leave debug mode
@@ -0,0 +1,911 @@
+//! Recording and replaying of the transactions between a probe and the target.
+//!
+//! The [RecordingProbe] wraps a real probe, and records all DAP and JTAG register
+//! accesses, together with their results. The recording is saved to a file, which
+//! can be loaded into a [ReplayProbe] later on. The replay probe returns the recorded
+//! results, without the need for any hardware, so higher layers like flashing or ROM
+//! table parsing can be tested using recordings of real sessions.
+
+use super::{
+    DAPAccess, DebugProbe, DebugProbeError, DebugProbeSelector, JTAGAccess, JtagChainItem,
+    ProbeCapabilities, ProbeCreationError, WireProtocol,
+};
+use crate::architecture::{
+    arm::{
+        communication_interface::ArmProbeInterface, ArmCommunicationInterface, DapError, PortType,
+    },
+    riscv::communication_interface::RiscvCommunicationInterface,
+};
+use crate::config::ScanChain;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// A single request sent to the probe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    Attach,
+    TargetReset,
+    TargetResetAssert,
+    TargetResetDeassert,
+    DapRead {
+        port: u16,
+        address: u16,
+    },
+    DapWrite {
+        port: u16,
+        address: u16,
+        value: u32,
+    },
+    DapReadBlock {
+        port: u16,
+        address: u16,
+        len: usize,
+    },
+    DapWriteBlock {
+        port: u16,
+        address: u16,
+        values: Vec<u32>,
+    },
+    DapFlush,
+    SwjSequence {
+        bit_len: u8,
+        bits: u64,
+    },
+    JtagRead {
+        address: u32,
+        len: u32,
+    },
+    JtagWrite {
+        address: u32,
+        data: Vec<u8>,
+        len: u32,
+    },
+}
+
+/// The data returned for a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    None,
+    Value(u32),
+    Values(Vec<u32>),
+    Data(Vec<u8>),
+}
+
+/// An error returned for a request.
+///
+/// Errors which are handled by higher layers keep their type, so they are
+/// replayed unchanged. All other errors are stored as their message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedError {
+    Dap(DapError),
+    Timeout,
+    CommandNotSupportedByProbe,
+    Other(String),
+}
+
+impl From<&DebugProbeError> for RecordedError {
+    fn from(error: &DebugProbeError) -> Self {
+        let dap_error = match error {
+            DebugProbeError::ArchitectureSpecific(e) => e.downcast_ref::<DapError>(),
+            DebugProbeError::Other(e) => e.downcast_ref::<DapError>(),
+            _ => None,
+        };
+
+        match (dap_error, error) {
+            (Some(dap_error), _) => RecordedError::Dap(*dap_error),
+            (None, DebugProbeError::Timeout) => RecordedError::Timeout,
+            (None, DebugProbeError::CommandNotSupportedByProbe) => {
+                RecordedError::CommandNotSupportedByProbe
+            }
+            (None, error) => RecordedError::Other(error_chain(error)),
+        }
+    }
+}
+
+impl From<RecordedError> for DebugProbeError {
+    fn from(error: RecordedError) -> Self {
+        match error {
+            RecordedError::Dap(e) => e.into(),
+            RecordedError::Timeout => DebugProbeError::Timeout,
+            RecordedError::CommandNotSupportedByProbe => {
+                DebugProbeError::CommandNotSupportedByProbe
+            }
+            RecordedError::Other(message) => ReplayError::Recorded(message).into(),
+        }
+    }
+}
+
+/// A request and its result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub request: Request,
+    pub response: Result<Response, RecordedError>,
+}
+
+/// All transactions of a session, together with information about the probe used.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Recording {
+    /// The name of the recorded probe.
+    pub probe_name: String,
+    /// The protocol selected when recording.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<WireProtocol>,
+    pub speed_khz: u32,
+    pub has_arm_interface: bool,
+    pub has_riscv_interface: bool,
+    pub transactions: Vec<Transaction>,
+}
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("Failed to access the recording file")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid recording")]
+    Format(#[from] serde_yaml::Error),
+}
+
+impl Recording {
+    /// Load a recording from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let file = File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Save the recording to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RecordingError> {
+        let file = File::create(path)?;
+        Ok(serde_yaml::to_writer(file, self)?)
+    }
+}
+
+/// An error returned by the [ReplayProbe].
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error(
+        "Transaction {index} does not match the recording, expected {expected:?}, got {actual:?}"
+    )]
+    Mismatch {
+        index: usize,
+        expected: Request,
+        actual: Request,
+    },
+    #[error("The recording ended, but got {actual:?}")]
+    EndOfRecording { actual: Request },
+    #[error("Unexpected response {response:?} for {request:?}")]
+    UnexpectedResponse {
+        request: Request,
+        response: Response,
+    },
+    #[error("{0}")]
+    Recorded(String),
+}
+
+impl From<ReplayError> for DebugProbeError {
+    fn from(error: ReplayError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// Convert an error into a message, including all of its sources,
+/// as the top level error is often not very descriptive.
+pub(crate) fn error_chain(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = error.source();
+    }
+
+    message
+}
+
+/// Convert a result into the form stored in the recording.
+fn record_result<T>(
+    result: &Result<T, DebugProbeError>,
+    response: impl FnOnce(&T) -> Response,
+) -> Result<Response, RecordedError> {
+    result.as_ref().map(response).map_err(RecordedError::from)
+}
+
+/// A probe which records all transactions of the probe it wraps.
+///
+/// The recording is saved when the probe is dropped, i.e. when the session ends.
+#[derive(Debug)]
+pub struct RecordingProbe {
+    inner: Box<dyn DebugProbe>,
+    recording: Recording,
+    path: PathBuf,
+}
+
+impl RecordingProbe {
+    /// Record the transactions of `inner`, and save them to `path`.
+    pub fn new(inner: Box<dyn DebugProbe>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            recording: Recording {
+                probe_name: inner.get_name().to_string(),
+                ..Default::default()
+            },
+            inner,
+            path: path.into(),
+        }
+    }
+
+    fn record(&mut self, request: Request, response: Result<Response, RecordedError>) {
+        self.recording
+            .transactions
+            .push(Transaction { request, response });
+    }
+
+    fn dap(&mut self) -> Result<&mut dyn DAPAccess, DebugProbeError> {
+        self.inner
+            .dap_access_mut()
+            .ok_or(DebugProbeError::InterfaceNotAvailable("DAP access"))
+    }
+
+    fn jtag(&mut self) -> Result<&mut dyn JTAGAccess, DebugProbeError> {
+        self.inner
+            .jtag_access_mut()
+            .ok_or(DebugProbeError::InterfaceNotAvailable("JTAG access"))
+    }
+
+    /// Perform an operation without return value on the inner probe, and record it.
+    fn record_unit(
+        &mut self,
+        request: Request,
+        operation: impl FnOnce(&mut dyn DebugProbe) -> Result<(), DebugProbeError>,
+    ) -> Result<(), DebugProbeError> {
+        let result = operation(self.inner.as_mut());
+        self.record(request, record_result(&result, |_| Response::None));
+        result
+    }
+}
+
+impl Drop for RecordingProbe {
+    fn drop(&mut self) {
+        self.recording.speed_khz = self.inner.speed();
+        self.recording.has_arm_interface = self.inner.has_arm_interface();
+        self.recording.has_riscv_interface = self.inner.has_riscv_interface();
+
+        match self.recording.save(&self.path) {
+            Ok(()) => log::info!(
+                "Saved {} transactions to {}",
+                self.recording.transactions.len(),
+                self.path.display()
+            ),
+            Err(e) => log::error!(
+                "Failed to save the recording to {}: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
+
+impl DebugProbe for RecordingProbe {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::Other("A recording probe has to wrap another probe."),
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        self.inner.get_name()
+    }
+
+    fn speed(&self) -> u32 {
+        self.inner.speed()
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        self.inner.set_speed(speed_khz)
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        self.record_unit(Request::Attach, |probe| probe.attach())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        self.inner.detach()
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.record_unit(Request::TargetReset, |probe| probe.target_reset())
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.record_unit(Request::TargetResetAssert, |probe| {
+            probe.target_reset_assert()
+        })
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.record_unit(Request::TargetResetDeassert, |probe| {
+            probe.target_reset_deassert()
+        })
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.inner.select_protocol(protocol)?;
+        self.recording.protocol = Some(protocol);
+        Ok(())
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        self.inner.set_scan_chain(scan_chain)
+    }
+
+    fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        self.inner.scan_chain()
+    }
+
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        self.inner.capabilities()
+    }
+
+    fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        self.inner.target_voltage()
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        self.inner.has_arm_interface()
+    }
+
+    fn get_arm_interface<'probe>(
+        mut self: Box<Self>,
+    ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
+        self.dap()?;
+
+        let interface = ArmCommunicationInterface::new(self, false)?;
+
+        Ok(Some(Box::new(interface)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.inner.has_riscv_interface()
+    }
+
+    fn get_riscv_interface(
+        mut self: Box<Self>,
+    ) -> Result<Option<RiscvCommunicationInterface>, DebugProbeError> {
+        self.jtag()?;
+
+        Ok(Some(RiscvCommunicationInterface::new(self)?))
+    }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        Some(self as _)
+    }
+}
+
+impl DAPAccess for RecordingProbe {
+    fn read_register(&mut self, port: PortType, addr: u16) -> Result<u32, DebugProbeError> {
+        let result = self.dap()?.read_register(port, addr);
+
+        let request = Request::DapRead {
+            port: port.into(),
+            address: addr,
+        };
+        self.record(request, record_result(&result, |v| Response::Value(*v)));
+
+        result
+    }
+
+    fn read_block(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        values: &mut [u32],
+    ) -> Result<(), DebugProbeError> {
+        let result = self.dap()?.read_block(port, addr, values);
+
+        let request = Request::DapReadBlock {
+            port: port.into(),
+            address: addr,
+            len: values.len(),
+        };
+        let response = record_result(&result, |_| Response::Values(values.to_vec()));
+        self.record(request, response);
+
+        result
+    }
+
+    fn write_register(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        let result = self.dap()?.write_register(port, addr, value);
+
+        let request = Request::DapWrite {
+            port: port.into(),
+            address: addr,
+            value,
+        };
+        self.record(request, record_result(&result, |_| Response::None));
+
+        result
+    }
+
+    fn write_block(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        values: &[u32],
+    ) -> Result<(), DebugProbeError> {
+        let result = self.dap()?.write_block(port, addr, values);
+
+        let request = Request::DapWriteBlock {
+            port: port.into(),
+            address: addr,
+            values: values.to_vec(),
+        };
+        self.record(request, record_result(&result, |_| Response::None));
+
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), DebugProbeError> {
+        let result = self.dap()?.flush();
+        self.record(
+            Request::DapFlush,
+            record_result(&result, |_| Response::None),
+        );
+        result
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        let result = self.dap()?.swj_sequence(bit_len, bits);
+
+        let request = Request::SwjSequence { bit_len, bits };
+        self.record(request, record_result(&result, |_| Response::None));
+
+        result
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl JTAGAccess for RecordingProbe {
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        let result = self.jtag()?.read_register(address, len);
+
+        let request = Request::JtagRead { address, len };
+        self.record(
+            request,
+            record_result(&result, |v| Response::Data(v.clone())),
+        );
+
+        result
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        // This only configures the probe, so it is not recorded.
+        if let Ok(jtag) = self.jtag() {
+            jtag.set_idle_cycles(idle_cycles);
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        let result = self.jtag()?.write_register(address, data, len);
+
+        let request = Request::JtagWrite {
+            address,
+            data: data.to_vec(),
+            len,
+        };
+        self.record(
+            request,
+            record_result(&result, |v| Response::Data(v.clone())),
+        );
+
+        result
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl<'a> AsRef<dyn DebugProbe + 'a> for RecordingProbe {
+    fn as_ref(&self) -> &(dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+impl<'a> AsMut<dyn DebugProbe + 'a> for RecordingProbe {
+    fn as_mut(&mut self) -> &mut (dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+/// A probe which returns the results of a [Recording], instead of accessing a target.
+///
+/// Every request has to match the next transaction of the recording, otherwise
+/// a [ReplayError::Mismatch] is returned, which contains the expected request.
+#[derive(Debug)]
+pub struct ReplayProbe {
+    recording: Recording,
+    /// Index of the next transaction.
+    position: usize,
+    speed_khz: u32,
+}
+
+impl ReplayProbe {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            speed_khz: recording.speed_khz,
+            recording,
+            position: 0,
+        }
+    }
+
+    /// Load a recording from a file, and replay it.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        Ok(Self::new(Recording::load(path)?))
+    }
+
+    /// The number of transactions which have not been replayed yet.
+    ///
+    /// This can be used to check that a test performed all recorded transactions.
+    pub fn remaining(&self) -> usize {
+        self.recording.transactions.len() - self.position
+    }
+
+    fn replay(&mut self, request: Request) -> Result<Response, DebugProbeError> {
+        let transaction = match self.recording.transactions.get(self.position) {
+            Some(transaction) => transaction,
+            None => return Err(ReplayError::EndOfRecording { actual: request }.into()),
+        };
+
+        if transaction.request != request {
+            let error = ReplayError::Mismatch {
+                index: self.position,
+                expected: transaction.request.clone(),
+                actual: request,
+            };
+            log::error!("{}", error);
+            return Err(error.into());
+        }
+
+        self.position += 1;
+
+        transaction.response.clone().map_err(DebugProbeError::from)
+    }
+
+    fn replay_unit(&mut self, request: Request) -> Result<(), DebugProbeError> {
+        match self.replay(request.clone())? {
+            Response::None => Ok(()),
+            response => Err(ReplayError::UnexpectedResponse { request, response }.into()),
+        }
+    }
+
+    fn replay_data(&mut self, request: Request) -> Result<Vec<u8>, DebugProbeError> {
+        match self.replay(request.clone())? {
+            Response::Data(data) => Ok(data),
+            response => Err(ReplayError::UnexpectedResponse { request, response }.into()),
+        }
+    }
+}
+
+impl DebugProbe for ReplayProbe {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::Other("A replay probe has to be created from a recording."),
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.recording.probe_name
+    }
+
+    fn speed(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        self.speed_khz = speed_khz;
+        Ok(speed_khz)
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::Attach)
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::TargetReset)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::TargetResetAssert)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::TargetResetDeassert)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        match self.recording.protocol {
+            Some(recorded) if recorded != protocol => {
+                Err(DebugProbeError::UnsupportedProtocol(protocol))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        self.recording.has_arm_interface
+    }
+
+    fn get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
+        let interface = ArmCommunicationInterface::new(self, false)?;
+
+        Ok(Some(Box::new(interface)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.recording.has_riscv_interface
+    }
+
+    fn get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<Option<RiscvCommunicationInterface>, DebugProbeError> {
+        Ok(Some(RiscvCommunicationInterface::new(self)?))
+    }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        Some(self as _)
+    }
+}
+
+impl DAPAccess for ReplayProbe {
+    fn read_register(&mut self, port: PortType, addr: u16) -> Result<u32, DebugProbeError> {
+        let request = Request::DapRead {
+            port: port.into(),
+            address: addr,
+        };
+
+        match self.replay(request.clone())? {
+            Response::Value(value) => Ok(value),
+            response => Err(ReplayError::UnexpectedResponse { request, response }.into()),
+        }
+    }
+
+    fn read_block(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        values: &mut [u32],
+    ) -> Result<(), DebugProbeError> {
+        let request = Request::DapReadBlock {
+            port: port.into(),
+            address: addr,
+            len: values.len(),
+        };
+
+        match self.replay(request.clone())? {
+            Response::Values(recorded) if recorded.len() == values.len() => {
+                values.copy_from_slice(&recorded);
+                Ok(())
+            }
+            response => Err(ReplayError::UnexpectedResponse { request, response }.into()),
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::DapWrite {
+            port: port.into(),
+            address: addr,
+            value,
+        })
+    }
+
+    fn write_block(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        values: &[u32],
+    ) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::DapWriteBlock {
+            port: port.into(),
+            address: addr,
+            values: values.to_vec(),
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::DapFlush)
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.replay_unit(Request::SwjSequence { bit_len, bits })
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl JTAGAccess for ReplayProbe {
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        self.replay_data(Request::JtagRead { address, len })
+    }
+
+    fn set_idle_cycles(&mut self, _idle_cycles: u8) {}
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        self.replay_data(Request::JtagWrite {
+            address,
+            data: data.to_vec(),
+            len,
+        })
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl<'a> AsRef<dyn DebugProbe + 'a> for ReplayProbe {
+    fn as_ref(&self) -> &(dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+impl<'a> AsMut<dyn DebugProbe + 'a> for ReplayProbe {
+    fn as_mut(&mut self) -> &mut (dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        RecordedError, Recording, RecordingProbe, ReplayProbe, Request, Response, Transaction,
+    };
+    use crate::architecture::arm::{DAPAccess, DapError, PortType};
+    use crate::{DebugProbe, DebugProbeError};
+
+    fn recording() -> Recording {
+        Recording {
+            probe_name: "Test probe".to_string(),
+            protocol: None,
+            speed_khz: 1000,
+            has_arm_interface: true,
+            has_riscv_interface: false,
+            transactions: vec![
+                Transaction {
+                    request: Request::Attach,
+                    response: Ok(Response::None),
+                },
+                Transaction {
+                    request: Request::DapRead {
+                        port: 0xffff,
+                        address: 0x0,
+                    },
+                    response: Ok(Response::Value(0x2ba0_1477)),
+                },
+                Transaction {
+                    request: Request::DapWrite {
+                        port: 0,
+                        address: 0x4,
+                        value: 0x2000_0000,
+                    },
+                    response: Err(RecordedError::Dap(DapError::FaultResponse)),
+                },
+                Transaction {
+                    request: Request::DapRead {
+                        port: 0xffff,
+                        address: 0x4,
+                    },
+                    response: Err(RecordedError::Other("The probe is gone".to_string())),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let mut probe = ReplayProbe::new(recording());
+
+        probe.attach().unwrap();
+        assert_eq!(
+            DAPAccess::read_register(&mut probe, PortType::DebugPort, 0x0).unwrap(),
+            0x2ba0_1477
+        );
+
+        // Errors keep their type.
+        let error =
+            DAPAccess::write_register(&mut probe, PortType::AccessPort(0), 0x4, 0x2000_0000)
+                .unwrap_err();
+        assert!(matches!(
+            error,
+            DebugProbeError::ArchitectureSpecific(e)
+                if matches!(e.downcast_ref::<DapError>(), Some(DapError::FaultResponse))
+        ));
+
+        let error = DAPAccess::read_register(&mut probe, PortType::DebugPort, 0x4).unwrap_err();
+        assert_eq!(
+            super::error_chain(&error),
+            "An error specific to a probe type occured: The probe is gone"
+        );
+
+        assert_eq!(probe.remaining(), 0);
+    }
+
+    #[test]
+    fn replay_mismatch() {
+        let mut probe = ReplayProbe::new(recording());
+
+        probe.attach().unwrap();
+
+        // A different register is read, so the transaction is not consumed.
+        assert!(DAPAccess::read_register(&mut probe, PortType::DebugPort, 0x4).is_err());
+        assert_eq!(probe.remaining(), 3);
+    }
+
+    #[test]
+    fn record_replayed_session() {
+        let path = std::env::temp_dir().join("probe-rs-record-replayed-session.yaml");
+
+        {
+            let replay = Box::new(ReplayProbe::new(recording()));
+            let mut probe = RecordingProbe::new(replay, &path);
+
+            probe.attach().unwrap();
+            DAPAccess::read_register(&mut probe, PortType::DebugPort, 0x0).unwrap();
+            DAPAccess::write_register(&mut probe, PortType::AccessPort(0), 0x4, 0x2000_0000)
+                .unwrap_err();
+        }
+
+        let recorded = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(recorded.transactions[..3], recording().transactions[..3]);
+    }
+}
@@ -0,0 +1,475 @@
+//! The client, which accesses a probe connected to a [ProbeServer](super::ProbeServer).
+
+use super::{
+    read_message, write_message, Command, ProbeDescription, RemoteError, Reply, MAX_BLOCK_LEN,
+    PROTOCOL_VERSION,
+};
+use crate::architecture::{
+    arm::{
+        communication_interface::ArmProbeInterface, ArmCommunicationInterface, PortType, SwoAccess,
+        SwoConfig,
+    },
+    riscv::communication_interface::RiscvCommunicationInterface,
+};
+use crate::config::ScanChain;
+use crate::probe::recording::{Request, Response};
+use crate::probe::{
+    DAPAccess, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, JTAGAccess,
+    JtagChainItem, ProbeCapabilities, ProbeCreationError, WireProtocol,
+};
+use std::io::{BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Maximum number of commands sent without waiting for their replies.
+///
+/// This has to be small enough that the replies fit into the buffers of the
+/// connection, otherwise the server would block while sending the replies.
+const MAX_PENDING_COMMANDS: usize = 256;
+
+/// A connection to a probe server.
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    /// Number of commands, whose replies have not been received yet.
+    pending: usize,
+}
+
+impl Connection {
+    fn connect(address: &str) -> Result<Self, RemoteError> {
+        let stream = TcpStream::connect(address)?;
+
+        // The messages are small, and sent as soon as a reply is needed.
+        stream.set_nodelay(true)?;
+
+        let mut connection = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            pending: 0,
+        };
+
+        match connection.call(&Command::Hello {
+            version: PROTOCOL_VERSION,
+        })? {
+            Reply::Hello { version } if version == PROTOCOL_VERSION => Ok(connection),
+            Reply::Hello { version } => Err(RemoteError::Version {
+                client: PROTOCOL_VERSION,
+                server: version,
+            }),
+            reply => Err(RemoteError::UnexpectedReply(format!("{:?}", reply))),
+        }
+    }
+
+    fn receive(&mut self) -> Result<Result<Reply, String>, RemoteError> {
+        read_message(&mut self.reader)?.ok_or(RemoteError::Disconnected)
+    }
+
+    /// Receive the replies of all pending commands, and return the first error.
+    fn receive_pending(&mut self) -> Result<(), RemoteError> {
+        let mut result = Ok(());
+
+        while self.pending > 0 {
+            self.pending -= 1;
+
+            if let Err(message) = self.receive()? {
+                if result.is_ok() {
+                    result = Err(RemoteError::Remote(message));
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Send a command, without waiting for the reply.
+    fn post(&mut self, command: &Command) -> Result<(), RemoteError> {
+        if self.pending >= MAX_PENDING_COMMANDS {
+            self.sync()?;
+        }
+
+        write_message(&mut self.writer, command)?;
+        self.pending += 1;
+
+        Ok(())
+    }
+
+    /// Wait until all pending commands are completed.
+    fn sync(&mut self) -> Result<(), RemoteError> {
+        self.writer.flush()?;
+        self.receive_pending()
+    }
+
+    /// Send a command, and wait for the reply.
+    ///
+    /// If a pending command failed, its error is returned instead.
+    fn call(&mut self, command: &Command) -> Result<Reply, RemoteError> {
+        write_message(&mut self.writer, command)?;
+        self.writer.flush()?;
+
+        // The reply has to be received in any case, to keep the connection in sync.
+        let pending = self.receive_pending();
+        let reply = self.receive()?;
+        pending?;
+
+        reply.map_err(RemoteError::Remote)
+    }
+}
+
+/// A probe connected to a [ProbeServer](super::ProbeServer) on another computer.
+#[derive(Debug)]
+pub struct RemoteProbe {
+    connection: Connection,
+    description: ProbeDescription,
+    scan_chain: Option<Vec<JtagChainItem>>,
+}
+
+impl RemoteProbe {
+    /// List the probes connected to the server at `address`.
+    pub fn list(address: &str) -> Result<Vec<DebugProbeInfo>, DebugProbeError> {
+        let mut connection = Connection::connect(address)?;
+
+        match connection.call(&Command::List)? {
+            Reply::Probes(probes) => Ok(probes.into_iter().map(DebugProbeInfo::from).collect()),
+            reply => Err(RemoteError::UnexpectedReply(format!("{:?}", reply)).into()),
+        }
+    }
+
+    /// Open the probe with the given serial number on the server at `address`,
+    /// or the first probe if no serial number is given.
+    pub fn connect(address: &str, serial: Option<&str>) -> Result<Self, DebugProbeError> {
+        let mut connection = Connection::connect(address)?;
+
+        let description = match connection.call(&Command::Open {
+            serial: serial.map(str::to_owned),
+        })? {
+            Reply::Opened(description) => description,
+            reply => return Err(RemoteError::UnexpectedReply(format!("{:?}", reply)).into()),
+        };
+
+        log::debug!("Opened remote probe {} at {}", description.name, address);
+
+        Ok(Self {
+            connection,
+            description,
+            scan_chain: None,
+        })
+    }
+
+    fn call(&mut self, command: Command) -> Result<Reply, DebugProbeError> {
+        Ok(self.connection.call(&command)?)
+    }
+
+    fn call_unit(&mut self, command: Command) -> Result<(), DebugProbeError> {
+        self.call(command).map(|_| ())
+    }
+
+    fn transfer(&mut self, request: Request) -> Result<Response, DebugProbeError> {
+        match self.call(Command::Transfer(request))? {
+            Reply::Transfer(response) => Ok(response),
+            reply => Err(RemoteError::UnexpectedReply(format!("{:?}", reply)).into()),
+        }
+    }
+
+    fn transfer_data(&mut self, request: Request) -> Result<Vec<u8>, DebugProbeError> {
+        match self.transfer(request)? {
+            Response::Data(data) => Ok(data),
+            response => Err(RemoteError::UnexpectedReply(format!("{:?}", response)).into()),
+        }
+    }
+
+    /// Send a request, whose result is checked by the next call.
+    fn post(&mut self, request: Request) -> Result<(), DebugProbeError> {
+        Ok(self.connection.post(&Command::Transfer(request))?)
+    }
+}
+
+impl DebugProbe for RemoteProbe {
+    fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        let selector = selector.into();
+
+        match &selector.remote_address {
+            Some(address) => Ok(Box::new(Self::connect(
+                address,
+                selector.serial_number.as_deref(),
+            )?)),
+            None => Err(DebugProbeError::ProbeCouldNotBeCreated(
+                ProbeCreationError::NotFound,
+            )),
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.description.name
+    }
+
+    fn speed(&self) -> u32 {
+        self.description.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        match self.call(Command::SetSpeed(speed_khz))? {
+            Reply::Speed(speed_khz) => {
+                self.description.speed_khz = speed_khz;
+                Ok(speed_khz)
+            }
+            reply => Err(RemoteError::UnexpectedReply(format!("{:?}", reply)).into()),
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        self.transfer(Request::Attach)?;
+
+        // The scan chain is only available for JTAG.
+        self.scan_chain = match self.call(Command::ScanChain)? {
+            Reply::ScanChain(items) => Some(items.into_iter().map(JtagChainItem::from).collect()),
+            _ => None,
+        };
+
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        self.call_unit(Command::Detach)
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.transfer(Request::TargetReset).map(|_| ())
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.transfer(Request::TargetResetAssert).map(|_| ())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.transfer(Request::TargetResetDeassert).map(|_| ())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.call_unit(Command::SelectProtocol(protocol))
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: ScanChain) -> Result<(), DebugProbeError> {
+        match self.call(Command::SetScanChain(scan_chain))? {
+            Reply::NotImplemented => Err(DebugProbeError::NotImplemented(
+                "JTAG scan chain configuration",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn scan_chain(&self) -> Result<&[JtagChainItem], DebugProbeError> {
+        self.scan_chain
+            .as_deref()
+            .ok_or(DebugProbeError::NotImplemented("JTAG scan chain"))
+    }
+
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        match self.call(Command::Capabilities)? {
+            Reply::Capabilities(capabilities) => Ok(capabilities),
+            Reply::NotImplemented => Err(DebugProbeError::NotImplemented("probe capabilities")),
+            reply => Err(RemoteError::UnexpectedReply(format!("{:?}", reply)).into()),
+        }
+    }
+
+    fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        match self.call(Command::TargetVoltage)? {
+            Reply::Voltage(voltage) => Ok(voltage),
+            reply => Err(RemoteError::UnexpectedReply(format!("{:?}", reply)).into()),
+        }
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        self.description.has_arm_interface
+    }
+
+    fn get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
+        let interface = ArmCommunicationInterface::new(self, false)?;
+
+        Ok(Some(Box::new(interface)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.description.has_riscv_interface
+    }
+
+    fn get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<Option<RiscvCommunicationInterface>, DebugProbeError> {
+        Ok(Some(RiscvCommunicationInterface::new(self)?))
+    }
+
+    fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
+        if self.description.has_swo_interface {
+            Some(self as _)
+        } else {
+            None
+        }
+    }
+
+    fn get_swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        if self.description.has_swo_interface {
+            Some(self as _)
+        } else {
+            None
+        }
+    }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+
+    fn jtag_access_mut(&mut self) -> Option<&mut dyn JTAGAccess> {
+        Some(self as _)
+    }
+}
+
+impl DAPAccess for RemoteProbe {
+    fn read_register(&mut self, port: PortType, addr: u16) -> Result<u32, DebugProbeError> {
+        match self.transfer(Request::DapRead {
+            port: port.into(),
+            address: addr,
+        })? {
+            Response::Value(value) => Ok(value),
+            response => Err(RemoteError::UnexpectedReply(format!("{:?}", response)).into()),
+        }
+    }
+
+    fn read_block(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        values: &mut [u32],
+    ) -> Result<(), DebugProbeError> {
+        // The server rejects longer blocks.
+        for chunk in values.chunks_mut(MAX_BLOCK_LEN) {
+            match self.transfer(Request::DapReadBlock {
+                port: port.into(),
+                address: addr,
+                len: chunk.len(),
+            })? {
+                Response::Values(received) if received.len() == chunk.len() => {
+                    chunk.copy_from_slice(&received);
+                }
+                response => {
+                    return Err(RemoteError::UnexpectedReply(format!("{:?}", response)).into())
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_register(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        self.post(Request::DapWrite {
+            port: port.into(),
+            address: addr,
+            value,
+        })
+    }
+
+    fn write_block(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        values: &[u32],
+    ) -> Result<(), DebugProbeError> {
+        for chunk in values.chunks(MAX_BLOCK_LEN) {
+            self.post(Request::DapWriteBlock {
+                port: port.into(),
+                address: addr,
+                values: chunk.to_vec(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), DebugProbeError> {
+        self.transfer(Request::DapFlush).map(|_| ())
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.post(Request::SwjSequence { bit_len, bits })
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl JTAGAccess for RemoteProbe {
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        self.transfer_data(Request::JtagRead { address, len })
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        if let Err(e) = self.connection.post(&Command::SetIdleCycles(idle_cycles)) {
+            log::warn!("Failed to set the JTAG idle cycles: {}", e);
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        self.transfer_data(Request::JtagWrite {
+            address,
+            data: data.to_vec(),
+            len,
+        })
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl SwoAccess for RemoteProbe {
+    fn enable_swo(&mut self, config: &SwoConfig) -> Result<(), crate::Error> {
+        Ok(self.call_unit(Command::EnableSwo(config.into()))?)
+    }
+
+    fn disable_swo(&mut self) -> Result<(), crate::Error> {
+        Ok(self.call_unit(Command::DisableSwo)?)
+    }
+
+    fn read_swo_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, crate::Error> {
+        let timeout_ms = timeout.as_millis() as u64;
+
+        match self.call(Command::ReadSwo { timeout_ms })? {
+            Reply::Transfer(Response::Data(data)) => Ok(data),
+            reply => Err(DebugProbeError::from(RemoteError::UnexpectedReply(format!(
+                "{:?}",
+                reply
+            )))
+            .into()),
+        }
+    }
+}
+
+impl<'a> AsRef<dyn DebugProbe + 'a> for RemoteProbe {
+    fn as_ref(&self) -> &(dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+impl<'a> AsMut<dyn DebugProbe + 'a> for RemoteProbe {
+    fn as_mut(&mut self) -> &mut (dyn DebugProbe + 'a) {
+        self
+    }
+}
@@ -0,0 +1,346 @@
+//! Access to probes connected to another computer.
+//!
+//! A [ProbeServer] makes the probes connected to a computer available over TCP,
+//! and a [RemoteProbe] is used to connect to one of them. The remote probe is
+//! selected using a [DebugProbeSelector](super::DebugProbeSelector) of the
+//! form `remote://host:port/serial`, where the serial number is optional.
+//!
+//! The probe is accessed on the level of DAP and JTAG registers, using the same
+//! [Request]s which are stored in a [Recording](super::Recording). Each message
+//! is a single line of JSON.
+//!
+//! To tolerate the latency of the network, writes are pipelined: the client doesn't
+//! wait for their results, and an error is only reported by the next access which
+//! needs an answer from the probe, e.g. a read or a flush.
+
+mod client;
+mod server;
+
+pub use client::RemoteProbe;
+pub use server::ProbeServer;
+
+use super::{recording::Request, recording::Response, DebugProbeError, DebugProbeInfo};
+use super::{DebugProbeType, JtagChainItem, ProbeCapabilities, WireProtocol};
+use crate::architecture::arm::{SwoConfig, SwoMode};
+use crate::config::ScanChain;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Read, Write};
+use thiserror::Error;
+
+/// Version of the protocol, which has to be the same for the client and the server.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Maximum number of words transferred by a single DAP block request.
+///
+/// Longer blocks are split by the client.
+const MAX_BLOCK_LEN: usize = 0x4000;
+
+/// Maximum length of a single message, which is enough for a block of
+/// [MAX_BLOCK_LEN] words.
+const MAX_MESSAGE_LEN: u64 = 0x4_0000;
+
+/// A command sent from the client to the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Command {
+    Hello { version: u32 },
+    List,
+    Open { serial: Option<String> },
+    SetSpeed(u32),
+    SelectProtocol(WireProtocol),
+    SetScanChain(ScanChain),
+    ScanChain,
+    Detach,
+    Capabilities,
+    TargetVoltage,
+    SetIdleCycles(u8),
+    Transfer(Request),
+    EnableSwo(RemoteSwoConfig),
+    DisableSwo,
+    ReadSwo { timeout_ms: u64 },
+}
+
+/// The reply of the server to a command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Reply {
+    Hello {
+        version: u32,
+    },
+    Probes(Vec<RemoteProbeInfo>),
+    Opened(ProbeDescription),
+    Speed(u32),
+    ScanChain(Vec<RemoteChainItem>),
+    Capabilities(ProbeCapabilities),
+    Voltage(Option<f32>),
+    Transfer(Response),
+    /// The operation is not implemented by the probe.
+    NotImplemented,
+    None,
+}
+
+/// Information about a probe connected to the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RemoteProbeInfo {
+    identifier: String,
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+}
+
+impl From<&DebugProbeInfo> for RemoteProbeInfo {
+    fn from(info: &DebugProbeInfo) -> Self {
+        Self {
+            identifier: info.identifier.clone(),
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            serial_number: info.serial_number.clone(),
+        }
+    }
+}
+
+impl From<RemoteProbeInfo> for DebugProbeInfo {
+    fn from(info: RemoteProbeInfo) -> Self {
+        DebugProbeInfo::new(
+            info.identifier,
+            info.vendor_id,
+            info.product_id,
+            info.serial_number,
+            DebugProbeType::Remote,
+        )
+    }
+}
+
+/// The properties of a probe opened by the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ProbeDescription {
+    name: String,
+    speed_khz: u32,
+    has_arm_interface: bool,
+    has_riscv_interface: bool,
+    has_swo_interface: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RemoteChainItem {
+    idcode: Option<u32>,
+    ir_len: usize,
+}
+
+impl From<&JtagChainItem> for RemoteChainItem {
+    fn from(item: &JtagChainItem) -> Self {
+        Self {
+            idcode: item.idcode,
+            ir_len: item.ir_len,
+        }
+    }
+}
+
+impl From<RemoteChainItem> for JtagChainItem {
+    fn from(item: RemoteChainItem) -> Self {
+        Self {
+            idcode: item.idcode,
+            ir_len: item.ir_len,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct RemoteSwoConfig {
+    manchester: bool,
+    baud: u32,
+    tpiu_clk: u32,
+    continuous_formatting: bool,
+}
+
+impl From<&SwoConfig> for RemoteSwoConfig {
+    fn from(config: &SwoConfig) -> Self {
+        Self {
+            manchester: matches!(config.mode(), SwoMode::Manchester),
+            baud: config.baud(),
+            tpiu_clk: config.tpiu_clk(),
+            continuous_formatting: config.tpiu_continuous_formatting(),
+        }
+    }
+}
+
+impl From<RemoteSwoConfig> for SwoConfig {
+    fn from(config: RemoteSwoConfig) -> Self {
+        let mode = if config.manchester {
+            SwoMode::Manchester
+        } else {
+            SwoMode::UART
+        };
+
+        SwoConfig::new(config.tpiu_clk)
+            .set_baud(config.baud)
+            .set_mode(mode)
+            .set_continuous_formatting(config.continuous_formatting)
+    }
+}
+
+/// An error which occured when using a remote probe.
+#[derive(Debug, Error)]
+pub enum RemoteError {
+    #[error("Failed to communicate with the probe server")]
+    Io(#[from] io::Error),
+    #[error("Invalid message from the probe server")]
+    Format(#[from] serde_json::Error),
+    #[error("The probe server closed the connection")]
+    Disconnected,
+    #[error("The probe server uses protocol version {server}, but version {client} is required")]
+    Version { client: u32, server: u32 },
+    #[error("Unexpected reply {0} from the probe server")]
+    UnexpectedReply(String),
+    #[error("A message exceeds the maximum length of {} bytes", MAX_MESSAGE_LEN)]
+    MessageTooLong,
+    #[error(
+        "A block of {0} words exceeds the maximum length of {} words",
+        MAX_BLOCK_LEN
+    )]
+    BlockTooLong(usize),
+    #[error("{0}")]
+    Remote(String),
+}
+
+impl From<RemoteError> for DebugProbeError {
+    fn from(error: RemoteError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// Write a message as a single line. The writer is not flushed.
+fn write_message(writer: &mut impl Write, message: &impl Serialize) -> Result<(), RemoteError> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/// Read a message, or `None` if the connection was closed.
+///
+/// Messages longer than [MAX_MESSAGE_LEN] are rejected.
+fn read_message<T: for<'de> Deserialize<'de>>(
+    reader: &mut impl BufRead,
+) -> Result<Option<T>, RemoteError> {
+    let mut line = String::new();
+
+    let read = reader.by_ref().take(MAX_MESSAGE_LEN).read_line(&mut line)?;
+
+    if read == 0 {
+        return Ok(None);
+    }
+
+    if !line.ends_with('\n') && read as u64 == MAX_MESSAGE_LEN {
+        return Err(RemoteError::MessageTooLong);
+    }
+
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_message, Command, ProbeServer, RemoteError, RemoteProbe, MAX_MESSAGE_LEN};
+    use crate::architecture::arm::{DAPAccess, PortType};
+    use crate::probe::{recording::error_chain, FakeProbe, ProbeCreationError};
+    use crate::{DebugProbe, DebugProbeError, DebugProbeSelector, DebugProbeType, SimulatedProbe};
+    use std::convert::TryFrom;
+
+    fn start_server(server: ProbeServer) -> String {
+        let address = server.local_addr().unwrap().to_string();
+        std::thread::spawn(move || server.run());
+        address
+    }
+
+    #[test]
+    fn parse_selector() {
+        let selector = DebugProbeSelector::try_from("remote://localhost:1234/SERIAL").unwrap();
+        assert_eq!(selector.remote_address.as_deref(), Some("localhost:1234"));
+        assert_eq!(selector.serial_number.as_deref(), Some("SERIAL"));
+
+        let selector = DebugProbeSelector::try_from("remote://10.0.0.1:1234").unwrap();
+        assert_eq!(selector.remote_address.as_deref(), Some("10.0.0.1:1234"));
+        assert_eq!(selector.serial_number, None);
+
+        assert!(DebugProbeSelector::try_from("remote://").is_err());
+    }
+
+    #[test]
+    fn reject_long_messages() {
+        let mut message = br#"{"Open":{"serial":""#.to_vec();
+        message.resize(MAX_MESSAGE_LEN as usize, b'0');
+        message.extend_from_slice(b"\"}}\n");
+
+        let result = read_message::<Command>(&mut message.as_slice());
+        assert!(matches!(result, Err(RemoteError::MessageTooLong)));
+    }
+
+    #[test]
+    fn list_and_open() {
+        let server = ProbeServer::bind("127.0.0.1:0")
+            .unwrap()
+            .serve_probe(|| Ok(Box::new(FakeProbe)));
+        let address = start_server(server);
+
+        let probes = RemoteProbe::list(&address).unwrap();
+        assert_eq!(probes.len(), 1);
+        assert_eq!(probes[0].probe_type, DebugProbeType::Remote);
+
+        let mut probe = RemoteProbe::connect(&address, None).unwrap();
+        assert_eq!(probe.get_name(), "Mock probe for testing");
+
+        // Errors of the probe are returned by the client.
+        let error = probe.target_reset().unwrap_err();
+        assert_eq!(
+            error_chain(&error),
+            "An error specific to a probe type occured: Command not supported by probe"
+        );
+    }
+
+    #[test]
+    fn pipelined_writes() {
+        let simulated = SimulatedProbe::new();
+        let target = simulated.target();
+
+        let mut simulated = Some(simulated);
+        let server = ProbeServer::bind("127.0.0.1:0")
+            .unwrap()
+            .serve_probe(move || match simulated.take() {
+                Some(probe) => Ok(Box::new(probe)),
+                None => Err(DebugProbeError::ProbeCouldNotBeCreated(
+                    ProbeCreationError::NotFound,
+                )),
+            });
+        let address = start_server(server);
+
+        let mut probe = RemoteProbe::connect(&address, None).unwrap();
+
+        // Power up, and write a block of memory using the MEM-AP.
+        probe
+            .write_register(PortType::DebugPort, 0x4, 0x5000_0000)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0x0, 0x0000_0012)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0x4, 0x2000_0000)
+            .unwrap();
+        probe
+            .write_block(PortType::AccessPort(0), 0xC, &[1, 2, 3, 4])
+            .unwrap();
+
+        // An unaligned access fails, which is only reported by the next read.
+        probe
+            .write_register(PortType::AccessPort(0), 0x4, 0x2000_0002)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0xC, 0)
+            .unwrap();
+        assert!(probe.read_register(PortType::DebugPort, 0x4).is_err());
+
+        let ctrl_stat = probe.read_register(PortType::DebugPort, 0x4).unwrap();
+        assert_ne!(ctrl_stat & (1 << 5), 0);
+
+        let mut data = [0u8; 16];
+        target.read_memory(0x2000_0000, &mut data);
+        assert_eq!(data, [1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0]);
+    }
+}
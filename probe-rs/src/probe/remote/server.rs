@@ -0,0 +1,315 @@
+//! The server, which makes local probes available to [RemoteProbe](super::RemoteProbe)s.
+
+use super::{
+    read_message, write_message, Command, ProbeDescription, RemoteChainItem, RemoteError,
+    RemoteProbeInfo, Reply, MAX_BLOCK_LEN, PROTOCOL_VERSION,
+};
+use crate::probe::recording::{error_chain, Request, Response};
+use crate::probe::{DAPAccess, DebugProbe, DebugProbeError, JTAGAccess, Probe, ProbeCreationError};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type ProbeFactory = dyn FnMut() -> Result<Box<dyn DebugProbe>, DebugProbeError> + Send;
+
+/// The probes made available by the server.
+#[derive(Clone)]
+enum ProbeSource {
+    /// The probes connected to this computer.
+    Local,
+    /// Probes created by a function, e.g. for testing.
+    Custom(Arc<Mutex<Box<ProbeFactory>>>),
+}
+
+impl ProbeSource {
+    fn list(&self) -> Vec<RemoteProbeInfo> {
+        match self {
+            ProbeSource::Local => Probe::list_all()
+                .iter()
+                .map(RemoteProbeInfo::from)
+                .collect(),
+            ProbeSource::Custom(_) => vec![RemoteProbeInfo {
+                identifier: "Custom probe".to_string(),
+                vendor_id: 0,
+                product_id: 0,
+                serial_number: None,
+            }],
+        }
+    }
+
+    fn open(&self, serial: Option<&str>) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
+        match self {
+            ProbeSource::Local => {
+                let probes = Probe::list_all();
+
+                let info = probes
+                    .iter()
+                    .find(|info| serial.is_none() || info.serial_number.as_deref() == serial)
+                    .ok_or(DebugProbeError::ProbeCouldNotBeCreated(
+                        ProbeCreationError::NotFound,
+                    ))?;
+
+                Ok(Probe::open(info)?.into_inner())
+            }
+            ProbeSource::Custom(factory) => {
+                let mut factory = factory.lock().unwrap_or_else(|e| e.into_inner());
+                (*factory)()
+            }
+        }
+    }
+}
+
+/// A server, which makes the probes connected to this computer available over TCP.
+///
+/// Each connection can open a single probe, and the connections are handled in parallel.
+pub struct ProbeServer {
+    listener: TcpListener,
+    probes: ProbeSource,
+}
+
+impl ProbeServer {
+    /// Listen for connections on `address`.
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            probes: ProbeSource::Local,
+        })
+    }
+
+    /// Serve probes created by `factory`, instead of the probes connected to this computer.
+    ///
+    /// The factory is called for every connection which opens a probe. This is mainly
+    /// useful for testing, e.g. using a [SimulatedProbe](crate::SimulatedProbe).
+    pub fn serve_probe(
+        self,
+        factory: impl FnMut() -> Result<Box<dyn DebugProbe>, DebugProbeError> + Send + 'static,
+    ) -> Self {
+        Self {
+            probes: ProbeSource::Custom(Arc::new(Mutex::new(Box::new(factory)))),
+            ..self
+        }
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Handle connections until an error occurs.
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let peer = stream.peer_addr()?;
+            let probes = self.probes.clone();
+
+            log::info!("Connection from {}", peer);
+
+            std::thread::spawn(move || match serve(stream, probes) {
+                Ok(()) => log::info!("Connection from {} closed", peer),
+                Err(e) => log::warn!("Connection from {} failed: {}", peer, e),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ProbeServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ProbeServer")
+            .field("listener", &self.listener)
+            .finish()
+    }
+}
+
+/// Handle the commands of a single connection.
+fn serve(stream: TcpStream, probes: ProbeSource) -> Result<(), RemoteError> {
+    stream.set_nodelay(true)?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut probe = None;
+
+    while let Some(command) = read_message::<Command>(&mut reader)? {
+        log::trace!("Received {:?}", command);
+
+        let reply = handle(&probes, &mut probe, command);
+        write_message(&mut writer, &reply)?;
+
+        // Pipelined commands are answered together, once all received commands are handled.
+        if reader.buffer().is_empty() {
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn opened(
+    probe: &mut Option<Box<dyn DebugProbe>>,
+) -> Result<&mut (dyn DebugProbe + 'static), String> {
+    probe
+        .as_deref_mut()
+        .ok_or_else(|| "No probe has been opened".to_string())
+}
+
+fn handle(
+    probes: &ProbeSource,
+    probe: &mut Option<Box<dyn DebugProbe>>,
+    command: Command,
+) -> Result<Reply, String> {
+    let message = |e: crate::Error| error_chain(&e);
+
+    match command {
+        Command::Hello { .. } => Ok(Reply::Hello {
+            version: PROTOCOL_VERSION,
+        }),
+        Command::List => Ok(Reply::Probes(probes.list())),
+        Command::Open { serial } => {
+            let opened = probes
+                .open(serial.as_deref())
+                .map_err(|e| error_chain(&e))?;
+
+            let description = ProbeDescription {
+                name: opened.get_name().to_string(),
+                speed_khz: opened.speed(),
+                has_arm_interface: opened.has_arm_interface(),
+                has_riscv_interface: opened.has_riscv_interface(),
+                has_swo_interface: opened.get_swo_interface().is_some(),
+            };
+
+            log::info!("Opened probe {}", description.name);
+            *probe = Some(opened);
+
+            Ok(Reply::Opened(description))
+        }
+        Command::SetSpeed(speed_khz) => opened(probe)?
+            .set_speed(speed_khz)
+            .map(Reply::Speed)
+            .map_err(|e| error_chain(&e)),
+        Command::SelectProtocol(protocol) => opened(probe)?
+            .select_protocol(protocol)
+            .map(|_| Reply::None)
+            .map_err(|e| error_chain(&e)),
+        Command::SetScanChain(scan_chain) => match opened(probe)?.set_scan_chain(scan_chain) {
+            Ok(()) => Ok(Reply::None),
+            Err(DebugProbeError::NotImplemented(_)) => Ok(Reply::NotImplemented),
+            Err(e) => Err(error_chain(&e)),
+        },
+        Command::ScanChain => match opened(probe)?.scan_chain() {
+            Ok(items) => Ok(Reply::ScanChain(
+                items.iter().map(RemoteChainItem::from).collect(),
+            )),
+            // The scan chain is only available when using JTAG.
+            Err(_) => Ok(Reply::NotImplemented),
+        },
+        Command::Detach => opened(probe)?
+            .detach()
+            .map(|_| Reply::None)
+            .map_err(|e| error_chain(&e)),
+        Command::Capabilities => match opened(probe)?.capabilities() {
+            Ok(capabilities) => Ok(Reply::Capabilities(capabilities)),
+            Err(DebugProbeError::NotImplemented(_)) => Ok(Reply::NotImplemented),
+            Err(e) => Err(error_chain(&e)),
+        },
+        Command::TargetVoltage => opened(probe)?
+            .target_voltage()
+            .map(Reply::Voltage)
+            .map_err(|e| error_chain(&e)),
+        Command::SetIdleCycles(idle_cycles) => {
+            if let Some(jtag) = opened(probe)?.jtag_access_mut() {
+                jtag.set_idle_cycles(idle_cycles);
+            }
+
+            Ok(Reply::None)
+        }
+        Command::Transfer(request) => execute(opened(probe)?, request)
+            .map(Reply::Transfer)
+            .map_err(|e| error_chain(&e)),
+        Command::EnableSwo(config) => swo(opened(probe)?)?
+            .enable_swo(&config.into())
+            .map(|_| Reply::None)
+            .map_err(message),
+        Command::DisableSwo => swo(opened(probe)?)?
+            .disable_swo()
+            .map(|_| Reply::None)
+            .map_err(message),
+        Command::ReadSwo { timeout_ms } => swo(opened(probe)?)?
+            .read_swo_timeout(Duration::from_millis(timeout_ms))
+            .map(|data| Reply::Transfer(Response::Data(data)))
+            .map_err(message),
+    }
+}
+
+fn swo(probe: &mut dyn DebugProbe) -> Result<&mut dyn crate::architecture::arm::SwoAccess, String> {
+    probe
+        .get_swo_interface_mut()
+        .ok_or_else(|| "The probe does not support SWO".to_string())
+}
+
+fn dap(probe: &mut dyn DebugProbe) -> Result<&mut dyn DAPAccess, DebugProbeError> {
+    probe
+        .dap_access_mut()
+        .ok_or(DebugProbeError::InterfaceNotAvailable("DAP access"))
+}
+
+fn jtag(probe: &mut dyn DebugProbe) -> Result<&mut dyn JTAGAccess, DebugProbeError> {
+    probe
+        .jtag_access_mut()
+        .ok_or(DebugProbeError::InterfaceNotAvailable("JTAG access"))
+}
+
+/// Perform a request on the probe.
+fn execute(probe: &mut dyn DebugProbe, request: Request) -> Result<Response, DebugProbeError> {
+    let response = match request {
+        Request::Attach => probe.attach().map(|_| Response::None)?,
+        Request::TargetReset => probe.target_reset().map(|_| Response::None)?,
+        Request::TargetResetAssert => probe.target_reset_assert().map(|_| Response::None)?,
+        Request::TargetResetDeassert => probe.target_reset_deassert().map(|_| Response::None)?,
+        Request::DapRead { port, address } => {
+            Response::Value(dap(probe)?.read_register(port.into(), address)?)
+        }
+        Request::DapWrite {
+            port,
+            address,
+            value,
+        } => {
+            dap(probe)?.write_register(port.into(), address, value)?;
+            Response::None
+        }
+        Request::DapReadBlock { port, address, len } => {
+            if len > MAX_BLOCK_LEN {
+                return Err(RemoteError::BlockTooLong(len).into());
+            }
+
+            let mut values = vec![0; len];
+            dap(probe)?.read_block(port.into(), address, &mut values)?;
+            Response::Values(values)
+        }
+        Request::DapWriteBlock {
+            port,
+            address,
+            values,
+        } => {
+            dap(probe)?.write_block(port.into(), address, &values)?;
+            Response::None
+        }
+        Request::DapFlush => {
+            dap(probe)?.flush()?;
+            Response::None
+        }
+        Request::SwjSequence { bit_len, bits } => {
+            dap(probe)?.swj_sequence(bit_len, bits)?;
+            Response::None
+        }
+        Request::JtagRead { address, len } => {
+            Response::Data(jtag(probe)?.read_register(address, len)?)
+        }
+        Request::JtagWrite { address, data, len } => {
+            Response::Data(jtag(probe)?.write_register(address, &data, len)?)
+        }
+    };
+
+    Ok(response)
+}
@@ -0,0 +1,526 @@
+//! Detection and configuration of the JTAG scan chain.
+//!
+//! This is shared by all probes which perform raw JTAG scans. The chain is scanned
+//! when attaching, and combined with the scan chain configured by the user or in
+//! the target description. Afterwards, all TAPs except the target TAP are put in
+//! BYPASS, using the [ChainParams] of the target TAP.
+
+use super::DebugProbeError;
+use crate::architecture::arm::dp::jtag::is_arm_idcode;
+use crate::config::ScanChain;
+use thiserror::Error;
+
+/// Maximum number of TAPs which are detected when scanning the chain.
+const MAX_TAPS: usize = 8;
+
+/// Maximum combined length of all instruction registers in the chain.
+const MAX_IR_CHAIN_LENGTH: usize = 64;
+
+/// IDCODEs of supported debug modules, which are not an ARM JTAG-DP.
+const KNOWN_IDCODES: [u32; 1] = [
+    0x1000_563d, // GD32VF103
+];
+
+/// A TAP found when scanning the JTAG chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JtagChainItem {
+    /// The IDCODE of the TAP, or `None` if the TAP has no IDCODE register.
+    pub idcode: Option<u32>,
+    /// The length of the instruction register.
+    pub ir_len: usize,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ScanChainError {
+    #[error("No TAPs were found in the JTAG scan chain")]
+    Empty,
+    #[error("The scan chain contains {found} TAPs, but {configured} TAPs are configured")]
+    TapCountMismatch { found: usize, configured: usize },
+    #[error("TAP {index} has the IDCODE {found:#010x}, but {expected:#010x} is configured")]
+    IdcodeMismatch {
+        index: usize,
+        found: u32,
+        expected: u32,
+    },
+    #[error("TAP {index} has no IDCODE, but {expected:#010x} is configured")]
+    MissingIdcode { index: usize, expected: u32 },
+    #[error("The IR length of TAP {0} could not be detected, it has to be configured")]
+    InvalidIrCapture(usize),
+    #[error("The IR chain is {found} bits long, but the IR lengths add up to {configured} bits")]
+    IrLengthMismatch { found: usize, configured: usize },
+    #[error("The target TAP {0} is not part of the scan chain")]
+    InvalidTargetTap(usize),
+    #[error("No TAP with a supported debug module was found in the scan chain")]
+    NoSupportedTap,
+}
+
+/// Raw access to the JTAG scan chain.
+pub(crate) trait RawJtagIo {
+    /// Move all TAPs to Test-Logic-Reset, and then to Run-Test/Idle.
+    fn reset_chain(&mut self) -> Result<(), DebugProbeError>;
+
+    /// Shift `tdi` through the IR chain, starting and ending in Run-Test/Idle.
+    ///
+    /// Returns the bits shifted out of the chain.
+    fn shift_ir_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError>;
+
+    /// Shift `tdi` through the DR chain, starting and ending in Run-Test/Idle.
+    ///
+    /// Returns the bits shifted out of the chain.
+    fn shift_dr_chain(&mut self, tdi: &[bool]) -> Result<Vec<bool>, DebugProbeError>;
+}
+
+/// Scan the JTAG chain, and determine the IDCODE and IR length of all TAPs.
+///
+/// Afterwards, all TAPs are in BYPASS.
+pub(crate) fn scan<P: RawJtagIo + ?Sized>(
+    probe: &mut P,
+    config: Option<&ScanChain>,
+) -> Result<Vec<JtagChainItem>, DebugProbeError> {
+    // After a reset, the DR of each TAP is either the IDCODE register,
+    // or the BYPASS register if the TAP has no IDCODE.
+    probe.reset_chain()?;
+    let tdo = probe.shift_dr_chain(&[true; MAX_TAPS * 32])?;
+    let idcodes = parse_idcodes(&tdo);
+
+    // The IR chain is first filled with zeros, then ones are shifted in. The
+    // values captured by the TAPs are shifted out first, followed by the zeros.
+    probe.reset_chain()?;
+    let mut tdi = vec![false; MAX_IR_CHAIN_LENGTH];
+    tdi.extend(std::iter::repeat(true).take(MAX_IR_CHAIN_LENGTH));
+
+    let tdo = probe.shift_ir_chain(&tdi)?;
+
+    let ir_chain_length = tdo
+        .get(MAX_IR_CHAIN_LENGTH..)
+        .and_then(|bits| bits.iter().position(|bit| *bit))
+        .ok_or(ScanChainError::Empty)?;
+
+    let chain = build_chain(&idcodes, &tdo[..ir_chain_length], config)?;
+
+    for (index, tap) in chain.iter().enumerate() {
+        match tap.idcode {
+            Some(idcode) => log::debug!(
+                "JTAG TAP {}: IDCODE {:#010x}, IR length {}",
+                index,
+                idcode,
+                tap.ir_len
+            ),
+            None => log::debug!("JTAG TAP {}: no IDCODE, IR length {}", index, tap.ir_len),
+        }
+    }
+
+    Ok(chain)
+}
+
+/// Determine the index of the TAP used for debugging.
+///
+/// If the TAP is not configured, the only TAP in the chain, or the first TAP
+/// with the IDCODE of a supported debug module is used.
+pub(crate) fn select_target_tap(
+    chain: &[JtagChainItem],
+    config: Option<&ScanChain>,
+) -> Result<usize, ScanChainError> {
+    if let Some(index) = config.and_then(|config| config.target_tap) {
+        return if index < chain.len() {
+            Ok(index)
+        } else {
+            Err(ScanChainError::InvalidTargetTap(index))
+        };
+    }
+
+    match chain.len() {
+        0 => Err(ScanChainError::Empty),
+        1 => Ok(0),
+        _ => chain
+            .iter()
+            .position(|tap| {
+                tap.idcode
+                    .map(|idcode| KNOWN_IDCODES.contains(&idcode) || is_arm_idcode(idcode))
+                    .unwrap_or(false)
+            })
+            .ok_or(ScanChainError::NoSupportedTap),
+    }
+}
+
+/// Split the bits shifted out of the DR chain after a reset into the IDCODEs of the TAPs.
+///
+/// An IDCODE always has bit 0 set, a TAP without IDCODE shifts out a single zero.
+fn parse_idcodes(tdo: &[bool]) -> Vec<Option<u32>> {
+    let mut idcodes = Vec::new();
+    let mut offset = 0;
+
+    while offset < tdo.len() && idcodes.len() < MAX_TAPS {
+        if !tdo[offset] {
+            idcodes.push(None);
+            offset += 1;
+            continue;
+        }
+
+        let idcode = match tdo.get(offset..offset + 32) {
+            Some(bits) => bits_to_u32(bits),
+            None => break,
+        };
+
+        // The ones shifted in are reached, so this is the end of the chain.
+        if idcode == 0xffff_ffff {
+            break;
+        }
+
+        idcodes.push(Some(idcode));
+        offset += 32;
+    }
+
+    idcodes
+}
+
+/// Combine the detected IDCODEs and the captured IR values with the configured scan chain.
+fn build_chain(
+    idcodes: &[Option<u32>],
+    ir_capture: &[bool],
+    config: Option<&ScanChain>,
+) -> Result<Vec<JtagChainItem>, ScanChainError> {
+    if idcodes.is_empty() {
+        return Err(ScanChainError::Empty);
+    }
+
+    let taps = config.map(|config| &config.taps[..]).unwrap_or(&[]);
+
+    if !taps.is_empty() && taps.len() != idcodes.len() {
+        return Err(ScanChainError::TapCountMismatch {
+            found: idcodes.len(),
+            configured: taps.len(),
+        });
+    }
+
+    for (index, (tap, idcode)) in taps.iter().zip(idcodes).enumerate() {
+        match (tap.idcode, idcode) {
+            (Some(expected), Some(found)) if expected != *found => {
+                return Err(ScanChainError::IdcodeMismatch {
+                    index,
+                    found: *found,
+                    expected,
+                })
+            }
+            (Some(expected), None) => {
+                return Err(ScanChainError::MissingIdcode { index, expected })
+            }
+            _ => (),
+        }
+    }
+
+    let configured_ir_lengths: Vec<_> = (0..idcodes.len())
+        .map(|index| taps.get(index).and_then(|tap| tap.ir_len))
+        .collect();
+
+    let ir_lengths = ir_lengths(ir_capture, &configured_ir_lengths)?;
+
+    Ok(idcodes
+        .iter()
+        .zip(ir_lengths)
+        .map(|(idcode, ir_len)| JtagChainItem {
+            idcode: *idcode,
+            ir_len,
+        })
+        .collect())
+}
+
+/// Determine the IR length of each TAP, using the configured lengths where available.
+///
+/// The value captured by an IR always ends with `0b01`. Unless the lengths of all
+/// following TAPs are known, the IR is assumed to end before the next set bit.
+fn ir_lengths(
+    ir_capture: &[bool],
+    configured: &[Option<u8>],
+) -> Result<Vec<usize>, ScanChainError> {
+    let mut lengths = Vec::with_capacity(configured.len());
+    let mut offset = 0;
+
+    for (index, ir_len) in configured.iter().enumerate() {
+        let ir_len = match ir_len {
+            Some(ir_len) => *ir_len as usize,
+            None => {
+                if ir_capture.get(offset..offset + 2) != Some(&[true, false][..]) {
+                    return Err(ScanChainError::InvalidIrCapture(index));
+                }
+
+                let remaining: Option<usize> = configured[index + 1..]
+                    .iter()
+                    .map(|ir_len| ir_len.map(usize::from))
+                    .sum();
+
+                match remaining {
+                    Some(remaining) => ir_capture
+                        .len()
+                        .checked_sub(offset + remaining)
+                        .filter(|ir_len| *ir_len >= 2)
+                        .ok_or(ScanChainError::InvalidIrCapture(index))?,
+                    None => ir_capture[offset + 2..]
+                        .iter()
+                        .position(|bit| *bit)
+                        .map(|position| position + 2)
+                        .ok_or(ScanChainError::InvalidIrCapture(index))?,
+                }
+            }
+        };
+
+        lengths.push(ir_len);
+        offset += ir_len;
+    }
+
+    if offset != ir_capture.len() {
+        return Err(ScanChainError::IrLengthMismatch {
+            found: ir_capture.len(),
+            configured: offset,
+        });
+    }
+
+    Ok(lengths)
+}
+
+/// The position of the target TAP in the scan chain.
+///
+/// The TAPs before the target TAP are closer to TDO, so the bits for
+/// them are shifted in first.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct ChainParams {
+    pub(crate) irpre: usize,
+    pub(crate) irpost: usize,
+    pub(crate) drpre: usize,
+    pub(crate) drpost: usize,
+    pub(crate) irlen: usize,
+}
+
+impl ChainParams {
+    pub(crate) fn new(chain: &[JtagChainItem], index: usize) -> Self {
+        let (before, after) = chain.split_at(index.min(chain.len()));
+
+        Self {
+            irpre: before.iter().map(|tap| tap.ir_len).sum(),
+            irpost: after.iter().skip(1).map(|tap| tap.ir_len).sum(),
+            drpre: before.len(),
+            drpost: after.len().saturating_sub(1),
+            irlen: after.first().map(|tap| tap.ir_len).unwrap_or(0),
+        }
+    }
+
+    /// Check if `instruction` fits into the IR of the target TAP.
+    pub(crate) fn is_valid_instruction(&self, instruction: u32) -> bool {
+        self.irlen >= 32 || instruction >> self.irlen == 0
+    }
+
+    /// The bits to shift through the IR chain to load `instruction`
+    /// into the target TAP, and BYPASS into all other TAPs.
+    pub(crate) fn ir_bits(&self, instruction: u32) -> Vec<bool> {
+        let mut bits = vec![true; self.irpre];
+        bits.extend(bytes_to_bits(&instruction.to_le_bytes(), self.irlen));
+        bits.extend(std::iter::repeat(true).take(self.irpost));
+        bits
+    }
+
+    /// The bits to shift through the DR chain to shift `data` into the target TAP.
+    pub(crate) fn dr_bits(&self, data: &[bool]) -> Vec<bool> {
+        let mut bits = vec![false; self.drpre];
+        bits.extend_from_slice(data);
+        bits.extend(std::iter::repeat(false).take(self.drpost));
+        bits
+    }
+
+    /// Extract the `len` bits shifted out of the target TAP from the bits shifted out of the DR chain.
+    pub(crate) fn dr_result<'a>(&self, tdo: &'a [bool], len: usize) -> &'a [bool] {
+        let start = self.drpre.min(tdo.len());
+        let end = (self.drpre + len).min(tdo.len());
+        &tdo[start..end]
+    }
+}
+
+/// Convert the first `length` bits of `bytes` into single bits, starting with the LSB.
+pub(crate) fn bytes_to_bits(bytes: &[u8], length: usize) -> Vec<bool> {
+    (0..length)
+        .map(|bit| {
+            bytes
+                .get(bit / 8)
+                .map(|byte| byte & (1 << (bit % 8)) != 0)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Pack single bits into bytes, starting with the LSB.
+pub(crate) fn bits_to_vec(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; (bits.len() + 7) / 8];
+
+    for (index, bit) in bits.iter().enumerate() {
+        if *bit {
+            bytes[index / 8] |= 1 << (index % 8);
+        }
+    }
+
+    bytes
+}
+
+fn bits_to_u32(bits: &[bool]) -> u32 {
+    bits.iter()
+        .enumerate()
+        .fold(0, |value, (index, bit)| value | (*bit as u32) << index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ScanChainElement;
+
+    fn idcode_bits(idcode: u32) -> Vec<bool> {
+        bytes_to_bits(&idcode.to_le_bytes(), 32)
+    }
+
+    fn chain(ir_lengths: &[Option<u8>], target_tap: Option<usize>) -> ScanChain {
+        ScanChain {
+            taps: ir_lengths
+                .iter()
+                .map(|ir_len| ScanChainElement {
+                    ir_len: *ir_len,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>()
+                .into(),
+            target_tap,
+        }
+    }
+
+    #[test]
+    fn bit_conversion() {
+        let bits = bytes_to_bits(&[0x35, 0x01], 9);
+
+        assert_eq!(
+            bits,
+            [true, false, true, false, true, true, false, false, true]
+        );
+        assert_eq!(bits_to_vec(&bits), [0x35, 0x01]);
+    }
+
+    #[test]
+    fn parse_idcodes_with_bypass_tap() {
+        let mut tdo = idcode_bits(0x4ba0_0477);
+        tdo.push(false);
+        tdo.extend(idcode_bits(0x0641_3041));
+        tdo.extend(std::iter::repeat(true).take(64));
+
+        assert_eq!(
+            parse_idcodes(&tdo),
+            [Some(0x4ba0_0477), None, Some(0x0641_3041)]
+        );
+    }
+
+    #[test]
+    fn detect_ir_lengths() {
+        // STM32: the JTAG-DP with a 4 bit IR, followed by the boundary scan TAP with a 5 bit IR.
+        let capture = [true, false, false, false, true, false, false, false, false];
+
+        assert_eq!(ir_lengths(&capture, &[None, None]), Ok(vec![4, 5]));
+    }
+
+    #[test]
+    fn configured_ir_lengths() {
+        // The first TAP captures a value with additional set bits.
+        let capture = [true, false, true, true, true, false, false, false, false];
+
+        assert_eq!(
+            ir_lengths(&capture, &[None, None]),
+            Err(ScanChainError::InvalidIrCapture(1))
+        );
+        assert_eq!(ir_lengths(&capture, &[Some(4), None]), Ok(vec![4, 5]));
+        assert_eq!(ir_lengths(&capture, &[None, Some(5)]), Ok(vec![4, 5]));
+        assert_eq!(
+            ir_lengths(&capture, &[Some(4), Some(4)]),
+            Err(ScanChainError::IrLengthMismatch {
+                found: 9,
+                configured: 8
+            })
+        );
+    }
+
+    #[test]
+    fn configured_idcode_mismatch() {
+        let mut config = chain(&[None], None);
+        config.taps.to_mut()[0].idcode = Some(0x1000_563d);
+
+        assert_eq!(
+            build_chain(
+                &[Some(0x4ba0_0477)],
+                &[true, false, false, false],
+                Some(&config)
+            ),
+            Err(ScanChainError::IdcodeMismatch {
+                index: 0,
+                found: 0x4ba0_0477,
+                expected: 0x1000_563d
+            })
+        );
+    }
+
+    #[test]
+    fn select_tap() {
+        let chain_items = [
+            JtagChainItem {
+                idcode: Some(0x0641_3041),
+                ir_len: 5,
+            },
+            JtagChainItem {
+                idcode: Some(0x4ba0_0477),
+                ir_len: 4,
+            },
+        ];
+
+        assert_eq!(select_target_tap(&chain_items, None), Ok(1));
+        assert_eq!(
+            select_target_tap(&chain_items, Some(&chain(&[], Some(0)))),
+            Ok(0)
+        );
+        assert_eq!(
+            select_target_tap(&chain_items, Some(&chain(&[], Some(2)))),
+            Err(ScanChainError::InvalidTargetTap(2))
+        );
+    }
+
+    #[test]
+    fn chain_params() {
+        let chain_items = [
+            JtagChainItem {
+                idcode: None,
+                ir_len: 3,
+            },
+            JtagChainItem {
+                idcode: Some(0x4ba0_0477),
+                ir_len: 4,
+            },
+            JtagChainItem {
+                idcode: Some(0x0641_3041),
+                ir_len: 5,
+            },
+        ];
+
+        let params = ChainParams::new(&chain_items, 1);
+
+        assert_eq!(
+            params,
+            ChainParams {
+                irpre: 3,
+                irpost: 5,
+                drpre: 1,
+                drpost: 1,
+                irlen: 4,
+            }
+        );
+
+        assert_eq!(
+            bits_to_vec(&params.ir_bits(0xa)),
+            // 3 BYPASS bits, 0xa, and 5 BYPASS bits
+            [0b1101_0111, 0b1111]
+        );
+
+        let dr = params.dr_bits(&[true, true]);
+        assert_eq!(dr, [false, true, true, false]);
+        assert_eq!(params.dr_result(&dr, 2), [true, true]);
+    }
+}
@@ -0,0 +1,778 @@
+//! Model of a Cortex-M4 core, as it is seen through the MEM-AP.
+//!
+//! The core does not execute any instructions. Instead, the program counter follows a
+//! script, which is a list of addresses provided by the user. The only instructions
+//! which are interpreted are `BKPT`, which halts the core, and `UDF` instructions
+//! used as entry points of the simulated flash algorithm, which are handled natively.
+
+use super::memory::SparseMemory;
+use super::{FLASH_SECTOR_SIZE, FLASH_SIZE};
+use crate::architecture::arm::core::{
+    m4::{Aircr, Dcrdr, Demcr, Dhcsr, FpCtrl, FpRev1CompX},
+    Dfsr,
+};
+use crate::architecture::arm::memory::adi_v5_memory_interface::Dcrsr;
+use crate::core::CoreRegister;
+use crate::flashing::{crc32, CRC32_ROUTINE};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Base address of the ROM table.
+pub(crate) const ROM_TABLE_ADDRESS: u32 = 0xE00F_F000;
+
+/// Number of instruction comparators of the FPB.
+const FPB_NUM_CODE: usize = 6;
+/// Number of literal comparators of the FPB.
+const FPB_NUM_LIT: usize = 2;
+
+/// Maximum number of scripted instructions executed when running,
+/// before the core is considered to be stuck in a loop.
+const MAX_INSTRUCTIONS: usize = 0x10000;
+
+// DHCSR bits
+const C_DEBUGEN: u32 = 1 << 0;
+const C_HALT: u32 = 1 << 1;
+const C_STEP: u32 = 1 << 2;
+const C_MASKINTS: u32 = 1 << 3;
+const S_REGRDY: u32 = 1 << 16;
+const S_HALT: u32 = 1 << 17;
+const S_RETIRE_ST: u32 = 1 << 24;
+const S_RESET_ST: u32 = 1 << 25;
+const DBGKEY: u32 = 0xA05F;
+
+// DFSR bits
+const DFSR_HALTED: u32 = 1 << 0;
+const DFSR_BKPT: u32 = 1 << 1;
+const DFSR_VCATCH: u32 = 1 << 3;
+
+// DEMCR bits
+const VC_CORERESET: u32 = 1 << 0;
+
+// AIRCR bits
+const VECTKEY: u32 = 0x05FA;
+const VECTKEYSTAT: u32 = 0xFA05;
+const SYSRESETREQ: u32 = 1 << 2;
+const VECTRESET: u32 = 1 << 0;
+
+// Register numbers used in DCRSR
+const REG_SP: usize = 13;
+const REG_LR: usize = 14;
+const REG_PC: usize = 15;
+const REG_XPSR: usize = 16;
+const REG_MSP: usize = 17;
+const REG_PSP: usize = 18;
+const REG_CONTROL: usize = 20;
+
+/// The Thumb encoding of `BKPT #imm8`.
+const BKPT: u16 = 0xBE00;
+/// The Thumb encoding of `UDF #imm8`.
+const UDF: u16 = 0xDE00;
+
+/// Identification of a CoreSight component in the ROM table.
+struct ComponentDescription {
+    address: u32,
+    /// PIDR0 to PIDR4.
+    peripheral_id: [u8; 5],
+    /// The component class, which is stored in CIDR1.
+    class: u8,
+}
+
+/// The components of a Cortex-M4, as listed in its ROM table.
+const COMPONENTS: [ComponentDescription; 5] = [
+    // ROM table
+    ComponentDescription {
+        address: ROM_TABLE_ADDRESS,
+        peripheral_id: [0xC4, 0xB4, 0x0B, 0x00, 0x04],
+        class: 0x1,
+    },
+    // SCS
+    ComponentDescription {
+        address: 0xE000_E000,
+        peripheral_id: [0x0C, 0xB0, 0x0B, 0x00, 0x04],
+        class: 0xE,
+    },
+    // DWT
+    ComponentDescription {
+        address: 0xE000_1000,
+        peripheral_id: [0x02, 0xB0, 0x3B, 0x00, 0x04],
+        class: 0xE,
+    },
+    // FPB
+    ComponentDescription {
+        address: 0xE000_2000,
+        peripheral_id: [0x03, 0xB0, 0x2B, 0x00, 0x04],
+        class: 0xE,
+    },
+    // ITM
+    ComponentDescription {
+        address: 0xE000_0000,
+        peripheral_id: [0x01, 0xB0, 0x3B, 0x00, 0x04],
+        class: 0xE,
+    },
+];
+
+/// Entry points of the simulated flash algorithm, encoded as `UDF #imm8` instructions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FlashFunction {
+    Init = 1,
+    UnInit = 2,
+    ProgramPage = 3,
+    EraseSector = 4,
+    EraseAll = 5,
+}
+
+impl FlashFunction {
+    fn from_instruction(instruction: u16) -> Option<Self> {
+        if instruction & 0xFF00 != UDF {
+            return None;
+        }
+
+        match instruction & 0xFF {
+            1 => Some(FlashFunction::Init),
+            2 => Some(FlashFunction::UnInit),
+            3 => Some(FlashFunction::ProgramPage),
+            4 => Some(FlashFunction::EraseSector),
+            5 => Some(FlashFunction::EraseAll),
+            _ => None,
+        }
+    }
+}
+
+/// The result of executing a single instruction.
+enum Execution {
+    /// The instruction was executed, and the PC points to the next one.
+    Continue,
+    /// The core has to halt, with the given DFSR bits.
+    Halt(u32),
+    /// The script has ended, so the core cannot make any progress.
+    Stalled,
+}
+
+#[derive(Debug)]
+pub(crate) struct CortexM {
+    memory: SparseMemory,
+    flash: Vec<u8>,
+
+    /// The core registers, indexed by their number in DCRSR.
+    registers: [u32; 128],
+    /// The addresses the PC will take next.
+    script: VecDeque<u32>,
+
+    /// The control bits of DHCSR.
+    control: u32,
+    halted: bool,
+    reset_asserted: bool,
+    reset_status: bool,
+    retired: bool,
+
+    dcrdr: u32,
+    demcr: u32,
+    dfsr: u32,
+
+    fpb_enabled: bool,
+    fpb_comparators: [u32; FPB_NUM_CODE + FPB_NUM_LIT],
+}
+
+impl CortexM {
+    pub fn new() -> Self {
+        Self {
+            memory: SparseMemory::new(),
+            flash: vec![0xFF; FLASH_SIZE as usize],
+            registers: [0; 128],
+            script: VecDeque::new(),
+            control: 0,
+            halted: false,
+            reset_asserted: false,
+            reset_status: false,
+            retired: false,
+            dcrdr: 0,
+            demcr: 0,
+            dfsr: 0,
+            fpb_enabled: false,
+            fpb_comparators: [0; FPB_NUM_CODE + FPB_NUM_LIT],
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn set_script(&mut self, script: impl IntoIterator<Item = u32>) {
+        self.script = script.into_iter().collect();
+    }
+
+    fn register_index(&self, number: u16) -> usize {
+        let number = number as usize & 0x7F;
+
+        if number == REG_SP {
+            // SPSEL in CONTROL selects the active stack pointer.
+            if self.registers[REG_CONTROL] & (1 << 25) != 0 {
+                REG_PSP
+            } else {
+                REG_MSP
+            }
+        } else {
+            number
+        }
+    }
+
+    pub fn register(&self, number: u16) -> u32 {
+        self.registers[self.register_index(number)]
+    }
+
+    pub fn set_register(&mut self, number: u16, value: u32) {
+        let index = self.register_index(number);
+        self.registers[index] = value;
+    }
+
+    fn pc(&self) -> u32 {
+        self.registers[REG_PC]
+    }
+
+    fn flash_offset(address: u32) -> Option<usize> {
+        // The flash starts at address 0.
+        if address < FLASH_SIZE {
+            Some(address as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Read memory, without accessing any registers.
+    pub fn read_memory(&self, address: u32, data: &mut [u8]) {
+        for (offset, byte) in data.iter_mut().enumerate() {
+            let address = address.wrapping_add(offset as u32);
+
+            *byte = match Self::flash_offset(address) {
+                Some(offset) => self.flash[offset],
+                None => self.memory.read_u8(address),
+            };
+        }
+    }
+
+    /// Write memory, including the flash, without accessing any registers.
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            let address = address.wrapping_add(offset as u32);
+
+            match Self::flash_offset(address) {
+                Some(offset) => self.flash[offset] = *byte,
+                None => self.memory.write_u8(address, *byte),
+            }
+        }
+    }
+
+    fn read_memory_u32(&self, address: u32) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.read_memory(address, &mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn read_memory_u16(&self, address: u32) -> u16 {
+        let mut bytes = [0u8; 2];
+        self.read_memory(address, &mut bytes);
+        u16::from_le_bytes(bytes)
+    }
+
+    /// The value of the CoreSight identification registers or the ROM table, if `address` is one of them.
+    fn identification_register(address: u32) -> Option<u32> {
+        if (ROM_TABLE_ADDRESS..ROM_TABLE_ADDRESS + 0xFD0).contains(&address) {
+            // One entry for each component except the ROM table itself, followed by the end marker.
+            let index = ((address - ROM_TABLE_ADDRESS) / 4) as usize + 1;
+
+            let entry = COMPONENTS
+                .get(index)
+                .map(|component| component.address.wrapping_sub(ROM_TABLE_ADDRESS) | 0x3)
+                .unwrap_or(0);
+
+            return Some(entry);
+        }
+
+        let component = COMPONENTS.iter().find(|component| {
+            (component.address + 0xFD0..component.address + 0x1000).contains(&address)
+        })?;
+
+        let value = match address - component.address {
+            // PIDR4
+            0xFD0 => component.peripheral_id[4],
+            // PIDR0 to PIDR3
+            offset @ 0xFE0..=0xFEC => component.peripheral_id[((offset - 0xFE0) / 4) as usize],
+            // CIDR0 to CIDR3
+            0xFF0 => 0x0D,
+            0xFF4 => component.class << 4,
+            0xFF8 => 0x05,
+            0xFFC => 0xB1,
+            _ => 0,
+        };
+
+        Some(u32::from(value))
+    }
+
+    fn fpb_comparator_index(address: u32) -> Option<usize> {
+        let range =
+            FpRev1CompX::ADDRESS..FpRev1CompX::ADDRESS + 4 * (FPB_NUM_CODE + FPB_NUM_LIT) as u32;
+
+        if range.contains(&address) {
+            Some(((address - FpRev1CompX::ADDRESS) / 4) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Read a word from the bus. The address has to be word aligned.
+    pub fn read_word(&mut self, address: u32) -> u32 {
+        if let Some(value) = Self::identification_register(address) {
+            return value;
+        }
+
+        if let Some(index) = Self::fpb_comparator_index(address) {
+            return self.fpb_comparators[index];
+        }
+
+        match address {
+            Dhcsr::ADDRESS => {
+                let mut value = self.control | S_REGRDY;
+
+                if self.halted {
+                    value |= S_HALT;
+                }
+
+                // The sticky status bits are cleared by reading.
+                if std::mem::replace(&mut self.retired, false) {
+                    value |= S_RETIRE_ST;
+                }
+
+                if std::mem::replace(&mut self.reset_status, false) || self.reset_asserted {
+                    value |= S_RESET_ST;
+                }
+
+                value
+            }
+            Dcrsr::ADDRESS => 0,
+            Dcrdr::ADDRESS => self.dcrdr,
+            Demcr::ADDRESS => self.demcr,
+            Dfsr::ADDRESS => self.dfsr,
+            Aircr::ADDRESS => VECTKEYSTAT << 16,
+            FpCtrl::ADDRESS => {
+                // Revision 0, with the number of comparators.
+                (FPB_NUM_LIT as u32) << 8 | (FPB_NUM_CODE as u32) << 4 | self.fpb_enabled as u32
+            }
+            address => self.read_memory_u32(address),
+        }
+    }
+
+    /// Write a word to the bus. Only the bytes selected by `mask` are written.
+    pub fn write_word(&mut self, address: u32, value: u32, mask: u32) {
+        if Self::identification_register(address).is_some() {
+            log::debug!("Ignoring write to read-only register {:#010x}", address);
+            return;
+        }
+
+        let value = (self.read_word_without_side_effects(address) & !mask) | (value & mask);
+
+        if let Some(index) = Self::fpb_comparator_index(address) {
+            self.fpb_comparators[index] = value;
+            return;
+        }
+
+        match address {
+            Dhcsr::ADDRESS => {
+                if value >> 16 == DBGKEY {
+                    self.write_dhcsr(value & 0xFFFF);
+                }
+            }
+            Dcrsr::ADDRESS => self.register_transfer(value),
+            Dcrdr::ADDRESS => self.dcrdr = value,
+            Demcr::ADDRESS => self.demcr = value,
+            // The bits are cleared by writing one.
+            Dfsr::ADDRESS => self.dfsr &= !value,
+            Aircr::ADDRESS => {
+                if value >> 16 == VECTKEY && value & (SYSRESETREQ | VECTRESET) != 0 {
+                    log::debug!("Reset requested using AIRCR");
+                    self.reset();
+                }
+            }
+            FpCtrl::ADDRESS => {
+                // The enable bit is only written if the key bit is set.
+                if value & 0b10 != 0 {
+                    self.fpb_enabled = value & 1 != 0;
+                }
+            }
+            address if Self::flash_offset(address).is_some() => {
+                log::warn!(
+                    "Ignoring write to flash at {:#010x}, flash can only be written using the flash algorithm",
+                    address
+                );
+            }
+            address => {
+                for (offset, byte) in value.to_le_bytes().iter().enumerate() {
+                    if mask & (0xFF << (offset * 8)) != 0 {
+                        self.memory.write_u8(address + offset as u32, *byte);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The current value of a word, used to merge partial writes.
+    fn read_word_without_side_effects(&self, address: u32) -> u32 {
+        match address {
+            Dhcsr::ADDRESS => self.control,
+            Dcrdr::ADDRESS => self.dcrdr,
+            Demcr::ADDRESS => self.demcr,
+            FpCtrl::ADDRESS => self.fpb_enabled as u32,
+            address => match Self::fpb_comparator_index(address) {
+                Some(index) => self.fpb_comparators[index],
+                None => self.read_memory_u32(address),
+            },
+        }
+    }
+
+    fn register_transfer(&mut self, dcrsr: u32) {
+        if !self.halted {
+            log::warn!("Ignoring core register access while the core is running");
+            return;
+        }
+
+        let number = (dcrsr & 0x7F) as u16;
+        let write = dcrsr & (1 << 16) != 0;
+
+        if write {
+            // Bit 0 of the PC is ignored, it only selects the Thumb state.
+            let value = if number as usize == REG_PC {
+                self.dcrdr & !1
+            } else {
+                self.dcrdr
+            };
+            self.set_register(number, value);
+        } else {
+            self.dcrdr = self.register(number);
+        }
+    }
+
+    fn write_dhcsr(&mut self, control: u32) {
+        self.control = control & (C_DEBUGEN | C_HALT | C_STEP | C_MASKINTS);
+
+        let debug_enabled = self.control & C_DEBUGEN != 0;
+
+        if debug_enabled && self.control & C_HALT != 0 {
+            if !self.halted {
+                self.halt(DFSR_HALTED);
+            }
+        } else if self.halted {
+            if debug_enabled && self.control & C_STEP != 0 {
+                self.step();
+            } else {
+                self.run();
+            }
+        }
+    }
+
+    fn halt(&mut self, reason: u32) {
+        log::debug!("Simulated core halted at {:#010x}", self.pc());
+
+        self.halted = true;
+        self.dfsr |= reason;
+        self.control |= C_HALT;
+    }
+
+    fn resume(&mut self) {
+        self.halted = false;
+        self.control &= !C_HALT;
+    }
+
+    /// Execute a single instruction.
+    fn execute(&mut self) -> Execution {
+        let pc = self.pc();
+        let instruction = self.read_memory_u16(pc);
+
+        if instruction & 0xFF00 == BKPT {
+            return Execution::Halt(DFSR_BKPT);
+        }
+
+        if let Some(function) = FlashFunction::from_instruction(instruction) {
+            let result = self.call_flash_function(function);
+
+            // Return to the caller.
+            self.registers[0] = result;
+            self.registers[REG_PC] = self.registers[REG_LR] & !1;
+            self.retired = true;
+
+            return Execution::Continue;
+        }
+
+        // The CRC32 routine used for incremental flashing is executed natively.
+        if self.is_crc32_routine(pc) {
+            let mut data = vec![0; self.registers[1] as usize];
+            self.read_memory(self.registers[0], &mut data);
+
+            self.registers[0] = crc32(&data);
+            self.registers[REG_PC] = self.registers[REG_LR] & !1;
+            self.retired = true;
+
+            return Execution::Continue;
+        }
+
+        match self.script.pop_front() {
+            Some(next) => {
+                self.registers[REG_PC] = next;
+                self.retired = true;
+
+                Execution::Continue
+            }
+            None => Execution::Stalled,
+        }
+    }
+
+    fn step(&mut self) {
+        self.resume();
+
+        match self.execute() {
+            Execution::Continue => self.halt(DFSR_HALTED),
+            Execution::Halt(reason) => self.halt(reason),
+            Execution::Stalled => {
+                // Without a script, the next instruction simply follows the current one.
+                self.registers[REG_PC] = self.pc().wrapping_add(2);
+                self.retired = true;
+                self.halt(DFSR_HALTED);
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        self.resume();
+
+        for _ in 0..MAX_INSTRUCTIONS {
+            match self.execute() {
+                Execution::Continue => {
+                    if self.breakpoint_hit(self.pc()) {
+                        self.halt(DFSR_BKPT);
+                        return;
+                    }
+                }
+                Execution::Halt(reason) => {
+                    self.halt(reason);
+                    return;
+                }
+                // The core keeps running at the current PC.
+                Execution::Stalled => return,
+            }
+        }
+
+        log::warn!(
+            "Simulated core executed {} instructions without halting",
+            MAX_INSTRUCTIONS
+        );
+    }
+
+    fn breakpoint_hit(&self, address: u32) -> bool {
+        if !self.fpb_enabled {
+            return false;
+        }
+
+        self.fpb_comparators[..FPB_NUM_CODE]
+            .iter()
+            .map(|&comparator| FpRev1CompX::from(comparator))
+            .filter(|comparator| comparator.enable())
+            .any(|comparator| {
+                let word_address = comparator.comp() << 2;
+
+                match comparator.replace() {
+                    0b01 => address == word_address,
+                    0b10 => address == word_address | 2,
+                    0b11 => address & !2 == word_address,
+                    _ => false,
+                }
+            })
+    }
+
+    /// Reset the core, which loads SP and PC from the vector table.
+    pub fn reset(&mut self) {
+        self.reset_status = true;
+
+        let initial_sp = self.read_memory_u32(0x0);
+        let reset_vector = self.read_memory_u32(0x4);
+
+        self.registers[REG_MSP] = initial_sp;
+        self.registers[REG_PC] = reset_vector & !1;
+        self.registers[REG_XPSR] = (reset_vector & 1) << 24;
+        self.registers[REG_LR] = 0xFFFF_FFFF;
+        self.registers[REG_CONTROL] = 0;
+
+        if self.control & C_DEBUGEN != 0 && self.demcr & VC_CORERESET != 0 {
+            self.halt(DFSR_VCATCH);
+        } else {
+            self.run();
+        }
+    }
+
+    /// Hold the core in reset, or release it.
+    pub fn set_reset(&mut self, asserted: bool) {
+        if asserted {
+            self.reset_asserted = true;
+            self.resume();
+        } else if self.reset_asserted {
+            self.reset_asserted = false;
+            self.reset();
+        }
+    }
+
+    fn flash_range(address: u32, size: u32) -> Option<Range<usize>> {
+        let start = Self::flash_offset(address)?;
+        let end = start + size as usize;
+
+        if end <= FLASH_SIZE as usize {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+
+    /// Check if the CRC32 routine of probe-rs was loaded to `address`.
+    fn is_crc32_routine(&self, address: u32) -> bool {
+        CRC32_ROUTINE
+            .iter()
+            .enumerate()
+            .all(|(i, word)| self.read_memory_u32(address + 4 * i as u32) == *word)
+    }
+
+    /// Execute a function of the flash algorithm, using the arguments in R0 to R2.
+    fn call_flash_function(&mut self, function: FlashFunction) -> u32 {
+        let arguments = [self.registers[0], self.registers[1], self.registers[2]];
+
+        log::debug!("Flash algorithm: {:?}({:#x?})", function, arguments);
+
+        match function {
+            FlashFunction::Init | FlashFunction::UnInit => 0,
+            FlashFunction::ProgramPage => {
+                let [address, size, buffer] = arguments;
+
+                match Self::flash_range(address, size) {
+                    Some(range) => {
+                        let mut data = vec![0u8; size as usize];
+                        self.memory.read(buffer, &mut data);
+
+                        // Programming can only clear bits.
+                        for (target, byte) in self.flash[range].iter_mut().zip(data) {
+                            *target &= byte;
+                        }
+
+                        0
+                    }
+                    None => 1,
+                }
+            }
+            FlashFunction::EraseSector => {
+                let address = arguments[0] & !(FLASH_SECTOR_SIZE - 1);
+
+                match Self::flash_range(address, FLASH_SECTOR_SIZE) {
+                    Some(range) => {
+                        self.flash[range].iter_mut().for_each(|byte| *byte = 0xFF);
+                        0
+                    }
+                    None => 1,
+                }
+            }
+            FlashFunction::EraseAll => {
+                self.flash.iter_mut().for_each(|byte| *byte = 0xFF);
+                0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CortexM, ROM_TABLE_ADDRESS};
+    use crate::architecture::arm::core::m4::{Dhcsr, FpCtrl, FpRev1CompX};
+    use crate::core::CoreRegister;
+
+    fn halted_core() -> CortexM {
+        let mut core = CortexM::new();
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0003, !0);
+        core
+    }
+
+    #[test]
+    fn rom_table_entries() {
+        let mut core = CortexM::new();
+
+        // SCS, DWT, FPB and ITM, followed by the end marker.
+        assert_eq!(core.read_word(ROM_TABLE_ADDRESS), 0xFFF0_F003);
+        assert_eq!(core.read_word(ROM_TABLE_ADDRESS + 0x4), 0xFFF0_2003);
+        assert_eq!(core.read_word(ROM_TABLE_ADDRESS + 0x8), 0xFFF0_3003);
+        assert_eq!(core.read_word(ROM_TABLE_ADDRESS + 0xC), 0xFFF0_1003);
+        assert_eq!(core.read_word(ROM_TABLE_ADDRESS + 0x10), 0);
+
+        // CIDR1 of the ROM table and the DWT.
+        assert_eq!(core.read_word(ROM_TABLE_ADDRESS + 0xFF4), 0x10);
+        assert_eq!(core.read_word(0xE000_1FF4), 0xE0);
+    }
+
+    #[test]
+    fn dhcsr_requires_key() {
+        let mut core = CortexM::new();
+
+        core.write_word(Dhcsr::ADDRESS, 0x0000_0003, !0);
+        assert!(!core.is_halted());
+
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0003, !0);
+        assert!(core.is_halted());
+    }
+
+    #[test]
+    fn step_follows_script() {
+        let mut core = halted_core();
+        core.set_register(15, 0x100);
+        core.set_script(vec![0x104, 0x200]);
+
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0005, !0);
+        assert!(core.is_halted());
+        assert_eq!(core.register(15), 0x104);
+
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0005, !0);
+        assert_eq!(core.register(15), 0x200);
+
+        // Without a script, the PC is incremented.
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0005, !0);
+        assert_eq!(core.register(15), 0x202);
+    }
+
+    #[test]
+    fn run_until_breakpoint() {
+        let mut core = halted_core();
+        core.set_register(15, 0x100);
+        core.set_script(vec![0x104, 0x108, 0x10c]);
+
+        core.write_word(FpCtrl::ADDRESS, 0b11, !0);
+        core.write_word(FpRev1CompX::ADDRESS, 0x4000_0109, !0);
+
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0001, !0);
+
+        assert!(core.is_halted());
+        assert_eq!(core.register(15), 0x108);
+    }
+
+    #[test]
+    fn run_without_breakpoint() {
+        let mut core = halted_core();
+        core.set_register(15, 0x100);
+        core.set_script(vec![0x104, 0x108]);
+
+        core.write_word(Dhcsr::ADDRESS, 0xA05F_0001, !0);
+
+        assert!(!core.is_halted());
+        assert_eq!(core.register(15), 0x108);
+    }
+
+    #[test]
+    fn flash_is_only_written_by_algorithm() {
+        let mut core = CortexM::new();
+
+        core.write_word(0x100, 0x1234_5678, !0);
+        assert_eq!(core.read_word(0x100), 0xFFFF_FFFF);
+
+        core.write_word(0x2000_0000, 0x1234_5678, 0x0000_FFFF);
+        assert_eq!(core.read_word(0x2000_0000), 0x0000_5678);
+    }
+}
@@ -0,0 +1,69 @@
+//! Sparse memory model of the simulated target.
+
+use std::collections::HashMap;
+
+/// Size of the blocks in which memory is allocated.
+const BLOCK_SIZE: u32 = 0x400;
+
+/// A 32 bit address space, where memory is only allocated when it is written.
+///
+/// Memory which was never written reads as zero.
+#[derive(Debug, Default)]
+pub(crate) struct SparseMemory {
+    blocks: HashMap<u32, Box<[u8; BLOCK_SIZE as usize]>>,
+}
+
+impl SparseMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read_u8(&self, address: u32) -> u8 {
+        self.blocks
+            .get(&(address / BLOCK_SIZE))
+            .map(|block| block[(address % BLOCK_SIZE) as usize])
+            .unwrap_or(0)
+    }
+
+    pub fn write_u8(&mut self, address: u32, value: u8) {
+        let block = self
+            .blocks
+            .entry(address / BLOCK_SIZE)
+            .or_insert_with(|| Box::new([0; BLOCK_SIZE as usize]));
+
+        block[(address % BLOCK_SIZE) as usize] = value;
+    }
+
+    pub fn read(&self, address: u32, data: &mut [u8]) {
+        for (offset, byte) in data.iter_mut().enumerate() {
+            *byte = self.read_u8(address.wrapping_add(offset as u32));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SparseMemory;
+
+    #[test]
+    fn unwritten_memory_reads_zero() {
+        let memory = SparseMemory::new();
+
+        assert_eq!(memory.read_u8(0x2000_0000), 0);
+        assert!(memory.blocks.is_empty());
+    }
+
+    #[test]
+    fn read_across_blocks() {
+        let mut memory = SparseMemory::new();
+
+        memory.write_u8(0x3ff, 0x78);
+        memory.write_u8(0x400, 0x56);
+
+        let mut data = [0u8; 4];
+        memory.read(0x3fe, &mut data);
+
+        assert_eq!(data, [0x00, 0x78, 0x56, 0x00]);
+        assert_eq!(memory.blocks.len(), 2);
+    }
+}
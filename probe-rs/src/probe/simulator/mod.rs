@@ -0,0 +1,641 @@
+//! A simulated target, which can be used to test probe-rs without any hardware.
+//!
+//! The [SimulatedProbe] emulates the DAP of a Cortex-M4, with a single MEM-AP.
+//! Behind the MEM-AP, the memory, the ROM table, and the debug registers of
+//! the core are simulated, so that a [Session](crate::Session) can be opened
+//! using the `simulated_m4` target, which is added to the registry using
+//! [SimulatedProbe::add_target]. The target also has a simulated flash,
+//! together with a flash algorithm, so flashing can be tested as well.
+//!
+//! The core doesn't execute any code. When it is running or stepping, the
+//! program counter follows a script, which is set using [SimulatedTarget::set_script].
+
+mod cortex_m;
+mod memory;
+
+use self::cortex_m::{CortexM, ROM_TABLE_ADDRESS};
+use super::{
+    DAPAccess, DebugProbe, DebugProbeError, DebugProbeSelector, ProbeCapabilities,
+    ProbeCreationError, WireProtocol,
+};
+use crate::architecture::arm::{
+    communication_interface::ArmProbeInterface, ArmCommunicationInterface, DapError, PortType,
+};
+use crate::config::{
+    add_target_family, Chip, ChipFamily, FlashProperties, FlashRegion, MemoryRegion, RamRegion,
+    RawFlashAlgorithm, RegistryError, SectorDescription,
+};
+use crate::CoreRegisterAddress;
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const FLASH_SIZE: u32 = 0x0004_0000;
+const FLASH_SECTOR_SIZE: u32 = 0x1000;
+const FLASH_PAGE_SIZE: u32 = 0x400;
+
+const RAM_START: u32 = 0x2000_0000;
+const RAM_SIZE: u32 = 0x0001_0000;
+
+/// The target voltage reported by the probe.
+const TARGET_VOLTAGE: f32 = 3.3;
+
+/// DPIDR of a DPv1, designed by ARM.
+const DPIDR: u32 = 0x2BA0_1477;
+
+/// IDR of the AHB-AP of a Cortex-M4.
+const AHB_AP_IDR: u32 = 0x2477_0011;
+
+// DP registers
+const DP_DPIDR_ABORT: u16 = 0x0;
+const DP_CTRL_STAT: u16 = 0x4;
+const DP_SELECT: u16 = 0x8;
+const DP_RDBUFF: u16 = 0xC;
+
+// CTRL/STAT bits
+const CSYSPWRUPREQ: u32 = 1 << 30;
+const CDBGPWRUPREQ: u32 = 1 << 28;
+const STICKYERR: u32 = 1 << 5;
+
+// ABORT bits
+const STKERRCLR: u32 = 1 << 2;
+
+// MEM-AP registers
+const AP_CSW: u16 = 0x00;
+const AP_TAR: u16 = 0x04;
+const AP_DRW: u16 = 0x0C;
+const AP_BD0: u16 = 0x10;
+const AP_BD3: u16 = 0x1C;
+const AP_BASE2: u16 = 0xF0;
+const AP_CFG: u16 = 0xF4;
+const AP_BASE: u16 = 0xF8;
+const AP_IDR: u16 = 0xFC;
+
+// CSW fields
+const CSW_SIZE_MASK: u32 = 0b111;
+const CSW_ADDRINC_SHIFT: u32 = 4;
+const CSW_DEVICEEN: u32 = 1 << 6;
+
+/// The flash algorithm of the simulated target.
+///
+/// Each function consists of a `UDF` instruction, which is handled
+/// by the simulated core, followed by a `NOP`.
+const FLASH_ALGORITHM: RawFlashAlgorithm = RawFlashAlgorithm {
+    name: Cow::Borrowed("simulated_m4"),
+    description: Cow::Borrowed("Flash algorithm of the simulated Cortex-M4"),
+    default: true,
+    instructions: Cow::Borrowed(&[
+        0x01, 0xDE, 0x00, 0xBF, // Init
+        0x02, 0xDE, 0x00, 0xBF, // UnInit
+        0x03, 0xDE, 0x00, 0xBF, // ProgramPage
+        0x04, 0xDE, 0x00, 0xBF, // EraseSector
+        0x05, 0xDE, 0x00, 0xBF, // EraseAll
+    ]),
+    pc_init: Some(0x0),
+    pc_uninit: Some(0x4),
+    pc_program_page: 0x8,
+    pc_erase_sector: 0xC,
+    pc_erase_all: Some(0x10),
+    pc_verify: None,
+    pc_blank_check: None,
+    data_section_offset: 0x14,
+    load_address: None,
+    stack_size: None,
+    data_load_address: None,
+    flash_properties: FlashProperties {
+        address_range: 0x0..FLASH_SIZE,
+        page_size: FLASH_PAGE_SIZE,
+        erased_byte_value: 0xFF,
+        program_page_timeout: 100,
+        erase_sector_timeout: 500,
+        sectors: Cow::Borrowed(&[SectorDescription {
+            size: FLASH_SECTOR_SIZE,
+            address: 0x0,
+        }]),
+    },
+};
+
+/// The target description of the simulated target.
+const SIMULATED_FAMILY: ChipFamily = ChipFamily {
+    name: Cow::Borrowed("Simulated Cortex-M4"),
+    manufacturer: None,
+    variants: Cow::Borrowed(&[Chip {
+        name: Cow::Borrowed("simulated_m4"),
+        part: None,
+        memory_map: Cow::Borrowed(&[
+            MemoryRegion::Flash(FlashRegion {
+                range: 0x0..FLASH_SIZE,
+                is_boot_memory: true,
+                algorithm: None,
+            }),
+            MemoryRegion::Ram(RamRegion {
+                range: RAM_START..RAM_START + RAM_SIZE,
+                is_boot_memory: false,
+            }),
+        ]),
+        flash_algorithms: Cow::Borrowed(&[Cow::Borrowed("simulated_m4")]),
+        scan_chain: None,
+        swd_targetsel: None,
+    }]),
+    flash_algorithms: Cow::Borrowed(&[FLASH_ALGORITHM]),
+    core: Cow::Borrowed("M4"),
+    debug_sequence: None,
+};
+
+/// A handle to the state of a simulated target.
+///
+/// The handle can be cloned, and is used to inspect and modify the
+/// target while the [SimulatedProbe] is used by a session.
+#[derive(Debug, Clone)]
+pub struct SimulatedTarget {
+    core: Arc<Mutex<CortexM>>,
+}
+
+impl SimulatedTarget {
+    fn lock(&self) -> MutexGuard<'_, CortexM> {
+        // The state of the core stays consistent, even if a panic occured while it was locked.
+        self.core.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Read memory of the target, including the flash.
+    pub fn read_memory(&self, address: u32, data: &mut [u8]) {
+        self.lock().read_memory(address, data)
+    }
+
+    /// Write memory of the target. Unlike a write using the debug
+    /// interface, this can also be used to write the flash.
+    pub fn write_memory(&self, address: u32, data: &[u8]) {
+        self.lock().write_memory(address, data)
+    }
+
+    /// Read a core register, independent of the state of the core.
+    pub fn register(&self, address: CoreRegisterAddress) -> u32 {
+        self.lock().register(address.0)
+    }
+
+    /// Write a core register, independent of the state of the core.
+    pub fn set_register(&self, address: CoreRegisterAddress, value: u32) {
+        self.lock().set_register(address.0, value)
+    }
+
+    /// Set the addresses which the program counter will take while the core is running.
+    ///
+    /// Each executed instruction moves the program counter to the next address of the script.
+    /// When the script has ended, the core keeps running without making any progress.
+    pub fn set_script(&self, addresses: impl IntoIterator<Item = u32>) {
+        self.lock().set_script(addresses)
+    }
+
+    /// Check if the core is halted.
+    pub fn is_halted(&self) -> bool {
+        self.lock().is_halted()
+    }
+}
+
+/// A probe connected to a simulated Cortex-M4.
+#[derive(Debug)]
+pub struct SimulatedProbe {
+    target: SimulatedTarget,
+    speed_khz: u32,
+
+    ctrl_stat: u32,
+    select: u32,
+    sticky_error: bool,
+
+    csw: u32,
+    tar: u32,
+    rdbuff: u32,
+}
+
+impl SimulatedProbe {
+    pub fn new() -> Self {
+        Self {
+            target: SimulatedTarget {
+                core: Arc::new(Mutex::new(CortexM::new())),
+            },
+            speed_khz: 1000,
+            ctrl_stat: 0,
+            select: 0,
+            sticky_error: false,
+            csw: 0,
+            tar: 0,
+            rdbuff: 0,
+        }
+    }
+
+    /// Get a handle to the simulated target.
+    pub fn target(&self) -> SimulatedTarget {
+        self.target.clone()
+    }
+
+    /// Add the `simulated_m4` target to the registry, so it can be used to attach.
+    ///
+    /// It is not part of the built-in targets, as it is only useful for tests.
+    pub fn add_target() -> Result<(), RegistryError> {
+        add_target_family(SIMULATED_FAMILY)
+    }
+
+    fn apsel(&self) -> u32 {
+        self.select >> 24
+    }
+
+    /// The address of an AP register, using the bank selected in SELECT.
+    fn ap_register_address(&self, addr: u16) -> u16 {
+        (((self.select >> 4) & 0xF) << 4) as u16 | (addr & 0xC)
+    }
+
+    /// Signal a FAULT response, which sets the sticky error flag.
+    fn fault(&mut self) -> DebugProbeError {
+        self.sticky_error = true;
+        DapError::FaultResponse.into()
+    }
+
+    /// Advance TAR after an access to DRW. The increment wraps within 1 KiB.
+    fn increment_tar(&mut self) {
+        let increment = match (self.csw >> CSW_ADDRINC_SHIFT) & 0b11 {
+            0 => return,
+            1 => 1 << (self.csw & CSW_SIZE_MASK),
+            _ => 4,
+        };
+
+        self.tar = (self.tar & !0x3FF) | (self.tar.wrapping_add(increment) & 0x3FF);
+    }
+
+    /// The byte lanes used by an access of the size selected in CSW.
+    fn byte_lanes(&self, address: u32) -> Option<u32> {
+        match self.csw & CSW_SIZE_MASK {
+            0 => Some(0xFF << ((address & 0b11) * 8)),
+            1 if address & 0b1 == 0 => Some(0xFFFF << ((address & 0b10) * 8)),
+            2 if address & 0b11 == 0 => Some(0xFFFF_FFFF),
+            _ => None,
+        }
+    }
+
+    fn read_memory_ap(&mut self, address: u32) -> Result<u32, DebugProbeError> {
+        let lanes = self.byte_lanes(address).ok_or_else(|| self.fault())?;
+        let value = self.target.lock().read_word(address & !0b11);
+
+        Ok(value & lanes)
+    }
+
+    fn write_memory_ap(&mut self, address: u32, value: u32) -> Result<(), DebugProbeError> {
+        let lanes = self.byte_lanes(address).ok_or_else(|| self.fault())?;
+        self.target.lock().write_word(address & !0b11, value, lanes);
+
+        Ok(())
+    }
+
+    fn read_ap_register(&mut self, addr: u16) -> Result<u32, DebugProbeError> {
+        if self.apsel() != 0 {
+            // Only the IDR of non-existing APs can be read, and it is zero.
+            return Ok(0);
+        }
+
+        match self.ap_register_address(addr) {
+            AP_CSW => Ok(self.csw | CSW_DEVICEEN),
+            AP_TAR => Ok(self.tar),
+            AP_DRW => {
+                let value = self.read_memory_ap(self.tar)?;
+                self.increment_tar();
+                Ok(value)
+            }
+            address @ AP_BD0..=AP_BD3 => {
+                let offset = u32::from(address - AP_BD0);
+                self.read_memory_ap((self.tar & !0xF) | offset)
+            }
+            AP_BASE2 | AP_CFG => Ok(0),
+            // The ROM table is present, using the ADIv5 format.
+            AP_BASE => Ok(ROM_TABLE_ADDRESS | 0b11),
+            AP_IDR => Ok(AHB_AP_IDR),
+            address => {
+                log::warn!("Read of unknown AP register {:#04x}", address);
+                Ok(0)
+            }
+        }
+    }
+
+    fn write_ap_register(&mut self, addr: u16, value: u32) -> Result<(), DebugProbeError> {
+        if self.apsel() != 0 {
+            return Err(self.fault());
+        }
+
+        match self.ap_register_address(addr) {
+            AP_CSW => {
+                if value & CSW_SIZE_MASK > 2 {
+                    return Err(self.fault());
+                }
+
+                self.csw = value & !CSW_DEVICEEN;
+            }
+            AP_TAR => self.tar = value,
+            AP_DRW => {
+                self.write_memory_ap(self.tar, value)?;
+                self.increment_tar();
+            }
+            address @ AP_BD0..=AP_BD3 => {
+                let offset = u32::from(address - AP_BD0);
+                self.write_memory_ap((self.tar & !0xF) | offset, value)?;
+            }
+            address => log::warn!("Write of read-only AP register {:#04x}", address),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for SimulatedProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugProbe for SimulatedProbe {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::Other(
+                "A simulated probe has to be created using SimulatedProbe::new.",
+            ),
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "Simulated probe"
+    }
+
+    fn speed(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        self.speed_khz = speed_khz;
+        Ok(speed_khz)
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.target.lock().reset();
+        Ok(())
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.target.lock().set_reset(true);
+        Ok(())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.target.lock().set_reset(false);
+        Ok(())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        match protocol {
+            WireProtocol::Swd => Ok(()),
+            protocol => Err(DebugProbeError::UnsupportedProtocol(protocol)),
+        }
+    }
+
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        Ok(ProbeCapabilities {
+            firmware_version: None,
+            protocols: vec![WireProtocol::Swd],
+            max_speed_khz: None,
+            swo_uart: false,
+            swo_manchester: false,
+            reset_control: true,
+            target_voltage: true,
+        })
+    }
+
+    fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        Ok(Some(TARGET_VOLTAGE))
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Option<Box<dyn ArmProbeInterface + 'probe>>, DebugProbeError> {
+        let interface = ArmCommunicationInterface::new(self, false)?;
+
+        Ok(Some(Box::new(interface)))
+    }
+
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
+}
+
+impl DAPAccess for SimulatedProbe {
+    fn read_register(&mut self, port: PortType, addr: u16) -> Result<u32, DebugProbeError> {
+        match port {
+            PortType::DebugPort => match addr {
+                DP_DPIDR_ABORT => Ok(DPIDR),
+                DP_CTRL_STAT => {
+                    // Power up requests are acknowledged immediately.
+                    let acknowledge = (self.ctrl_stat & (CSYSPWRUPREQ | CDBGPWRUPREQ)) << 1;
+                    let sticky_error = if self.sticky_error { STICKYERR } else { 0 };
+
+                    Ok(self.ctrl_stat | acknowledge | sticky_error)
+                }
+                DP_SELECT => Ok(self.select),
+                DP_RDBUFF => Ok(self.rdbuff),
+                _ => Err(self.fault()),
+            },
+            PortType::AccessPort(_) => {
+                if self.sticky_error {
+                    return Err(DapError::FaultResponse.into());
+                }
+
+                let value = self.read_ap_register(addr)?;
+                self.rdbuff = value;
+
+                Ok(value)
+            }
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        port: PortType,
+        addr: u16,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        match port {
+            PortType::DebugPort => match addr {
+                DP_DPIDR_ABORT => {
+                    if value & STKERRCLR != 0 {
+                        self.sticky_error = false;
+                    }
+                }
+                DP_CTRL_STAT => self.ctrl_stat = value & (CSYSPWRUPREQ | CDBGPWRUPREQ),
+                DP_SELECT => self.select = value,
+                _ => return Err(self.fault()),
+            },
+            PortType::AccessPort(_) => {
+                if self.sticky_error {
+                    return Err(DapError::FaultResponse.into());
+                }
+
+                self.write_ap_register(addr, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl<'a> AsRef<dyn DebugProbe + 'a> for SimulatedProbe {
+    fn as_ref(&self) -> &(dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+impl<'a> AsMut<dyn DebugProbe + 'a> for SimulatedProbe {
+    fn as_mut(&mut self) -> &mut (dyn DebugProbe + 'a) {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SimulatedProbe, DPIDR};
+    use crate::architecture::arm::{DAPAccess, PortType};
+    use crate::DebugProbeError;
+
+    fn powered_up_probe() -> SimulatedProbe {
+        let mut probe = SimulatedProbe::new();
+        probe
+            .write_register(PortType::DebugPort, 0x4, 0x5000_0000)
+            .unwrap();
+        probe
+    }
+
+    #[test]
+    fn debug_port() {
+        let mut probe = SimulatedProbe::new();
+
+        assert_eq!(
+            probe.read_register(PortType::DebugPort, 0x0).unwrap(),
+            DPIDR
+        );
+
+        probe
+            .write_register(PortType::DebugPort, 0x4, 0x5000_0000)
+            .unwrap();
+        assert_eq!(
+            probe.read_register(PortType::DebugPort, 0x4).unwrap(),
+            0xF000_0000
+        );
+    }
+
+    #[test]
+    fn access_port_identification() {
+        let mut probe = powered_up_probe();
+
+        // IDR of AP 0 and AP 1.
+        probe
+            .write_register(PortType::DebugPort, 0x8, 0x0000_00F0)
+            .unwrap();
+        assert_eq!(
+            probe.read_register(PortType::AccessPort(0), 0xFC).unwrap(),
+            0x2477_0011
+        );
+
+        probe
+            .write_register(PortType::DebugPort, 0x8, 0x0100_00F0)
+            .unwrap();
+        assert_eq!(
+            probe.read_register(PortType::AccessPort(1), 0xFC).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn memory_access_with_auto_increment() {
+        let mut probe = powered_up_probe();
+        let target = probe.target();
+
+        // 32 bit accesses, single increment.
+        probe
+            .write_register(PortType::AccessPort(0), 0x0, 0x0000_0012)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0x4, 0x2000_03FC)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0xC, 0x1234_5678)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0xC, 0x9ABC_DEF0)
+            .unwrap();
+
+        // The increment wraps within 1 KiB.
+        assert_eq!(
+            probe.read_register(PortType::AccessPort(0), 0x4).unwrap(),
+            0x2000_0004
+        );
+
+        let mut data = [0u8; 4];
+        target.read_memory(0x2000_03FC, &mut data);
+        assert_eq!(data, [0x78, 0x56, 0x34, 0x12]);
+        target.read_memory(0x2000_0000, &mut data);
+        assert_eq!(data, [0xF0, 0xDE, 0xBC, 0x9A]);
+
+        // 8 bit read, using the byte lane of the address.
+        probe
+            .write_register(PortType::AccessPort(0), 0x0, 0x0000_0000)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0x4, 0x2000_03FD)
+            .unwrap();
+        assert_eq!(
+            probe.read_register(PortType::AccessPort(0), 0xC).unwrap(),
+            0x0000_5600
+        );
+    }
+
+    #[test]
+    fn unaligned_access_sets_sticky_error() {
+        let mut probe = powered_up_probe();
+
+        probe
+            .write_register(PortType::AccessPort(0), 0x0, 0x0000_0002)
+            .unwrap();
+        probe
+            .write_register(PortType::AccessPort(0), 0x4, 0x2000_0002)
+            .unwrap();
+
+        let result = probe.read_register(PortType::AccessPort(0), 0xC);
+        assert!(matches!(
+            result,
+            Err(DebugProbeError::ArchitectureSpecific(_))
+        ));
+
+        let ctrl_stat = probe.read_register(PortType::DebugPort, 0x4).unwrap();
+        assert_ne!(ctrl_stat & (1 << 5), 0);
+
+        // Clear the sticky error using ABORT.
+        probe
+            .write_register(PortType::DebugPort, 0x0, 0x0000_0004)
+            .unwrap();
+        let ctrl_stat = probe.read_register(PortType::DebugPort, 0x4).unwrap();
+        assert_eq!(ctrl_stat & (1 << 5), 0);
+    }
+}
@@ -3,7 +3,10 @@ pub mod tools;
 mod usb_interface;
 
 use self::usb_interface::{STLinkUSBDevice, StLinkUsb};
-use super::{DAPAccess, DebugProbe, DebugProbeError, PortType, ProbeCreationError, WireProtocol};
+use super::{
+    DAPAccess, DebugProbe, DebugProbeError, PortType, ProbeCapabilities, ProbeCreationError,
+    WireProtocol,
+};
 use crate::{
     architecture::arm::communication_interface::MemoryApInformation,
     architecture::arm::{
@@ -12,7 +15,7 @@ use crate::{
             GenericAP, MemoryAP, BASE, BASE2, CSW, IDR,
         },
         communication_interface::{ArmCommunicationInterfaceState, ArmProbeInterface},
-        dp::{DPAccess, DPBankSel, DPRegister, DebugPortError, Select},
+        dp::{DPAccess, DPBankSel, DPRegister, DebugPortError, DpAddress, Select},
         memory::{adi_v5_memory_interface::ArmProbe, Component},
         ApInformation, ArmChipInfo, SwoAccess, SwoConfig, SwoMode,
     },
@@ -234,6 +237,33 @@ impl DebugProbe for STLink<STLinkUSBDevice> {
         Ok(())
     }
 
+    fn capabilities(&mut self) -> Result<ProbeCapabilities, DebugProbeError> {
+        let max_speed_khz = match self.hw_version.cmp(&3) {
+            Ordering::Less => match self.protocol {
+                WireProtocol::Swd => SwdFrequencyToDelayCount::Hz4600000.to_khz(),
+                WireProtocol::Jtag => JTagFrequencyToDivider::Hz18000000.to_khz(),
+            },
+            _ => {
+                let (available, _) = self.get_communication_frequencies(self.protocol)?;
+                available.into_iter().max().unwrap_or_default()
+            }
+        };
+
+        Ok(ProbeCapabilities {
+            firmware_version: Some(format!("V{}J{}", self.hw_version, self.jtag_version)),
+            protocols: vec![WireProtocol::Swd, WireProtocol::Jtag],
+            max_speed_khz: Some(max_speed_khz),
+            swo_uart: true,
+            swo_manchester: false,
+            reset_control: true,
+            target_voltage: true,
+        })
+    }
+
+    fn target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        self.get_target_voltage().map(Some)
+    }
+
     fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
         Some(self as _)
     }
@@ -253,6 +283,12 @@ impl DebugProbe for STLink<STLinkUSBDevice> {
     fn has_arm_interface(&self) -> bool {
         true
     }
+
+    /// The registers are accessed using the DAP commands of the ST-Link, which is
+    /// slower than the memory access of [StlinkArmDebug].
+    fn dap_access_mut(&mut self) -> Option<&mut dyn DAPAccess> {
+        Some(self as _)
+    }
 }
 
 impl DAPAccess for STLink<STLinkUSBDevice> {
@@ -1353,6 +1389,49 @@ impl<'probe> ArmProbeInterface for StlinkArmDebug {
         self.state.ap_information.len()
     }
 
+    fn select_debug_port(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        match dp {
+            DpAddress::Default => Ok(()),
+            // The ST-Link firmware does not support SWD multi-drop.
+            DpAddress::Multidrop(_) => Err(DebugProbeError::NotImplemented("SWD multi-drop")),
+        }
+    }
+
+    fn read_raw_ap_register(&mut self, port: u8, address: u8) -> Result<u32, DebugProbeError> {
+        log::debug!("Reading register {:#04x} of AP {}", address, port);
+        self.select_ap_and_ap_bank(port, address >> 4)?;
+
+        let value = self.probe.read_register(
+            PortType::AccessPort(u16::from(self.state.current_apsel)),
+            u16::from(address),
+        )?;
+
+        log::debug!("Read register    {:#04x}, value=0x{:08x}", address, value);
+
+        Ok(value)
+    }
+
+    fn write_raw_ap_register(
+        &mut self,
+        port: u8,
+        address: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        log::debug!(
+            "Writing register {:#04x} of AP {}, value=0x{:08x}",
+            address,
+            port,
+            value
+        );
+        self.select_ap_and_ap_bank(port, address >> 4)?;
+
+        self.probe.write_register(
+            PortType::AccessPort(u16::from(self.state.current_apsel)),
+            u16::from(address),
+            value,
+        )
+    }
+
     fn close(self: Box<Self>) -> Probe {
         Probe::from_attached_probe(self.probe)
     }
@@ -0,0 +1,606 @@
+use super::{
+    complete_request, read_c_string, read_string, SemihostingCommand, ADP_STOPPED_APPLICATION_EXIT,
+};
+use crate::{Core, Error, MemoryInterface};
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Special file name used to open the console.
+const CONSOLE_PATH: &str = ":tt";
+
+const STDIN_HANDLE: u32 = 0;
+const STDOUT_HANDLE: u32 = 1;
+const STDERR_HANDLE: u32 = 2;
+
+/// Frequency of the ticks reported by `SYS_ELAPSED`, in Hz.
+const TICK_FREQUENCY: u32 = 1_000_000;
+
+/// Maximum length of a string written by `SYS_WRITE0`.
+const MAX_WRITE0_LENGTH: u32 = 4096;
+
+/// Maximum length of a path passed by the target.
+const MAX_PATH_LENGTH: u32 = 4096;
+
+/// Maximum number of bytes transferred by a single `SYS_READ` or `SYS_WRITE`.
+///
+/// Longer requests are shortened, which the target sees as a partial transfer.
+const MAX_TRANSFER_LENGTH: u32 = 0x1_0000;
+
+// errno values reported to the target. These follow the values used by newlib.
+const EBADF: i32 = 9;
+const EACCES: i32 = 13;
+const EINVAL: i32 = 22;
+const ENOSYS: i32 = 88;
+
+/// The result of servicing a semihosting request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SemihostingOutcome {
+    /// The request was handled, and the core can be resumed.
+    Continue,
+    /// The application requested to exit, the core is left halted.
+    ///
+    /// `reason` is the reason code passed to `SYS_EXIT`, `code` is the exit status
+    /// of the application, if it exited normally.
+    Exit { reason: u32, code: Option<u32> },
+}
+
+impl SemihostingOutcome {
+    /// Returns `true` if the application exited normally with exit status zero.
+    pub fn is_success(&self) -> bool {
+        match self {
+            SemihostingOutcome::Exit {
+                reason: ADP_STOPPED_APPLICATION_EXIT,
+                code,
+            } => code.unwrap_or(0) == 0,
+            _ => false,
+        }
+    }
+}
+
+/// Performs semihosting operations on the host.
+///
+/// Console operations are performed on the console given to
+/// [SemihostingHost::with_console], or on `stdin` / `stdout` by default.
+///
+/// File operations are restricted to a sandbox directory. If no directory is
+/// configured using [SemihostingHost::with_root], all file operations fail.
+pub struct SemihostingHost {
+    root: Option<PathBuf>,
+    command_line: String,
+    files: HashMap<u32, File>,
+    next_handle: u32,
+    errno: i32,
+    start: Instant,
+    input: Box<dyn Read + Send>,
+    output: Box<dyn Write + Send>,
+}
+
+impl std::fmt::Debug for SemihostingHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SemihostingHost")
+            .field("root", &self.root)
+            .field("command_line", &self.command_line)
+            .field("open_files", &self.files.len())
+            .field("errno", &self.errno)
+            .finish()
+    }
+}
+
+impl Default for SemihostingHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SemihostingHost {
+    /// Create a new host, which uses the console of the current process
+    /// and does not allow any file access.
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            command_line: String::new(),
+            files: HashMap::new(),
+            next_handle: STDERR_HANDLE + 1,
+            errno: 0,
+            start: Instant::now(),
+            input: Box::new(io::stdin()),
+            output: Box::new(io::stdout()),
+        }
+    }
+
+    /// Allow file access to all files in the directory `root`.
+    ///
+    /// Paths given by the target are interpreted relative to this directory,
+    /// and cannot refer to files outside of it.
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Set the command line returned by `SYS_GET_CMDLINE`.
+    pub fn with_command_line(mut self, command_line: impl Into<String>) -> Self {
+        self.command_line = command_line.into();
+        self
+    }
+
+    /// Use the given reader and writer for console input and output.
+    pub fn with_console(
+        mut self,
+        input: impl Read + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        self.input = Box::new(input);
+        self.output = Box::new(output);
+        self
+    }
+
+    /// The errno value of the last failed operation.
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+
+    /// Set the errno value, e.g. when an operation was performed by somebody else.
+    pub fn set_errno(&mut self, errno: i32) {
+        self.errno = errno;
+    }
+
+    /// Service a pending semihosting request, and resume the core afterwards.
+    ///
+    /// If the core is not halted on a semihosting request, `None` is returned
+    /// and the core is not touched. If the application requested to exit, the
+    /// core is left halted.
+    pub fn service(&mut self, core: &mut Core) -> Result<Option<SemihostingOutcome>, Error> {
+        let command = match super::check_semihosting(core)? {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+
+        let outcome = self.handle(core, command)?;
+
+        if outcome == SemihostingOutcome::Continue {
+            core.run()?;
+        }
+
+        Ok(Some(outcome))
+    }
+
+    /// Perform the operation `command` and write the result back to the core.
+    ///
+    /// The core is left halted after the semihosting instruction.
+    pub fn handle(
+        &mut self,
+        core: &mut Core,
+        command: SemihostingCommand,
+    ) -> Result<SemihostingOutcome, Error> {
+        if let SemihostingCommand::Exit { reason, subcode } = command {
+            log::info!(
+                "Target exited with reason {:#x} and subcode {:?}",
+                reason,
+                subcode
+            );
+
+            let code = match reason {
+                ADP_STOPPED_APPLICATION_EXIT => Some(subcode.unwrap_or(0)),
+                _ => None,
+            };
+
+            return Ok(SemihostingOutcome::Exit { reason, code });
+        }
+
+        let result = self.perform(core, command)?;
+
+        complete_request(core, result as u32)?;
+
+        Ok(SemihostingOutcome::Continue)
+    }
+
+    fn perform(&mut self, core: &mut Core, command: SemihostingCommand) -> Result<i32, Error> {
+        let result = match command {
+            SemihostingCommand::Open {
+                path,
+                path_len,
+                mode,
+            } => {
+                let path = read_string(core, path, path_len, MAX_PATH_LENGTH)?;
+                self.open(&path, mode)
+            }
+            SemihostingCommand::Close { handle } => self.close(handle),
+            SemihostingCommand::WriteC { address } => {
+                let character = core.read_word_8(address)?;
+                self.write_console(STDOUT_HANDLE, &[character]);
+                0
+            }
+            SemihostingCommand::Write0 { address } => {
+                let string = read_c_string(core, address, MAX_WRITE0_LENGTH)?;
+                self.write_console(STDOUT_HANDLE, &string);
+                0
+            }
+            SemihostingCommand::Write {
+                handle,
+                buffer,
+                length,
+            } => {
+                let mut data = vec![0u8; length.min(MAX_TRANSFER_LENGTH) as usize];
+                core.read_8(buffer, &mut data)?;
+
+                // SYS_WRITE returns the number of bytes which were *not* written.
+                match self.write(handle, &data) {
+                    Some(written) => (length - written as u32) as i32,
+                    None => length as i32,
+                }
+            }
+            SemihostingCommand::Read {
+                handle,
+                buffer,
+                length,
+            } => {
+                let mut data = vec![0u8; length.min(MAX_TRANSFER_LENGTH) as usize];
+
+                // SYS_READ returns the number of bytes which were *not* read.
+                match self.read(handle, &mut data) {
+                    Some(read) => {
+                        core.write_8(buffer, &data[..read])?;
+                        (length - read as u32) as i32
+                    }
+                    None => -1,
+                }
+            }
+            SemihostingCommand::ReadC => {
+                let mut character = [0u8];
+                match self.input.read_exact(&mut character) {
+                    Ok(()) => i32::from(character[0]),
+                    Err(e) => self.fail(e),
+                }
+            }
+            SemihostingCommand::IsError { status } => (status < 0) as i32,
+            SemihostingCommand::IsTty { handle } => match handle {
+                STDIN_HANDLE | STDOUT_HANDLE | STDERR_HANDLE => 1,
+                handle if self.files.contains_key(&handle) => 0,
+                _ => self.fail_with(EBADF),
+            },
+            SemihostingCommand::Seek { handle, position } => {
+                let result = self
+                    .files
+                    .get_mut(&handle)
+                    .map(|file| file.seek(SeekFrom::Start(u64::from(position))));
+
+                match result {
+                    Some(Ok(_)) => 0,
+                    Some(Err(e)) => self.fail(e),
+                    None => self.fail_with(EBADF),
+                }
+            }
+            SemihostingCommand::Flen { handle } => {
+                let result = self.files.get(&handle).map(|file| file.metadata());
+
+                match result {
+                    Some(Ok(metadata)) => metadata.len() as i32,
+                    Some(Err(e)) => self.fail(e),
+                    None => self.fail_with(EBADF),
+                }
+            }
+            SemihostingCommand::Remove { path, path_len } => {
+                let path = read_string(core, path, path_len, MAX_PATH_LENGTH)?;
+                match self.resolve(&path) {
+                    Some(path) => match std::fs::remove_file(path) {
+                        Ok(()) => 0,
+                        Err(e) => self.fail(e),
+                    },
+                    None => self.fail_with(EACCES),
+                }
+            }
+            SemihostingCommand::Rename {
+                from,
+                from_len,
+                to,
+                to_len,
+            } => {
+                let from = read_string(core, from, from_len, MAX_PATH_LENGTH)?;
+                let to = read_string(core, to, to_len, MAX_PATH_LENGTH)?;
+                match (self.resolve(&from), self.resolve(&to)) {
+                    (Some(from), Some(to)) => match std::fs::rename(from, to) {
+                        Ok(()) => 0,
+                        Err(e) => self.fail(e),
+                    },
+                    _ => self.fail_with(EACCES),
+                }
+            }
+            SemihostingCommand::Clock => (self.start.elapsed().as_millis() / 10) as i32,
+            SemihostingCommand::Time => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs() as i32)
+                .unwrap_or(0),
+            SemihostingCommand::Errno => self.errno,
+            SemihostingCommand::GetCmdline {
+                block,
+                buffer,
+                length,
+            } => {
+                let mut command_line = self.command_line.clone().into_bytes();
+                command_line.push(0);
+
+                if command_line.len() > length as usize {
+                    self.fail_with(EINVAL)
+                } else {
+                    core.write_8(buffer, &command_line)?;
+                    // The length written back does not include the terminating null byte.
+                    core.write_word_32(block + 4, command_line.len() as u32 - 1)?;
+                    0
+                }
+            }
+            SemihostingCommand::HeapInfo { block } => {
+                // Reporting zero for all values tells the C library
+                // to use the values from the linker script.
+                let info_block = core.read_word_32(block)?;
+                core.write_32(info_block, &[0, 0, 0, 0])?;
+                0
+            }
+            SemihostingCommand::Elapsed { block } => {
+                let ticks = self.start.elapsed().as_micros() as u64;
+                core.write_32(block, &[ticks as u32, (ticks >> 32) as u32])?;
+                0
+            }
+            SemihostingCommand::TickFreq => TICK_FREQUENCY as i32,
+            // Running commands on the host is not something a target should be able to do.
+            SemihostingCommand::System { .. } | SemihostingCommand::TmpNam { .. } => {
+                self.fail_with(ENOSYS)
+            }
+            SemihostingCommand::Unknown { operation, .. } => {
+                log::warn!("Unsupported semihosting operation {:#04x}", operation);
+                self.fail_with(ENOSYS)
+            }
+            SemihostingCommand::Exit { .. } => unreachable!("Exit is handled by the caller"),
+        };
+
+        Ok(result)
+    }
+
+    /// Resolve a path given by the target to a path inside the sandbox directory.
+    ///
+    /// Returns `None` if file access is disabled, or if the path would
+    /// refer to a file outside of the sandbox. Symbolic links are only
+    /// followed if they point to a file inside of the sandbox.
+    pub(crate) fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+        let canonical_root = root.canonicalize().unwrap_or_else(|_| root.clone());
+
+        let path = Path::new(path);
+
+        let mut resolved = root.clone();
+
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+
+                    let is_link = std::fs::symlink_metadata(&resolved)
+                        .map(|metadata| metadata.file_type().is_symlink())
+                        .unwrap_or(false);
+
+                    // A link which can't be resolved is rejected as well, because
+                    // creating a file would follow it.
+                    let inside_root = !is_link
+                        || resolved
+                            .canonicalize()
+                            .map(|target| target.starts_with(&canonical_root))
+                            .unwrap_or(false);
+
+                    if !inside_root {
+                        log::warn!(
+                            "Target tried to access {:?}, which links outside of the sandbox",
+                            path
+                        );
+                        return None;
+                    }
+                }
+                Component::CurDir => (),
+                // Absolute paths and references to parent directories could
+                // be used to escape the sandbox.
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    log::warn!("Target tried to access {:?}, which is not allowed", path);
+                    return None;
+                }
+            }
+        }
+
+        Some(resolved)
+    }
+
+    fn open(&mut self, path: &str, mode: u32) -> i32 {
+        if path == CONSOLE_PATH {
+            return match mode {
+                0..=3 => STDIN_HANDLE as i32,
+                4..=7 => STDOUT_HANDLE as i32,
+                _ => STDERR_HANDLE as i32,
+            };
+        }
+
+        let resolved = match self.resolve(path) {
+            Some(resolved) => resolved,
+            None => return self.fail_with(EACCES),
+        };
+
+        let mut options = OpenOptions::new();
+
+        // The mode corresponds to the ISO C fopen modes, in the order
+        // "r", "rb", "r+", "r+b", "w", "wb", "w+", "w+b", "a", "ab", "a+", "a+b".
+        match mode / 4 {
+            0 => options.read(true).write(mode & 2 != 0),
+            1 => options
+                .write(true)
+                .read(mode & 2 != 0)
+                .create(true)
+                .truncate(true),
+            2 => options.append(true).read(mode & 2 != 0).create(true),
+            _ => return self.fail_with(EINVAL),
+        };
+
+        match options.open(&resolved) {
+            Ok(file) => {
+                let handle = self.next_handle;
+                self.next_handle += 1;
+
+                log::debug!("Opened {:?} as handle {}", resolved, handle);
+
+                self.files.insert(handle, file);
+                handle as i32
+            }
+            Err(e) => self.fail(e),
+        }
+    }
+
+    fn close(&mut self, handle: u32) -> i32 {
+        match handle {
+            STDIN_HANDLE | STDOUT_HANDLE | STDERR_HANDLE => 0,
+            handle => match self.files.remove(&handle) {
+                Some(_) => 0,
+                None => self.fail_with(EBADF),
+            },
+        }
+    }
+
+    /// Write to a handle, returning the number of bytes written.
+    fn write(&mut self, handle: u32, data: &[u8]) -> Option<usize> {
+        match handle {
+            STDOUT_HANDLE | STDERR_HANDLE => {
+                self.write_console(handle, data);
+                Some(data.len())
+            }
+            handle => match self.files.get_mut(&handle).map(|file| file.write(data)) {
+                Some(Ok(written)) => Some(written),
+                Some(Err(e)) => {
+                    self.fail(e);
+                    None
+                }
+                None => {
+                    self.fail_with(EBADF);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Read from a handle, returning the number of bytes read.
+    fn read(&mut self, handle: u32, data: &mut [u8]) -> Option<usize> {
+        let result = match handle {
+            STDIN_HANDLE => self.input.read(data),
+            handle => match self.files.get_mut(&handle) {
+                Some(file) => file.read(data),
+                None => {
+                    self.fail_with(EBADF);
+                    return None;
+                }
+            },
+        };
+
+        match result {
+            Ok(read) => Some(read),
+            Err(e) => {
+                self.fail(e);
+                None
+            }
+        }
+    }
+
+    fn write_console(&mut self, handle: u32, data: &[u8]) {
+        let result = if handle == STDERR_HANDLE {
+            io::stderr().write_all(data)
+        } else {
+            self.output
+                .write_all(data)
+                .and_then(|_| self.output.flush())
+        };
+
+        if let Err(e) = result {
+            log::warn!("Failed to write semihosting output: {}", e);
+        }
+    }
+
+    fn fail(&mut self, error: io::Error) -> i32 {
+        log::debug!("Semihosting operation failed: {}", error);
+        self.fail_with(error.raw_os_error().unwrap_or(EINVAL))
+    }
+
+    fn fail_with(&mut self, errno: i32) -> i32 {
+        self.errno = errno;
+        -1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::semihosting::ADP_STOPPED_RUN_TIME_ERROR_UNKNOWN;
+
+    #[test]
+    fn resolve_without_root_is_rejected() {
+        let host = SemihostingHost::new();
+
+        assert_eq!(host.resolve("test.txt"), None);
+    }
+
+    #[test]
+    fn resolve_inside_root() {
+        let host = SemihostingHost::new().with_root("/tmp/sandbox");
+
+        assert_eq!(
+            host.resolve("./output/test.txt"),
+            Some(PathBuf::from("/tmp/sandbox/output/test.txt"))
+        );
+    }
+
+    #[test]
+    fn resolve_outside_root_is_rejected() {
+        let host = SemihostingHost::new().with_root("/tmp/sandbox");
+
+        assert_eq!(host.resolve("../secret.txt"), None);
+        assert_eq!(host.resolve("/etc/passwd"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_link_outside_root_is_rejected() {
+        let root = std::env::temp_dir().join(format!("probe-rs-sandbox-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+
+        std::os::unix::fs::symlink("/etc", root.join("outside")).unwrap();
+        std::os::unix::fs::symlink("/nonexistent/file", root.join("dangling")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("inside")).unwrap();
+
+        let host = SemihostingHost::new().with_root(&root);
+
+        let outside = host.resolve("outside/passwd");
+        let dangling = host.resolve("dangling");
+        let inside = host.resolve("inside/test.txt");
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(outside, None);
+        assert_eq!(dangling, None);
+        assert_eq!(inside, Some(root.join("inside/test.txt")));
+    }
+
+    #[test]
+    fn exit_status() {
+        let success = SemihostingOutcome::Exit {
+            reason: ADP_STOPPED_APPLICATION_EXIT,
+            code: Some(0),
+        };
+        let failure = SemihostingOutcome::Exit {
+            reason: ADP_STOPPED_APPLICATION_EXIT,
+            code: Some(3),
+        };
+        let error = SemihostingOutcome::Exit {
+            reason: ADP_STOPPED_RUN_TIME_ERROR_UNKNOWN,
+            code: None,
+        };
+
+        assert!(success.is_success());
+        assert!(!failure.is_success());
+        assert!(!error.is_success());
+    }
+}
@@ -0,0 +1,354 @@
+//! Semihosting support
+//!
+//! Semihosting allows a program running on the target to use I/O facilities of the
+//! host computer, e.g. to print to the console, to access files or to report an exit status.
+//!
+//! The target requests an operation by executing a special breakpoint instruction, with the
+//! operation number in the first argument register and a pointer to a parameter block
+//! (or a single parameter) in the second argument register:
+//!
+//! - On ARM Cortex-M cores, this is the `BKPT 0xAB` instruction, and the arguments are passed in `R0` and `R1`.
+//! - On RISC-V cores, this is an `ebreak` instruction surrounded by `slli x0, x0, 0x1f` and
+//!   `srai x0, x0, 7`, with the arguments passed in `a0` and `a1`.
+//!
+//! When the core halts due to such a request, the halt reason is reported as
+//! [HaltReason::Breakpoint] with [BreakpointCause::Semihosting]. The request can then be
+//! decoded using [SemihostingCommand::decode], and serviced using a [SemihostingHost].
+//!
+//! See the [ARM semihosting specification] for a description of the operations.
+//!
+//! [ARM semihosting specification]: https://github.com/ARM-software/abi-aa/blob/main/semihosting/semihosting.rst
+//! [HaltReason::Breakpoint]: crate::HaltReason::Breakpoint
+//! [BreakpointCause::Semihosting]: crate::BreakpointCause::Semihosting
+
+mod host;
+
+pub use host::{SemihostingHost, SemihostingOutcome};
+
+use crate::{Architecture, BreakpointCause, Core, CoreStatus, Error, HaltReason, MemoryInterface};
+
+/// Operation numbers of the semihosting operations, as passed in the first argument register.
+pub mod operation {
+    pub const SYS_OPEN: u32 = 0x01;
+    pub const SYS_CLOSE: u32 = 0x02;
+    pub const SYS_WRITEC: u32 = 0x03;
+    pub const SYS_WRITE0: u32 = 0x04;
+    pub const SYS_WRITE: u32 = 0x05;
+    pub const SYS_READ: u32 = 0x06;
+    pub const SYS_READC: u32 = 0x07;
+    pub const SYS_ISERROR: u32 = 0x08;
+    pub const SYS_ISTTY: u32 = 0x09;
+    pub const SYS_SEEK: u32 = 0x0A;
+    pub const SYS_FLEN: u32 = 0x0C;
+    pub const SYS_TMPNAM: u32 = 0x0D;
+    pub const SYS_REMOVE: u32 = 0x0E;
+    pub const SYS_RENAME: u32 = 0x0F;
+    pub const SYS_CLOCK: u32 = 0x10;
+    pub const SYS_TIME: u32 = 0x11;
+    pub const SYS_SYSTEM: u32 = 0x12;
+    pub const SYS_ERRNO: u32 = 0x13;
+    pub const SYS_GET_CMDLINE: u32 = 0x15;
+    pub const SYS_HEAPINFO: u32 = 0x16;
+    pub const SYS_EXIT: u32 = 0x18;
+    pub const SYS_EXIT_EXTENDED: u32 = 0x20;
+    pub const SYS_ELAPSED: u32 = 0x30;
+    pub const SYS_TICKFREQ: u32 = 0x31;
+}
+
+/// Reason code used by `SYS_EXIT` to signal a normal termination of the application.
+pub const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x20026;
+
+/// Reason code used by `SYS_EXIT` to signal an unknown runtime error.
+pub const ADP_STOPPED_RUN_TIME_ERROR_UNKNOWN: u32 = 0x20023;
+
+/// A decoded semihosting request.
+///
+/// Pointers into target memory are kept as addresses, the referenced
+/// data is only read when the request is serviced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SemihostingCommand {
+    /// Open the file at `path` with the given ISO C `fopen` mode (0 = `"r"` ... 11 = `"a+b"`).
+    Open { path: u32, path_len: u32, mode: u32 },
+    /// Close a file handle.
+    Close { handle: u32 },
+    /// Write the character at `address` to the console.
+    WriteC { address: u32 },
+    /// Write the null-terminated string at `address` to the console.
+    Write0 { address: u32 },
+    /// Write `length` bytes from `buffer` to a file handle.
+    Write {
+        handle: u32,
+        buffer: u32,
+        length: u32,
+    },
+    /// Read up to `length` bytes from a file handle into `buffer`.
+    Read {
+        handle: u32,
+        buffer: u32,
+        length: u32,
+    },
+    /// Read a character from the console.
+    ReadC,
+    /// Check if a status code returned by another operation is an error.
+    IsError { status: i32 },
+    /// Check if a file handle is connected to an interactive device.
+    IsTty { handle: u32 },
+    /// Seek to an absolute position in a file.
+    Seek { handle: u32, position: u32 },
+    /// Get the length of a file.
+    Flen { handle: u32 },
+    /// Create a name for a temporary file.
+    TmpNam { buffer: u32, id: u32, length: u32 },
+    /// Delete the file at `path`.
+    Remove { path: u32, path_len: u32 },
+    /// Rename the file at `from` to `to`.
+    Rename {
+        from: u32,
+        from_len: u32,
+        to: u32,
+        to_len: u32,
+    },
+    /// Get the number of centiseconds since the execution started.
+    Clock,
+    /// Get the number of seconds since the Unix epoch.
+    Time,
+    /// Execute a command on the host.
+    System { command: u32, command_len: u32 },
+    /// Get the errno value of the last failed operation.
+    Errno,
+    /// Get the command line of the application.
+    ///
+    /// The command line is written to `buffer`, and the actual length
+    /// is written back into the parameter block at `block`.
+    GetCmdline {
+        block: u32,
+        buffer: u32,
+        length: u32,
+    },
+    /// Get the stack and heap location of the application.
+    HeapInfo { block: u32 },
+    /// Report an exception or the termination of the application.
+    ///
+    /// The `subcode` is only available when `SYS_EXIT_EXTENDED` was used.
+    Exit { reason: u32, subcode: Option<u32> },
+    /// Get the number of ticks since the execution started, as a 64 bit value stored at `block`.
+    Elapsed { block: u32 },
+    /// Get the frequency of the ticks reported by `SYS_ELAPSED`.
+    TickFreq,
+    /// An operation which is not known to probe-rs.
+    Unknown { operation: u32, parameter: u32 },
+}
+
+impl SemihostingCommand {
+    /// Decode the semihosting request the core is currently halted at.
+    ///
+    /// This reads the operation number and the parameter block, and
+    /// does not check whether the core is actually halted on a semihosting
+    /// breakpoint. Use [check_semihosting] for that.
+    pub fn decode(core: &mut Core) -> Result<Self, Error> {
+        use operation::*;
+
+        let registers = core.registers();
+        let operation = core.read_core_reg(registers.argument_register(0))?;
+        let parameter = core.read_core_reg(registers.argument_register(1))?;
+
+        let mut read_block = |len: usize| -> Result<Vec<u32>, Error> {
+            let mut block = vec![0u32; len];
+            core.read_32(parameter, &mut block)?;
+            Ok(block)
+        };
+
+        log::debug!(
+            "Semihosting operation {:#04x} with parameter {:#010x}",
+            operation,
+            parameter
+        );
+
+        let command = match operation {
+            SYS_OPEN => {
+                let block = read_block(3)?;
+                SemihostingCommand::Open {
+                    path: block[0],
+                    mode: block[1],
+                    path_len: block[2],
+                }
+            }
+            SYS_CLOSE => SemihostingCommand::Close {
+                handle: read_block(1)?[0],
+            },
+            SYS_WRITEC => SemihostingCommand::WriteC { address: parameter },
+            SYS_WRITE0 => SemihostingCommand::Write0 { address: parameter },
+            SYS_WRITE => {
+                let block = read_block(3)?;
+                SemihostingCommand::Write {
+                    handle: block[0],
+                    buffer: block[1],
+                    length: block[2],
+                }
+            }
+            SYS_READ => {
+                let block = read_block(3)?;
+                SemihostingCommand::Read {
+                    handle: block[0],
+                    buffer: block[1],
+                    length: block[2],
+                }
+            }
+            SYS_READC => SemihostingCommand::ReadC,
+            SYS_ISERROR => SemihostingCommand::IsError {
+                status: read_block(1)?[0] as i32,
+            },
+            SYS_ISTTY => SemihostingCommand::IsTty {
+                handle: read_block(1)?[0],
+            },
+            SYS_SEEK => {
+                let block = read_block(2)?;
+                SemihostingCommand::Seek {
+                    handle: block[0],
+                    position: block[1],
+                }
+            }
+            SYS_FLEN => SemihostingCommand::Flen {
+                handle: read_block(1)?[0],
+            },
+            SYS_TMPNAM => {
+                let block = read_block(3)?;
+                SemihostingCommand::TmpNam {
+                    buffer: block[0],
+                    id: block[1],
+                    length: block[2],
+                }
+            }
+            SYS_REMOVE => {
+                let block = read_block(2)?;
+                SemihostingCommand::Remove {
+                    path: block[0],
+                    path_len: block[1],
+                }
+            }
+            SYS_RENAME => {
+                let block = read_block(4)?;
+                SemihostingCommand::Rename {
+                    from: block[0],
+                    from_len: block[1],
+                    to: block[2],
+                    to_len: block[3],
+                }
+            }
+            SYS_CLOCK => SemihostingCommand::Clock,
+            SYS_TIME => SemihostingCommand::Time,
+            SYS_SYSTEM => {
+                let block = read_block(2)?;
+                SemihostingCommand::System {
+                    command: block[0],
+                    command_len: block[1],
+                }
+            }
+            SYS_ERRNO => SemihostingCommand::Errno,
+            SYS_GET_CMDLINE => {
+                let block = read_block(2)?;
+                SemihostingCommand::GetCmdline {
+                    block: parameter,
+                    buffer: block[0],
+                    length: block[1],
+                }
+            }
+            SYS_HEAPINFO => SemihostingCommand::HeapInfo { block: parameter },
+            // On 32 bit targets, the reason is passed directly in the parameter register.
+            SYS_EXIT => SemihostingCommand::Exit {
+                reason: parameter,
+                subcode: None,
+            },
+            SYS_EXIT_EXTENDED => {
+                let block = read_block(2)?;
+                SemihostingCommand::Exit {
+                    reason: block[0],
+                    subcode: Some(block[1]),
+                }
+            }
+            SYS_ELAPSED => SemihostingCommand::Elapsed { block: parameter },
+            SYS_TICKFREQ => SemihostingCommand::TickFreq,
+            operation => SemihostingCommand::Unknown {
+                operation,
+                parameter,
+            },
+        };
+
+        Ok(command)
+    }
+}
+
+/// Check if the core is halted on a semihosting request, and decode it if that is the case.
+pub fn check_semihosting(core: &mut Core) -> Result<Option<SemihostingCommand>, Error> {
+    match core.status()? {
+        CoreStatus::Halted(HaltReason::Breakpoint(BreakpointCause::Semihosting)) => {
+            SemihostingCommand::decode(core).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Finish a semihosting request.
+///
+/// This writes `result` into the first result register, and moves the program counter
+/// past the semihosting instruction. The core is left halted, so that it can
+/// be resumed by the caller.
+pub fn complete_request(core: &mut Core, result: u32) -> Result<(), Error> {
+    let registers = core.registers();
+
+    core.write_core_reg(registers.result_register(0).into(), result)?;
+
+    // The semihosting sequence on RISC-V consists of uncompressed instructions,
+    // the `BKPT` instruction on ARM is always a 16 bit Thumb instruction.
+    let instruction_size = match core.architecture() {
+        Architecture::Arm => 2,
+        Architecture::Riscv => 4,
+    };
+
+    let pc = core.read_core_reg(registers.program_counter())?;
+    core.write_core_reg(registers.program_counter().into(), pc + instruction_size)?;
+
+    Ok(())
+}
+
+/// Read a string with a known length from target memory.
+///
+/// At most `max_length` bytes are read, longer strings are truncated.
+pub(crate) fn read_string(
+    core: &mut Core,
+    address: u32,
+    length: u32,
+    max_length: u32,
+) -> Result<String, Error> {
+    let mut data = vec![0u8; length.min(max_length) as usize];
+    core.read_8(address, &mut data)?;
+
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Read a null-terminated string from target memory.
+///
+/// Reading stops after `max_length` bytes, if no terminating null byte is found.
+pub fn read_c_string(core: &mut Core, address: u32, max_length: u32) -> Result<Vec<u8>, Error> {
+    const CHUNK_SIZE: u32 = 64;
+
+    let mut string = Vec::new();
+    let mut chunk = [0u8; CHUNK_SIZE as usize];
+
+    while (string.len() as u32) < max_length {
+        let offset = string.len() as u32;
+        let chunk_len = CHUNK_SIZE.min(max_length - offset) as usize;
+
+        core.read_8(address + offset, &mut chunk[..chunk_len])?;
+
+        match chunk[..chunk_len].iter().position(|b| *b == 0) {
+            Some(end) => {
+                string.extend_from_slice(&chunk[..end]);
+                return Ok(string);
+            }
+            None => string.extend_from_slice(&chunk[..chunk_len]),
+        }
+    }
+
+    Ok(string)
+}
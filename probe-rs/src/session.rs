@@ -6,7 +6,8 @@ use crate::architecture::{
             ApInformation::{MemoryAp, Other},
             ArmProbeInterface, MemoryApInformation,
         },
-        core::{debug_core_start, reset_catch_clear, reset_catch_set},
+        core::debug_core_start,
+        dp::DpAddress,
         memory::Component,
         SwoConfig,
     },
@@ -16,7 +17,7 @@ use crate::config::{
     ChipInfo, MemoryRegion, RawFlashAlgorithm, RegistryError, Target, TargetSelector,
 };
 use crate::core::{Architecture, CoreState, SpecificCoreState};
-use crate::{AttachMethod, Core, CoreType, DebugProbe, Error, Probe};
+use crate::{AttachMethod, Core, CoreType, DebugProbe, DebugProbeError, Error, Probe};
 use anyhow::anyhow;
 use std::time::Duration;
 
@@ -45,6 +46,7 @@ pub struct Session {
 
 #[derive(Debug)]
 enum ArchitectureInterface {
+    /// The interface to an ARM target, which keeps the state of each debug port used by a core.
     Arm(Box<dyn ArmProbeInterface + 'static>),
     Riscv(RiscvCommunicationInterface),
 }
@@ -63,9 +65,12 @@ impl ArchitectureInterface {
         &'probe mut self,
         core: &'probe mut SpecificCoreState,
         core_state: &'probe mut CoreState,
+        debug_port: DpAddress,
     ) -> Result<Core<'probe>, Error> {
         match self {
             ArchitectureInterface::Arm(state) => {
+                state.select_debug_port(debug_port)?;
+
                 let memory = state.memory_interface(0.into())?;
 
                 core.attach_arm(core_state, memory)
@@ -75,6 +80,15 @@ impl ArchitectureInterface {
     }
 }
 
+impl<'a> AsRef<dyn DebugProbe + 'a> for ArchitectureInterface {
+    fn as_ref(&self) -> &(dyn DebugProbe + 'a) {
+        match self {
+            ArchitectureInterface::Arm(interface) => interface.as_ref().as_ref(),
+            ArchitectureInterface::Riscv(interface) => interface.as_ref(),
+        }
+    }
+}
+
 impl<'a> AsMut<dyn DebugProbe + 'a> for ArchitectureInterface {
     fn as_mut(&mut self) -> &mut (dyn DebugProbe + 'a) {
         match self {
@@ -95,25 +109,39 @@ impl Session {
 
         let mut session = match target.architecture() {
             Architecture::Arm => {
-                let core = (
-                    SpecificCoreState::from_core_type(target.core_type),
-                    Core::create_state(0),
-                );
-
-                let interface = probe.into_arm_interface()?;
+                // Each debug port of the target is used by one core.
+                let cores = (0..target.debug_ports.len().max(1))
+                    .map(|id| {
+                        (
+                            SpecificCoreState::from_core_type(target.core_type),
+                            Core::create_state(id),
+                        )
+                    })
+                    .collect();
+
+                let debug_port = target
+                    .debug_ports
+                    .first()
+                    .copied()
+                    .unwrap_or(DpAddress::Default);
+                let interface = probe.into_arm_interface_with_dp(debug_port)?;
 
                 let mut session = Session {
                     target,
                     interface: ArchitectureInterface::Arm(interface.unwrap()),
-                    cores: vec![core],
+                    cores,
                 };
 
+                let sequence = session.target.debug_sequence.clone();
+
                 // Enable debug mode
-                debug_core_start(&mut session.core(0)?)?;
+                for n in 0..session.cores.len() {
+                    debug_core_start(&mut session.core(n)?)?;
+                }
 
                 if attach_method == AttachMethod::UnderReset {
                     // we need to halt the chip here
-                    reset_catch_set(&mut session.core(0)?)?;
+                    sequence.reset_catch_set(&mut session.core(0)?)?;
 
                     // Deassert the reset pin
                     session.interface.as_mut().target_reset_deassert()?;
@@ -123,7 +151,13 @@ impl Session {
 
                     core.wait_for_core_halted(Duration::from_millis(100))?;
 
-                    reset_catch_clear(&mut core)?;
+                    sequence.reset_catch_clear(&mut core)?;
+                }
+
+                {
+                    let mut core = session.core(0)?;
+                    sequence.enable_debug_in_low_power(&mut core)?;
+                    sequence.freeze_watchdogs(&mut core)?;
                 }
 
                 session
@@ -186,8 +220,38 @@ impl Session {
     /// Attaches to the core with the given number.
     pub fn core(&mut self, n: usize) -> Result<Core<'_>, Error> {
         let (core, core_state) = self.cores.get_mut(n).ok_or(Error::CoreNotFound(n))?;
+        let debug_port = self
+            .target
+            .debug_ports
+            .get(n)
+            .copied()
+            .unwrap_or(DpAddress::Default);
+
+        self.interface.attach(core, core_state, debug_port)
+    }
+
+    /// Returns the target this session is attached to.
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
 
-        self.interface.attach(core, core_state)
+    /// Returns the human readable name of the debug probe used by this session.
+    pub fn probe_name(&self) -> &str {
+        self.interface.as_ref().get_name()
+    }
+
+    /// Get the currently used maximum speed of the debug probe in kHz.
+    ///
+    /// See [DebugProbe::speed] for details.
+    pub fn speed_khz(&self) -> u32 {
+        self.interface.as_ref().speed()
+    }
+
+    /// Set the speed in kHz used by the debug probe for communication with the target.
+    ///
+    /// Returns the speed which is actually used, see [DebugProbe::set_speed] for details.
+    pub fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        self.interface.as_mut().set_speed(speed_khz)
     }
 
     /// Returns a list of the flash algotithms on the target.
@@ -214,7 +278,15 @@ impl Session {
     }
 
     fn get_arm_component(&mut self) -> Result<Component, Error> {
+        // The trace components used by the session belong to the first core.
+        let debug_port = self
+            .target
+            .debug_ports
+            .first()
+            .copied()
+            .unwrap_or(DpAddress::Default);
         let interface = self.get_arm_interface()?;
+        interface.select_debug_port(debug_port)?;
 
         let ap_index = 0;
 
@@ -255,10 +327,12 @@ impl Session {
             interface.enable_swo(config)?;
         }
 
-        // Enable tracing on the target
+        // Enable tracing on the target, and configure the trace pins
         {
+            let sequence = self.target.debug_sequence.clone();
             let mut core = self.core(0)?;
             crate::architecture::arm::component::enable_tracing(&mut core)?;
+            sequence.setup_trace(&mut core, config)?;
         }
 
         // Configure SWV on the target
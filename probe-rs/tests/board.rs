@@ -0,0 +1,30 @@
+use probe_rs::config::{BoardDescription, FlashRegion, MemoryRegion};
+use probe_rs::SimulatedProbe;
+use std::path::Path;
+
+#[test]
+fn board_adds_external_flash() {
+    SimulatedProbe::add_target().unwrap();
+
+    let board = BoardDescription::from_yaml_file(Path::new("tests/qspi_board.yaml")).unwrap();
+    assert_eq!(board.chip, "simulated_m4");
+
+    let target = board.target().unwrap();
+
+    // The internal flash and RAM of the chip are kept.
+    assert_eq!(target.memory_map.len(), 3);
+    assert!(target
+        .memory_map
+        .contains(&MemoryRegion::Flash(FlashRegion {
+            range: 0x9000_0000..0x9080_0000,
+            is_boot_memory: false,
+            algorithm: Some("qspi".into()),
+        })));
+
+    let names: Vec<_> = target
+        .flash_algorithms
+        .iter()
+        .map(|algorithm| algorithm.name.as_ref())
+        .collect();
+    assert_eq!(names, ["simulated_m4", "qspi"]);
+}
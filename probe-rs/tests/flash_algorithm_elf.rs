@@ -0,0 +1,43 @@
+use probe_rs::config::{add_target_from_yaml, families, RawFlashAlgorithm};
+use probe_rs::{CoreType, Target};
+use std::path::Path;
+
+#[test]
+fn target_description_with_elf_algorithm() {
+    add_target_from_yaml(Path::new("tests/qspi_target.yaml")).unwrap();
+
+    let families = families().unwrap();
+    let family = families
+        .iter()
+        .find(|family| family.name == "QSPI Test Series")
+        .unwrap();
+
+    let algorithm = family.get_algorithm("qspi").unwrap();
+    assert!(algorithm.default);
+    assert_eq!(algorithm.description, "QSPI 8MB Flash");
+    assert_eq!(algorithm.pc_program_page, 0x11);
+    assert_eq!(algorithm.pc_verify, Some(0x15));
+    assert_eq!(
+        algorithm.flash_properties.address_range,
+        0x9000_0000..0x9080_0000
+    );
+}
+
+#[test]
+fn add_elf_algorithm_to_target() {
+    let data = std::fs::read("tests/qspi_flash_algorithm.elf").unwrap();
+    let algorithm = RawFlashAlgorithm::from_elf("qspi", &data).unwrap();
+
+    let family = families()
+        .unwrap()
+        .into_iter()
+        .find(|family| family.name == "Generic Cortex-M4")
+        .unwrap();
+    let mut target = Target::new(&family, &family.variants()[0], vec![], CoreType::M4);
+
+    target.add_flash_algorithm(algorithm.clone());
+    target.add_flash_algorithm(algorithm);
+
+    assert_eq!(target.flash_algorithms.len(), 1);
+    assert_eq!(target.memory_map.len(), 1);
+}
@@ -0,0 +1,84 @@
+use probe_rs::config::{
+    add_target_from_pack, families, FlashRegion, MemoryRegion, RamRegion, SectorDescription,
+};
+use probe_rs::Architecture;
+use std::path::Path;
+
+#[test]
+fn import_synthetic_pack() {
+    add_target_from_pack(Path::new("tests/Synthetic.Demo_DFP.1.0.0.pack")).unwrap();
+
+    let families = families().unwrap();
+    let family = families
+        .iter()
+        .find(|family| family.name == "DEMO Series")
+        .expect("The family of the pack was not added to the registry");
+
+    assert_eq!(family.core, "M4");
+
+    let names: Vec<_> = family.variants().iter().map(|v| v.name.as_ref()).collect();
+    assert_eq!(names, ["DEMO100xA", "DEMO100xB", "DEMO101"]);
+
+    // The RAM of DEMO100xB overrides the RAM inherited from the sub family.
+    assert_eq!(
+        family.variants()[1].memory_map.as_ref(),
+        [
+            MemoryRegion::Ram(RamRegion {
+                range: 0x2000_0000..0x2000_8000,
+                is_boot_memory: false,
+            }),
+            MemoryRegion::Flash(FlashRegion {
+                range: 0x0800_0000..0x0801_0000,
+                is_boot_memory: true,
+                algorithm: None,
+            }),
+        ]
+    );
+
+    // All devices share the same flash algorithm.
+    assert_eq!(family.algorithms().len(), 1);
+    for variant in family.variants() {
+        assert_eq!(variant.flash_algorithms.as_ref(), ["demo"]);
+    }
+
+    let algorithm = family.get_algorithm("demo").unwrap();
+    assert!(algorithm.default);
+    assert_eq!(algorithm.description, "DEMO 64kB Flash");
+    assert_eq!(algorithm.instructions.len(), 0x1C);
+    assert_eq!(algorithm.pc_init, Some(0x1));
+    assert_eq!(algorithm.pc_uninit, Some(0x5));
+    assert_eq!(algorithm.pc_erase_all, Some(0x9));
+    assert_eq!(algorithm.pc_erase_sector, 0xD);
+    assert_eq!(algorithm.pc_program_page, 0x11);
+    assert_eq!(algorithm.data_section_offset, 0x14);
+
+    let properties = &algorithm.flash_properties;
+    assert_eq!(properties.address_range, 0x0800_0000..0x0801_0000);
+    assert_eq!(properties.page_size, 0x100);
+    assert_eq!(properties.erased_byte_value, 0xFF);
+    assert_eq!(properties.program_page_timeout, 100);
+    assert_eq!(properties.erase_sector_timeout, 3000);
+    assert_eq!(
+        properties.sectors.as_ref(),
+        [
+            SectorDescription {
+                size: 0x400,
+                address: 0x0,
+            },
+            SectorDescription {
+                size: 0x800,
+                address: 0x8000,
+            },
+        ]
+    );
+
+    let ram = RamRegion {
+        range: 0x2000_0000..0x2000_8000,
+        is_boot_memory: false,
+    };
+    let assembled = algorithm.assemble(&ram, Architecture::Arm).unwrap();
+    assert_eq!(
+        assembled.static_base,
+        assembled.pc_program_page - 0x11 + 0x14
+    );
+}
@@ -0,0 +1,41 @@
+use probe_rs::{
+    DebugProbeSelector, MemoryInterface, Probe, ProbeServer, SimulatedProbe, WireProtocol,
+};
+use std::time::Duration;
+
+#[test]
+fn attach_through_server() {
+    SimulatedProbe::add_target().unwrap();
+
+    let simulated = SimulatedProbe::new();
+    let target = simulated.target();
+
+    let mut simulated = Some(simulated);
+    let server = ProbeServer::bind("127.0.0.1:0")
+        .unwrap()
+        .serve_probe(move || Ok(Box::new(simulated.take().expect("Probe opened twice"))));
+    let address = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+
+    let selector: DebugProbeSelector = format!("remote://{}", address).parse().unwrap();
+    let mut probe = Probe::open(selector).unwrap();
+
+    let capabilities = probe.capabilities().unwrap();
+    assert_eq!(capabilities.protocols, [WireProtocol::Swd]);
+
+    let voltage = probe.target_voltage().unwrap().unwrap();
+    assert!((voltage - 3.3).abs() < 0.01);
+
+    let mut session = probe.attach("simulated_m4").unwrap();
+    let mut core = session.core(0).unwrap();
+
+    core.halt(Duration::from_millis(100)).unwrap();
+    assert!(target.is_halted());
+
+    let data: Vec<u32> = (0..256).collect();
+    core.write_32(0x2000_0000, &data).unwrap();
+
+    let mut read = vec![0; data.len()];
+    core.read_32(0x2000_0000, &mut read).unwrap();
+    assert_eq!(read, data);
+}
@@ -0,0 +1,22 @@
+use probe_rs::{MemoryInterface, Probe, ReplayProbe, SimulatedProbe};
+use std::time::Duration;
+
+/// The session was recorded using the simulated target, with the PC set to 0x100,
+/// and "Recorded session" written to the start of the RAM.
+#[test]
+fn replay_session() {
+    SimulatedProbe::add_target().unwrap();
+
+    let replay = ReplayProbe::from_file("tests/replay_session.yaml").unwrap();
+    let probe = Probe::from_specific_probe(Box::new(replay));
+
+    let mut session = probe.attach("simulated_m4").unwrap();
+    let mut core = session.core(0).unwrap();
+
+    let info = core.halt(Duration::from_millis(100)).unwrap();
+    assert_eq!(info.pc, 0x100);
+
+    let mut data = [0u8; 16];
+    core.read_8(0x2000_0000, &mut data).unwrap();
+    assert_eq!(&data, b"Recorded session");
+}
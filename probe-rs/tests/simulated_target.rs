@@ -0,0 +1,344 @@
+use probe_rs::flashing::{
+    download_file, download_file_with_options, dump_memory_range, erase_all,
+    erase_range_with_options, erase_region, memory_region_range, plan_erase_all, plan_erase_range,
+    read_memory_range, BinOptions, DownloadOptions, DumpFormat, DumpOptions, EraseOptions,
+    FlashError, FlashProgress, Format, ProgressEvent,
+};
+use probe_rs::{
+    BreakpointCause, CoreRegisterAddress, CoreStatus, HaltReason, MemoryInterface, Probe,
+    SimulatedProbe, SimulatedTarget,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Once;
+use std::time::Duration;
+
+const PC: CoreRegisterAddress = CoreRegisterAddress(15);
+const R0: CoreRegisterAddress = CoreRegisterAddress(0);
+
+fn simulated_probe() -> (Probe, SimulatedTarget) {
+    static ADD_TARGET: Once = Once::new();
+    ADD_TARGET.call_once(|| SimulatedProbe::add_target().unwrap());
+
+    let probe = SimulatedProbe::new();
+    let target = probe.target();
+
+    (Probe::new(probe), target)
+}
+
+/// A progress reporter which collects all events.
+fn collect_events() -> (FlashProgress, Rc<RefCell<Vec<ProgressEvent>>>) {
+    let events = Rc::new(RefCell::new(Vec::new()));
+
+    let progress = {
+        let events = events.clone();
+        FlashProgress::new(move |event| events.borrow_mut().push(event))
+    };
+
+    (progress, events)
+}
+
+/// A file in the temporary directory, which is removed when it is dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("probe-rs-{}-{}", std::process::id(), name)))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn halt_and_access_registers() {
+    let (probe, target) = simulated_probe();
+    target.set_register(PC, 0x100);
+
+    let mut session = probe.attach("simulated_m4").unwrap();
+    let mut core = session.core(0).unwrap();
+
+    let info = core.halt(Duration::from_millis(100)).unwrap();
+    assert_eq!(info.pc, 0x100);
+    assert!(target.is_halted());
+
+    core.write_core_reg(R0, 0xdead_beef).unwrap();
+    assert_eq!(core.read_core_reg(R0).unwrap(), 0xdead_beef);
+    assert_eq!(target.register(R0), 0xdead_beef);
+}
+
+#[test]
+fn step_and_run_to_breakpoint() {
+    let (probe, target) = simulated_probe();
+    target.set_register(PC, 0x100);
+    target.set_script(vec![0x104, 0x108, 0x10c, 0x110]);
+
+    let mut session = probe.attach("simulated_m4").unwrap();
+    let mut core = session.core(0).unwrap();
+
+    core.halt(Duration::from_millis(100)).unwrap();
+
+    let info = core.step().unwrap();
+    assert_eq!(info.pc, 0x104);
+
+    core.set_hw_breakpoint(0x10c).unwrap();
+    core.run().unwrap();
+    core.wait_for_core_halted(Duration::from_millis(100))
+        .unwrap();
+
+    assert_eq!(core.read_core_reg(PC).unwrap(), 0x10c);
+    assert_eq!(
+        core.status().unwrap(),
+        CoreStatus::Halted(HaltReason::Breakpoint(BreakpointCause::Hardware))
+    );
+}
+
+#[test]
+fn memory_access() {
+    let (probe, target) = simulated_probe();
+
+    let mut session = probe.attach("simulated_m4").unwrap();
+    let mut core = session.core(0).unwrap();
+
+    core.write_32(0x2000_0100, &[0x0403_0201, 0x0807_0605])
+        .unwrap();
+    core.write_word_8(0x2000_0108, 0x09).unwrap();
+
+    let mut data = [0u8; 9];
+    target.read_memory(0x2000_0100, &mut data);
+    assert_eq!(data, [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+    let mut data = [0u8; 7];
+    core.read_8(0x2000_0101, &mut data).unwrap();
+    assert_eq!(data, [2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn flash_and_erase() {
+    let (probe, target) = simulated_probe();
+    let mut session = probe.attach("simulated_m4").unwrap();
+
+    let contents: Vec<u8> = (0..0x1800u32).map(|i| i as u8).collect();
+
+    let file = TempFile::new("simulated.bin");
+    std::fs::write(file.path(), &contents).unwrap();
+
+    download_file(
+        &mut session,
+        file.path(),
+        Format::Bin(BinOptions {
+            base_address: Some(0x1000),
+            skip: 0,
+        }),
+    )
+    .unwrap();
+
+    let mut flash = vec![0u8; contents.len()];
+    target.read_memory(0x1000, &mut flash);
+    assert_eq!(flash, contents);
+
+    // The memory before the binary is untouched.
+    let mut flash = [0u8; 4];
+    target.read_memory(0xffc, &mut flash);
+    assert_eq!(flash, [0xff; 4]);
+
+    erase_all(&mut session).unwrap();
+
+    let mut flash = vec![0u8; contents.len()];
+    target.read_memory(0x1000, &mut flash);
+    assert!(flash.iter().all(|&byte| byte == 0xff));
+}
+
+/// Flash `contents` to 0x1000 incrementally, and return the addresses of the
+/// skipped sectors and the number of erased sectors.
+fn download_incremental(session: &mut probe_rs::Session, contents: &[u8]) -> (Vec<u32>, usize) {
+    let (progress, events) = collect_events();
+
+    let file = TempFile::new("incremental.bin");
+    std::fs::write(file.path(), contents).unwrap();
+
+    download_file_with_options(
+        session,
+        file.path(),
+        Format::Bin(BinOptions {
+            base_address: Some(0x1000),
+            skip: 0,
+        }),
+        DownloadOptions {
+            progress: Some(&progress),
+            incremental: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let events = events.borrow();
+    let skipped = events
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::SectorSkipped { address, .. } => Some(*address),
+            _ => None,
+        })
+        .collect();
+    let erased = events
+        .iter()
+        .filter(|event| matches!(event, ProgressEvent::SectorErased { .. }))
+        .count();
+
+    (skipped, erased)
+}
+
+#[test]
+fn incremental_flashing() {
+    let (probe, target) = simulated_probe();
+    let mut session = probe.attach("simulated_m4").unwrap();
+
+    let mut contents: Vec<u8> = (0..0x3000u32).map(|i| i as u8).collect();
+
+    // Nothing can be skipped on the erased flash.
+    assert_eq!(download_incremental(&mut session, &contents), (vec![], 3));
+
+    // Flashing the same contents again skips all sectors.
+    assert_eq!(
+        download_incremental(&mut session, &contents),
+        (vec![0x1000, 0x2000, 0x3000], 0)
+    );
+
+    // Only the sector with the changed byte is flashed.
+    contents[0x1800] ^= 0xff;
+    assert_eq!(
+        download_incremental(&mut session, &contents),
+        (vec![0x1000, 0x3000], 1)
+    );
+
+    let mut flash = vec![0u8; contents.len()];
+    target.read_memory(0x1000, &mut flash);
+    assert_eq!(flash, contents);
+}
+
+#[test]
+fn read_back_flash() {
+    let (probe, target) = simulated_probe();
+    let mut session = probe.attach("simulated_m4").unwrap();
+
+    let contents: Vec<u8> = (0..0x5000u32).map(|i| (i * 7) as u8).collect();
+    target.write_memory(0x1000, &contents);
+
+    let (progress, events) = collect_events();
+
+    let data = read_memory_range(
+        &mut session,
+        0x1000..0x6000,
+        &DumpOptions {
+            progress: Some(&progress),
+        },
+    )
+    .unwrap();
+    assert_eq!(data, contents);
+
+    let blocks: Vec<_> = events
+        .borrow()
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::BlockRead { size, .. } => Some(*size),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(blocks, [0x4000, 0x1000]);
+
+    let range = memory_region_range(session.memory_map(), "flash").unwrap();
+    assert_eq!(range, 0x0..0x4_0000);
+
+    let file = TempFile::new("dump.bin");
+    dump_memory_range(
+        &mut session,
+        range,
+        file.path(),
+        DumpFormat::Bin,
+        DumpOptions::default(),
+    )
+    .unwrap();
+
+    let dump = std::fs::read(file.path()).unwrap();
+    assert_eq!(dump.len(), 0x4_0000);
+    assert_eq!(&dump[0x1000..0x6000], &contents[..]);
+    assert!(dump[..0x1000].iter().all(|&byte| byte == 0xff));
+}
+
+#[test]
+fn erase_sectors_of_range() {
+    let (probe, target) = simulated_probe();
+    let mut session = probe.attach("simulated_m4").unwrap();
+
+    let contents = vec![0x55u8; 0x4000];
+    target.write_memory(0x0, &contents);
+
+    // The range is extended to whole sectors.
+    let areas = plan_erase_range(&session, 0x1800..0x2100).unwrap();
+    assert_eq!(areas.len(), 1);
+    assert_eq!(areas[0].range, 0x1000..0x3000);
+    assert_eq!(areas[0].sectors, 2);
+    assert!(!areas[0].chip_erase);
+    assert!(areas[0].is_boot_memory);
+
+    // The whole flash is erased using the chip erase routine.
+    let areas = plan_erase_all(&session).unwrap();
+    assert_eq!(areas.len(), 1);
+    assert_eq!(areas[0].range, 0x0..0x4_0000);
+    assert!(areas[0].chip_erase);
+
+    // The chip boots from the flash, so erasing it has to be confirmed.
+    let result = erase_range_with_options(&mut session, 0x1800..0x2100, EraseOptions::default());
+    assert!(matches!(
+        result,
+        Err(FlashError::BootMemoryNotConfirmed {
+            start: 0x1000,
+            end: 0x3000
+        })
+    ));
+
+    let mut flash = vec![0u8; contents.len()];
+    target.read_memory(0x0, &mut flash);
+    assert_eq!(flash, contents);
+
+    let (progress, events) = collect_events();
+
+    erase_range_with_options(
+        &mut session,
+        0x1800..0x2100,
+        EraseOptions {
+            progress: Some(&progress),
+            erase_boot_memory: true,
+        },
+    )
+    .unwrap();
+
+    let erased: Vec<_> = events
+        .borrow()
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::SectorErased { size, .. } => Some(*size),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(erased, [0x1000, 0x1000]);
+
+    target.read_memory(0x0, &mut flash);
+    assert!(flash[..0x1000].iter().all(|&byte| byte == 0x55));
+    assert!(flash[0x1000..0x3000].iter().all(|&byte| byte == 0xff));
+    assert!(flash[0x3000..].iter().all(|&byte| byte == 0x55));
+
+    // Only flash regions can be erased by name.
+    assert!(matches!(
+        erase_region(&mut session, "ram", EraseOptions::default()),
+        Err(FlashError::RegionNotFound(_))
+    ));
+}